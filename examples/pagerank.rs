@@ -0,0 +1,66 @@
+extern crate rand;
+extern crate timely;
+extern crate differential_dataflow;
+
+use std::time::Instant;
+
+use rand::{Rng, SeedableRng, StdRng};
+
+use differential_dataflow::input::Input;
+use differential_dataflow::algorithms::graphs::pagerank;
+
+fn main() {
+
+    let nodes: u32 = std::env::args().nth(1).unwrap().parse().unwrap();
+    let edges: u32 = std::env::args().nth(2).unwrap().parse().unwrap();
+    let batch: u32 = std::env::args().nth(3).unwrap().parse().unwrap();
+    let iters: u64 = std::env::args().nth(4).unwrap().parse().unwrap();
+
+    println!("performing {} iterations of pagerank on {} nodes, {} edges:", iters, nodes, edges);
+
+    timely::execute_from_args(std::env::args().skip(5), move |worker| {
+
+        let timer = Instant::now();
+
+        // define pagerank dataflow; return handle to edges input
+        let (mut graph, probe) = worker.dataflow(|scope| {
+            let (edge_input, graph) = scope.new_collection();
+            let probe = pagerank(iters, &graph).probe();
+            (edge_input, probe)
+        });
+
+        let seed: &[_] = &[1, 2, 3, 4];
+        let mut rng1: StdRng = SeedableRng::from_seed(seed);    // rng for edge additions
+        let mut rng2: StdRng = SeedableRng::from_seed(seed);    // rng for edge deletions
+
+        if worker.index() == 0 {
+            for _ in 0 .. edges {
+                graph.insert((rng1.gen_range(0, nodes), rng1.gen_range(0, nodes)));
+            }
+        }
+
+        graph.advance_to(1); graph.flush();
+        worker.step_while(|| probe.less_than(graph.time()));
+
+        if worker.index() == 0 {
+            println!("stable; elapsed: {:?}", timer.elapsed());
+        }
+
+        // each round replaces `batch` edges, and the ranks are updated in response.
+        for round in 1 .. 11 {
+            let timer = Instant::now();
+            if worker.index() == 0 {
+                for _ in 0 .. batch {
+                    graph.insert((rng1.gen_range(0, nodes), rng1.gen_range(0, nodes)));
+                    graph.remove((rng2.gen_range(0, nodes), rng2.gen_range(0, nodes)));
+                }
+            }
+            graph.advance_to(round + 1); graph.flush();
+            worker.step_while(|| probe.less_than(graph.time()));
+
+            if worker.index() == 0 {
+                println!("round {}; elapsed: {:?}", round, timer.elapsed());
+            }
+        }
+    }).unwrap();
+}
@@ -1,71 +1,50 @@
 extern crate timely;
 extern crate timely_communication;
 extern crate differential_dataflow;
+#[macro_use]
 extern crate dd_server;
 
-use std::any::Any;
-use std::collections::HashMap;
-
-use timely_communication::Allocator;
-use timely::dataflow::scopes::{Child, Root};
-use timely::dataflow::operators::probe::Handle as ProbeHandle;
-
 use differential_dataflow::input::Input;
 use differential_dataflow::operators::JoinCore;
 use differential_dataflow::operators::Consolidate;
 use differential_dataflow::operators::arrange::ArrangeByKey;
+use differential_dataflow::hashable::UnsignedWrapper;
 
-use dd_server::{RootTime, TraceHandle};
-
+use dd_server::Environment;
 
 // load ./dataflows/neighborhood/target/debug/libneighborhood.dylib build <graph_name> 0
 
-#[no_mangle]
-pub fn build(
-    dataflow: &mut Child<Root<Allocator>,usize>, 
-    handles: &mut HashMap<String, Box<Any>>, 
-    probe: &mut ProbeHandle<RootTime>,
-    args: &[String]) 
-{
-    println!("initializing neighborhood dataflow");
-
-    if args.len() == 2 {
+export_version!();
 
-        let graph_name = &args[0];
-        if let Some(boxed) = handles.get_mut(graph_name) {
-            if let Some(mut handle) = boxed.downcast_mut::<TraceHandle>() {
+#[no_mangle]
+pub fn build(env: Environment) -> Result<(), String> {
 
-                if let Ok(source) = args[1].parse::<usize>() {
+    let Environment { dataflow, traces, probe, args, .. } = env;
 
-                    let edges = handle.import(dataflow);
-                    let (_input, query) = dataflow.new_collection_from(Some(source));
+    println!("initializing neighborhood dataflow");
 
-                    query
-                        .map(|x| (x, x))
-                        .arrange_by_key_u()
-                        .join_core(&edges, |_n, &q, &d| Some((d, q)))
-                        .arrange_by_key_u()
-                        .join_core(&edges, |_n, &q, &d| Some((d, q)))
-                        .arrange_by_key_u()
-                        .join_core(&edges, |_n, &q, &d| Some((d, q)))
-                        .map(|x| x.1)
-                        .consolidate()
-                        .inspect(|x| println!("{:?}", x))
-                        .probe_with(probe);
-                }
-                else {
-                    println!("failed to parse {:?} as usize", args[1]);
-                }
-            }
-            else {
-                println!("failed to downcast_mut to TraceHandle");
-            }
-        }
-        else {
-            println!("failed to find graph: {:?}", graph_name);
-        }
-    }
-    else {
-        println!("expected two arguments: <graph name> <source node>; instead: {:?}", args);
+    if args.len() != 2 {
+        return Err(format!("expected two arguments: <graph name> <source node>; instead: {:?}", args));
     }
+
+    let handle = traces.get_mut::<UnsignedWrapper<usize>, usize, isize>(&args[0])?;
+    let source = args[1].parse::<usize>().map_err(|_| format!("failed to parse {:?} as usize", args[1]))?;
+
+    let edges = handle.import(dataflow);
+    let (_input, query) = dataflow.new_collection_from(Some(source));
+
+    query
+        .map(|x| (x, x))
+        .arrange_by_key_u()
+        .join_core(&edges, |_n, &q, &d| Some((d, q)))
+        .arrange_by_key_u()
+        .join_core(&edges, |_n, &q, &d| Some((d, q)))
+        .arrange_by_key_u()
+        .join_core(&edges, |_n, &q, &d| Some((d, q)))
+        .map(|x| x.1)
+        .consolidate()
+        .inspect(|x| println!("{:?}", x))
+        .probe_with(probe);
+
+    Ok(())
 }
\ No newline at end of file
@@ -3,58 +3,62 @@ extern crate rand;
 extern crate timely;
 extern crate timely_communication;
 extern crate differential_dataflow;
+#[macro_use]
 extern crate dd_server;
 
-use std::any::Any;
-use std::collections::HashMap;
-
 use rand::{Rng, SeedableRng, StdRng};
 
-use timely_communication::Allocator;
-use timely::dataflow::scopes::{Child, Root};
-use timely::dataflow::operators::probe::Handle as ProbeHandle;
 use timely::dataflow::operators::Probe;
 
 use differential_dataflow::AsCollection;
 use differential_dataflow::operators::arrange::ArrangeByKey;
 
-use dd_server::{Environment, RootTime, TraceHandle};
+use dd_server::{Environment, TraceHandle};
 
 // load ./dataflows/random_graph/target/debug/librandom_graph.dylib build <graph_name> 1000 2000 10
 
+export_version!();
+
 #[no_mangle]
-// pub fn build((dataflow, handles, probe, args): Environment) {
-pub fn build(
-    dataflow: &mut Child<Root<Allocator>,usize>, 
-    handles: &mut HashMap<String, Box<Any>>, 
-    probe: &mut ProbeHandle<RootTime>,
-    args: &[String]) 
-{
-    if args.len() == 4 {
+pub fn build(env: Environment) -> Result<(), String> {
+
+    let Environment { dataflow, traces, probe, token, args } = env;
+
+    if args.len() != 4 {
+        return Err(format!("expected four arguments: <graph name> <nodes> <edges> <batch>; instead: {:?}", args));
+    }
 
-        let name = &args[0];
-        let nodes: usize = args[1].parse().unwrap();
-        let edges: usize = args[2].parse().unwrap();
-        let batch: usize = args[3].parse().unwrap();
+    let name = &args[0];
+    let nodes = parse(&args[1])?;
+    let edges = parse(&args[2])?;
+    let batch = parse(&args[3])?;
 
-        // create a trace from a source of random graph edges.
-        let trace: TraceHandle = timely::dataflow::operators::operator::source(dataflow, "RandomGraph", |mut capability| {
+    // create a trace from a source of random graph edges.
+    let trace: TraceHandle = timely::dataflow::operators::operator::source(dataflow, "RandomGraph", |capability| {
 
-            let index = dataflow.index();
-            let peers = dataflow.peers();
+        let index = dataflow.index();
+        let peers = dataflow.peers();
 
-            let seed: &[_] = &[1, 2, 3, index];
-            let mut rng1: StdRng = SeedableRng::from_seed(seed);    // rng for edge additions
-            let mut rng2: StdRng = SeedableRng::from_seed(seed);    // rng for edge deletions
+        let seed: &[_] = &[1, 2, 3, index];
+        let mut rng1: StdRng = SeedableRng::from_seed(seed);    // rng for edge additions
+        let mut rng2: StdRng = SeedableRng::from_seed(seed);    // rng for edge deletions
 
-            let mut additions = 0;
-            let mut deletions = 0;
+        let mut additions = 0;
+        let mut deletions = 0;
 
-            let handle = probe.clone();
+        let handle = probe.clone();
+        let token = token.clone();
+        let mut capability = Some(capability);
 
-            move |output| {
+        move |output| {
 
-                // do nothing if the probe is not caught up to us
+            // stop producing edges once the dataflow has been dropped.
+            if !token.alive() {
+                capability = None;
+            }
+
+            // do nothing if the probe is not caught up to us
+            if let Some(ref mut capability) = capability {
                 if !handle.less_than(capability.time()) {
 
                     let mut time = capability.time().clone();
@@ -91,19 +95,18 @@ pub fn build(
                     capability.downgrade(&time);
                 }
             }
-        })
-        .probe_with(probe)
-        .as_collection()
-        .arrange_by_key_u()
-        .trace;
+        }
+    })
+    .probe_with(probe)
+    .as_collection()
+    .arrange_by_key_u()
+    .trace;
 
-        let boxed: Box<Any> = Box::new(trace);
+    traces.insert(name, trace);
 
-        // println!("boxed correctly?: {:?}", boxed.downcast_ref::<TraceHandle>().is_some());
+    Ok(())
+}
 
-        handles.insert(name.to_owned(), boxed);
-    }
-    else {
-        println!("expect four arguments, found: {:?}", args);
-    }
+fn parse(arg: &str) -> Result<usize, String> {
+    arg.parse().map_err(|_| format!("failed to parse {:?} as usize", arg))
 }
\ No newline at end of file
@@ -1,55 +1,52 @@
 extern crate timely;
 extern crate timely_communication;
 extern crate differential_dataflow;
+#[macro_use]
 extern crate dd_server;
 
-use std::collections::HashMap;
-
-use timely_communication::Allocator;
-use timely::dataflow::scopes::{Child, Root};
-use timely::dataflow::operators::probe::Handle as ProbeHandle;
-
 use differential_dataflow::input::Input;
 use differential_dataflow::operators::{Group, Iterate, JoinCore, Consolidate};
 use differential_dataflow::operators::arrange::ArrangeByKey;
+use differential_dataflow::hashable::UnsignedWrapper;
 
-use dd_server::{RootTime, TraceHandle};
+use dd_server::Environment;
 
+export_version!();
 
 #[no_mangle]
-pub fn build(
-    dataflow: &mut Child<Root<Allocator>,usize>, 
-    handles: &mut HashMap<String, TraceHandle>, 
-    probe: &mut ProbeHandle<RootTime>,
-    args: &[String]) 
-{
+pub fn build(env: Environment) -> Result<(), String> {
+
+    let Environment { dataflow, traces, inputs, outputs, probe, .. } = env;
+
     println!("initializing reachability dataflow");
 
-    if let Some(handle) = handles.get_mut("random") {
+    let handle = traces.get_mut::<UnsignedWrapper<usize>, usize, isize>("random")?;
+
+    let edges = handle.import(dataflow);
+    let (input, roots) = dataflow.new_collection::<_,isize>();
+
+    // clients supply roots with `update roots <node> <diff>`.
+    inputs.insert("roots", input);
 
-        let edges = handle.import(dataflow);
-        let (_input, roots) = dataflow.new_collection::<_,isize>();
+    let roots = roots.map(|x| (x, 0));
 
-        let roots = roots.map(|x| (x, 0));
+    // repeatedly update minimal distances each node can be reached from each root
+    let distances = roots.iterate(|dists| {
 
-        // repeatedly update minimal distances each node can be reached from each root
-        roots.iterate(|dists| {
+        let edges = edges.enter(&dists.scope());
+        let roots = roots.enter(&dists.scope());
 
-            let edges = edges.enter(&dists.scope());
-            let roots = roots.enter(&dists.scope());
+        dists.arrange_by_key_u()
+             .join_core(&edges, |_k,l,d| Some((*d, l+1)))
+             .concat(&roots)
+             .group_u(|_, s, t| t.push((s[0].0, 1)))
+    })
+    .map(|(_node, dist)| dist)
+    .consolidate();
 
-            dists.arrange_by_key_u()
-                 .join_core(&edges, |_k,l,d| Some((*d, l+1)))
-                 .concat(&roots)
-                 .group_u(|_, s, t| t.push((s[0].0, 1)))
-        })
-        .map(|(_node, dist)| dist)
-        .consolidate()
-        .inspect(|x| println!("distance update: {:?}", x))
-        .probe_with(probe);
+    // clients observe changes with `subscribe distances`.
+    outputs.publish("distances", &distances)
+           .probe_with(probe);
 
-    }
-    else {
-        println!("failed to find graph: random");
-    }
+    Ok(())
 }
\ No newline at end of file
@@ -10,41 +10,129 @@ extern crate timely_communication;
 extern crate differential_dataflow;
 extern crate dd_server;
 
-use std::any::Any;
-
-use std::io::BufRead;
+use std::io::{BufRead, BufReader};
 use std::io::Write;
 use std::collections::HashMap;
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
 
 use std::rc::Rc;
 use std::cell::RefCell;
 use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{Sender, Receiver, TryRecvError};
 
-use timely_communication::Allocator;
 use timely::PartialOrder;
-use timely::dataflow::scopes::{Root, Child};
-use timely::dataflow::operators::probe::Handle as ProbeHandle;
 use timely::dataflow::operators::Unary;
 use timely::dataflow::channels::pact::Exchange;
 use timely::dataflow::operators::Probe;
 
-use libloading::{Library, Symbol};
-
-use dd_server::RootTime;
+use libloading::Library;
+
+use dd_server::plan::Plan;
+use dd_server::{Build, Environment, Token, TraceRegistry, InputRegistry, OutputRegistry, VERSION, VERSION_SYMBOL};
+
+/// The address clients connect to, unless `--listen <address>` is supplied.
+const DEFAULT_ADDRESS: &'static str = "127.0.0.1:8000";
+
+/// A command, and the identifier of the request that submitted it.
+type Command = (usize, Vec<String>);
+
+/// Flags for the subscriptions of connected clients, by request identifier.
+///
+/// A flag is cleared, and removed from the map, once its client disconnects or its request fails; workers then
+/// remove the subscribers they hold for the request.
+type Subscriptions = Arc<Mutex<HashMap<usize, Arc<AtomicBool>>>>;
+
+/// A dataflow loaded from a shared library or built from a query, and bound to a name.
+struct Dataflow {
+    /// The library, symbol, and arguments used to build the dataflow, or the plan of a query.
+    command: Vec<String>,
+    /// Cancelled when the dataflow is dropped.
+    token: Token,
+    /// Names of the traces the dataflow registered, released when it is dropped.
+    traces: Vec<String>,
+    /// Names of the inputs the dataflow registered, closed when it is dropped.
+    inputs: Vec<String>,
+    /// Names of the outputs the dataflow published, removed when it is dropped.
+    outputs: Vec<String>,
+}
+
+/// A worker's response to a command.
+struct Response {
+    /// The index of the responding worker.
+    worker: usize,
+    /// The number of workers, each of which responds to each command.
+    peers: usize,
+    /// Lines of output, for example from `list`.
+    output: Vec<String>,
+    /// Errors encountered while performing the command.
+    errors: Vec<String>,
+}
+
+/// Messages to the thread that returns responses to clients.
+enum Message {
+    /// A new request, the writer its responses should be directed to, and its subscription flag if it subscribes
+    /// to updates.
+    Request(usize, Box<Write+Send>, Option<Arc<AtomicBool>>),
+    /// A worker's response to a request.
+    Response(usize, Response),
+    /// Updates from a worker to an output, for the subscription established by a request.
+    Update(usize, usize, Vec<String>),
+    /// The workers have completed; outstanding requests fail, and no further messages are delivered.
+    Close,
+}
 
 fn main() {
 
+    // extract the address to listen on, leaving the remaining arguments for timely.
+    let mut args: Vec<String> = std::env::args().collect();
+    let address = match args.iter().position(|arg| arg == "--listen") {
+        Some(index) if index + 1 < args.len() => { args.remove(index); args.remove(index) },
+        _ => DEFAULT_ADDRESS.to_owned(),
+    };
+
+    // clients may load and run arbitrary code, so only local clients are accepted unless `--allow-remote` is supplied.
+    let allow_remote = match args.iter().position(|arg| arg == "--allow-remote") {
+        Some(index) => { args.remove(index); true },
+        None => false,
+    };
+    if !allow_remote {
+        let mut addresses = address.to_socket_addrs().unwrap_or_else(|error| panic!("failed to resolve address {:?}: {}", address, error));
+        if !addresses.all(|resolved| resolved.ip().is_loopback()) {
+            eprintln!("refusing to listen on non-loopback address {:?}, as any client may run code in the server; pass --allow-remote to accept remote clients", address);
+            std::process::exit(1);
+        }
+    }
+
     let (send, recv) = std::sync::mpsc::channel();
     let recv = Arc::new(Mutex::new(recv));
 
+    let (route, routes) = std::sync::mpsc::channel();
+    let respond = Arc::new(Mutex::new(route.clone()));
+
+    // set once `exit` has been received, after which no further commands are accepted.
+    let closed = Arc::new(AtomicBool::new(false));
+
+    // flags of subscriptions, registered by clients, consulted by workers, and cleared by the delivery thread.
+    let subscriptions: Subscriptions = Arc::new(Mutex::new(HashMap::new()));
+    let subscriptions2 = subscriptions.clone();
+
     // demonstrate dynamic loading of dataflows via shared libraries.
-    let guards = timely::execute_from_args(std::env::args(), move |worker| {
+    let guards = timely::execute_from_args(args.into_iter(), move |worker| {
 
         let recv = recv.clone();
+        let closed = closed.clone();
+        let respond: Sender<Message> = respond.lock().unwrap().clone();
         let timer = ::std::time::Instant::now();
 
-        // map from string name to arranged graph.
-        let mut handles = HashMap::new();
+        // maps from string name to shared trace, input, and output.
+        let mut traces = TraceRegistry::new();
+        let mut inputs = InputRegistry::new();
+        let mut outputs = OutputRegistry::new();
+
+        // map from string name to loaded dataflow.
+        let mut dataflows = HashMap::<String, Dataflow>::new();
+        let mut unnamed = 0;
 
         // common probe used by all dataflows to express progress information.
         let mut probe = timely::dataflow::operators::probe::Handle::new();
@@ -62,34 +150,55 @@ fn main() {
             let mut recvd = Vec::new();
 
             // a source that attempts to pull from `recv` and produce commands for everyone
-            timely::dataflow::operators::generic::source(dataflow, "InputCommands", move |mut capability| {
+            timely::dataflow::operators::generic::source(dataflow, "InputCommands", move |capability| {
+
+                let mut capability = Some(capability);
 
                 // closure broadcasts any commands it grabs.
                 move |output| {
 
-                    // determine current nanoseconds
-                    let elapsed = timer.elapsed();
-                    let ns = (elapsed.as_secs() * 1000000000 + elapsed.subsec_nanos() as u64) as usize;
-
-                    // this could be less frequent if needed.
-                    let mut time = capability.time().clone();
-                    time.inner = ns;
-
-                    capability.downgrade(&time);
-
-                    if let Ok(recv) = recv.try_lock() {
-                        while let Ok(command) = recv.try_recv() {
-                            let command: Vec<String> = command;
-                            let mut session = output.session(&capability);
-                            for worker_index in 0 .. peers {
-                                session.give((worker_index, command.clone()));
+                    if let Some(ref mut capability) = capability {
+
+                        // determine current nanoseconds
+                        let elapsed = timer.elapsed();
+                        let ns = (elapsed.as_secs() * 1000000000 + elapsed.subsec_nanos() as u64) as usize;
+
+                        // this could be less frequent if needed.
+                        let mut time = capability.time().clone();
+                        time.inner = ns;
+
+                        capability.downgrade(&time);
+
+                        if let Ok(recv) = recv.try_lock() {
+                            // commands after `exit` are left queued, and fail once the workers complete.
+                            // `closed` is checked with the lock held, as another worker may have just set it.
+                            while !closed.load(Ordering::SeqCst) {
+                                match recv.try_recv() {
+                                    Ok(command) => {
+                                        let command: Command = command;
+                                        if command.1[0] == "exit" {
+                                            closed.store(true, Ordering::SeqCst);
+                                        }
+                                        let mut session = output.session(&capability);
+                                        for worker_index in 0 .. peers {
+                                            session.give((worker_index, command.clone()));
+                                        }
+                                    },
+                                    Err(TryRecvError::Empty) => break,
+                                    Err(TryRecvError::Disconnected) => closed.store(true, Ordering::SeqCst),
+                                }
                             }
                         }
                     }
+
+                    // no more commands will be accepted, and the dataflow may complete.
+                    if closed.load(Ordering::SeqCst) {
+                        capability = None;
+                    }
                 }
 
             })
-            .unary_notify(Exchange::new(|x: &(usize, Vec<String>)| x.0 as u64), "InputCommandsRecv", Vec::new(), move |input, output, notificator| {
+            .unary_notify(Exchange::new(|x: &(usize, Command)| x.0 as u64), "InputCommandsRecv", Vec::new(), move |input, output, notificator| {
 
                 // grab each command and queue it up
                 input.for_each(|time, data| {
@@ -113,87 +222,449 @@ fn main() {
         });
 
 
-        loop {
+        let mut running = true;
+        while running {
 
             if let Ok(mut borrow) = command_queue2.try_borrow_mut() {
-                while let Some((_time, command)) = borrow.pop_front() {
+                while let Some((time, (id, mut command))) = borrow.pop_front() {
 
                     let index = worker.index();
                     println!("worker {:?}: received command: {:?}", index, command);
 
-                    if command.len() >= 2 {
+                    let mut response = Response { worker: index, peers: worker.peers(), output: Vec::new(), errors: Vec::new() };
 
-                        let lib_path = &command[0];
-                        let sym_name = &command[1];
+                    let verb = command.remove(0);
+                    match verb.as_str() {
+                        "bind" | "load" => {
 
-                        // try to open the shared library
-                        if let Ok(lib) = Library::new(lib_path) {
+                            // `load` binds the dataflow to a name of our choosing.
+                            let name = if verb == "bind" && command.len() > 0 { command.remove(0) }
+                                       else { unnamed += 1; format!("dataflow{}", unnamed) };
 
-                            // look up the symbol in the shared library
-                            worker.dataflow_using(lib, |lib, child| {
-                                unsafe {
-                                    // if let Ok(func) = lib.get::<Symbol<unsafe extern fn(Environment)>>(sym_name.as_bytes()) {
-                                    if let Ok(func) = lib.get::<Symbol<unsafe extern fn(&mut Child<Root<Allocator>,usize>, &mut HashMap<String, Box<Any>>, &mut ProbeHandle<RootTime>, &[String])>>(sym_name.as_bytes()) {
-                                        // func((child, handles, probe, args))
-                                        func(child, &mut handles, &mut probe, &command[2..])
+                            if dataflows.contains_key(&name) {
+                                response.errors.push(format!("name already bound: {:?}", name));
+                            }
+                            else if command.len() >= 2 {
+
+                                let lib_path = &command[0];
+                                let sym_name = &command[1];
+
+                                let token = Token::new();
+                                let existing = (traces.names(), inputs.names(), outputs.names());
+                                let mut built = false;
+
+                                // try to open the shared library
+                                if let Ok(lib) = Library::new(lib_path) {
+
+                                    // libraries built against other versions may misinterpret the environment.
+                                    let version = unsafe { lib.get::<*const u64>(VERSION_SYMBOL).ok().map(|symbol| **symbol) };
+                                    match version {
+                                        Some(version) if version == VERSION => {
+
+                                            // look up the symbol in the shared library
+                                            let errors = &mut response.errors;
+                                            worker.dataflow_using(lib, |lib, child| {
+                                                unsafe {
+                                                    if let Ok(func) = lib.get::<Build>(sym_name.as_bytes()) {
+                                                        let result = func(Environment {
+                                                            dataflow: child,
+                                                            traces: &mut traces,
+                                                            inputs: &mut inputs,
+                                                            outputs: &mut outputs,
+                                                            probe: &mut probe,
+                                                            token: &token,
+                                                            args: &command[2..],
+                                                        });
+                                                        match result {
+                                                            Ok(()) => built = true,
+                                                            Err(error) => errors.push(error),
+                                                        }
+                                                    }
+                                                    else { errors.push(format!("failed to find symbol {:?} in shared library {:?}", sym_name, lib_path)); }
+                                                }
+                                            });
+                                        },
+                                        Some(version) => {
+                                            response.errors.push(format!("shared library {:?} was built against version {}, but the server is version {}", lib_path, version, VERSION));
+                                        },
+                                        None => {
+                                            response.errors.push(format!("shared library {:?} does not declare a version; use `export_version!()`", lib_path));
+                                        },
                                     }
-                                    else { println!("worker {:?}: failed to find symbol {:?} in shared library {:?}.", index, sym_name, lib_path); }
                                 }
-                            });
-
-                        }
-                        else { println!("worker {:?}: failed to open shared library: {:?}", index, lib_path); }
+                                else { response.errors.push(format!("failed to open shared library: {:?}", lib_path)); }
+
+                                if built {
+                                    // the dataflow owns the traces, inputs, and outputs it registered.
+                                    let dataflow = Dataflow {
+                                        command: command.clone(),
+                                        token: token,
+                                        traces: added(&existing.0, traces.names()),
+                                        inputs: added(&existing.1, inputs.names()),
+                                        outputs: added(&existing.2, outputs.names()),
+                                    };
+                                    response.output.push(format!("bound dataflow {:?}", name));
+                                    dataflows.insert(name, dataflow);
+                                }
+                                else {
+                                    // release anything the library registered before it failed.
+                                    token.cancel();
+                                    for trace in added(&existing.0, traces.names()).iter() {
+                                        traces.remove(trace);
+                                    }
+                                    for input in added(&existing.1, inputs.names()).iter() {
+                                        inputs.remove(input);
+                                    }
+                                    for output in added(&existing.2, outputs.names()).iter() {
+                                        outputs.remove(output);
+                                    }
+                                }
+                            }
+                            else { response.errors.push(format!("expected a shared library and a symbol: {:?}", command)); }
+                        },
+                        "query" => {
+                            if command.len() >= 2 {
+                                let name = command.remove(0);
+                                if dataflows.contains_key(&name) {
+                                    response.errors.push(format!("name already bound: {:?}", name));
+                                }
+                                else if outputs.names().contains(&name) {
+                                    response.errors.push(format!("output already published: {:?}", name));
+                                }
+                                else {
+                                    match command.join(" ").parse::<Plan>() {
+                                        Ok(plan) => {
+                                            // the plan is checked before anything is added to the dataflow.
+                                            let token = Token::new();
+                                            let rendered = worker.dataflow(|scope| {
+                                                plan.render(scope, &mut traces, &token)
+                                                    .map(|rows| { outputs.publish(&name, &rows).probe_with(&mut probe); })
+                                            });
+                                            match rendered {
+                                                Ok(()) => {
+                                                    let dataflow = Dataflow {
+                                                        command: vec![plan.to_string()],
+                                                        token: token,
+                                                        traces: Vec::new(),
+                                                        inputs: Vec::new(),
+                                                        outputs: vec![name.clone()],
+                                                    };
+                                                    response.output.push(format!("bound query {:?}", name));
+                                                    dataflows.insert(name, dataflow);
+                                                },
+                                                Err(error) => response.errors.push(error),
+                                            }
+                                        },
+                                        Err(error) => response.errors.push(format!("failed to parse plan: {}", error)),
+                                    }
+                                }
+                            }
+                            else { response.errors.push(format!("expected a name and a plan: {:?}", command)); }
+                        },
+                        "list" => {
+
+                            let mut names: Vec<_> = dataflows.keys().cloned().collect();
+                            names.sort();
+                            for name in names.iter() {
+                                let dataflow = &dataflows[name];
+                                response.output.push(format!("dataflow {:?}: {}, traces: {:?}, inputs: {:?}, outputs: {:?}",
+                                                             name, dataflow.command.join(" "), dataflow.traces, dataflow.inputs, dataflow.outputs));
+                            }
 
+                            for name in traces.names().iter() {
+                                if let Some((types, stats)) = traces.describe(name) {
+                                    response.output.push(format!("trace {:?}: {}, {} batches, {} tuples, {} bytes, {} agents",
+                                                                 name, types, stats.batches, stats.size.tuples, stats.size.bytes, stats.agents));
+                                }
+                            }
+                            for name in inputs.names().iter() {
+                                response.output.push(format!("input {:?}", name));
+                            }
+                            for name in outputs.names().iter() {
+                                response.output.push(format!("output {:?}", name));
+                            }
+                        },
+                        "drop" => {
+                            for name in command.iter() {
+                                if let Some(dataflow) = dataflows.remove(name) {
+                                    // releasing our handles allows the traces to compact, or be discarded.
+                                    dataflow.token.cancel();
+                                    for trace in dataflow.traces.iter() {
+                                        traces.remove(trace);
+                                    }
+                                    for input in dataflow.inputs.iter() {
+                                        inputs.remove(input);
+                                    }
+                                    for output in dataflow.outputs.iter() {
+                                        outputs.remove(output);
+                                    }
+                                }
+                                else {
+                                    response.errors.push(format!("failed to find dataflow: {:?}", name));
+                                }
+                            }
+                        },
+                        "update" => {
+                            if command.len() > 0 {
+                                // every worker advances the input, but only the first introduces the updates.
+                                if let Err(error) = inputs.apply(&command[0], time.inner, &command[1..], index == 0) {
+                                    response.errors.push(error);
+                                }
+                            }
+                            else { response.errors.push("expected an input name".to_owned()); }
+                        },
+                        "subscribe" => {
+                            if command.len() == 1 {
+                                // the flag is registered before the command is submitted, and is not removed
+                                // before every worker has responded to the request.
+                                let live = subscriptions2.lock().unwrap().get(&id).cloned();
+                                if let Some(live) = live {
+                                    let respond = respond.clone();
+                                    let subscriber = Box::new(move |lines: &[String]| {
+                                        // the subscriber is removed once its client disconnects.
+                                        live.load(Ordering::SeqCst) && respond.send(Message::Update(id, index, lines.to_vec())).is_ok()
+                                    });
+                                    if let Err(error) = outputs.subscribe(&command[0], subscriber) {
+                                        response.errors.push(error);
+                                    }
+                                }
+                                else { response.errors.push("subscription closed".to_owned()); }
+                            }
+                            else { response.errors.push(format!("expected an output name: {:?}", command)); }
+                        },
+                        "exit" => {
+                            running = false;
+                        },
+                        _ => {
+                            response.errors.push(format!("unrecognized command: {:?}", verb));
+                        },
                     }
+
+                    respond.send(Message::Response(id, response)).expect("failed to send response");
                 }
             }
 
-            // arguably we should pick a time (now) and `step_while` until it has passed. 
+            // arguably we should pick a time (now) and `step_while` until it has passed.
             // this should ensure that we actually fully drain ranges of updates, rather
             // than providing no guaranteed progress for e.g. iterative computations.
 
             worker.step();
             std::thread::yield_now();   // so that over-subscribed worker counts still feel interactive
         }
+
+        // stop all dataflows and release all traces, so that the worker can complete its dataflows.
+        for (_name, dataflow) in dataflows.drain() {
+            dataflow.token.cancel();
+        }
+        traces.clear();
+        inputs.clear();
+        outputs.clear();
     });
 
-    // print!("> ");
-    std::io::stdout().flush().unwrap();
-    let input = std::io::stdin();
-    for line in input.lock().lines().map(|x| x.unwrap()) {
-        let mut elts: Vec<_> = line.split_whitespace().map(|x| x.to_owned()).collect();
+    // return each worker's responses to the client that submitted the request.
+    let delivery = {
+        let subscriptions = subscriptions.clone();
+        std::thread::spawn(move || deliver(routes, &subscriptions))
+    };
+
+    // accept clients, each of which submits commands one line at a time.
+    let listener = TcpListener::bind(&address[..]).expect("failed to bind address");
+    let next_id = Arc::new(AtomicUsize::new(0));
+    {
+        let send = send.clone();
+        let route = route.clone();
+        let next_id = next_id.clone();
+        let subscriptions = subscriptions.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                if let Ok(stream) = stream {
+                    let send = send.clone();
+                    let route = route.clone();
+                    let next_id = next_id.clone();
+                    let subscriptions = subscriptions.clone();
+                    std::thread::spawn(move || serve(stream, &next_id, &send, &route, &subscriptions));
+                }
+            }
+        });
+    }
 
-        if elts.len() > 0 {
+    println!("listening for commands on {}", address);
 
-            let command = elts.remove(0);
+    // the terminal is also a client, whose responses are printed.
+    {
+        let route = route.clone();
+        std::thread::spawn(move || {
+            let input = std::io::stdin();
+            for line in input.lock().lines().map(|x| x.unwrap()) {
+                if !submit(&line, Box::new(std::io::stdout()), &next_id, &send, &route, &subscriptions) {
+                    break;
+                }
+            }
+        });
+    }
 
-            match command.as_str() {
-                "help" => {
-                    println!("valid commands are currently: bind, exit, help, list, load");
-                },
-                "bind" => {
-                    println!("ideally this would load and bind a library to some delightful name");
-                    println!("it doesn't do this yet");
-                },
-                "load" => {
-                    send.send(elts).expect("failed to send command");
-                },
-                "list" => {
-                    println!("this is where we would list loaded collections");
-                },
-                "exit" => {
-                    println!("gotta ^C, sorry.");
-                },
-                _ => { 
-                    println!("unrecognized command: {:?}", command); 
+    // the workers complete once any client has submitted `exit`.
+    guards.unwrap();
+
+    // fail requests the workers will not respond to, for example those submitted after `exit`.
+    route.send(Message::Close).expect("failed to close delivery");
+    delivery.join().expect("failed to join delivery thread");
+}
+
+/// The names in `names` that are not in `existing`, each of which is sorted.
+fn added(existing: &[String], names: Vec<String>) -> Vec<String> {
+    names.into_iter().filter(|name| existing.binary_search(name).is_err()).collect()
+}
+
+/// Submits each line read from `stream` as a command, until `exit` or the connection closes.
+fn serve(stream: TcpStream, next_id: &AtomicUsize, send: &Sender<Command>, route: &Sender<Message>, subscriptions: &Subscriptions) {
+    if let Ok(reader) = stream.try_clone() {
+        for line in BufReader::new(reader).lines() {
+            match (line, stream.try_clone()) {
+                (Ok(line), Ok(writer)) => {
+                    if !submit(&line, Box::new(writer), next_id, send, route, subscriptions) { break; }
                 },
+                _ => break,
             }
-
         }
-
-        // print!("> ");
-        std::io::stdout().flush().unwrap();
+    }
+}
+
+/// Submits a line of text as a command, directing the responses to `writer`.
+///
+/// Each request receives zero or more lines of the form `output <worker> <text>` or `error <worker> <text>`,
+/// followed by a single line `ok` if no errors were reported and `failed` otherwise. Errors not specific to
+/// a worker are reported by `server` in place of the worker index. A successful `subscribe` is followed by
+/// lines of the form `update <worker> <text>`, one for each update to the output, for as long as the client
+/// remains connected.
+///
+/// Returns `false` if the command was `exit`, after which the server shuts down.
+fn submit(line: &str, mut writer: Box<Write+Send>, next_id: &AtomicUsize, send: &Sender<Command>, route: &Sender<Message>, subscriptions: &Subscriptions) -> bool {
+
+    let elts: Vec<_> = line.split_whitespace().map(|x| x.to_owned()).collect();
+
+    if elts.len() > 0 {
+        match elts[0].as_str() {
+            "help" => {
+                let _ = writeln!(writer, "output server valid commands are currently: bind, drop, exit, help, list, load, query, subscribe, update");
+                let _ = writeln!(writer, "output server   bind <name> <library> <symbol> [args ..]");
+                let _ = writeln!(writer, "output server   drop <name> [names ..]");
+                let _ = writeln!(writer, "output server   list");
+                let _ = writeln!(writer, "output server   load <library> <symbol> [args ..]");
+                let _ = writeln!(writer, "output server   query <name> <plan>");
+                let _ = writeln!(writer, "output server   subscribe <output>");
+                let _ = writeln!(writer, "output server   update <input> [<data> <diff> ..]");
+                let _ = writeln!(writer, "ok");
+            },
+            "bind" | "drop" | "exit" | "list" | "load" | "query" | "subscribe" | "update" => {
+                let exit = elts[0] == "exit";
+                let id = next_id.fetch_add(1, Ordering::SeqCst);
+                let live = if elts[0] == "subscribe" {
+                    let live = Arc::new(AtomicBool::new(true));
+                    subscriptions.lock().unwrap().insert(id, live.clone());
+                    Some(live)
+                }
+                else { None };
+                // register the request before any worker can respond to it.
+                route.send(Message::Request(id, writer, live)).expect("failed to send request");
+                send.send((id, elts)).expect("failed to send command");
+                return !exit;
+            },
+            _ => {
+                let _ = writeln!(writer, "error server unrecognized command: {:?}", elts[0]);
+                let _ = writeln!(writer, "failed");
+            },
+        }
+        let _ = writer.flush();
     }
 
-    guards.unwrap();
-}
\ No newline at end of file
+    true
+}
+
+/// Writes worker responses and output updates to the clients awaiting them.
+///
+/// Clears the flag of each subscription whose request fails or whose client disconnects, and removes it from
+/// `flags`.
+fn deliver(routes: Receiver<Message>, flags: &Subscriptions) {
+
+    // writers for outstanding requests, with the number of responses, whether any reported errors, and
+    // the subscription flag if the request subscribes to updates.
+    let mut pending = HashMap::new();
+
+    // writers for established subscriptions, with their flags, removed once their clients disconnect.
+    let mut subscriptions = HashMap::<usize, (Box<Write+Send>, Arc<AtomicBool>)>::new();
+
+    // clears the flag of a subscription, so that workers remove its subscribers.
+    let close = |id: usize, live: Arc<AtomicBool>| {
+        live.store(false, Ordering::SeqCst);
+        flags.lock().unwrap().remove(&id);
+    };
+
+    for message in routes {
+        match message {
+            Message::Request(id, writer, live) => {
+                pending.insert(id, (writer, 0, false, live));
+            },
+            Message::Response(id, response) => {
+
+                let complete = if let Some(&mut (ref mut writer, ref mut responses, ref mut failed, _)) = pending.get_mut(&id) {
+                    for line in response.output.iter() {
+                        let _ = writeln!(writer, "output {} {}", response.worker, line);
+                    }
+                    for line in response.errors.iter() {
+                        let _ = writeln!(writer, "error {} {}", response.worker, line);
+                    }
+                    *failed = *failed || response.errors.len() > 0;
+                    *responses += 1;
+                    *responses == response.peers
+                }
+                else { false };
+
+                if complete {
+                    if let Some((mut writer, _, failed, live)) = pending.remove(&id) {
+                        let _ = writeln!(writer, "{}", if failed { "failed" } else { "ok" });
+                        let _ = writer.flush();
+                        if let Some(live) = live {
+                            if failed { close(id, live); }
+                            else { subscriptions.insert(id, (writer, live)); }
+                        }
+                    }
+                }
+            },
+            Message::Update(id, worker, lines) => {
+
+                // updates may arrive before all workers have responded to the subscription.
+                let disconnected = {
+                    let writer = match pending.get_mut(&id) {
+                        Some(&mut (ref mut writer, _, _, _)) => Some(writer),
+                        None => subscriptions.get_mut(&id).map(|&mut (ref mut writer, _)| writer),
+                    };
+                    if let Some(writer) = writer {
+                        let mut result = Ok(());
+                        for line in lines.iter() {
+                            if result.is_ok() {
+                                result = writeln!(writer, "update {} {}", worker, line);
+                            }
+                        }
+                        result.and_then(|_| writer.flush()).is_err()
+                    }
+                    else { false }
+                };
+
+                if disconnected {
+                    if let Some((_writer, live)) = subscriptions.remove(&id) {
+                        close(id, live);
+                    }
+                }
+            },
+            Message::Close => {
+                for (_id, (mut writer, _, _, _)) in pending.drain() {
+                    let _ = writeln!(writer, "error server the server has shut down");
+                    let _ = writeln!(writer, "failed");
+                    let _ = writer.flush();
+                }
+                return;
+            },
+        }
+    }
+}
@@ -1,35 +1,304 @@
+#![feature(core_intrinsics)]
+
 extern crate timely;
 extern crate timely_communication;
 extern crate differential_dataflow;
 
+pub mod plan;
+
+use std::any::Any;
 use std::rc::Rc;
+use std::cell::{Cell, RefCell};
 use std::collections::HashMap;
+use std::str::FromStr;
 
 use timely_communication::Allocator;
+use timely::dataflow::Scope;
 use timely::dataflow::scopes::{Child, Root};
 use timely::dataflow::operators::probe::Handle as ProbeHandle;
 
 // stuff for talking about shared trace types ...
-use differential_dataflow::hashable::UnsignedWrapper;
-use differential_dataflow::operators::arrange::TraceAgent;
+use differential_dataflow::{Collection, Data, Diff};
+use differential_dataflow::input::InputSession;
+use differential_dataflow::hashable::{HashOrdered, UnsignedWrapper};
+use differential_dataflow::operators::arrange::{TraceAgent, TraceStats};
 use differential_dataflow::trace::implementations::spine::Spine;
 use differential_dataflow::trace::implementations::ord::OrdValBatch;
 
 pub type RootTime = timely::progress::nested::product::Product<timely::progress::timestamp::RootTimestamp, usize>;
-type TraceBatch = OrdValBatch<UnsignedWrapper<usize>, usize, RootTime, isize>;
-type TraceSpine = Spine<UnsignedWrapper<usize>, usize, RootTime, isize, Rc<TraceBatch>>;
-pub type TraceHandle = TraceAgent<UnsignedWrapper<usize>, usize, RootTime, isize, TraceSpine>;
-
-// &mut Child<Root<Allocator>,usize>, 
-//     handles: &mut HashMap<String, TraceHandle>, 
-//     probe: &mut ProbeHandle<RootTime>,
-//     args: &[String]) 
-
-pub type Environment<'a, 'b> = (&'a mut Child<'b, Root<Allocator>,usize>, &'a mut HashMap<String, TraceHandle>, &'a mut ProbeHandle<RootTime>, &'a [String]);
-
-// pub struct Environment<'a, 'b> {
-//     pub worker: &'a mut Child<'b, Root<Allocator>,usize>,
-//     pub handles: &'a mut HashMap<String, TraceHandle>,
-//     pub probe: &'a mut ProbeHandle<RootTime>,
-//     pub args: &'a [String],
-// }
\ No newline at end of file
+pub type TraceBatch<K, V, R> = OrdValBatch<K, V, RootTime, R>;
+pub type TraceSpine<K, V, R> = Spine<K, V, RootTime, R, Rc<TraceBatch<K, V, R>>>;
+
+/// A shared trace of `(K, V)` pairs with differences of type `R`.
+pub type SharedTrace<K, V, R> = TraceAgent<K, V, RootTime, R, TraceSpine<K, V, R>>;
+
+/// A shared trace of graph edges, keyed by their source.
+pub type TraceHandle = SharedTrace<UnsignedWrapper<usize>, usize, isize>;
+
+/// The version of the interface between the server and the dataflows it loads.
+///
+/// This should be incremented whenever `Environment`, the registries, or the types they expose change, so
+/// that the server refuses libraries built against other versions rather than misinterpreting them.
+pub const VERSION: u64 = 3;
+
+/// The name of the symbol with which libraries declare the interface version they were built against.
+pub const VERSION_SYMBOL: &'static [u8] = b"DD_SERVER_VERSION";
+
+/// Declares the interface version the library is built against, which the server checks before loading it.
+#[macro_export]
+macro_rules! export_version {
+    () => {
+        #[no_mangle]
+        pub static DD_SERVER_VERSION: u64 = $crate::VERSION;
+    }
+}
+
+/// The signature of the functions libraries export to build dataflows.
+///
+/// Errors, for example from malformed arguments or missing traces, are reported to the client that loaded
+/// the library, and anything the library registered before failing is removed.
+pub type Build = fn(Environment) -> Result<(), String>;
+
+/// The environment in which a loaded library builds its dataflow.
+pub struct Environment<'a, 'b: 'a> {
+    /// The scope in which to build the dataflow.
+    pub dataflow: &'a mut Child<'b, Root<Allocator>, usize>,
+    /// Traces shared between dataflows.
+    pub traces: &'a mut TraceRegistry,
+    /// Inputs to which clients may supply updates.
+    pub inputs: &'a mut InputRegistry,
+    /// Outputs to which clients may subscribe.
+    pub outputs: &'a mut OutputRegistry,
+    /// A probe the dataflow should attach to its outputs, so that the worker can await them.
+    pub probe: &'a mut ProbeHandle<RootTime>,
+    /// Cancelled when the dataflow should stop.
+    pub token: &'a Token,
+    /// Arguments supplied with the command that loaded the library.
+    pub args: &'a [String],
+}
+
+/// Indicates whether a loaded dataflow should keep running.
+///
+/// The server cancels a dataflow's token when the dataflow is dropped or the server shuts down. Dataflows
+/// with sources that would otherwise run forever should check the token, and release their capabilities
+/// once it has been cancelled so that the dataflow can complete.
+#[derive(Clone)]
+pub struct Token {
+    alive: Rc<Cell<bool>>,
+}
+
+impl Token {
+    /// Creates a new live token.
+    pub fn new() -> Self { Token { alive: Rc::new(Cell::new(true)) } }
+    /// Returns `true` until the token has been cancelled.
+    pub fn alive(&self) -> bool { self.alive.get() }
+    /// Cancels the token, and all of its clones.
+    pub fn cancel(&self) { self.alive.set(false); }
+}
+
+/// Named traces of various key, value, and difference types, shared between dataflows.
+///
+/// Traces are checked against the requested types on lookup, which reports an error describing both the
+/// registered and the requested types if they differ.
+pub struct TraceRegistry {
+    traces: HashMap<String, Registered>,
+}
+
+/// A trace whose type has been erased, and what we need to know about it without its type.
+struct Registered {
+    trace: Box<Any>,
+    types: String,
+    stats: fn(&Any) -> TraceStats,
+}
+
+impl TraceRegistry {
+    /// Creates a new empty registry.
+    pub fn new() -> Self { TraceRegistry { traces: HashMap::new() } }
+    /// Registers `trace` under `name`, replacing any trace previously registered under that name.
+    pub fn insert<K, V, R>(&mut self, name: &str, trace: SharedTrace<K, V, R>)
+    where K: Ord+Clone+HashOrdered+'static, V: Ord+Clone+'static, R: Diff {
+        let registered = Registered {
+            trace: Box::new(trace),
+            types: types::<K, V, R>(),
+            stats: stats::<K, V, R>,
+        };
+        self.traces.insert(name.to_owned(), registered);
+    }
+    /// Returns the trace registered under `name`, if it exists and has the requested types.
+    pub fn get_mut<K, V, R>(&mut self, name: &str) -> Result<&mut SharedTrace<K, V, R>, String>
+    where K: Ord+Clone+HashOrdered+'static, V: Ord+Clone+'static, R: Diff {
+        if let Some(registered) = self.traces.get_mut(name) {
+            let Registered { ref mut trace, ref types, .. } = *registered;
+            trace.downcast_mut::<SharedTrace<K, V, R>>()
+                 .ok_or_else(|| format!("trace {:?} has types {}, but types {} were requested", name, types, self::types::<K, V, R>()))
+        }
+        else {
+            Err(format!("failed to find trace: {:?}", name))
+        }
+    }
+    /// Removes the trace registered under `name`, returning `true` if it existed.
+    pub fn remove(&mut self, name: &str) -> bool { self.traces.remove(name).is_some() }
+    /// Removes all traces.
+    pub fn clear(&mut self) { self.traces.clear(); }
+    /// The names of all registered traces, in sorted order.
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<_> = self.traces.keys().cloned().collect();
+        names.sort();
+        names
+    }
+    /// Describes the types of the trace registered under `name`, and reports its statistics.
+    pub fn describe(&self, name: &str) -> Option<(&str, TraceStats)> {
+        self.traces.get(name).map(|registered| (&registered.types[..], (registered.stats)(&*registered.trace)))
+    }
+}
+
+/// Describes the key, value, and difference types of a trace.
+fn types<K, V, R>() -> String {
+    unsafe {
+        format!("({}, {}, {})", ::std::intrinsics::type_name::<K>(), ::std::intrinsics::type_name::<V>(), ::std::intrinsics::type_name::<R>())
+    }
+}
+
+/// Reports the statistics of a type-erased trace.
+fn stats<K, V, R>(trace: &Any) -> TraceStats
+where K: Ord+Clone+HashOrdered+'static, V: Ord+Clone+'static, R: Diff {
+    trace.downcast_ref::<SharedTrace<K, V, R>>()
+         .map(|trace| trace.stats())
+         .unwrap_or_default()
+}
+
+/// Named inputs of loaded dataflows, to which clients may supply updates.
+///
+/// Updates arrive as text, and so the data and difference types of the inputs must implement `FromStr`.
+pub struct InputRegistry {
+    inputs: HashMap<String, Box<ApplyUpdates>>,
+}
+
+/// Applies updates to an input whose type has been erased.
+trait ApplyUpdates {
+    /// Parses pairs of data and differences from `words`, introduces them at `time` if `apply` is set, and
+    /// advances the input beyond `time`.
+    fn apply(&mut self, time: usize, words: &[String], apply: bool) -> Result<(), String>;
+}
+
+impl<D: Data+FromStr, R: Diff+FromStr> ApplyUpdates for InputSession<usize, D, R> {
+    fn apply(&mut self, time: usize, words: &[String], apply: bool) -> Result<(), String> {
+
+        if words.len() % 2 != 0 {
+            return Err(format!("expected pairs of data and differences: {:?}", words));
+        }
+
+        // parse all updates before introducing any of them.
+        let mut updates = Vec::with_capacity(words.len() / 2);
+        for pair in words.chunks(2) {
+            let data = pair[0].parse::<D>().map_err(|_| format!("failed to parse data: {:?}", pair[0]))?;
+            let diff = pair[1].parse::<R>().map_err(|_| format!("failed to parse difference: {:?}", pair[1]))?;
+            updates.push((data, diff));
+        }
+
+        // the input may already have advanced beyond `time`, if it was created after the command was issued.
+        let time = ::std::cmp::max(time, *self.epoch());
+        self.advance_to(time);
+        if apply {
+            for (data, diff) in updates {
+                self.update(data, diff);
+            }
+        }
+        self.advance_to(time + 1);
+        self.flush();
+        Ok(())
+    }
+}
+
+impl InputRegistry {
+    /// Creates a new empty registry.
+    pub fn new() -> Self { InputRegistry { inputs: HashMap::new() } }
+    /// Registers `input` under `name`, replacing any input previously registered under that name.
+    pub fn insert<D: Data+FromStr, R: Diff+FromStr>(&mut self, name: &str, input: InputSession<usize, D, R>) {
+        self.inputs.insert(name.to_owned(), Box::new(input));
+    }
+    /// Parses updates from `words` and applies them to the input registered under `name` at `time`.
+    ///
+    /// Each worker should call this for each command, so that all workers advance their inputs, but only one
+    /// worker should set `apply` so that the updates are introduced only once.
+    pub fn apply(&mut self, name: &str, time: usize, words: &[String], apply: bool) -> Result<(), String> {
+        if let Some(input) = self.inputs.get_mut(name) {
+            input.apply(time, words, apply)
+        }
+        else {
+            Err(format!("failed to find input: {:?}", name))
+        }
+    }
+    /// Removes the input registered under `name`, closing it, and returns `true` if it existed.
+    pub fn remove(&mut self, name: &str) -> bool { self.inputs.remove(name).is_some() }
+    /// Removes and closes all inputs.
+    pub fn clear(&mut self) { self.inputs.clear(); }
+    /// The names of all registered inputs, in sorted order.
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<_> = self.inputs.keys().cloned().collect();
+        names.sort();
+        names
+    }
+}
+
+/// Receives the updates of an output, each formatted as text.
+///
+/// Returns `false` once it no longer wants updates, for example because its client has disconnected, after which
+/// it is removed from the output.
+pub type Subscriber = Box<Fn(&[String])->bool>;
+
+/// Named outputs of loaded dataflows, to which clients may subscribe.
+pub struct OutputRegistry {
+    outputs: HashMap<String, Rc<RefCell<Vec<Subscriber>>>>,
+}
+
+impl OutputRegistry {
+    /// Creates a new empty registry.
+    pub fn new() -> Self { OutputRegistry { outputs: HashMap::new() } }
+    /// Publishes the updates of `collection` under `name`, replacing any output previously published under that name.
+    ///
+    /// Each update is formatted using `Debug` and reported to the subscribers of the output at the time the
+    /// update is produced; subscribers do not receive updates produced before they subscribed.
+    pub fn publish<G: Scope, D: Data, R: Diff>(&mut self, name: &str, collection: &Collection<G, D, R>) -> Collection<G, D, R>
+    where G::Timestamp: Data {
+        let subscribers = Rc::new(RefCell::new(Vec::<Subscriber>::new()));
+        self.outputs.insert(name.to_owned(), subscribers.clone());
+        collection.inspect_batch(move |_time, updates| {
+            let mut subscribers = subscribers.borrow_mut();
+            if subscribers.len() > 0 {
+                let lines: Vec<String> = updates.iter().map(|update| format!("{:?}", update)).collect();
+                subscribers.retain(|subscriber| subscriber(&lines[..]));
+            }
+        })
+    }
+    /// Adds `subscriber` to the output published under `name`.
+    pub fn subscribe(&mut self, name: &str, subscriber: Subscriber) -> Result<(), String> {
+        if let Some(subscribers) = self.outputs.get(name) {
+            subscribers.borrow_mut().push(subscriber);
+            Ok(())
+        }
+        else {
+            Err(format!("failed to find output: {:?}", name))
+        }
+    }
+    /// Removes the output published under `name`, and its subscribers, returning `true` if it existed.
+    pub fn remove(&mut self, name: &str) -> bool {
+        if let Some(subscribers) = self.outputs.remove(name) {
+            subscribers.borrow_mut().clear();
+            true
+        }
+        else { false }
+    }
+    /// Removes all outputs and their subscribers.
+    pub fn clear(&mut self) {
+        for (_name, subscribers) in self.outputs.drain() {
+            subscribers.borrow_mut().clear();
+        }
+    }
+    /// The names of all published outputs, in sorted order.
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<_> = self.outputs.keys().cloned().collect();
+        names.sort();
+        names
+    }
+}
@@ -0,0 +1,853 @@
+//! Declarative dataflow plans, which the server interprets without compiling a library.
+//!
+//! A plan describes a computation over the traces registered with the server, each of whose records is
+//! presented as a row of the fields of its key followed by those of its value; the edges of a graph trace
+//! are rows `[src, dst]`. Plans are written as s-expressions, and may be submitted as text:
+//!
+//! ```text
+//! plan := (source <trace>)              the records of a registered trace, as rows [key .. val ..]
+//!       | (variable)                    the collection being iterated, within the body of `iterate`
+//!       | (map (<expr> ..) <plan>)      each row replaced by the values of the expressions
+//!       | (filter <pred> <plan>)        the rows satisfying the predicate
+//!       | (join <n> <plan> <plan>)      pairs of rows agreeing on their first n fields, as [key .. rest1 .. rest2 ..]
+//!       | (distinct <plan>)             each row once
+//!       | (count <plan>)                each row once, followed by its count
+//!       | (min <n> <plan>)              for each key of n fields, the least row with that key
+//!       | (max <n> <plan>)              for each key of n fields, the greatest row with that key
+//!       | (concat <plan> ..)            the rows of all plans
+//!       | (negate <plan>)               the rows of the plan, with negated multiplicities
+//!       | (iterate <plan> <plan>)       the fixed point of the second plan, starting from the first
+//!
+//! expr := <integer> | $<field> | (add <expr> <expr>) | (sub <expr> <expr>) | (mul <expr> <expr>)
+//! pred := (eq <expr> <expr>) | (ne ..) | (lt ..) | (le ..) | (gt ..) | (ge ..)
+//! ```
+//!
+//! For example, the nodes reachable from node zero in a graph registered as `graph` are
+//!
+//! ```text
+//! (iterate (distinct (map (0) (filter (eq $0 0) (source graph))))
+//!          (distinct (concat (variable) (map ($1) (join 1 (variable) (source graph))))))
+//! ```
+//!
+//! Iterations may not be nested. Plans are checked before they are rendered, so that field references and
+//! other mistakes are reported as errors rather than encountered while the dataflow runs.
+//!
+//! Plans read traces with differences `isize`, keys `UnsignedWrapper<usize>`, `OrdWrapper<usize>` or
+//! `OrdWrapper<isize>`, and values `usize`, `isize` or `()`. A join whose fields are exactly the key of a
+//! source uses the source's arrangement, rather than arranging its rows again.
+
+use std::fmt;
+use std::str::FromStr;
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use timely::dataflow::Scope;
+use timely::dataflow::scopes::Child;
+
+use differential_dataflow::{Collection, Data};
+use differential_dataflow::hashable::{Hashable, HashOrdered, OrdWrapper, UnsignedWrapper};
+use differential_dataflow::lattice::Lattice;
+use differential_dataflow::operators::{Join, JoinCore, Distinct, Count, Group, Iterate};
+use differential_dataflow::operators::arrange::{Arrange, Arranged};
+use differential_dataflow::trace::{BatchReader, Trace, TraceReader};
+use differential_dataflow::trace::implementations::ord::OrdValSpine;
+
+use {RootTime, SharedTrace, Token, TraceRegistry};
+
+/// The values contained in rows.
+pub type Value = isize;
+
+/// The records of the collections described by plans.
+pub type Row = Vec<Value>;
+
+/// An expression evaluated against a row.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Expr {
+    /// The value of a field of the row.
+    Field(usize),
+    /// A constant value.
+    Constant(Value),
+    /// The sum of two expressions.
+    Add(Box<Expr>, Box<Expr>),
+    /// The difference of two expressions.
+    Sub(Box<Expr>, Box<Expr>),
+    /// The product of two expressions.
+    Mul(Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+    /// Evaluates the expression against `row`.
+    ///
+    /// Arithmetic wraps on overflow, rather than panicking in the worker.
+    pub fn eval(&self, row: &[Value]) -> Value {
+        match *self {
+            Expr::Field(index) => row[index],
+            Expr::Constant(value) => value,
+            Expr::Add(ref e1, ref e2) => e1.eval(row).wrapping_add(e2.eval(row)),
+            Expr::Sub(ref e1, ref e2) => e1.eval(row).wrapping_sub(e2.eval(row)),
+            Expr::Mul(ref e1, ref e2) => e1.eval(row).wrapping_mul(e2.eval(row)),
+        }
+    }
+    /// Checks that the expression only refers to fields of rows with `arity` fields.
+    fn check(&self, arity: usize) -> Result<(), String> {
+        match *self {
+            Expr::Field(index) => {
+                if index < arity { Ok(()) }
+                else { Err(format!("field ${} does not exist in rows of {} fields", index, arity)) }
+            },
+            Expr::Constant(_) => Ok(()),
+            Expr::Add(ref e1, ref e2) |
+            Expr::Sub(ref e1, ref e2) |
+            Expr::Mul(ref e1, ref e2) => { e1.check(arity)?; e2.check(arity) },
+        }
+    }
+}
+
+/// A comparison between the values of two expressions.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Comparison {
+    /// Equal to.
+    Eq,
+    /// Not equal to.
+    Ne,
+    /// Less than.
+    Lt,
+    /// Less than or equal to.
+    Le,
+    /// Greater than.
+    Gt,
+    /// Greater than or equal to.
+    Ge,
+}
+
+/// A predicate comparing the values of two expressions.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Predicate {
+    /// The comparison to perform.
+    pub comparison: Comparison,
+    /// The left-hand side of the comparison.
+    pub left: Expr,
+    /// The right-hand side of the comparison.
+    pub right: Expr,
+}
+
+impl Predicate {
+    /// Evaluates the predicate against `row`.
+    pub fn eval(&self, row: &[Value]) -> bool {
+        let (left, right) = (self.left.eval(row), self.right.eval(row));
+        match self.comparison {
+            Comparison::Eq => left == right,
+            Comparison::Ne => left != right,
+            Comparison::Lt => left < right,
+            Comparison::Le => left <= right,
+            Comparison::Gt => left > right,
+            Comparison::Ge => left >= right,
+        }
+    }
+}
+
+/// A declarative description of a dataflow computation.
+///
+/// # Examples
+///
+/// ```
+/// use dd_server::plan::Plan;
+///
+/// // pairs of nodes connected by paths of length two.
+/// let text = "(distinct (map ($1 $2) (join 1 (map ($1 $0) (source graph)) (source graph))))";
+/// let plan = text.parse::<Plan>().unwrap();
+///
+/// assert_eq!(plan.arity(), Ok(2));
+/// assert_eq!(plan.to_string(), text);
+/// assert!("(map ($2) (source graph))".parse::<Plan>().unwrap().arity().is_err());
+/// ```
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Plan {
+    /// The edges of a registered graph trace, as rows `[src, dst]`.
+    Source(String),
+    /// The collection being iterated, within the body of `Iterate`.
+    Variable,
+    /// Each row replaced by the values of the expressions.
+    Map(Vec<Expr>, Box<Plan>),
+    /// The rows satisfying the predicate.
+    Filter(Predicate, Box<Plan>),
+    /// Pairs of rows agreeing on their first fields, as the shared fields followed by those of each row.
+    Join(usize, Box<Plan>, Box<Plan>),
+    /// Each distinct row once.
+    Distinct(Box<Plan>),
+    /// Each distinct row once, followed by its count.
+    Count(Box<Plan>),
+    /// For each key of the first fields, the least row with that key.
+    Min(usize, Box<Plan>),
+    /// For each key of the first fields, the greatest row with that key.
+    Max(usize, Box<Plan>),
+    /// The rows of all plans.
+    Concat(Vec<Plan>),
+    /// The rows of the plan, with negated multiplicities.
+    Negate(Box<Plan>),
+    /// The fixed point of the second plan, starting from the first.
+    Iterate(Box<Plan>, Box<Plan>),
+}
+
+impl Plan {
+
+    /// Checks that the plan is well formed, and returns the number of fields of each of its rows.
+    ///
+    /// Each source is taken to be a graph, whose rows are `[src, dst]`.
+    pub fn arity(&self) -> Result<usize, String> {
+        self.arity_with(&|_: &str| 2)
+    }
+
+    /// Checks that the plan is well formed, given the number of fields of the rows of each source.
+    pub fn arity_with(&self, sources: &Fn(&str) -> usize) -> Result<usize, String> {
+        self.arity_within(sources, None)
+    }
+
+    /// Checks the plan, given the arity of the variable of an enclosing iteration, if any.
+    fn arity_within(&self, sources: &Fn(&str) -> usize, variable: Option<usize>) -> Result<usize, String> {
+        match *self {
+            Plan::Source(ref name) => Ok(sources(name.as_str())),
+            Plan::Variable => variable.ok_or_else(|| "(variable) may only be used within (iterate ..)".to_owned()),
+            Plan::Map(ref exprs, ref plan) => {
+                let arity = plan.arity_within(sources, variable)?;
+                for expr in exprs.iter() {
+                    expr.check(arity)?;
+                }
+                Ok(exprs.len())
+            },
+            Plan::Filter(ref predicate, ref plan) => {
+                let arity = plan.arity_within(sources, variable)?;
+                predicate.left.check(arity)?;
+                predicate.right.check(arity)?;
+                Ok(arity)
+            },
+            Plan::Join(fields, ref plan1, ref plan2) => {
+                let arity1 = plan1.arity_within(sources, variable)?;
+                let arity2 = plan2.arity_within(sources, variable)?;
+                if fields > arity1 || fields > arity2 {
+                    Err(format!("cannot join on {} fields rows of {} and {} fields", fields, arity1, arity2))
+                }
+                else { Ok(arity1 + arity2 - fields) }
+            },
+            Plan::Distinct(ref plan) => plan.arity_within(sources, variable),
+            Plan::Count(ref plan) => Ok(plan.arity_within(sources, variable)? + 1),
+            Plan::Min(fields, ref plan) |
+            Plan::Max(fields, ref plan) => {
+                let arity = plan.arity_within(sources, variable)?;
+                if fields < arity { Ok(arity) }
+                else { Err(format!("cannot group by {} fields rows of {} fields", fields, arity)) }
+            },
+            Plan::Concat(ref plans) => {
+                let mut arities = Vec::new();
+                for plan in plans.iter() {
+                    arities.push(plan.arity_within(sources, variable)?);
+                }
+                match arities.first() {
+                    Some(&arity) if arities.iter().all(|&x| x == arity) => Ok(arity),
+                    Some(_) => Err(format!("cannot concatenate rows of differing arities: {:?}", arities)),
+                    None => Err("(concat ..) requires at least one plan".to_owned()),
+                }
+            },
+            Plan::Negate(ref plan) => plan.arity_within(sources, variable),
+            Plan::Iterate(ref initial, ref body) => {
+                if variable.is_some() {
+                    return Err("(iterate ..) may not be nested".to_owned());
+                }
+                let arity = initial.arity_within(sources, None)?;
+                let result = body.arity_within(sources, Some(arity))?;
+                if arity == result { Ok(arity) }
+                else { Err(format!("iteration starts with rows of {} fields, but produces rows of {} fields", arity, result)) }
+            },
+        }
+    }
+
+    /// The names of the traces the plan reads.
+    pub fn sources(&self) -> Vec<String> {
+        let mut sources = Vec::new();
+        self.collect_sources(&mut sources);
+        sources.sort();
+        sources.dedup();
+        sources
+    }
+
+    fn collect_sources(&self, sources: &mut Vec<String>) {
+        match *self {
+            Plan::Source(ref name) => sources.push(name.clone()),
+            Plan::Variable => { },
+            Plan::Map(_, ref plan) |
+            Plan::Filter(_, ref plan) |
+            Plan::Distinct(ref plan) |
+            Plan::Count(ref plan) |
+            Plan::Min(_, ref plan) |
+            Plan::Max(_, ref plan) |
+            Plan::Negate(ref plan) => plan.collect_sources(sources),
+            Plan::Join(_, ref plan1, ref plan2) |
+            Plan::Iterate(ref plan1, ref plan2) => {
+                plan1.collect_sources(sources);
+                plan2.collect_sources(sources);
+            },
+            Plan::Concat(ref plans) => {
+                for plan in plans.iter() {
+                    plan.collect_sources(sources);
+                }
+            },
+        }
+    }
+
+    /// Builds the plan in `scope`, reading the current contents of the traces it names from `traces`.
+    ///
+    /// The plan is checked, and its traces found, before anything is added to `scope`. The dataflow stops
+    /// reading the traces once `token` is cancelled, after which it can complete.
+    pub fn render<G: Scope<Timestamp=RootTime>>(&self, scope: &G, traces: &mut TraceRegistry, token: &Token) -> Result<Collection<G, Row>, String> {
+
+        let mut sources = HashMap::new();
+        for name in self.sources() {
+            let source = import(scope, traces, &name, token)?;
+            sources.insert(name, source);
+        }
+
+        self.arity_with(&|name: &str| sources[name].arity())?;
+
+        Ok(self.build(&Sources { sources: sources }, None))
+    }
+
+    /// Builds the plan, reading its sources and rendering iterations with `context`, given the variable of an
+    /// enclosing iteration, if any.
+    fn build<G: Scope, C: Context<G>>(&self, context: &C, variable: Option<&Collection<G, Row>>) -> Collection<G, Row>
+    where G::Timestamp: Lattice+Data {
+        match *self {
+            Plan::Source(ref name) => context.rows(name),
+            Plan::Variable => variable.expect("variable outside of iteration").clone(),
+            Plan::Map(ref exprs, ref plan) => {
+                let exprs = exprs.clone();
+                plan.build(context, variable)
+                    .map(move |row| exprs.iter().map(|expr| expr.eval(&row)).collect::<Row>())
+            },
+            Plan::Filter(ref predicate, ref plan) => {
+                let predicate = predicate.clone();
+                plan.build(context, variable)
+                    .filter(move |row| predicate.eval(row))
+            },
+            Plan::Join(fields, ref plan1, ref plan2) => {
+                // joins on the keys of a source use its arrangement, rather than arranging its rows again.
+                match (&**plan1, &**plan2) {
+                    (_, &Plan::Source(ref name)) if context.key_arity(name) == fields => {
+                        context.join(name, &plan1.build(context, variable), false)
+                    },
+                    (&Plan::Source(ref name), _) if context.key_arity(name) == fields => {
+                        context.join(name, &plan2.build(context, variable), true)
+                    },
+                    _ => {
+                        let rows1 = plan1.build(context, variable).map(move |row| split(row, fields));
+                        let rows2 = plan2.build(context, variable).map(move |row| split(row, fields));
+                        rows1.join_map(&rows2, |key, rest1, rest2| {
+                            let mut row = key.clone();
+                            row.extend(rest1.iter().cloned());
+                            row.extend(rest2.iter().cloned());
+                            row
+                        })
+                    },
+                }
+            },
+            Plan::Distinct(ref plan) => plan.build(context, variable).distinct(),
+            Plan::Count(ref plan) => {
+                plan.build(context, variable)
+                    .count()
+                    .map(|(mut row, count)| { row.push(count as Value); row })
+            },
+            Plan::Min(fields, ref plan) => {
+                plan.build(context, variable)
+                    .map(move |row| split(row, fields))
+                    .group(|_key, rest, output| output.push((rest[0].0.clone(), 1)))
+                    .map(|(mut key, rest)| { key.extend(rest); key })
+            },
+            Plan::Max(fields, ref plan) => {
+                plan.build(context, variable)
+                    .map(move |row| split(row, fields))
+                    .group(|_key, rest, output| output.push((rest[rest.len() - 1].0.clone(), 1)))
+                    .map(|(mut key, rest)| { key.extend(rest); key })
+            },
+            Plan::Concat(ref plans) => {
+                let mut rows = plans[0].build(context, variable);
+                for plan in plans[1..].iter() {
+                    rows = rows.concat(&plan.build(context, variable));
+                }
+                rows
+            },
+            Plan::Negate(ref plan) => plan.build(context, variable).negate(),
+            Plan::Iterate(ref initial, ref body) => context.iterate(&initial.build(context, variable), body),
+        }
+    }
+}
+
+/// Splits the first `fields` fields from `row`, as a key and the remaining fields.
+fn split(mut row: Row, fields: usize) -> (Row, Row) {
+    let rest = row.split_off(fields);
+    (row, rest)
+}
+
+/// Types whose values are presented as fields of rows, such as the keys and values of the traces plans read.
+pub trait Fields: Sized {
+    /// The number of fields of each value.
+    fn arity() -> usize;
+    /// Appends the fields of the value to `row`.
+    fn push_to(&self, row: &mut Row);
+    /// The value whose fields are `fields`, if there is one.
+    fn from_fields(fields: &[Value]) -> Option<Self>;
+}
+
+impl Fields for usize {
+    fn arity() -> usize { 1 }
+    fn push_to(&self, row: &mut Row) { row.push(*self as Value); }
+    fn from_fields(fields: &[Value]) -> Option<Self> {
+        if fields[0] >= 0 { Some(fields[0] as usize) } else { None }
+    }
+}
+
+impl Fields for isize {
+    fn arity() -> usize { 1 }
+    fn push_to(&self, row: &mut Row) { row.push(*self); }
+    fn from_fields(fields: &[Value]) -> Option<Self> { Some(fields[0]) }
+}
+
+impl Fields for () {
+    fn arity() -> usize { 0 }
+    fn push_to(&self, _row: &mut Row) { }
+    fn from_fields(_fields: &[Value]) -> Option<Self> { Some(()) }
+}
+
+impl Fields for UnsignedWrapper<usize> {
+    fn arity() -> usize { 1 }
+    fn push_to(&self, row: &mut Row) { self.item.push_to(row); }
+    fn from_fields(fields: &[Value]) -> Option<Self> { usize::from_fields(fields).map(UnsignedWrapper::from) }
+}
+
+impl<T: Fields+Ord+Hashable> Fields for OrdWrapper<T> {
+    fn arity() -> usize { T::arity() }
+    fn push_to(&self, row: &mut Row) { self.item.push_to(row); }
+    fn from_fields(fields: &[Value]) -> Option<Self> { T::from_fields(fields).map(|item| OrdWrapper { item: item }) }
+}
+
+/// The row of a key and value, the fields of the key followed by those of the value.
+fn row_of<K: Fields, V: Fields>(key: &K, val: &V) -> Row {
+    let mut row = Vec::with_capacity(K::arity() + V::arity());
+    key.push_to(&mut row);
+    val.push_to(&mut row);
+    row
+}
+
+/// Finds the trace registered under `name`, if it has one of the types plans can read.
+fn import<'a, G: Scope<Timestamp=RootTime>+'a>(scope: &G, traces: &mut TraceRegistry, name: &str, token: &Token) -> Result<Box<Source<G>+'a>, String> {
+
+    macro_rules! import_as {
+        ($($key:ty, $val:ty;)*) => {$(
+            if let Ok(trace) = traces.get_mut::<$key, $val, isize>(name) {
+                let imported: Imported<G, $key, $val> = Imported::new(scope, trace, name, token)?;
+                return Ok(Box::new(imported));
+            }
+        )*}
+    }
+
+    import_as! {
+        UnsignedWrapper<usize>, usize;
+        UnsignedWrapper<usize>, isize;
+        UnsignedWrapper<usize>, ();
+        OrdWrapper<usize>, usize;
+        OrdWrapper<usize>, isize;
+        OrdWrapper<usize>, ();
+        OrdWrapper<isize>, usize;
+        OrdWrapper<isize>, isize;
+        OrdWrapper<isize>, ();
+    }
+
+    match traces.describe(name) {
+        Some((types, _)) => Err(format!("trace {:?} has types {}, which plans cannot read", name, types)),
+        None => Err(format!("failed to find trace: {:?}", name)),
+    }
+}
+
+/// A trace read by a plan, whose key and value types have been erased.
+trait Source<G: Scope<Timestamp=RootTime>> {
+    /// The number of fields of each key.
+    fn key_arity(&self) -> usize;
+    /// The number of fields of each row, those of the key followed by those of the value.
+    fn arity(&self) -> usize;
+    /// The rows of the trace.
+    fn rows(&self) -> Collection<G, Row>;
+    /// Joins `rows` with the arrangement of the trace, as `Context::join`.
+    fn join(&self, rows: &Collection<G, Row>, source_first: bool) -> Collection<G, Row>;
+    /// The rows of the trace, brought into an iteration.
+    fn rows_in<'a>(&self, scope: &Child<'a, G, u64>) -> Collection<Child<'a, G, u64>, Row>;
+    /// Joins `rows` with the arrangement of the trace within an iteration, as `Context::join`.
+    fn join_in<'a>(&self, rows: &Collection<Child<'a, G, u64>, Row>, source_first: bool) -> Collection<Child<'a, G, u64>, Row>;
+}
+
+/// A registered trace, imported into a scope at most once as rows and at most once as an arrangement.
+struct Imported<G: Scope<Timestamp=RootTime>, K: Data+HashOrdered, V: Data> {
+    scope: G,
+    trace: RefCell<SharedTrace<K, V, isize>>,
+    time: RootTime,
+    token: Token,
+    rows: RefCell<Option<Collection<G, Row>>>,
+    arranged: RefCell<Option<Arranged<G, K, V, isize, SharedTrace<K, V, isize>>>>,
+}
+
+impl<G, K, V> Imported<G, K, V>
+where G: Scope<Timestamp=RootTime>, K: Data+HashOrdered+Fields, V: Data+Fields {
+
+    /// Prepares to import `trace`, whose rows are read from a snapshot at the time to which it has been compacted.
+    fn new(scope: &G, trace: &mut SharedTrace<K, V, isize>, name: &str, token: &Token) -> Result<Self, String> {
+        let time = trace.advance_frontier().first().cloned().ok_or_else(|| format!("trace {:?} has been closed", name))?;
+        Ok(Imported {
+            scope: scope.clone(),
+            trace: RefCell::new(trace.clone()),
+            time: time,
+            token: token.clone(),
+            rows: RefCell::new(None),
+            arranged: RefCell::new(None),
+        })
+    }
+
+    /// The arrangement of the trace, imported on first use.
+    fn arranged(&self) -> Arranged<G, K, V, isize, SharedTrace<K, V, isize>> {
+        let mut arranged = self.arranged.borrow_mut();
+        if arranged.is_none() {
+            let token = self.token.clone();
+            *arranged = Some(self.trace.borrow_mut().import_while(&self.scope, move || token.alive()));
+        }
+        let arranged = arranged.as_ref().unwrap();
+        Arranged { stream: arranged.stream.clone(), trace: arranged.trace.clone() }
+    }
+}
+
+impl<G, K, V> Source<G> for Imported<G, K, V>
+where G: Scope<Timestamp=RootTime>, K: Data+HashOrdered+Fields, V: Data+Fields {
+    fn key_arity(&self) -> usize { K::arity() }
+    fn arity(&self) -> usize { K::arity() + V::arity() }
+    fn rows(&self) -> Collection<G, Row> {
+        let mut rows = self.rows.borrow_mut();
+        if rows.is_none() {
+            let token = self.token.clone();
+            let imported = self.trace.borrow_mut().import_compacted_while(&self.scope, self.time.clone(), move || token.alive());
+            *rows = Some(imported.map(|(key, val)| row_of(&key, &val)));
+        }
+        rows.as_ref().unwrap().clone()
+    }
+    fn join(&self, rows: &Collection<G, Row>, source_first: bool) -> Collection<G, Row> {
+        join_arranged(&self.arranged(), rows, source_first)
+    }
+    fn rows_in<'a>(&self, scope: &Child<'a, G, u64>) -> Collection<Child<'a, G, u64>, Row> {
+        self.rows().enter(scope)
+    }
+    fn join_in<'a>(&self, rows: &Collection<Child<'a, G, u64>, Row>, source_first: bool) -> Collection<Child<'a, G, u64>, Row> {
+        join_arranged(&self.arranged().enter(&rows.scope()), rows, source_first)
+    }
+}
+
+/// Joins `rows` with `arranged` on the fields of its keys, as `Context::join`.
+fn join_arranged<G, K, V, Tr>(arranged: &Arranged<G, K, V, isize, Tr>, rows: &Collection<G, Row>, source_first: bool) -> Collection<G, Row>
+where
+    G: Scope,
+    G::Timestamp: Lattice+Data,
+    K: Data+HashOrdered+Fields,
+    V: Data+Fields,
+    Tr: TraceReader<K, V, G::Timestamp, isize>+Clone+'static,
+    Tr::Batch: BatchReader<K, V, G::Timestamp, isize>+'static,
+{
+    let fields = K::arity();
+    rows.flat_map(move |mut row| {
+            // rows whose leading fields are not a key cannot match any record of the trace.
+            let rest = row.split_off(fields);
+            K::from_fields(&row).map(|key| (key, rest))
+        })
+        .arrange(OrdValSpine::new())
+        .join_core(arranged, move |key, rest, val| {
+            let mut row = Vec::new();
+            key.push_to(&mut row);
+            if source_first {
+                val.push_to(&mut row);
+                row.extend(rest.iter().cloned());
+            }
+            else {
+                row.extend(rest.iter().cloned());
+                val.push_to(&mut row);
+            }
+            Some(row)
+        })
+}
+
+/// Reads the sources of a plan and renders its iterations, in some scope.
+///
+/// Rendering an iteration renders its body in a nested scope, and so would otherwise require unboundedly
+/// many nested scope types. `Sources` renders iterations, and renders their bodies with `Entered`, which
+/// never encounters an iteration as `Plan::arity` rejects nested iterations.
+trait Context<G: Scope> where G::Timestamp: Lattice+Data {
+    /// The number of fields of the keys of the source `name`.
+    fn key_arity(&self, name: &str) -> usize;
+    /// The rows of the source `name`.
+    fn rows(&self, name: &str) -> Collection<G, Row>;
+    /// Joins `rows` with the arrangement of the source `name`, on the fields of its keys.
+    ///
+    /// Each result is the key followed by the remaining fields of the row and then the fields of the value,
+    /// or by the fields of the value and then the remaining fields of the row if `source_first` is set.
+    fn join(&self, name: &str, rows: &Collection<G, Row>, source_first: bool) -> Collection<G, Row>;
+    /// The fixed point of `body`, starting from `initial`.
+    fn iterate(&self, initial: &Collection<G, Row>, body: &Plan) -> Collection<G, Row>;
+}
+
+/// The sources of a plan, in the outermost scope.
+struct Sources<'a, G: Scope<Timestamp=RootTime>+'a> {
+    sources: HashMap<String, Box<Source<G>+'a>>,
+}
+
+impl<'a, G: Scope<Timestamp=RootTime>+'a> Context<G> for Sources<'a, G> {
+    fn key_arity(&self, name: &str) -> usize { self.sources[name].key_arity() }
+    fn rows(&self, name: &str) -> Collection<G, Row> { self.sources[name].rows() }
+    fn join(&self, name: &str, rows: &Collection<G, Row>, source_first: bool) -> Collection<G, Row> {
+        self.sources[name].join(rows, source_first)
+    }
+    fn iterate(&self, initial: &Collection<G, Row>, body: &Plan) -> Collection<G, Row> {
+        initial.iterate(|variable| {
+            let entered = Entered { sources: self, scope: variable.scope() };
+            body.build(&entered, Some(variable))
+        })
+    }
+}
+
+/// The sources of a plan, brought into the scope of an iteration.
+struct Entered<'s, 'a: 's, 'b, G: Scope<Timestamp=RootTime>+'a> {
+    sources: &'s Sources<'a, G>,
+    scope: Child<'b, G, u64>,
+}
+
+impl<'s, 'a: 's, 'b, G: Scope<Timestamp=RootTime>+'a> Context<Child<'b, G, u64>> for Entered<'s, 'a, 'b, G> {
+    fn key_arity(&self, name: &str) -> usize { self.sources.key_arity(name) }
+    fn rows(&self, name: &str) -> Collection<Child<'b, G, u64>, Row> { self.sources.sources[name].rows_in(&self.scope) }
+    fn join(&self, name: &str, rows: &Collection<Child<'b, G, u64>, Row>, source_first: bool) -> Collection<Child<'b, G, u64>, Row> {
+        self.sources.sources[name].join_in(rows, source_first)
+    }
+    fn iterate(&self, _initial: &Collection<Child<'b, G, u64>, Row>, _body: &Plan) -> Collection<Child<'b, G, u64>, Row> {
+        panic!("nested iterations should be rejected by `Plan::arity`");
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Expr::Field(index) => write!(f, "${}", index),
+            Expr::Constant(value) => write!(f, "{}", value),
+            Expr::Add(ref e1, ref e2) => write!(f, "(add {} {})", e1, e2),
+            Expr::Sub(ref e1, ref e2) => write!(f, "(sub {} {})", e1, e2),
+            Expr::Mul(ref e1, ref e2) => write!(f, "(mul {} {})", e1, e2),
+        }
+    }
+}
+
+impl fmt::Display for Predicate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self.comparison {
+            Comparison::Eq => "eq",
+            Comparison::Ne => "ne",
+            Comparison::Lt => "lt",
+            Comparison::Le => "le",
+            Comparison::Gt => "gt",
+            Comparison::Ge => "ge",
+        };
+        write!(f, "({} {} {})", name, self.left, self.right)
+    }
+}
+
+impl fmt::Display for Plan {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Plan::Source(ref name) => write!(f, "(source {})", name),
+            Plan::Variable => write!(f, "(variable)"),
+            Plan::Map(ref exprs, ref plan) => {
+                let exprs = exprs.iter().map(|expr| expr.to_string()).collect::<Vec<_>>();
+                write!(f, "(map ({}) {})", exprs.join(" "), plan)
+            },
+            Plan::Filter(ref predicate, ref plan) => write!(f, "(filter {} {})", predicate, plan),
+            Plan::Join(fields, ref plan1, ref plan2) => write!(f, "(join {} {} {})", fields, plan1, plan2),
+            Plan::Distinct(ref plan) => write!(f, "(distinct {})", plan),
+            Plan::Count(ref plan) => write!(f, "(count {})", plan),
+            Plan::Min(fields, ref plan) => write!(f, "(min {} {})", fields, plan),
+            Plan::Max(fields, ref plan) => write!(f, "(max {} {})", fields, plan),
+            Plan::Concat(ref plans) => {
+                let plans = plans.iter().map(|plan| plan.to_string()).collect::<Vec<_>>();
+                write!(f, "(concat {})", plans.join(" "))
+            },
+            Plan::Negate(ref plan) => write!(f, "(negate {})", plan),
+            Plan::Iterate(ref initial, ref body) => write!(f, "(iterate {} {})", initial, body),
+        }
+    }
+}
+
+/// An s-expression, from which plans are read.
+enum Sexp {
+    Atom(String),
+    List(Vec<Sexp>),
+}
+
+impl Sexp {
+
+    /// Reads a single s-expression from `text`.
+    fn parse(text: &str) -> Result<Sexp, String> {
+        let spaced = text.replace("(", " ( ").replace(")", " ) ");
+        let tokens = spaced.split_whitespace().collect::<Vec<_>>();
+        let mut position = 0;
+        let sexp = Sexp::read(&tokens[..], &mut position)?;
+        if position < tokens.len() {
+            Err(format!("unexpected text after plan: {:?}", tokens[position..].join(" ")))
+        }
+        else { Ok(sexp) }
+    }
+
+    fn read(tokens: &[&str], position: &mut usize) -> Result<Sexp, String> {
+        if *position >= tokens.len() {
+            return Err("unexpected end of plan".to_owned());
+        }
+        let token = tokens[*position];
+        *position += 1;
+        match token {
+            "(" => {
+                let mut list = Vec::new();
+                while *position < tokens.len() && tokens[*position] != ")" {
+                    list.push(Sexp::read(tokens, position)?);
+                }
+                if *position < tokens.len() {
+                    *position += 1;
+                    Ok(Sexp::List(list))
+                }
+                else { Err("unclosed parenthesis".to_owned()) }
+            },
+            ")" => Err("unexpected closing parenthesis".to_owned()),
+            atom => Ok(Sexp::Atom(atom.to_owned())),
+        }
+    }
+}
+
+impl fmt::Display for Sexp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Sexp::Atom(ref atom) => write!(f, "{}", atom),
+            Sexp::List(ref list) => {
+                let items = list.iter().map(|item| item.to_string()).collect::<Vec<_>>();
+                write!(f, "({})", items.join(" "))
+            },
+        }
+    }
+}
+
+/// Reads a non-negative integer, such as a number of fields.
+fn read_count(sexp: &Sexp) -> Result<usize, String> {
+    match *sexp {
+        Sexp::Atom(ref atom) => atom.parse().map_err(|_| format!("expected a number of fields, found: {}", atom)),
+        Sexp::List(_) => Err(format!("expected a number of fields, found: {}", sexp)),
+    }
+}
+
+fn read_expr(sexp: &Sexp) -> Result<Expr, String> {
+    match *sexp {
+        Sexp::Atom(ref atom) => {
+            if atom.starts_with('$') {
+                atom[1..].parse().map(Expr::Field).map_err(|_| format!("invalid field: {}", atom))
+            }
+            else {
+                atom.parse().map(Expr::Constant).map_err(|_| format!("invalid constant: {}", atom))
+            }
+        },
+        Sexp::List(ref list) => {
+            match (list.first(), list.len()) {
+                (Some(&Sexp::Atom(ref op)), 3) => {
+                    let e1 = Box::new(read_expr(&list[1])?);
+                    let e2 = Box::new(read_expr(&list[2])?);
+                    match op.as_str() {
+                        "add" => Ok(Expr::Add(e1, e2)),
+                        "sub" => Ok(Expr::Sub(e1, e2)),
+                        "mul" => Ok(Expr::Mul(e1, e2)),
+                        _ => Err(format!("unrecognized expression: {}", sexp)),
+                    }
+                },
+                _ => Err(format!("unrecognized expression: {}", sexp)),
+            }
+        },
+    }
+}
+
+fn read_predicate(sexp: &Sexp) -> Result<Predicate, String> {
+    if let Sexp::List(ref list) = *sexp {
+        if let (Some(&Sexp::Atom(ref op)), 3) = (list.first(), list.len()) {
+            let comparison = match op.as_str() {
+                "eq" => Comparison::Eq,
+                "ne" => Comparison::Ne,
+                "lt" => Comparison::Lt,
+                "le" => Comparison::Le,
+                "gt" => Comparison::Gt,
+                "ge" => Comparison::Ge,
+                _ => return Err(format!("unrecognized predicate: {}", sexp)),
+            };
+            return Ok(Predicate { comparison: comparison, left: read_expr(&list[1])?, right: read_expr(&list[2])? });
+        }
+    }
+    Err(format!("unrecognized predicate: {}", sexp))
+}
+
+fn read_plan(sexp: &Sexp) -> Result<Plan, String> {
+
+    let list = match *sexp {
+        Sexp::List(ref list) => list,
+        Sexp::Atom(_) => return Err(format!("expected a plan, found: {}", sexp)),
+    };
+
+    let op = match list.first() {
+        Some(&Sexp::Atom(ref op)) => op.as_str(),
+        _ => return Err(format!("expected a plan, found: {}", sexp)),
+    };
+
+    let args = &list[1..];
+    let plan = |index: usize| read_plan(&args[index]).map(Box::new);
+    match (op, args.len()) {
+        ("source", 1) => {
+            match args[0] {
+                Sexp::Atom(ref name) => Ok(Plan::Source(name.clone())),
+                Sexp::List(_) => Err(format!("expected a trace name, found: {}", args[0])),
+            }
+        },
+        ("variable", 0) => Ok(Plan::Variable),
+        ("map", 2) => {
+            match args[0] {
+                Sexp::List(ref exprs) => {
+                    let mut result = Vec::new();
+                    for expr in exprs.iter() {
+                        result.push(read_expr(expr)?);
+                    }
+                    Ok(Plan::Map(result, plan(1)?))
+                },
+                Sexp::Atom(_) => Err(format!("expected a list of expressions, found: {}", args[0])),
+            }
+        },
+        ("filter", 2) => Ok(Plan::Filter(read_predicate(&args[0])?, plan(1)?)),
+        ("join", 3) => Ok(Plan::Join(read_count(&args[0])?, plan(1)?, plan(2)?)),
+        ("distinct", 1) => Ok(Plan::Distinct(plan(0)?)),
+        ("count", 1) => Ok(Plan::Count(plan(0)?)),
+        ("min", 2) => Ok(Plan::Min(read_count(&args[0])?, plan(1)?)),
+        ("max", 2) => Ok(Plan::Max(read_count(&args[0])?, plan(1)?)),
+        ("concat", _) => {
+            let mut plans = Vec::new();
+            for arg in args.iter() {
+                plans.push(read_plan(arg)?);
+            }
+            Ok(Plan::Concat(plans))
+        },
+        ("negate", 1) => Ok(Plan::Negate(plan(0)?)),
+        ("iterate", 2) => Ok(Plan::Iterate(plan(0)?, plan(1)?)),
+        _ => Err(format!("unrecognized plan: {}", sexp)),
+    }
+}
+
+impl FromStr for Plan {
+    type Err = String;
+    fn from_str(text: &str) -> Result<Plan, String> {
+        read_plan(&Sexp::parse(text)?)
+    }
+}
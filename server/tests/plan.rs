@@ -0,0 +1,181 @@
+extern crate timely;
+extern crate differential_dataflow;
+extern crate dd_server;
+
+use std::rc::Rc;
+use std::cell::RefCell;
+
+use timely::progress::timestamp::RootTimestamp;
+
+use differential_dataflow::input::Input;
+use differential_dataflow::hashable::OrdWrapper;
+use differential_dataflow::operators::arrange::ArrangeByKey;
+use differential_dataflow::trace::consolidate;
+
+use dd_server::{SharedTrace, Token, TraceHandle, TraceRegistry};
+use dd_server::plan::{Plan, Row};
+
+fn graph() -> Vec<(usize, usize)> {
+    vec![(0, 1), (1, 2), (1, 3), (2, 0), (4, 0)]
+}
+
+fn rows(rows: Vec<Vec<isize>>) -> Vec<(Row, isize)> {
+    rows.into_iter().map(|row| (row, 1)).collect()
+}
+
+// renders `plan` against `graph()` registered as "graph", and labels of nodes registered as "labels", and
+// returns the rows it produces.
+fn run(plan: &str) -> Vec<(Row, isize)> {
+
+    let plan = plan.parse::<Plan>().unwrap();
+
+    timely::execute(timely::Configuration::Thread, move |worker| {
+
+        let mut traces = TraceRegistry::new();
+
+        let (mut edges, mut labels, graph_trace, labels_trace) = worker.dataflow::<usize,_,_>(|scope| {
+            let (edges, graph) = scope.new_collection();
+            let (labels, labelled) = scope.new_collection();
+            let graph_trace: TraceHandle = graph.arrange_by_key_u().trace;
+            let labels_trace: SharedTrace<OrdWrapper<isize>, usize, isize> = labelled.arrange_by_key_hashed().trace;
+            (edges, labels, graph_trace, labels_trace)
+        });
+
+        traces.insert("graph", graph_trace);
+        traces.insert("labels", labels_trace);
+
+        for edge in graph() {
+            edges.insert(edge);
+        }
+        labels.insert((0isize, 10usize));
+        labels.insert((1, 11));
+
+        let results = Rc::new(RefCell::new(Vec::new()));
+        let results2 = results.clone();
+
+        let token = Token::new();
+        let probe = worker.dataflow(|scope| {
+            plan.render(scope, &mut traces, &token)
+                .unwrap()
+                .inspect(move |&(ref row, _, diff)| results2.borrow_mut().push((row.clone(), diff)))
+                .probe()
+        });
+
+        edges.advance_to(1);
+        edges.flush();
+        labels.advance_to(1);
+        labels.flush();
+        while probe.less_than(edges.time()) { worker.step(); }
+
+        let mut results = results.borrow().clone();
+        consolidate(&mut results, 0);
+        results
+
+    }).unwrap().join().into_iter().map(|x| x.unwrap()).next().unwrap()
+}
+
+#[test]
+fn join() {
+    // paths of length two, through the shared arrangement of the source.
+    assert_eq!(run("(join 1 (map ($1 $0) (source graph)) (source graph))"), rows(vec![
+        vec![0, 2, 1], vec![0, 4, 1], vec![1, 0, 2], vec![1, 0, 3], vec![2, 1, 0],
+    ]));
+    // the fields of the source come first when it is the first input.
+    assert_eq!(run("(join 1 (source graph) (map ($1 $0) (source graph)))"), rows(vec![
+        vec![0, 1, 2], vec![0, 1, 4], vec![1, 2, 0], vec![1, 3, 0], vec![2, 0, 1],
+    ]));
+    // joins on more than the key of the source arrange both inputs.
+    assert_eq!(run("(join 2 (source graph) (source graph))"), rows(vec![
+        vec![0, 1], vec![1, 2], vec![1, 3], vec![2, 0], vec![4, 0],
+    ]));
+    // traces of other types may be joined.
+    assert_eq!(run("(join 1 (source graph) (source labels))"), rows(vec![
+        vec![0, 1, 10], vec![1, 2, 11], vec![1, 3, 11],
+    ]));
+}
+
+#[test]
+fn count() {
+    assert_eq!(run("(count (map ($0) (source graph)))"), rows(vec![
+        vec![0, 1], vec![1, 2], vec![2, 1], vec![4, 1],
+    ]));
+}
+
+#[test]
+fn min_max() {
+    assert_eq!(run("(min 1 (source graph))"), rows(vec![
+        vec![0, 1], vec![1, 2], vec![2, 0], vec![4, 0],
+    ]));
+    assert_eq!(run("(max 1 (source graph))"), rows(vec![
+        vec![0, 1], vec![1, 3], vec![2, 0], vec![4, 0],
+    ]));
+}
+
+#[test]
+fn iterate() {
+    // the nodes reachable from node zero.
+    let plan = "(iterate (distinct (map (0) (filter (eq $0 0) (source graph))))
+                         (distinct (concat (variable) (map ($1) (join 1 (variable) (source graph))))))";
+    assert_eq!(run(plan), rows(vec![vec![0], vec![1], vec![2], vec![3]]));
+}
+
+#[test]
+fn unreadable_types() {
+    timely::execute(timely::Configuration::Thread, |worker| {
+        let mut traces = TraceRegistry::new();
+        let trace: SharedTrace<OrdWrapper<String>, usize, isize> = worker.dataflow::<usize,_,_>(|scope| {
+            scope.new_collection_from(vec![("a".to_owned(), 0usize)]).1.arrange_by_key_hashed().trace
+        });
+        traces.insert("names", trace);
+        worker.dataflow(|scope| {
+            let plan = "(source names)".parse::<Plan>().unwrap();
+            assert!(plan.render(scope, &mut traces, &Token::new()).is_err());
+            let plan = "(source missing)".parse::<Plan>().unwrap();
+            assert!(plan.render(scope, &mut traces, &Token::new()).is_err());
+        });
+    }).unwrap();
+}
+
+#[test]
+fn cancel() {
+    timely::execute(timely::Configuration::Thread, |worker| {
+
+        let mut traces = TraceRegistry::new();
+
+        let (mut edges, trace) = worker.dataflow::<usize,_,_>(|scope| {
+            let (edges, graph) = scope.new_collection();
+            let trace: TraceHandle = graph.arrange_by_key_u().trace;
+            (edges, trace)
+        });
+        traces.insert("graph", trace);
+
+        let results = Rc::new(RefCell::new(Vec::new()));
+        let results2 = results.clone();
+
+        let token = Token::new();
+        let probe = worker.dataflow(|scope| {
+            "(join 1 (map ($1 $0) (source graph)) (source graph))".parse::<Plan>().unwrap()
+                .render(scope, &mut traces, &token)
+                .unwrap()
+                .inspect(move |&(ref row, _, diff)| results2.borrow_mut().push((row.clone(), diff)))
+                .probe()
+        });
+
+        edges.insert((0, 1));
+        edges.insert((1, 2));
+        edges.advance_to(1);
+        edges.flush();
+        while probe.less_than(edges.time()) { worker.step(); }
+        assert_eq!(*results.borrow(), vec![(vec![1, 0, 2], 1)]);
+
+        // once cancelled, the query stops reading the trace and completes, though the trace continues.
+        token.cancel();
+        edges.insert((2, 3));
+        edges.advance_to(2);
+        edges.flush();
+        for _ in 0 .. 100 { worker.step(); }
+        assert!(!probe.less_than(&RootTimestamp::new(usize::max_value())));
+        assert_eq!(*results.borrow(), vec![(vec![1, 0, 2], 1)]);
+
+    }).unwrap();
+}
@@ -0,0 +1,73 @@
+//! Breadth-first search distances from a set of roots.
+
+use std::hash::Hash;
+
+use timely::dataflow::*;
+
+use ::{Collection, Data};
+use lattice::Lattice;
+use hashable::OrdWrapper;
+use operators::*;
+use operators::arrange::{Arranged, ArrangeByKey};
+use trace::{BatchReader, TraceReader};
+
+/// Reports for each node reachable from `roots` the least number of edges on a path from some root.
+///
+/// # Examples
+///
+/// ```
+/// #
+/// extern crate timely;
+/// extern crate differential_dataflow;
+///
+/// use differential_dataflow::input::Input;
+/// use differential_dataflow::algorithms::graphs::bfs;
+///
+/// fn main() {
+///     ::timely::example(|scope| {
+///
+///         let edges = scope.new_collection_from(vec![(0u32, 1), (1, 2), (3, 4)]).1;
+///         let roots = scope.new_collection_from(vec![0]).1;
+///         let expected = scope.new_collection_from(vec![(0, 0), (1, 1), (2, 2)]).1;
+///
+///         bfs(&edges, &roots)
+///             .assert_eq(&expected);
+///     });
+/// }
+/// ```
+pub fn bfs<G, N>(edges: &Collection<G, (N, N)>, roots: &Collection<G, N>) -> Collection<G, (N, u32)>
+where
+    G: Scope,
+    G::Timestamp: Lattice+Ord,
+    N: Data+Default+Hash,
+{
+    bfs_arranged(&edges.arrange_by_key_hashed(), roots)
+}
+
+/// Reports breadth-first search distances from `roots`, using edges arranged by source.
+///
+/// The arrangement is entered into the iterative scope rather than re-arranged in each iteration, and may
+/// be shared with other computations on the same graph.
+pub fn bfs_arranged<G, N, Tr>(edges: &Arranged<G, OrdWrapper<N>, N, isize, Tr>, roots: &Collection<G, N>) -> Collection<G, (N, u32)>
+where
+    G: Scope,
+    G::Timestamp: Lattice+Ord,
+    N: Data+Default+Hash,
+    Tr: TraceReader<OrdWrapper<N>, N, G::Timestamp, isize>+Clone+'static,
+    Tr::Batch: BatchReader<OrdWrapper<N>, N, G::Timestamp, isize>+Clone+'static,
+{
+    // initialize roots as reaching themselves at distance 0
+    let nodes = roots.map(|x| (x, 0));
+
+    // repeatedly update minimal distances each node can be reached from each root
+    nodes.iterate(|inner| {
+
+        let edges = edges.enter(&inner.scope());
+        let nodes = nodes.enter(&inner.scope());
+
+        inner.arrange_by_key_hashed()
+             .join_core(&edges, |_k, l, d| Some((d.clone(), l + 1)))
+             .concat(&nodes)
+             .group(|_, s, t| t.push((*s[0].0, 1)))
+    })
+}
@@ -0,0 +1,109 @@
+//! Shortest path lengths between pairs of nodes, by bidirectional search.
+
+use std::hash::Hash;
+
+use timely::dataflow::*;
+
+use ::{Collection, Data};
+use lattice::Lattice;
+use operators::*;
+use operators::iterate::Variable;
+
+/// Reports for each goal `(src, dst)` the least number of edges on a path from `src` to `dst`.
+///
+/// The search proceeds from the sources forward and from the destinations backward, and stops expanding
+/// from a source or destination once each of its goals has been reached. Goals without a path are not
+/// reported.
+///
+/// # Examples
+///
+/// ```
+/// #
+/// extern crate timely;
+/// extern crate differential_dataflow;
+///
+/// use differential_dataflow::input::Input;
+/// use differential_dataflow::algorithms::graphs::bidijkstra;
+///
+/// fn main() {
+///     ::timely::example(|scope| {
+///
+///         let edges = scope.new_collection_from(vec![(0u32, 1), (1, 2), (2, 3), (0, 2)]).1;
+///         let goals = scope.new_collection_from(vec![(0, 3), (1, 3), (3, 0)]).1;
+///         let expected = scope.new_collection_from(vec![((0, 3), 2), ((1, 3), 2)]).1;
+///
+///         bidijkstra(&edges, &goals)
+///             .assert_eq(&expected);
+///     });
+/// }
+/// ```
+pub fn bidijkstra<G, N>(edges: &Collection<G, (N, N)>, goals: &Collection<G, (N, N)>) -> Collection<G, ((N, N), u32)>
+where
+    G: Scope,
+    G::Timestamp: Lattice+Ord,
+    N: Data+Default+Hash,
+{
+    edges.scope().scoped(|inner| {
+
+        // Our plan is to start evolving distances from both sources and destinations.
+        // The evolution from a source or destination should continue as long as there
+        // is a corresponding destination or source that has not yet been reached.
+
+        // forward and reverse (node, (root, dist))
+        let forward = Variable::from(goals.map(|(x, _)| (x.clone(), (x, 0))).enter(inner));
+        let reverse = Variable::from(goals.map(|(_, y)| (y.clone(), (y, 0))).enter(inner));
+
+        let goals = goals.enter(inner);
+        let edges = edges.enter(inner);
+
+        // Let's determine which (src, dst) pairs are ready to return.
+        //
+        //   done(src, dst) := forward(src, med), reverse(dst, med), goal(src, dst).
+        //
+        // This is a cyclic join, which should scare us a bunch.
+        let reached =
+        forward
+            .join_map(&reverse, |_, &(ref src, d1), &(ref dst, d2)| ((src.clone(), dst.clone()), d1 + d2))
+            .group(|_key, s, t| t.push((*s[0].0, 1)))
+            .semijoin(&goals);
+
+        let active =
+        reached
+            .negate()
+            .map(|(srcdst, _)| srcdst)
+            .concat(&goals)
+            .consolidate();
+
+        // Let's expand out forward queries that are active.
+        let forward_active = active.map(|(x, _y)| x).distinct();
+        let forward_next =
+        forward
+            .map(|(med, (src, dist))| (src, (med, dist)))
+            .semijoin(&forward_active)
+            .map(|(src, (med, dist))| (med, (src, dist)))
+            .join_map(&edges, |_med, &(ref src, dist), next| (next.clone(), (src.clone(), dist + 1)))
+            .concat(&forward)
+            .map(|(next, (src, dist))| ((next, src), dist))
+            .group(|_key, s, t| t.push((*s[0].0, 1)))
+            .map(|((next, src), dist)| (next, (src, dist)));
+
+        forward.set(&forward_next);
+
+        // Let's expand out reverse queries that are active.
+        let reverse_active = active.map(|(_x, y)| y).distinct();
+        let reverse_next =
+        reverse
+            .map(|(med, (rev, dist))| (rev, (med, dist)))
+            .semijoin(&reverse_active)
+            .map(|(rev, (med, dist))| (med, (rev, dist)))
+            .join_map(&edges.map(|(x, y)| (y, x)), |_med, &(ref rev, dist), next| (next.clone(), (rev.clone(), dist + 1)))
+            .concat(&reverse)
+            .map(|(next, (rev, dist))| ((next, rev), dist))
+            .group(|_key, s, t| t.push((*s[0].0, 1)))
+            .map(|((next, rev), dist)| (next, (rev, dist)));
+
+        reverse.set(&reverse_next);
+
+        reached.leave()
+    })
+}
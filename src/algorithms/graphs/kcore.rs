@@ -0,0 +1,63 @@
+//! The k-core of an undirected graph.
+
+use std::hash::Hash;
+
+use timely::dataflow::*;
+
+use ::{Collection, Data};
+use lattice::Lattice;
+use operators::*;
+
+/// Reports the edges of the `k`-core, the largest subgraph in which each node has at least `k` neighbors.
+///
+/// The direction of edges is ignored, as are self-loops and the multiplicities of edges. Each edge of the
+/// `k`-core is reported in both directions.
+///
+/// # Examples
+///
+/// ```
+/// #
+/// extern crate timely;
+/// extern crate differential_dataflow;
+///
+/// use differential_dataflow::input::Input;
+/// use differential_dataflow::algorithms::graphs::k_core;
+///
+/// fn main() {
+///     ::timely::example(|scope| {
+///
+///         let edges = scope.new_collection_from(vec![(0u32, 1), (1, 2), (2, 0), (2, 3)]).1;
+///         let expected = scope.new_collection_from(vec![(0, 1), (1, 0), (1, 2), (2, 1), (0, 2), (2, 0)]).1;
+///
+///         k_core(&edges, 2)
+///             .assert_eq(&expected);
+///     });
+/// }
+/// ```
+pub fn k_core<G, N>(edges: &Collection<G, (N, N)>, k: isize) -> Collection<G, (N, N)>
+where
+    G: Scope,
+    G::Timestamp: Lattice+Ord,
+    N: Data+Default+Hash,
+{
+    // each edge should exist once in each direction.
+    let edges = edges.filter(|&(ref x, ref y)| x != y)
+                     .flat_map(|(x, y)| vec![(x.clone(), y.clone()), (y, x)])
+                     .distinct();
+
+    edges.iterate(|inner| {
+
+        // nodes with at least `k` neighbors among the remaining edges.
+        let active = inner.map(|(src, _)| src)
+                          .count()
+                          .filter(move |&(_, count)| count >= k)
+                          .map(|(node, _)| node);
+
+        // keep edges whose endpoints are both active.
+        edges.enter(&inner.scope())
+             .semijoin(&active)
+             .map(|(src, dst)| (dst, src))
+             .semijoin(&active)
+             .map(|(dst, src)| (src, dst))
+    })
+}
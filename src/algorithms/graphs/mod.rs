@@ -0,0 +1,24 @@
+//! Algorithms on graphs, represented as collections of directed edges `(src, dst)`.
+//!
+//! Nodes may be any type implementing `Data`, `Default`, and `Hash`, the requirements of the `group` and
+//! `join` operators used throughout. Where an algorithm repeatedly joins against the edges of the graph it
+//! is also available for edges already arranged by source, so that one arrangement may be shared among
+//! several computations.
+
+pub mod bfs;
+pub mod reachability;
+pub mod propagate;
+pub mod scc;
+pub mod bijkstra;
+pub mod triangles;
+pub mod kcore;
+pub mod pagerank;
+
+pub use self::bfs::{bfs, bfs_arranged};
+pub use self::reachability::{reach, reach_arranged};
+pub use self::propagate::{propagate, propagate_arranged, connected_components};
+pub use self::scc::{trim_and_flip, strongly_connected};
+pub use self::bijkstra::bidijkstra;
+pub use self::triangles::{triangles, count_triangles};
+pub use self::kcore::k_core;
+pub use self::pagerank::pagerank;
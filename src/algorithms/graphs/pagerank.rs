@@ -0,0 +1,75 @@
+//! PageRank, computed with integer ranks for a fixed number of iterations.
+//!
+//! Ranks are represented by the multiplicities of nodes in a collection, rather than as values, so that
+//! changes to the graph produce changes to the multiplicities, and only nodes whose ranks change produce
+//! updates. Each node starts with `6_000_000` units of rank. In each iteration a node retains none of its
+//! rank, sends five sixths of it divided equally (rounding down) along each of its out-edges, and receives
+//! `1_000_000` units of reset rank. Rank sent to nodes without out-edges is not passed on.
+
+use std::hash::Hash;
+
+use timely::dataflow::*;
+
+use ::{Collection, Data};
+use lattice::Lattice;
+use operators::*;
+
+/// Reports the rank of each node after `iterations` rounds, as the node's multiplicity.
+///
+/// # Examples
+///
+/// ```
+/// #
+/// extern crate timely;
+/// extern crate differential_dataflow;
+///
+/// use differential_dataflow::input::Input;
+/// use differential_dataflow::algorithms::graphs::pagerank;
+///
+/// fn main() {
+///     ::timely::example(|scope| {
+///
+///         // in a cycle, each node keeps its initial rank.
+///         let edges = scope.new_collection_from(vec![(0u32, 1), (1, 2), (2, 0)]).1;
+///         let expected = scope.new_collection_from(vec![0, 1, 2]).1
+///                             .explode(|node| Some((node, 6_000_000isize)));
+///
+///         pagerank(10, &edges)
+///             .assert_eq(&expected);
+///     });
+/// }
+/// ```
+pub fn pagerank<G, N>(iterations: u64, edges: &Collection<G, (N, N)>) -> Collection<G, N>
+where
+    G: Scope,
+    G::Timestamp: Lattice+Ord,
+    N: Data+Default+Hash,
+{
+    // each node that is an endpoint of an edge has a rank.
+    let nodes = edges.flat_map(|(src, dst)| vec![src, dst])
+                     .distinct();
+
+    // the out-degree of each node.
+    let degrs = edges.map(|(src, _dst)| src)
+                     .count();
+
+    // start everyone with six million units of rank.
+    nodes.explode(|node| Some((node, 6_000_000isize)))
+         .iterate_bounded(iterations, |ranks| {
+
+             let edges = edges.enter(&ranks.scope());
+             let nodes = nodes.enter(&ranks.scope());
+             let degrs = degrs.enter(&ranks.scope());
+
+             // the share of its rank each node sends along each of its out-edges.
+             let shares = degrs.semijoin(&ranks)
+                               .threshold(|&(_, degr), rank| (5 * rank) / (6 * degr))
+                               .map(|(node, _degr)| node);
+
+             // propagate shares along edges, and blend in reset rank.
+             edges.semijoin(&shares)
+                  .map(|(_src, dst)| dst)
+                  .concat(&nodes.explode(|node| Some((node, 1_000_000isize))))
+                  .consolidate()
+         })
+}
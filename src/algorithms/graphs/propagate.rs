@@ -0,0 +1,93 @@
+//! Label propagation, and connected components as an application of it.
+
+use std::hash::Hash;
+
+use timely::dataflow::*;
+
+use ::{Collection, Data};
+use lattice::Lattice;
+use hashable::OrdWrapper;
+use operators::*;
+use operators::arrange::{Arranged, ArrangeByKey};
+use trace::{BatchReader, TraceReader};
+
+/// Propagates labels along directed edges, reporting for each node the least label that reaches it.
+///
+/// Each node in `labels` starts with its own labels, and each edge `(src, dst)` offers the labels of `src`
+/// to `dst`. Nodes not reachable from any labeled node are not reported.
+pub fn propagate<G, N, L>(edges: &Collection<G, (N, N)>, labels: &Collection<G, (N, L)>) -> Collection<G, (N, L)>
+where
+    G: Scope,
+    G::Timestamp: Lattice+Ord,
+    N: Data+Default+Hash,
+    L: Data,
+{
+    propagate_arranged(&edges.arrange_by_key_hashed(), labels)
+}
+
+/// Propagates labels along directed edges arranged by source.
+pub fn propagate_arranged<G, N, L, Tr>(edges: &Arranged<G, OrdWrapper<N>, N, isize, Tr>, labels: &Collection<G, (N, L)>) -> Collection<G, (N, L)>
+where
+    G: Scope,
+    G::Timestamp: Lattice+Ord,
+    N: Data+Default+Hash,
+    L: Data,
+    Tr: TraceReader<OrdWrapper<N>, N, G::Timestamp, isize>+Clone+'static,
+    Tr::Batch: BatchReader<OrdWrapper<N>, N, G::Timestamp, isize>+Clone+'static,
+{
+    // don't actually use these labels, just grab the type
+    labels.filter(|_| false)
+          .iterate(|inner| {
+
+              let edges = edges.enter(&inner.scope());
+              let labels = labels.enter(&inner.scope());
+
+              inner.arrange_by_key_hashed()
+                   .join_core(&edges, |_src, label, dst| Some((dst.clone(), label.clone())))
+                   .concat(&labels)
+                   .group(|_, s, t| t.push((s[0].0.clone(), 1)))
+          })
+}
+
+/// Labels each node with the least node in its connected component, ignoring the direction of edges.
+///
+/// # Examples
+///
+/// ```
+/// #
+/// extern crate timely;
+/// extern crate differential_dataflow;
+///
+/// use differential_dataflow::input::Input;
+/// use differential_dataflow::algorithms::graphs::connected_components;
+///
+/// fn main() {
+///     ::timely::example(|scope| {
+///
+///         let edges = scope.new_collection_from(vec![(0u32, 1), (2, 1), (4, 3)]).1;
+///         let expected = scope.new_collection_from(vec![(0, 0), (1, 0), (2, 0), (3, 3), (4, 3)]).1;
+///
+///         connected_components(&edges)
+///             .assert_eq(&expected);
+///     });
+/// }
+/// ```
+pub fn connected_components<G, N>(edges: &Collection<G, (N, N)>) -> Collection<G, (N, N)>
+where
+    G: Scope,
+    G::Timestamp: Lattice+Ord,
+    N: Data+Default+Hash,
+{
+    // each edge (x,y) means that we need at least a label for the min of x and y.
+    let nodes = edges.map(|(x, y)| {
+                         let min = ::std::cmp::min(x, y);
+                         (min.clone(), min)
+                     })
+                     .consolidate();
+
+    // each edge should exist in both directions.
+    let edges = edges.map(|(x, y)| (y, x))
+                     .concat(&edges);
+
+    propagate(&edges, &nodes)
+}
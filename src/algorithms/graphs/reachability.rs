@@ -0,0 +1,66 @@
+//! The set of nodes reachable from a set of roots.
+
+use std::hash::Hash;
+
+use timely::dataflow::*;
+
+use ::{Collection, Data};
+use lattice::Lattice;
+use hashable::OrdWrapper;
+use operators::*;
+use operators::arrange::{Arranged, ArrangeByKey, ArrangeBySelf};
+use trace::{BatchReader, TraceReader};
+
+/// Reports the nodes reachable from `roots` along directed edges, including the roots themselves.
+///
+/// # Examples
+///
+/// ```
+/// #
+/// extern crate timely;
+/// extern crate differential_dataflow;
+///
+/// use differential_dataflow::input::Input;
+/// use differential_dataflow::algorithms::graphs::reach;
+///
+/// fn main() {
+///     ::timely::example(|scope| {
+///
+///         let edges = scope.new_collection_from(vec![(0u32, 1), (1, 2), (3, 4)]).1;
+///         let roots = scope.new_collection_from(vec![1]).1;
+///         let expected = scope.new_collection_from(vec![1, 2]).1;
+///
+///         reach(&edges, &roots)
+///             .assert_eq(&expected);
+///     });
+/// }
+/// ```
+pub fn reach<G, N>(edges: &Collection<G, (N, N)>, roots: &Collection<G, N>) -> Collection<G, N>
+where
+    G: Scope,
+    G::Timestamp: Lattice+Ord,
+    N: Data+Default+Hash,
+{
+    reach_arranged(&edges.arrange_by_key_hashed(), roots)
+}
+
+/// Reports the nodes reachable from `roots`, using edges arranged by source.
+pub fn reach_arranged<G, N, Tr>(edges: &Arranged<G, OrdWrapper<N>, N, isize, Tr>, roots: &Collection<G, N>) -> Collection<G, N>
+where
+    G: Scope,
+    G::Timestamp: Lattice+Ord,
+    N: Data+Default+Hash,
+    Tr: TraceReader<OrdWrapper<N>, N, G::Timestamp, isize>+Clone+'static,
+    Tr::Batch: BatchReader<OrdWrapper<N>, N, G::Timestamp, isize>+Clone+'static,
+{
+    roots.iterate(|inner| {
+
+        let edges = edges.enter(&inner.scope());
+        let roots = roots.enter(&inner.scope());
+
+        inner.arrange_by_self()
+             .join_core(&edges, |_src, &(), dst| Some(dst.clone()))
+             .concat(&roots)
+             .distinct()
+    })
+}
@@ -0,0 +1,92 @@
+//! Strongly connected components, by repeated trimming of edges.
+//!
+//! An edge lies in a strongly connected component exactly when its destination reaches its source. Each
+//! round labels nodes with the least node that reaches them along the remaining edges, keeps only edges
+//! whose endpoints have the same label, and then does the same along the reversed edges. The remaining
+//! edges shrink to those within strongly connected components.
+
+use std::hash::Hash;
+
+use timely::dataflow::*;
+
+use ::{Collection, Data};
+use lattice::Lattice;
+use operators::*;
+
+use super::propagate::propagate;
+
+/// Removes edges whose source has no incoming edges, repeatedly, and then reverses the remaining edges.
+///
+/// Edges from nodes with no incoming edges cannot lie on a cycle, and this is a cheap way to shrink a graph
+/// before determining its strongly connected components. Applying this twice trims from both directions and
+/// restores the orientation of the edges.
+pub fn trim_and_flip<G, N>(graph: &Collection<G, (N, N)>) -> Collection<G, (N, N)>
+where
+    G: Scope,
+    G::Timestamp: Lattice+Ord,
+    N: Data+Default+Hash,
+{
+    graph.iterate(|edges| {
+        // keep edges from active edge destinations.
+        let active = edges.map(|(_, dst)| dst).distinct();
+        graph.enter(&edges.scope())
+             .semijoin(&active)
+    })
+    .map(|(src, dst)| (dst, src))
+}
+
+/// Reports the edges of `graph` whose endpoints lie in the same strongly connected component.
+///
+/// # Examples
+///
+/// ```
+/// #
+/// extern crate timely;
+/// extern crate differential_dataflow;
+///
+/// use differential_dataflow::input::Input;
+/// use differential_dataflow::algorithms::graphs::strongly_connected;
+///
+/// fn main() {
+///     ::timely::example(|scope| {
+///
+///         let edges = scope.new_collection_from(vec![(0u32, 1), (1, 0), (1, 2), (2, 3), (3, 2)]).1;
+///         let expected = scope.new_collection_from(vec![(0, 1), (1, 0), (2, 3), (3, 2)]).1;
+///
+///         strongly_connected(&edges)
+///             .assert_eq(&expected);
+///     });
+/// }
+/// ```
+pub fn strongly_connected<G, N>(graph: &Collection<G, (N, N)>) -> Collection<G, (N, N)>
+where
+    G: Scope,
+    G::Timestamp: Lattice+Ord,
+    N: Data+Default+Hash,
+{
+    graph.iterate(|inner| {
+        let edges = graph.enter(&inner.scope());
+        let trans = edges.map(|(src, dst)| (dst, src));
+        trim_edges(&trim_edges(inner, &edges), &trans)
+    })
+}
+
+/// Retains those of `edges` whose endpoints receive the same label when propagated along `cycle`.
+///
+/// The retained edges are reported reversed.
+fn trim_edges<G, N>(cycle: &Collection<G, (N, N)>, edges: &Collection<G, (N, N)>) -> Collection<G, (N, N)>
+where
+    G: Scope,
+    G::Timestamp: Lattice+Ord,
+    N: Data+Default+Hash,
+{
+    let nodes = edges.map(|(_, dst)| (dst.clone(), dst))
+                     .consolidate();
+
+    let labels = propagate(&cycle, &nodes);
+
+    edges.join_map(&labels, |e1, e2, l1| (e2.clone(), (e1.clone(), l1.clone())))
+         .join_map(&labels, |e2, &(ref e1, ref l1), l2| ((e1.clone(), e2.clone()), (l1.clone(), l2.clone())))
+         .filter(|&(_, (ref l1, ref l2))| l1 == l2)
+         .map(|((x1, x2), _)| (x2, x1))
+}
@@ -0,0 +1,64 @@
+//! Triangle enumeration and counting in undirected graphs.
+
+use std::hash::Hash;
+
+use timely::dataflow::*;
+
+use ::{Collection, Data};
+use lattice::Lattice;
+use operators::*;
+
+/// Reports each triangle `(a, b, c)` of the graph, with `a < b < c`.
+///
+/// The direction of edges is ignored, as are self-loops and the multiplicities of edges.
+///
+/// # Examples
+///
+/// ```
+/// #
+/// extern crate timely;
+/// extern crate differential_dataflow;
+///
+/// use differential_dataflow::input::Input;
+/// use differential_dataflow::algorithms::graphs::triangles;
+///
+/// fn main() {
+///     ::timely::example(|scope| {
+///
+///         let edges = scope.new_collection_from(vec![(0u32, 1), (2, 1), (0, 2), (2, 3)]).1;
+///         let expected = scope.new_collection_from(vec![(0, 1, 2)]).1;
+///
+///         triangles(&edges)
+///             .assert_eq(&expected);
+///     });
+/// }
+/// ```
+pub fn triangles<G, N>(edges: &Collection<G, (N, N)>) -> Collection<G, (N, N, N)>
+where
+    G: Scope,
+    G::Timestamp: Lattice+Ord,
+    N: Data+Default+Hash,
+{
+    // orient each edge from its lesser to its greater endpoint.
+    let edges = edges.filter(|&(ref x, ref y)| x != y)
+                     .map(|(x, y)| if x < y { (x, y) } else { (y, x) })
+                     .distinct();
+
+    // pairs of edges from a common least node, which form a triangle if their endpoints are connected.
+    edges.join_map(&edges, |a, b, c| ((b.clone(), c.clone()), a.clone()))
+         .filter(|&((ref b, ref c), _)| b < c)
+         .semijoin(&edges)
+         .map(|((b, c), a)| (a, b, c))
+}
+
+/// Reports for each node the number of triangles containing it, for nodes in at least one triangle.
+pub fn count_triangles<G, N>(edges: &Collection<G, (N, N)>) -> Collection<G, (N, isize)>
+where
+    G: Scope,
+    G::Timestamp: Lattice+Ord,
+    N: Data+Default+Hash,
+{
+    triangles(edges)
+        .flat_map(|(a, b, c)| vec![a, b, c])
+        .count()
+}
@@ -0,0 +1,7 @@
+//! Algorithms assembled from differential dataflow operators.
+//!
+//! The modules here contain functions from collections (and arrangements) to collections, which can be
+//! used as parts of larger computations. Each is incrementally maintained as its inputs change, just as
+//! the operators it is built from.
+
+pub mod graphs;
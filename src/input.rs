@@ -6,6 +6,9 @@
 //! timely dataflow capabilities, exposing more concurrency to the operator implementations
 //! than are evident from the logical times, which appear to execute in sequence.
 
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
 use timely_communication::Allocate;
 
 use timely::progress::Timestamp;
@@ -16,6 +19,7 @@ use timely::dataflow::operators::input::Handle;
 use timely::dataflow::scopes::{Child, Root};
 
 use ::{Data, Diff};
+use lattice::TotalOrder;
 use collection::{Collection, AsCollection};
 
 /// Create a new collection and input handle to control the collection.
@@ -160,6 +164,7 @@ impl<'a, A: Allocate, T: Timestamp+Ord> Input<'a, A, T> for Child<'a, Root<A>, T
 pub struct InputSession<T: Timestamp+Clone, D: Data, R: Diff> {
 	time: Product<RootTimestamp, T>,
 	buffer: Vec<(D, Product<RootTimestamp, T>, R)>,
+	future: BinaryHeap<HeldUpdate<T, D, R>>,
 	handle: Handle<T,(D,Product<RootTimestamp, T>,R)>,
 }
 
@@ -177,6 +182,7 @@ impl<'a, T: Timestamp+Clone, D: Data, R: Diff> InputSession<T, D, R> {
 		InputSession {
 			time: handle.time().clone(),
 			buffer: Vec::new(),
+			future: BinaryHeap::new(),
 			handle: handle,
 		}
 	}
@@ -214,6 +220,12 @@ impl<'a, T: Timestamp+Clone, D: Data, R: Diff> InputSession<T, D, R> {
 		assert!(self.handle.epoch().less_equal(&time));
 		assert!(&self.time.inner.less_equal(&time));
 		self.time = Product::new(RootTimestamp, time);
+
+		// Release held back updates whose times the session has now reached, which the heap presents first.
+		while self.future.peek().map(|held| held.time.inner.less_equal(&self.time.inner)).unwrap_or(false) {
+			let held = self.future.pop().unwrap();
+			self.buffer.push((held.element, held.time, held.change));
+		}
 	}
 
 	/// Reveals the current time of the session.
@@ -225,8 +237,97 @@ impl<'a, T: Timestamp+Clone, D: Data, R: Diff> InputSession<T, D, R> {
 	pub fn close(self) { }
 }
 
+impl<T: Timestamp+Clone+TotalOrder, D: Data, R: Diff> InputSession<T, D, R> {
+
+	/// Adds to the weight of an element in the collection at a time not earlier than that of the session.
+	///
+	/// Updates at times the session has not yet reached are held back until `advance_to` reaches them, and
+	/// are then sent with the session's other updates. Any updates still held back when the session is closed
+	/// or dropped are sent at that point.
+	///
+	/// The time must be totally ordered, so that every held back update remains in advance of the session's
+	/// time, and of the time to which `flush` advances the timely dataflow input.
+	///
+	/// # Examples
+	///
+	/// ```
+	/// #
+	/// extern crate timely;
+	/// extern crate timely_communication;
+	/// extern crate differential_dataflow;
+	///
+	/// use timely_communication::Configuration;
+	/// use differential_dataflow::input::Input;
+	///
+	/// fn main() {
+	///     ::timely::execute(Configuration::Thread, |worker| {
+	///
+	///         let (mut handle, probe) = worker.dataflow(|scope| {
+	///             // create input handle and collection.
+	///             let (handle, data) = scope.new_collection();
+	///             let probe = data.inspect(|x| println!("{:?}", x))
+	///                             .probe();
+	///             (handle, probe)
+	///         });
+	///
+	///         // insert an element now, and schedule its removal.
+	///         handle.insert(3);
+	///         handle.update_at(3, 5, -1);
+	///         handle.advance_to(1);
+	///         handle.flush();
+	///
+	///         while probe.less_than(handle.time()) {
+	///             worker.step();
+	///         }
+	///
+	///     }).unwrap();
+	/// }
+	/// ```
+	pub fn update_at(&mut self, element: D, time: T, change: R) {
+		assert!(self.time.inner.less_equal(&time));
+		let time = Product::new(RootTimestamp, time);
+		if time == self.time {
+			self.update(element, change);
+		}
+		else {
+			self.future.push(HeldUpdate { element: element, time: time, change: change });
+		}
+	}
+}
+
 impl<T: Timestamp+Clone, D: Data, R: Diff> Drop for InputSession<T, D, R> {
 	fn drop(&mut self) {
+		// Held back updates exist only for totally ordered times, and are then in advance of the input's
+		// epoch; they may be sent as is.
+		self.buffer.extend(self.future.drain().map(|held| (held.element, held.time, held.change)));
 		self.flush();
 	}
 }
+
+/// An update held back by `update_at` until the session reaches its time.
+///
+/// Updates are ordered by time alone, and in reverse, so that a `BinaryHeap` presents the earliest first.
+/// Only sessions with totally ordered times hold back updates, and for them this is a total order.
+struct HeldUpdate<T: Timestamp, D, R> {
+	element: D,
+	time: Product<RootTimestamp, T>,
+	change: R,
+}
+
+impl<T: Timestamp, D, R> Ord for HeldUpdate<T, D, R> {
+	fn cmp(&self, other: &Self) -> Ordering {
+		if self.time.inner.less_than(&other.time.inner) { Ordering::Greater }
+		else if other.time.inner.less_than(&self.time.inner) { Ordering::Less }
+		else { Ordering::Equal }
+	}
+}
+
+impl<T: Timestamp, D, R> PartialOrd for HeldUpdate<T, D, R> {
+	fn partial_cmp(&self, other: &Self) -> Option<Ordering> { Some(self.cmp(other)) }
+}
+
+impl<T: Timestamp, D, R> PartialEq for HeldUpdate<T, D, R> {
+	fn eq(&self, other: &Self) -> bool { self.cmp(other) == Ordering::Equal }
+}
+
+impl<T: Timestamp, D, R> Eq for HeldUpdate<T, D, R> { }
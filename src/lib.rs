@@ -91,6 +91,7 @@ extern crate timely;
 extern crate timely_sort;
 extern crate timely_communication;
 extern crate abomonation;
+extern crate memmap;
 
 pub mod hashable;
 pub mod operators;
@@ -98,4 +99,6 @@ pub mod lattice;
 pub mod trace;
 pub mod input;
 pub mod difference;
-pub mod collection;
\ No newline at end of file
+pub mod collection;
+pub mod logging;
+pub mod algorithms;
\ No newline at end of file
@@ -0,0 +1,179 @@
+//! Loggers and events for differential dataflow.
+//!
+//! Differential dataflow reports events about the construction and maintenance of its traces, and the work
+//! done by its operators, to a logger registered for the current thread (each timely dataflow worker runs on
+//! its own thread). No events are recorded until a logger is registered, at which point each event invokes it.
+//! Differential dataflow only reports events when built with the `logging` feature, so that other builds pay
+//! nothing for them.
+//!
+//! The `EventBuffer` logger retains events so that they may be introduced into a dataflow through an input
+//! session, producing a collection of events that the dataflow can itself analyze.
+//!
+//! # Examples
+//!
+//! ```
+//! use differential_dataflow::logging;
+//!
+//! logging::register(|event| println!("{:?}", event));
+//! logging::log(logging::DifferentialEvent::Batch(logging::BatchEvent { length: 10 }));
+//! logging::unregister();
+//! ```
+
+use std::rc::Rc;
+use std::cell::RefCell;
+use std::time::Duration;
+
+use abomonation::Abomonation;
+use timely::progress::Timestamp;
+
+use input::InputSession;
+
+/// A batch of updates was sealed by a batcher.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct BatchEvent {
+    /// The number of updates in the batch.
+    pub length: usize,
+}
+
+/// A merge of two batches was begun or finished.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct MergeEvent {
+    /// The number of updates in the older batch.
+    pub length1: usize,
+    /// The number of updates in the newer batch.
+    pub length2: usize,
+    /// The number of updates in the merged batch, if the merge has finished.
+    pub complete: Option<usize>,
+    /// The time spent working on the merge, if the merge has finished.
+    pub elapsed: Option<Duration>,
+}
+
+/// A trace was dropped.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct DropEvent {
+    /// The number of updates in the trace when it was dropped.
+    pub length: usize,
+}
+
+/// An operator spent fuel on deferred work.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct FuelEvent {
+    /// The amount of fuel spent.
+    pub fuel: usize,
+    /// The number of output records produced.
+    pub produced: usize,
+    /// Whether the deferred work is now complete.
+    pub complete: bool,
+}
+
+/// A `group` operator re-evaluated its logic for some keys.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct GroupEvent {
+    /// The number of keys considered.
+    pub keys: usize,
+    /// The number of output records produced.
+    pub produced: usize,
+}
+
+/// An event in the execution of differential dataflow operators and traces.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum DifferentialEvent {
+    /// A batch was sealed.
+    Batch(BatchEvent),
+    /// A merge was begun or finished.
+    Merge(MergeEvent),
+    /// A trace was dropped.
+    Drop(DropEvent),
+    /// Fuel was spent on deferred work.
+    Fuel(FuelEvent),
+    /// Keys were re-evaluated by `group`.
+    Group(GroupEvent),
+}
+
+// Events contain no owned allocations, and so require no special treatment.
+impl Abomonation for DifferentialEvent { }
+
+thread_local!(static LOGGER: RefCell<Option<Rc<Fn(DifferentialEvent)>>> = RefCell::new(None));
+
+/// Registers `logger` to receive the events of the current thread, replacing any previous logger.
+pub fn register<L: Fn(DifferentialEvent)+'static>(logger: L) {
+    LOGGER.with(|cell| *cell.borrow_mut() = Some(Rc::new(logger)));
+}
+
+/// Removes the logger of the current thread, if any.
+pub fn unregister() {
+    LOGGER.with(|cell| *cell.borrow_mut() = None);
+}
+
+/// Reports `event` to the logger of the current thread, if any.
+#[inline]
+pub fn log(event: DifferentialEvent) {
+    // The logger is cloned out of the cell, so that it may itself register or log.
+    let logger = LOGGER.with(|cell| cell.borrow().clone());
+    if let Some(logger) = logger {
+        logger(event);
+    }
+}
+
+/// A logger that retains events, to be introduced into a dataflow.
+///
+/// # Examples
+///
+/// ```
+/// #
+/// extern crate timely;
+/// extern crate timely_communication;
+/// extern crate differential_dataflow;
+///
+/// use timely_communication::Configuration;
+/// use differential_dataflow::input::Input;
+/// use differential_dataflow::logging::EventBuffer;
+///
+/// fn main() {
+///     ::timely::execute(Configuration::Thread, |worker| {
+///
+///         let buffer = EventBuffer::register();
+///
+///         let (mut events, probe) = worker.dataflow(|scope| {
+///             let (handle, events) = scope.new_collection();
+///             let probe = events.inspect(|x| println!("{:?}", x))
+///                               .probe();
+///             (handle, probe)
+///         });
+///
+///         // introduce the events recorded so far, and await their processing.
+///         buffer.drain_into(&mut events);
+///         events.advance_to(1);
+///         events.flush();
+///
+///         while probe.less_than(events.time()) {
+///             worker.step();
+///         }
+///
+///     }).unwrap();
+/// }
+/// ```
+#[derive(Clone)]
+pub struct EventBuffer {
+    events: Rc<RefCell<Vec<DifferentialEvent>>>,
+}
+
+impl EventBuffer {
+    /// Creates a new buffer, and registers it as the logger of the current thread.
+    pub fn register() -> Self {
+        let buffer = EventBuffer { events: Rc::new(RefCell::new(Vec::new())) };
+        let events = buffer.events.clone();
+        register(move |event| events.borrow_mut().push(event));
+        buffer
+    }
+    /// Moves the retained events into `session`, at the session's current time.
+    pub fn drain_into<T: Timestamp+Clone>(&self, session: &mut InputSession<T, DifferentialEvent, isize>) {
+        for event in self.events.borrow_mut().drain(..) {
+            session.insert(event);
+        }
+    }
+    /// Removes and returns the retained events.
+    pub fn drain(&self) -> Vec<DifferentialEvent> {
+        ::std::mem::replace(&mut *self.events.borrow_mut(), Vec::new())
+    }
+}
@@ -38,7 +38,7 @@ use hashable::{HashOrdered, HashableWrapper, OrdWrapper, UnsignedWrapper};
 
 use ::{Data, Diff, Collection, AsCollection, Hashable};
 use lattice::Lattice;
-use trace::{Trace, TraceReader, Batch, BatchReader, Batcher, Cursor};
+use trace::{Trace, TraceReader, Batch, BatchReader, Batcher, Cursor, Size};
 // use trace::implementations::hash::HashValSpine as DefaultValTrace;
 // use trace::implementations::hash::HashKeySpine as DefaultKeyTrace;
 use trace::implementations::ord::OrdValSpine as DefaultValTrace;
@@ -52,7 +52,8 @@ use trace::wrappers::rc::TraceBox;
 /// The `BatchWrapper`s sole purpose in life is to implement `Abomonation` with methods that panic
 /// when called. This allows the wrapped data to be transited along timely's `Pipeline` channels. 
 /// The wrapper cannot fake out `Send`, and so cannot be used on timely's `Exchange` channels, which
-/// is good.
+/// is good. The batches themselves (e.g. `OrdValBatch`, rather than `Rc<OrdValBatch>`) do implement
+/// `Abomonation`, and may be unwrapped and cloned to be exchanged, written to disk, or sent elsewhere.
 #[derive(Clone,Eq,PartialEq,Debug)]
 pub struct BatchWrapper<T> {
     /// The wrapped item.
@@ -201,6 +202,37 @@ where T: Lattice+Clone+'static, Tr: TraceReader<K,V,T,R> {
 
         reference
     }
+
+    /// Performs deferred work in the shared trace, such as merging batches.
+    ///
+    /// The trace decrements `fuel` by the amount of work performed. This is a good use of otherwise idle time,
+    /// and reduces the work that subsequent insertions must perform.
+    pub fn exert(&mut self, fuel: &mut isize) where Tr: Trace<K,V,T,R>, Tr::Batch: Batch<K,V,T,R> {
+        self.trace.borrow_mut().trace.exert(fuel);
+    }
+
+    /// Reports statistics about the shared trace.
+    ///
+    /// The statistics describe the trace shared by all agents, rather than anything specific to this agent.
+    pub fn stats(&self) -> TraceStats {
+        let mut stats = TraceStats { agents: Rc::strong_count(&self.trace), batches: 0, size: Size::default() };
+        self.trace.borrow_mut().trace.map_batches(|batch| {
+            stats.batches += 1;
+            stats.size = stats.size + batch.size();
+        });
+        stats
+    }
+}
+
+/// Statistics about a trace shared by `TraceAgent`s.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct TraceStats {
+    /// The number of agents sharing the trace.
+    pub agents: usize,
+    /// The number of batches the trace manages.
+    pub batches: usize,
+    /// The total size of the batches.
+    pub size: Size,
 }
 
 impl<K, V, T, R, Tr> TraceAgent<K, V, T, R, Tr>
@@ -271,8 +303,17 @@ where T: Lattice+Clone+'static, Tr: TraceReader<K,V,T,R> {
     /// }
     /// ```
     pub fn import<G: Scope<Timestamp=T>>(&mut self, scope: &G) -> Arranged<G, K, V, R, TraceAgent<K, V, T, R, Tr>> where T: Timestamp {
+        self.import_while(scope, || true)
+    }
+
+    /// Copies an existing collection into the supplied scope, until `live` returns false.
+    ///
+    /// This method behaves as `import`, except that once `live` returns false the source releases its
+    /// capabilities and stops listening to the trace, so that the importing dataflow can complete even
+    /// though the trace continues to change.
+    pub fn import_while<G: Scope<Timestamp=T>, L: Fn()->bool+'static>(&mut self, scope: &G, live: L) -> Arranged<G, K, V, R, TraceAgent<K, V, T, R, Tr>> where T: Timestamp {
 
-        let queue = self.new_listener();
+        let mut queue = self.new_listener();
 
         let collection = source(scope, "ArrangedSource", move |capability| {
             
@@ -281,6 +322,12 @@ where T: Lattice+Clone+'static, Tr: TraceReader<K,V,T,R> {
             
             move |output| {
 
+                // replacing the queue drops our reference to it, which the trace then stops updating.
+                if !live() {
+                    capabilities.clear();
+                    queue = Default::default();
+                }
+
                 let mut borrow = queue.borrow_mut();
                 while let Some((frontier, sent)) = borrow.pop_front() {
                     // if data are associated, send em!
@@ -315,6 +362,162 @@ where T: Lattice+Clone+'static, Tr: TraceReader<K,V,T,R> {
             trace: self.clone(),
         }
     }
+
+    /// Copies an existing collection into the supplied scope, starting from a compacted snapshot.
+    ///
+    /// Unlike `import`, which replays the batches of the trace, this method first produces the current contents
+    /// of the trace as a single consolidated snapshot, with times advanced to `time`, and then produces the
+    /// updates of each subsequent batch as it arrives. The result is a collection rather than an arrangement,
+    /// which makes it suitable for bootstrapping downstream consumers of the updates.
+    ///
+    /// The collection is only correct at times greater or equal to `time`, which must itself be greater or equal
+    /// to some element of `self.advance_frontier()`, as the trace cannot distinguish earlier times.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// #
+    /// extern crate timely;
+    /// extern crate timely_communication;
+    /// extern crate differential_dataflow;
+    ///
+    /// use timely_communication::Configuration;
+    /// use differential_dataflow::input::Input;
+    /// use differential_dataflow::operators::arrange::Arrange;
+    /// use differential_dataflow::trace::Trace;
+    /// use differential_dataflow::trace::implementations::ord::OrdValSpine;
+    /// use differential_dataflow::hashable::OrdWrapper;
+    /// use timely::progress::timestamp::RootTimestamp;
+    ///
+    /// fn main() {
+    ///     ::timely::execute(Configuration::Thread, |worker| {
+    ///
+    ///         // create a first dataflow
+    ///         let mut trace = worker.dataflow::<u32,_,_>(|scope| {
+    ///             // create input handle and collection.
+    ///             scope.new_collection_from(0 .. 10).1
+    ///                  .map(|x| (OrdWrapper { item: x }, x))
+    ///                  .arrange(OrdValSpine::new())
+    ///                  .trace
+    ///         });
+    ///
+    ///         // do some work.
+    ///         worker.step();
+    ///         worker.step();
+    ///
+    ///         // create a second dataflow
+    ///         worker.dataflow(move |scope| {
+    ///             trace.import_compacted(scope, RootTimestamp::new(0))
+    ///                  .inspect(|x| println!("{:?}", x));
+    ///         });
+    ///
+    ///     }).unwrap();
+    /// }
+    /// ```
+    pub fn import_compacted<G: Scope<Timestamp=T>>(&mut self, scope: &G, time: T) -> Collection<G, (K, V), R>
+    where T: Timestamp+Ord, K: Data, V: Data, R: Diff {
+        self.import_compacted_while(scope, time, || true)
+    }
+
+    /// Copies an existing collection into the supplied scope starting from a compacted snapshot, until `live`
+    /// returns false.
+    ///
+    /// This method behaves as `import_compacted`, except that once `live` returns false the source releases its
+    /// capabilities and stops listening to the trace, as with `import_while`.
+    pub fn import_compacted_while<G: Scope<Timestamp=T>, L: Fn()->bool+'static>(&mut self, scope: &G, time: T, live: L) -> Collection<G, (K, V), R>
+    where T: Timestamp+Ord, K: Data, V: Data, R: Diff {
+
+        assert!(self.advance_frontier().iter().any(|t| t.less_equal(&time)), "snapshot time {:?} not in advance of the trace's frontier {:?}", time, self.advance_frontier());
+
+        let mut queue = self.new_listener();
+
+        // The listener is initially populated with the batches of the trace, which we consolidate into a snapshot.
+        let mut snapshot = Vec::new();
+        {
+            let mut borrow = queue.borrow_mut();
+            while borrow.front().map(|&(_, ref sent)| sent.is_some()).unwrap_or(false) {
+                if let Some((_, Some((_, batch)))) = borrow.pop_front() {
+                    let (mut cursor, storage) = batch.cursor();
+                    while cursor.key_valid(&storage) {
+                        while cursor.val_valid(&storage) {
+                            let key = cursor.key(&storage);
+                            let val = cursor.val(&storage);
+                            cursor.map_times(&storage, |t, diff| {
+                                snapshot.push((((key.clone(), val.clone()), t.join(&time)), diff));
+                            });
+                            cursor.step_val(&storage);
+                        }
+                        cursor.step_key(&storage);
+                    }
+                }
+            }
+        }
+        ::trace::consolidate(&mut snapshot, 0);
+        let mut snapshot = Some(snapshot);
+
+        source(scope, "CompactedSource", move |capability| {
+
+            // capabilities the source maintains.
+            let mut capabilities = vec![capability];
+
+            move |output| {
+
+                // as in `import_while`, stop listening to the trace once no longer live.
+                if !live() {
+                    capabilities.clear();
+                    queue = Default::default();
+                    snapshot = None;
+                }
+
+                // the snapshot is sent first, using the initial capability.
+                if let Some(mut snapshot) = snapshot.take() {
+                    let mut session = output.session(&capabilities[0]);
+                    for ((data, time), diff) in snapshot.drain(..) {
+                        session.give((data, time, diff));
+                    }
+                }
+
+                let mut borrow = queue.borrow_mut();
+                while let Some((frontier, sent)) = borrow.pop_front() {
+                    // if data are associated, send their updates.
+                    if let Some((time, batch)) = sent {
+                        if let Some(cap) = capabilities.iter().find(|c| c.time().less_equal(&time)) {
+                            let delayed = cap.delayed(&time);
+                            let mut session = output.session(&delayed);
+                            let (mut cursor, storage) = batch.cursor();
+                            while cursor.key_valid(&storage) {
+                                while cursor.val_valid(&storage) {
+                                    let key = cursor.key(&storage);
+                                    let val = cursor.val(&storage);
+                                    cursor.map_times(&storage, |time, diff| {
+                                        session.give(((key.clone(), val.clone()), time.clone(), diff));
+                                    });
+                                    cursor.step_val(&storage);
+                                }
+                                cursor.step_key(&storage);
+                            }
+                        }
+                        else {
+                            panic!("failed to find capability for {:?} in {:?}", time, capabilities);
+                        }
+                    }
+
+                    // advance capabilities to look like `frontier`.
+                    let mut new_capabilities = Vec::new();
+                    for time in frontier.iter() {
+                        if let Some(cap) = capabilities.iter().find(|c| c.time().less_equal(&time)) {
+                            new_capabilities.push(cap.delayed(&time));
+                        }
+                        else {
+                            panic!("failed to find capability for {:?} in {:?}", time, capabilities);
+                        }
+                    }
+                    capabilities = new_capabilities;
+                }
+            }
+        })
+        .as_collection()
+    }
 }
 
 impl<K, V, T, R, Tr> Clone for TraceAgent<K, V, T, R, Tr>
@@ -420,6 +623,76 @@ impl<G: Scope, K, V, R, T> Arranged<G, K, V, R, T> where G::Timestamp: Lattice,
         })
         .as_collection()
     }
+
+    /// Arranges the same updates by a new key and value, determined by `logic`.
+    ///
+    /// The updates are read from the arranged batches using their cursors, with `logic` applied once for each
+    /// key and value rather than once for each update. They are then exchanged to the workers responsible for
+    /// their new keys, where they are pushed directly into the batcher of an arrangement into `empty_trace`.
+    /// This avoids the `as_collection` and `arrange` operators, but not the exchange and batching themselves,
+    /// as the new keys generally belong to other workers.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// #
+    /// extern crate timely;
+    /// extern crate differential_dataflow;
+    ///
+    /// use differential_dataflow::input::Input;
+    /// use differential_dataflow::operators::arrange::ArrangeByKey;
+    /// use differential_dataflow::trace::Trace;
+    /// use differential_dataflow::trace::implementations::ord::OrdValSpine;
+    /// use differential_dataflow::hashable::OrdWrapper;
+    ///
+    /// fn main() {
+    ///     ::timely::example(|scope| {
+    ///
+    ///         let by_key = scope.new_collection_from(1 .. 10u32).1
+    ///                           .map(|x| (x / 3, x))
+    ///                           .arrange_by_key_hashed();
+    ///
+    ///         // index the same pairs by their values.
+    ///         by_key.rekey(OrdValSpine::new(), |key, val| (OrdWrapper { item: *val }, key.item))
+    ///               .as_collection(|val, key| (val.item, *key))
+    ///               .inspect(|x| println!("{:?}", x));
+    ///     });
+    /// }
+    /// ```
+    pub fn rekey<K2, V2, T2, L>(&self, empty_trace: T2, logic: L) -> Arranged<G, K2, V2, R, TraceAgent<K2, V2, G::Timestamp, R, T2>>
+        where
+            R: Diff,
+            G::Timestamp: Ord,
+            T::Batch: Clone+'static,
+            K: Clone, V: Clone,
+            K2: Data+HashOrdered, V2: Data,
+            T2: Trace<K2, V2, G::Timestamp, R>+'static,
+            T2::Batch: Batch<K2, V2, G::Timestamp, R>,
+            L: Fn(&K, &V) -> (K2, V2)+'static,
+    {
+        let updates = self.stream.unary_stream(Pipeline, "Rekey", move |input, output| {
+
+            input.for_each(|time, data| {
+                let mut session = output.session(&time);
+                for wrapper in data.drain(..) {
+                    let batch = wrapper.item;
+                    let (mut cursor, storage) = batch.cursor();
+                    while cursor.key_valid(&storage) {
+                        while cursor.val_valid(&storage) {
+                            let (key, val) = logic(cursor.key(&storage), cursor.val(&storage));
+                            cursor.map_times(&storage, |time, diff| {
+                                session.give(((key.clone(), val.clone()), time.clone(), diff.clone()));
+                            });
+                            cursor.step_val(&storage);
+                        }
+                        cursor.step_key(&storage);
+                    }
+                }
+            });
+        });
+
+        arrange_updates(&updates, empty_trace)
+    }
 }
 
 /// Arranges something as `(Key,Val)` pairs according to a type `T` of trace.
@@ -429,6 +702,10 @@ pub trait Arrange<G: Scope, K, V, R: Diff> where G::Timestamp: Lattice {
     /// This operator arranges a stream of values into a shared trace, whose contents it maintains.
     /// This trace is current for all times marked completed in the output stream, and probing this stream
     /// is the correct way to determine that times in the shared trace are committed.
+    ///
+    /// The trace may also be non-empty, for example one produced by `trace::checkpoint::restore`, in which
+    /// case new batches continue from the upper frontier of its last batch. The output stream carries only 
+    /// the new batches; the `import` method of the resulting trace presents the restored batches as well.
     fn arrange<T>(&self, empty_trace: T) -> Arranged<G, K, V, R, TraceAgent<K, V, G::Timestamp, R, T>> 
         where 
             T: Trace<K, V, G::Timestamp, R>+'static,
@@ -442,109 +719,135 @@ impl<G: Scope, K: Data+HashOrdered, V: Data, R: Diff> Arrange<G, K, V, R> for Co
             T: Trace<K, V, G::Timestamp, R>+'static,
             T::Batch: Batch<K, V, G::Timestamp, R> {
 
-        let (reader, mut writer) = TraceAgent::new(empty_trace);
+        arrange_updates(&self.inner, empty_trace)
+    }
+}
 
-        // Where we will deposit received updates, and from which we extract batches.
-        let mut batcher = <T::Batch as Batch<K,V,G::Timestamp,R>>::Batcher::new();
+/// Arranges a stream of `((Key, Val), Time, Diff)` updates into `empty_trace`, exchanging updates by `Key`.
+///
+/// This is the operator behind `arrange`, and is also used by `Arranged::rekey` to arrange updates it reads
+/// directly from another arrangement's batches.
+fn arrange_updates<G, K, V, R, T>(updates: &Stream<G, ((K, V), G::Timestamp, R)>, empty_trace: T) -> Arranged<G, K, V, R, TraceAgent<K, V, G::Timestamp, R, T>>
+where
+    G: Scope,
+    G::Timestamp: Lattice+Ord,
+    K: Data+HashOrdered,
+    V: Data,
+    R: Diff,
+    T: Trace<K, V, G::Timestamp, R>+'static,
+    T::Batch: Batch<K, V, G::Timestamp, R> {
+
+    let (mut reader, mut writer) = TraceAgent::new(empty_trace);
+
+    // Where we will deposit received updates, and from which we extract batches.
+    let mut batcher = <T::Batch as Batch<K,V,G::Timestamp,R>>::Batcher::new();
+
+    // If the trace already holds batches (e.g. it was restored from a checkpoint), new batches must
+    // start where the trace leaves off. Sealing the empty batcher advances its lower bound; the input
+    // should not contain updates at times not in advance of this bound.
+    let mut resume = None;
+    reader.map_batches(|batch| resume = Some(batch.upper().to_vec()));
+    if let Some(upper) = resume {
+        batcher.seal(&upper[..]);
+    }
 
-        // Capabilities for the lower envelope of updates in `batcher`.
-        let mut capabilities = Vec::<Capability<G::Timestamp>>::new();
+    // Capabilities for the lower envelope of updates in `batcher`.
+    let mut capabilities = Vec::<Capability<G::Timestamp>>::new();
 
-        // fabricate a data-parallel operator using the `unary_notify` pattern.
-        let exchange = Exchange::new(move |update: &((K,V),G::Timestamp,R)| (update.0).0.hashed().as_u64());
-        let stream = self.inner.unary_notify(exchange, "Arrange", vec![], move |input, output, notificator| {
+    // fabricate a data-parallel operator using the `unary_notify` pattern.
+    let exchange = Exchange::new(move |update: &((K,V),G::Timestamp,R)| (update.0).0.hashed().as_u64());
+    let stream = updates.unary_notify(exchange, "Arrange", vec![], move |input, output, notificator| {
 
-            // As we receive data, we need to (i) stash the data and (ii) keep *enough* capabilities.
-            // We don't have to keep all capabilities, but we need to be able to form output messages
-            // when we realize that time intervals are complete.
+        // As we receive data, we need to (i) stash the data and (ii) keep *enough* capabilities.
+        // We don't have to keep all capabilities, but we need to be able to form output messages
+        // when we realize that time intervals are complete.
 
-            input.for_each(|cap, data| {
+        input.for_each(|cap, data| {
 
-                // add the capability to our list of capabilities.
-                capabilities.retain(|c| !cap.time().less_than(&c.time()));
-                if !capabilities.iter().any(|c| c.time().less_equal(&cap.time())) { 
-                    capabilities.push(cap);
-                }
+            // add the capability to our list of capabilities.
+            capabilities.retain(|c| !cap.time().less_than(&c.time()));
+            if !capabilities.iter().any(|c| c.time().less_equal(&cap.time())) { 
+                capabilities.push(cap);
+            }
 
-                batcher.push_batch(data.deref_mut());
-            });
+            batcher.push_batch(data.deref_mut());
+        });
 
-            // Timely dataflow currently only allows one capability per message, and we may have multiple
-            // incomparable times for which we need to send data. This would normally require shattering
-            // all updates we might send into multiple batches, each associated with a capability. 
-            //
-            // Instead! We can cheat a bit. We can extract one batch, and just make sure to send all of 
-            // capabilities along in separate messages. This is a bit dubious, and we will want to make 
-            // sure that each operator that consumes batches (group, join, as_collection) understands this.
-            // 
-            // At the moment this is painful for non-group operators, who each rely on having the correct 
-            // capabilities at hand, and must find the right capability record-by-record otherwise. But, 
-            // something like this should ease some pain. (we could also just fix timely).
-
-            // If there is at least one capability no longer in advance of the input frontier ...
-            if capabilities.iter().any(|c| !notificator.frontier(0).iter().any(|t| t.less_equal(&c.time()))) {
-
-                // For each capability not in advance of the input frontier ... 
-                for index in 0 .. capabilities.len() {
-                    if !notificator.frontier(0).iter().any(|t| t.less_equal(&capabilities[index].time())) {
-
-                        // Assemble the upper bound on times we can commit with this capabilities.
-                        // This is determined both by the input frontier, and by subsequent capabilities
-                        // which may shadow this capability for some times.
-                        let mut upper = notificator.frontier(0).to_vec();
-                        for capability in &capabilities[(index + 1) .. ] {
-                            let time = capability.time().clone();
-                            if !upper.iter().any(|t| t.less_equal(&time)) {
-                                upper.retain(|t| !time.less_equal(t));
-                                upper.push(time);
-                            }
+        // Timely dataflow currently only allows one capability per message, and we may have multiple
+        // incomparable times for which we need to send data. This would normally require shattering
+        // all updates we might send into multiple batches, each associated with a capability. 
+        //
+        // Instead! We can cheat a bit. We can extract one batch, and just make sure to send all of 
+        // capabilities along in separate messages. This is a bit dubious, and we will want to make 
+        // sure that each operator that consumes batches (group, join, as_collection) understands this.
+        // 
+        // At the moment this is painful for non-group operators, who each rely on having the correct 
+        // capabilities at hand, and must find the right capability record-by-record otherwise. But, 
+        // something like this should ease some pain. (we could also just fix timely).
+
+        // If there is at least one capability no longer in advance of the input frontier ...
+        if capabilities.iter().any(|c| !notificator.frontier(0).iter().any(|t| t.less_equal(&c.time()))) {
+
+            // For each capability not in advance of the input frontier ... 
+            for index in 0 .. capabilities.len() {
+                if !notificator.frontier(0).iter().any(|t| t.less_equal(&capabilities[index].time())) {
+
+                    // Assemble the upper bound on times we can commit with this capabilities.
+                    // This is determined both by the input frontier, and by subsequent capabilities
+                    // which may shadow this capability for some times.
+                    let mut upper = notificator.frontier(0).to_vec();
+                    for capability in &capabilities[(index + 1) .. ] {
+                        let time = capability.time().clone();
+                        if !upper.iter().any(|t| t.less_equal(&time)) {
+                            upper.retain(|t| !time.less_equal(t));
+                            upper.push(time);
                         }
+                    }
 
-                        // Extract updates not in advance of `upper`.
-                        let batch = batcher.seal(&upper[..]);
+                    // Extract updates not in advance of `upper`.
+                    let batch = batcher.seal(&upper[..]);
 
-                        writer.seal(&upper[..], Some((capabilities[index].time().clone(), batch.clone())));
+                    writer.seal(&upper[..], Some((capabilities[index].time().clone(), batch.clone())));
 
-                        // send the batch to downstream consumers, empty or not.
-                        output.session(&capabilities[index]).give(BatchWrapper { item: batch });
-                    }
+                    // send the batch to downstream consumers, empty or not.
+                    output.session(&capabilities[index]).give(BatchWrapper { item: batch });
                 }
+            }
 
-                // Having extracted and sent batches between each capability and the input frontier,
-                // we should downgrade all capabilities to match the batcher's lower update frontier.
-                // This may involve discarding capabilities, which is fine as any new updates arrive 
-                // in messages with new capabilities.
+            // Having extracted and sent batches between each capability and the input frontier,
+            // we should downgrade all capabilities to match the batcher's lower update frontier.
+            // This may involve discarding capabilities, which is fine as any new updates arrive 
+            // in messages with new capabilities.
 
-                let mut new_capabilities = Vec::new();
-                for time in batcher.frontier() {
-                    if let Some(capability) = capabilities.iter().find(|c| c.time().less_equal(time)) {
-                        new_capabilities.push(capability.delayed(time));
-                    }
+            let mut new_capabilities = Vec::new();
+            for time in batcher.frontier() {
+                if let Some(capability) = capabilities.iter().find(|c| c.time().less_equal(time)) {
+                    new_capabilities.push(capability.delayed(time));
                 }
+            }
 
-                capabilities = new_capabilities;
+            capabilities = new_capabilities;
 
-                // writer.seal(notificator.frontier(0), None);
+            // writer.seal(notificator.frontier(0), None);
 
-                // // This very aggressively pushes frontier information along. We may want to dial it back 
-                // // if we find that we are spamming folks.
-                // queues.upgrade().map(|queues| {
-                //     let mut borrow = queues.borrow_mut();
-                //     for queue in borrow.iter_mut() {
-                //         queue.upgrade().map(|queue| {
-                //             queue.borrow_mut().push_back((notificator.frontier(0).to_vec(), None));
-                //         });
-                //     }
-                //     borrow.retain(|w| w.upgrade().is_some());
-                // });
+            // // This very aggressively pushes frontier information along. We may want to dial it back 
+            // // if we find that we are spamming folks.
+            // queues.upgrade().map(|queues| {
+            //     let mut borrow = queues.borrow_mut();
+            //     for queue in borrow.iter_mut() {
+            //         queue.upgrade().map(|queue| {
+            //             queue.borrow_mut().push_back((notificator.frontier(0).to_vec(), None));
+            //         });
+            //     }
+            //     borrow.retain(|w| w.upgrade().is_some());
+            // });
 
-            }
+        }
 
-            writer.seal(notificator.frontier(0), None);
-        });
+        writer.seal(notificator.frontier(0), None);
+    });
 
-        Arranged { stream: stream, trace: reader }
-    }
+    Arranged { stream: stream, trace: reader }
 }
 
 /// Arranges something as `(Key,Val)` pairs according to a type `T` of trace.
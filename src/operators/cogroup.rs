@@ -1,272 +1,470 @@
-//! Group records by a key, and apply a reduction function.
+//! Group records from several collections by a common key, and apply a reduction function.
 //!
-//! The `group` operators act on data that can be viewed as pairs `(key, val)`. They group records
-//! with the same key, and apply user supplied functions to the key and a list of values, which are
-//! expected to populate a list of output values.
+//! The `cogroup` operators act on any number of collections of `(key, val)` pairs which share key and value
+//! types. For each key they present user logic with the accumulated values of each input, in the order the
+//! inputs were supplied, and the logic is expected to populate a list of output values. A key is presented if
+//! any input has values for it, which makes `cogroup` suitable for computations like a full outer join
+//! followed by an aggregation. Inputs with different value types can be brought to a common type with an
+//! `enum`, or by mapping values before the `cogroup`.
 //!
-//! Several variants of `group` exist which allow more precise control over how grouping is done.
-//! For example, the `_by` suffixed variants take arbitrary data, but require a key-value selector
-//! to be applied to each record. The `_u` suffixed variants use unsigned integers as keys, and
-//! will use a dense array rather than a `HashMap` to store their keys.
-//!
-//! The list of values are presented as an iterator which internally merges sorted lists of values.
-//! This ordering can be exploited in several cases to avoid computation when only the first few
-//! elements are required.
+//! As with `group`, the `_u` suffixed variants use unsigned integers as keys, and `cogroup_arranged` acts
+//! on arranged inputs and produces an arranged output which can be re-used by other operators.
 //!
 //! #Examples
 //!
-//! This example groups a stream of `(key,val)` pairs by `key`, and yields only the most frequently
-//! occurring value for each key.
+//! This example reports, for each key, the number of values in each of three collections.
 //!
 //! ```ignore
-//! stream.group(|key, vals, output| {
-//!     let (mut max_val, mut max_wgt) = vals.peek().unwrap();
-//!     for (val, wgt) in vals {
-//!         if wgt > max_wgt {
-//!             max_wgt = wgt;
-//!             max_val = val;
-//!         }
-//!     }
-//!     output.push((max_val.clone(), max_wgt));
+//! stream1.cogroup(&[stream2, stream3], |_key, vals, output| {
+//!     output.push(((vals[0].len(), vals[1].len(), vals[2].len()), 1));
 //! })
 //! ```
 
-use std::rc::Rc;
+use std::fmt::Debug;
 use std::default::Default;
-use std::hash::Hasher;
-use std::ops::DerefMut;
 
-use itertools::Itertools;
+use hashable::{Hashable, UnsignedWrapper};
+use ::{Data, Collection, Diff};
 
-use ::{Collection, Data};
+use timely::order::PartialOrder;
+use timely::progress::Timestamp;
 use timely::dataflow::*;
-use timely::dataflow::operators::{Map, Binary};
-use timely::dataflow::channels::pact::Exchange;
-use timely_sort::{LSBRadixSorter, Unsigned};
-
-use collection::{LeastUpperBound, Lookup, Trace, Offset};
-use collection::trace::{CollectionIterator, DifferenceIterator, Traceable};
-
-use iterators::coalesce::Coalesce;
-use collection::compact::Compact;
+use timely::dataflow::operators::{Unary, Map, Concat};
+use timely::dataflow::channels::pact::Pipeline;
+use timely::dataflow::operators::Capability;
+use timely_sort::Unsigned;
+
+use operators::arrange::{Arrange, Arranged, ArrangeByKey, BatchWrapper, TraceAgent};
+use operators::history_replay::{HistoryReplayer, PerKeyCompute, sort_dedup};
+use lattice::Lattice;
+use trace::{Batch, BatchReader, Cursor, Trace, Builder};
+use trace::cursor::cursor_list::CursorList;
+use trace::implementations::ord::OrdValSpine as DefaultValTrace;
+
+use trace::TraceReader;
+
+/// Extension trait for the `cogroup` differential dataflow method.
+pub trait CoGroup<G: Scope, K: Data, V1: Data, R1: Diff> where G::Timestamp: Lattice+Ord {
+    /// Groups records of this and the other collections by their first field, and applies reduction logic to
+    /// the associated values.
+    ///
+    /// The logic is applied to each key with values in at least one of the collections, and is presented with
+    /// one list of values for each collection, starting with `self` and followed by `others` in order. Any of
+    /// the lists may be empty.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// #
+    /// extern crate timely;
+    /// extern crate differential_dataflow;
+    ///
+    /// use differential_dataflow::input::Input;
+    /// use differential_dataflow::operators::CoGroup;
+    ///
+    /// fn main() {
+    ///     ::timely::example(|scope| {
+    ///         // report the number of values in each input for each key.
+    ///         let x = scope.new_collection_from(1 .. 10).1.map(|x| (x / 3, x));
+    ///         let y = scope.new_collection_from(1 .. 10).1.map(|x| (x / 2, x));
+    ///         let z = scope.new_collection_from(1 .. 10).1.map(|x| (x / 4, x));
+    ///         x.cogroup(&[y, z], |_key, src, dst| {
+    ///             dst.push(((src[0].len(), src[1].len(), src[2].len()), 1))
+    ///         });
+    ///     });
+    /// }
+    /// ```
+    fn cogroup<L, V3: Data, R3: Diff>(&self, others: &[Collection<G, (K, V1), R1>], logic: L) -> Collection<G, (K, V3), R3>
+    where L: Fn(&K, &[Vec<(&V1, R1)>], &mut Vec<(V3, R3)>)+'static;
+    /// Groups records of this and the other collections by their first field, and applies reduction logic to
+    /// the associated values.
+    ///
+    /// This method is a specialization for when the key is an unsigned integer fit for distributing the data.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// #
+    /// extern crate timely;
+    /// extern crate differential_dataflow;
+    ///
+    /// use differential_dataflow::input::Input;
+    /// use differential_dataflow::operators::CoGroup;
+    ///
+    /// fn main() {
+    ///     ::timely::example(|scope| {
+    ///         // report the number of values in each input for each key.
+    ///         let x = scope.new_collection_from(1 .. 10u32).1.map(|x| (x / 3, x));
+    ///         let y = scope.new_collection_from(1 .. 10u32).1.map(|x| (x / 2, x));
+    ///         x.cogroup_u(&[y], |_key, src, dst| {
+    ///             dst.push(((src[0].len(), src[1].len()), 1))
+    ///         });
+    ///     });
+    /// }
+    /// ```
+    fn cogroup_u<L, V3: Data, R3: Diff>(&self, others: &[Collection<G, (K, V1), R1>], logic: L) -> Collection<G, (K, V3), R3>
+    where L: Fn(&K, &[Vec<(&V1, R1)>], &mut Vec<(V3, R3)>)+'static, K: Unsigned+Copy;
+}
 
-/// Extension trait for the `group_by` and `group_by_u` differential dataflow methods.
-pub trait CoGroupBy<G: Scope, K: Data, V1: Data> where G::Timestamp: LeastUpperBound {
+impl<G: Scope, K: Data+Default+Hashable, V1: Data, R1: Diff> CoGroup<G, K, V1, R1> for Collection<G, (K, V1), R1>
+    where G::Timestamp: Lattice+Ord+Debug, <K as Hashable>::Output: Data+Default {
+    fn cogroup<L, V3: Data, R3: Diff>(&self, others: &[Collection<G, (K, V1), R1>], logic: L) -> Collection<G, (K, V3), R3>
+        where L: Fn(&K, &[Vec<(&V1, R1)>], &mut Vec<(V3, R3)>)+'static {
+        let others = others.iter().map(|other| other.arrange_by_key_hashed()).collect::<Vec<_>>();
+        self.arrange_by_key_hashed()
+            .cogroup_arranged(&others[..], move |k,s,t| logic(&k.item,s,t), DefaultValTrace::new())
+            .as_collection(|k,v| (k.item.clone(), v.clone()))
+    }
+    fn cogroup_u<L, V3: Data, R3: Diff>(&self, others: &[Collection<G, (K, V1), R1>], logic: L) -> Collection<G, (K, V3), R3>
+        where L: Fn(&K, &[Vec<(&V1, R1)>], &mut Vec<(V3, R3)>)+'static, K: Unsigned+Copy {
+        let others = others.iter().map(|other| {
+            other.map(|(k,v)| (UnsignedWrapper::from(k), v))
+                 .arrange(DefaultValTrace::new())
+        }).collect::<Vec<_>>();
+        self.map(|(k,v)| (UnsignedWrapper::from(k), v))
+            .arrange(DefaultValTrace::new())
+            .cogroup_arranged(&others[..], move |k,s,t| logic(&k.item,s,t), DefaultValTrace::new())
+            .as_collection(|k,v| (k.item.clone(), v.clone()))
+    }
+}
 
-    /// A primitive binary version of `group_by`, which acts on a `Collection<G, (K, V1)>` and a `Collection<G, (K, V2)>`.
+/// Extension trait for the `cogroup_arranged` differential dataflow method.
+pub trait CoGroupArranged<G: Scope, K: Data, V1: Data, R1: Diff, T1> where G::Timestamp: Lattice+Ord {
+    /// Applies `cogroup` to this and the other arranged inputs, and returns an arrangement of output data.
     ///
-    /// The two streams must already be key-value pairs, which is too bad. Also, in addition to the
-    /// normal arguments (another stream, a hash for the key, a reduction function, and per-key logic),
-    /// the user must specify a function implmenting `Fn(u64) -> Look`, where `Look: Lookup<K, Offset>` is something you shouldn't have to know about yet.
-    /// The right thing to use here, for the moment, is `|_| HashMap::new()`.
+    /// This method is used by the more ergonomic `cogroup` methods, although it can be very useful if one
+    /// needs to manually attach and re-use existing arranged collections. The arrangements must all have the
+    /// same type, and so must have been arranged by the same method.
     ///
-    /// There are better options if you know your key is an unsigned integer, namely `|x| (Vec::new(), x)`.
-    fn cogroup_by_inner<
-        D:     Data,
-        V2:    Data+Default,
-        V3:    Data+Default,
-        U:     Unsigned+Default,
-        KH:    Fn(&K)->U+'static,
-        Look:  Lookup<K, Offset>+'static,
-        LookG: Fn(u64)->Look,
-        Logic: Fn(&K, &mut CollectionIterator<DifferenceIterator<V1>>, &mut CollectionIterator<DifferenceIterator<V2>>, &mut Vec<(V3, i32)>)+'static,
-        Reduc: Fn(&K, &V3)->D+'static,
-    >
-    (&self, other: &Collection<G, (K, V2)>, key_h: KH, reduc: Reduc, look: LookG, logic: Logic) -> Collection<G, D>;
+    /// # Examples
+    ///
+    /// ```
+    /// #
+    /// extern crate timely;
+    /// extern crate differential_dataflow;
+    ///
+    /// use differential_dataflow::input::Input;
+    /// use differential_dataflow::operators::arrange::ArrangeByKey;
+    /// use differential_dataflow::operators::cogroup::CoGroupArranged;
+    /// use differential_dataflow::trace::Trace;
+    /// use differential_dataflow::trace::implementations::ord::OrdValSpine;
+    ///
+    /// fn main() {
+    ///     ::timely::example(|scope| {
+    ///
+    ///         let x = scope.new_collection_from(1 .. 10).1.map(|x| (x / 3, x)).arrange_by_key_hashed();
+    ///         let y = scope.new_collection_from(1 .. 10).1.map(|x| (x / 2, x)).arrange_by_key_hashed();
+    ///
+    ///         // report the least value of the first input, if the second input has any values.
+    ///         x.cogroup_arranged(
+    ///             &[y],
+    ///             move |_key, src, dst| {
+    ///                 if src[0].len() > 0 && src[1].len() > 0 {
+    ///                     dst.push((*src[0][0].0, 1))
+    ///                 }
+    ///             },
+    ///             OrdValSpine::new()
+    ///         );
+    ///     });
+    /// }
+    /// ```
+    fn cogroup_arranged<V3, R3, T3, L>(&self, others: &[Arranged<G, K, V1, R1, T1>], logic: L, empty: T3) -> Arranged<G, K, V3, R3, TraceAgent<K, V3, G::Timestamp, R3, T3>>
+        where
+            V3: Data,
+            R3: Diff,
+            T3: Trace<K, V3, G::Timestamp, R3>+'static,
+            T3::Batch: Batch<K, V3, G::Timestamp, R3>,
+            L: Fn(&K, &[Vec<(&V1, R1)>], &mut Vec<(V3, R3)>)+'static
+            ;
 }
 
-impl<G: Scope, K: Data, V1: Data> CoGroupBy<G, K, V1> for Collection<G, (K, V1)>
-where G::Timestamp: LeastUpperBound {
-    fn cogroup_by_inner<
-        D:     Data,
-        V2:    Data+Default,
-        V3:    Data+Default,
-        U:     Unsigned+Default,
-        KH:    Fn(&K)->U+'static,
-        Look:  Lookup<K, Offset>+'static,
-        LookG: Fn(u64)->Look,
-        Logic: Fn(&K, &mut CollectionIterator<V1>, &mut CollectionIterator<V2>, &mut Vec<(V3, i32)>)+'static,
-        Reduc: Fn(&K, &V3)->D+'static,
-    >
-    (&self, other: &Collection<G, (K, V2)>, key_h: KH, reduc: Reduc, look: LookG, logic: Logic) -> Collection<G, D> {
-
-        let mut source1 = Trace::new(look(0));
-        let mut source2 = Trace::new(look(0));
-        let mut result = Trace::new(look(0));
-
-        // A map from times to received (key, val, wgt) triples.
-        let mut inputs1 = Vec::new();
-        let mut inputs2 = Vec::new();
-
-        // A map from times to a list of keys that need processing at that time.
-        let mut to_do = Vec::new();
-
-        // temporary storage for operator implementations to populate
-        let mut buffer = vec![];
-
-        let key_h = Rc::new(key_h);
-        let key_1 = key_h.clone();
-        let key_2 = key_h.clone();
-
-        // create an exchange channel based on the supplied Fn(&D1)->u64.
-        let exch1 = Exchange::new(move |&((ref k, _),_)| key_1(k).as_u64());
-        let exch2 = Exchange::new(move |&((ref k, _),_)| key_2(k).as_u64());
-
-        let mut sorter1 = LSBRadixSorter::new();
-        let mut sorter2 = LSBRadixSorter::new();
-
-        // fabricate a data-parallel operator using the `unary_notify` pattern.
-        Collection::new(self.inner.binary_notify(&other.inner, exch1, exch2, "CoGroupBy", vec![], move |input1, input2, output, notificator| {
-
-            // 1. read each input, and stash it in our staging area
-            while let Some((time, data)) = input1.next() {
-                inputs1.entry_or_insert(time.time(), || Vec::new())
-                       .push(::std::mem::replace(data.deref_mut(), Vec::new()));
-                notificator.notify_at(time);
+impl<G: Scope, K: Data, V1: Data, T1, R1: Diff> CoGroupArranged<G, K, V1, R1, T1> for Arranged<G, K, V1, R1, T1>
+where
+    G::Timestamp: Lattice+Ord,
+    T1: TraceReader<K, V1, G::Timestamp, R1>+Clone+'static,
+    T1::Batch: BatchReader<K, V1, G::Timestamp, R1> {
+
+    fn cogroup_arranged<V3, R3, T3, L>(&self, others: &[Arranged<G, K, V1, R1, T1>], logic: L, empty: T3) -> Arranged<G, K, V3, R3, TraceAgent<K, V3, G::Timestamp, R3, T3>>
+        where
+            V3: Data,
+            R3: Diff,
+            T3: Trace<K, V3, G::Timestamp, R3>+'static,
+            T3::Batch: Batch<K, V3, G::Timestamp, R3>,
+            L: Fn(&K, &[Vec<(&V1, R1)>], &mut Vec<(V3, R3)>)+'static {
+
+        // The number of inputs, with `self` as input zero.
+        let inputs = 1 + others.len();
+
+        let mut source_traces = vec![self.trace.clone()];
+        source_traces.extend(others.iter().map(|other| other.trace.clone()));
+
+        let (mut output_reader, mut output_writer) = TraceAgent::new(empty);
+        let result_trace = output_reader.clone();
+
+        let mut temporary = Vec::<G::Timestamp>::new();
+
+        // Outstanding `(key, time)` synthetic interesting times, and capabilities for their lower envelope.
+        let mut interesting = Vec::<(K, G::Timestamp)>::new();
+        let mut capabilities = Vec::<Capability<G::Timestamp>>::new();
+
+        // buffers and logic for computing per-key interesting times "efficiently".
+        let mut interesting_times = Vec::<G::Timestamp>::new();
+
+        // space for assembling the upper bound of times to process.
+        let mut upper_limit = Vec::<G::Timestamp>::new();
+
+        // Batches received from each input, but not yet processed.
+        //
+        // Unlike `group`, we may receive batches from one input with updates at times that another input
+        // has not yet completed. Each invocation that produces output processes all pending batches, and
+        // the updates at times not yet retired by `upper_limit` are deferred by recording their keys and
+        // times in `interesting`, to be revisited once `upper_limit` passes them.
+        let mut pending = (0 .. inputs).map(|_| Vec::new()).collect::<Vec<_>>();
+
+        // tracks frontiers received from batches, and the frontiers through which they have been processed.
+        let mut upper_received = vec![vec![<G::Timestamp as Lattice>::minimum()]; inputs];
+        let mut lower_received = upper_received.clone();
+
+        // We separately track the frontiers for what we have sent, and what we have sealed.
+        let mut lower_issued = vec![<G::Timestamp as Lattice>::minimum()];
+
+        // Tag each batch with the index of its input, and merge the inputs into one stream.
+        let mut batches = self.stream.map(|batch| BatchWrapper { item: (0, batch.item) });
+        for (index, other) in others.iter().enumerate() {
+            batches = batches.concat(&other.stream.map(move |batch| BatchWrapper { item: (index + 1, batch.item) }));
+        }
+
+        let stream = batches.unary_notify(Pipeline, "CoGroup", Vec::new(), move |input, output, notificator| {
+
+            // Drain the input stream of batches, validating the contiguity of the batch descriptions of each
+            // input and retaining each of the batches as well as ensuring we hold a capability for their times.
+            input.for_each(|capability, batches| {
+                for (index, batch) in batches.drain(..).map(|x| x.item) {
+                    assert!(&upper_received[index][..] == batch.description().lower());
+                    upper_received[index] = batch.description().upper().to_vec();
+                    pending[index].push(batch);
+                }
+                insert_capability(&mut capabilities, capability);
+            });
+
+            // The interval of times we can retire is upper bounded, for each input, by both the most recently
+            // received batch upper bound and the input progress frontier. As in `group`, we use the pairwise
+            // joins of these antichains for each input, and then the lower envelope of the results from all
+            // inputs, as a time may only be retired once it is retired for every input. The inputs share one
+            // progress frontier, which is at most the frontier of any one input.
+            upper_limit.clear();
+            for upper_received in upper_received.iter() {
+                for time1 in notificator.frontier(0) {
+                    for time2 in upper_received.iter() {
+                        let join = time1.join(time2);
+                        if !upper_limit.iter().any(|t| t.less_equal(&join)) {
+                            upper_limit.retain(|t| !join.less_equal(t));
+                            upper_limit.push(join);
+                        }
+                    }
+                }
             }
 
-            // 1. read each input, and stash it in our staging area
-            while let Some((time, data)) = input2.next() {
-                inputs2.entry_or_insert(time.time(), || Vec::new())
-                       .push(::std::mem::replace(data.deref_mut(), Vec::new()));
-                notificator.notify_at(time);
-            }
+            // If we have no capabilities that are not in advance of `upper_limit` we should not produce
+            // any outputs, and we leave any pending batches for a future invocation.
+            if capabilities.iter().any(|c| !upper_limit.iter().any(|t| t.less_equal(&c.time()))) {
+
+                // `interesting` contains "warnings" about keys and times that may need to be re-considered.
+                // We first extract those times from this list that lie in the interval we will process.
+                sort_dedup(&mut interesting);
+                let mut new_interesting = Vec::new();
+                let mut exposed = Vec::new();
+                segment(&mut interesting, &mut exposed, &mut new_interesting, |&(_, ref time)| {
+                    !upper_limit.iter().any(|t| t.less_equal(&time))
+                });
+                interesting = new_interesting;
+
+                // Prepare an output buffer and builder for each capability.
+                let mut buffers = Vec::<(G::Timestamp, Vec<(V3, G::Timestamp, R3)>)>::new();
+                let mut builders = Vec::new();
+                for i in 0 .. capabilities.len() {
+                    buffers.push((capabilities[i].time().clone(), Vec::new()));
+                    builders.push(<T3::Batch as Batch<K,V3,G::Timestamp,R3>>::Builder::new());
+                }
+
+                // cursors for the pending batches of each input.
+                let mut batch_cursor_lists = Vec::with_capacity(inputs);
+                let mut batch_storage = Vec::with_capacity(inputs);
+                for pending in pending.iter_mut() {
+                    let mut cursors = Vec::new();
+                    let mut storage = Vec::new();
+                    for batch in pending.drain(..) {
+                        let (cursor, store) = batch.cursor();
+                        cursors.push(cursor);
+                        storage.push(store);
+                    }
+                    batch_cursor_lists.push(CursorList::new(cursors, &storage));
+                    batch_storage.push(storage);
+                }
 
-            // 2. go through each time of interest that has reached completion
-            // times are interesting either because we received data, or because we conclude
-            // in the processing of a time that a future time will be interesting.
-            while let Some((index, _count)) = notificator.next() {
+                // cursors for navigating input and output traces.
+                let mut source_cursor_list = Vec::with_capacity(inputs);
+                let mut source_storage = Vec::with_capacity(inputs);
+                for (source_trace, lower_received) in source_traces.iter_mut().zip(lower_received.iter()) {
+                    let (cursor, storage): (T1::Cursor, _) = source_trace.cursor_through(&lower_received[..]).unwrap();
+                    source_cursor_list.push(cursor);
+                    source_storage.push(storage);
+                }
+                let (mut output_cursor, output_storage): (T3::Cursor, _) = output_reader.cursor();
+                let output_storage = &output_storage;
 
-                let mut stash = Vec::new();
+                // pair each cursor with its storage, as the history replayer expects.
+                let mut source_cursors = source_cursor_list.into_iter().zip(source_storage.iter()).collect::<Vec<_>>();
+                let mut batch_cursors = batch_cursor_lists.into_iter().zip(batch_storage.iter()).collect::<Vec<_>>();
 
-                panic!("interesting times needs to do LUB of union of times for each key, input");
+                let mut thinker = HistoryReplayer::<V1, V3, G::Timestamp, R1, R3>::new();
 
-                // 2a. fetch any data associated with this time.
-                if let Some(mut queue) = inputs1.remove_key(&index) {
+                // We now march through the keys we must work on, drawing from all batches and `exposed`.
+                let mut exposed_position = 0;
+                while batch_cursors.iter().any(|&(ref cursor, storage)| cursor.key_valid(storage)) || exposed_position < exposed.len() {
 
-                    // sort things; radix if many, .sort_by if few.
-                    let compact = if queue.len() > 1 {
-                        for element in queue.into_iter() {
-                            sorter1.extend(element.into_iter(), &|x| key_h(&(x.0).0));
-                        }
-                        let mut sorted = sorter1.finish(&|x| key_h(&(x.0).0));
-                        let result = Compact::from_radix(&mut sorted, &|k| key_h(k));
-                        sorted.truncate(256);
-                        sorter1.recycle(sorted);
-                        result
+                    // Determine the next key we will work on; could be synthetic, could be from any batch.
+                    let mut key = None;
+                    if exposed_position < exposed.len() {
+                        key = Some(exposed[exposed_position].0.clone());
                     }
-                    else {
-                        let mut vec = queue.pop().unwrap();
-                        let mut vec = vec.drain(..).collect::<Vec<_>>();
-                        vec.sort_by(|x,y| key_h(&(x.0).0).cmp(&key_h((&(y.0).0))));
-                        Compact::from_radix(&mut vec![vec], &|k| key_h(k))
-                    };
-
-                    if let Some(compact) = compact {
-
-                        for key in &compact.keys {
-                            stash.push(index.clone());
-                            source1.interesting_times(key, &index, &mut stash);
-                            for time in &stash {
-                                let mut queue = to_do.entry_or_insert((*time).clone(), || { notificator.notify_at(index.delayed(time)); Vec::new() });
-                                queue.push((*key).clone());
+                    for &(ref cursor, storage) in batch_cursors.iter() {
+                        if cursor.key_valid(storage) {
+                            if key.as_ref().map(|k| cursor.key(storage) < k).unwrap_or(true) {
+                                key = Some(cursor.key(storage).clone());
                             }
-                            stash.clear();
                         }
-
-                        source1.set_difference(index.time(), compact);
                     }
-                }
+                    let key = key.unwrap();
 
-                // 2a. fetch any data associated with this time.
-                if let Some(mut queue) = inputs2.remove_key(&index) {
+                    // Populate `interesting_times` with synthetic interesting times for this key.
+                    interesting_times.clear();
+                    while exposed_position < exposed.len() && exposed[exposed_position].0 == key {
+                        interesting_times.push(exposed[exposed_position].1.clone());
+                        exposed_position += 1;
+                    }
 
-                    // sort things; radix if many, .sort_by if few.
-                    let compact = if queue.len() > 1 {
-                        for element in queue.into_iter() {
-                            sorter2.extend(element.into_iter(), &|x| key_h(&(x.0).0));
+                    // tidy up times, removing redundancy.
+                    sort_dedup(&mut interesting_times);
+
+                    // do the per-key computation.
+                    thinker.compute(
+                        &key,
+                        &mut source_cursors[..],
+                        (&mut output_cursor, output_storage),
+                        &mut batch_cursors[..],
+                        &mut interesting_times,
+                        &logic,
+                        &upper_limit[..],
+                        &mut buffers[..],
+                        &mut temporary,
+                    );
+
+                    for &mut (ref mut cursor, storage) in batch_cursors.iter_mut() {
+                        if cursor.key_valid(storage) && cursor.key(storage) == &key {
+                            cursor.step_key(storage);
                         }
-                        let mut sorted = sorter2.finish(&|x| key_h(&(x.0).0));
-                        let result = Compact::from_radix(&mut sorted, &|k| key_h(k));
-                        sorted.truncate(256);
-                        sorter2.recycle(sorted);
-                        result
                     }
-                    else {
-                        let mut vec = queue.pop().unwrap();
-                        let mut vec = vec.drain(..).collect::<Vec<_>>();
-                        vec.sort_by(|x,y| key_h(&(x.0).0).cmp(&key_h((&(y.0).0))));
-                        Compact::from_radix(&mut vec![vec], &|k| key_h(k))
-                    };
-
-                    if let Some(compact) = compact {
-
-                        for key in &compact.keys {
-                            stash.push(index.clone());
-                            source2.interesting_times(key, &index, &mut stash);
-                            for time in &stash {
-                                let mut queue = to_do.entry_or_insert((*time).clone(), || { notificator.notify_at(index.delayed(time)); Vec::new() });
-                                queue.push((*key).clone());
-                            }
-                            stash.clear();
-                        }
 
-                        source2.set_difference(index.time(), compact);
+                    // Record future warnings about interesting times (and assert they should be "future").
+                    for time in temporary.drain(..) {
+                        assert!(upper_limit.iter().any(|t| t.less_equal(&time)));
+                        interesting.push((key.clone(), time));
                     }
-                }
 
-                // we may need to produce output at index
-                let mut session = output.session(&index);
+                    // Sort each buffer by value and move into the corresponding builder.
+                    for index in 0 .. buffers.len() {
+                        buffers[index].1.sort_by(|x,y| x.0.cmp(&y.0));
+                        for (val, time, diff) in buffers[index].1.drain(..) {
+                            builders[index].push((key.clone(), val, time, diff));
+                        }
+                    }
+                }
 
+                // build and ship each batch (because only one capability per message).
+                for (index, builder) in builders.drain(..).enumerate() {
+                    let mut local_upper = upper_limit.clone();
+                    for capability in &capabilities[index + 1 ..] {
+                        let time = capability.time().clone();
+                        if !local_upper.iter().any(|t| t.less_equal(&time)) {
+                            local_upper.retain(|t| !time.less_than(t));
+                            local_upper.push(time);
+                        }
+                    }
 
-                    // 2b. We must now determine for each interesting key at this time, how does the
-                    // currently reported output match up with what we need as output. Should we send
-                    // more output differences, and what are they?
+                    if lower_issued != local_upper {
 
-                // Much of this logic used to hide in `OperatorTrace` and `CollectionTrace`.
-                // They are now gone and simpler, respectively.
-                if let Some(mut keys) = to_do.remove_key(&index) {
+                        let batch = builder.done(&lower_issued[..], &local_upper[..], &lower_issued[..]);
 
-                    // we would like these keys in a particular order.
-                    // TODO : use a radix sort since we have `key_h`.
-                    keys.sort_by(|x,y| (key_h(&x), x).cmp(&(key_h(&y), y)));
-                    keys.dedup();
+                        // ship batch to the output, and commit to the output trace.
+                        output.session(&capabilities[index]).give(BatchWrapper { item: batch.clone() });
+                        output_writer.seal(&local_upper[..], Some((capabilities[index].time().clone(), batch)));
 
-                    // accumulations for installation into result
-                    let mut accumulation = Compact::new(0,0);
+                        lower_issued = local_upper;
+                    }
+                }
 
-                    for key in keys {
+                // Determine the frontier of our interesting times.
+                let mut frontier = Vec::<G::Timestamp>::new();
+                for &(_, ref time) in &interesting {
+                    if !frontier.iter().any(|t| t.less_equal(time)) {
+                        frontier.retain(|t| !time.less_than(t));
+                        frontier.push(time.clone());
+                    }
+                }
 
-                        // acquire an iterator over the collection at `time`.
-                        let mut input1 = source1.get_collection(&key, &index);
-                        let mut input2 = source2.get_collection(&key, &index);
+                // Update `capabilities` to reflect interesting pairs described by `frontier`.
+                let mut new_capabilities = Vec::new();
+                for time in frontier.drain(..) {
+                    if let Some(cap) = capabilities.iter().find(|c| c.time().less_equal(&time)) {
+                        new_capabilities.push(cap.delayed(&time));
+                    }
+                    else {
+                        panic!("failed to find capability less than new frontier time: {:?} (capabilities: {:?}, upper limit: {:?})", time, capabilities, upper_limit);
+                    }
+                }
+                capabilities = new_capabilities;
 
-                        // if we have some data, invoke logic to populate self.dst
-                        if input1.peek().is_some() || input2.peek().is_some() { logic(&key, &mut input1, &mut input2, &mut buffer); }
+                // ensure that observed progres is reflected in the output.
+                output_writer.seal(&upper_limit[..], None);
 
-                        buffer.sort_by(|x,y| x.0.cmp(&y.0));
+                // The pending batches have now been processed.
+                lower_received = upper_received.clone();
+            }
 
-                        // push differences in to Compact.
-                        let mut compact = accumulation.session();
-                        for (val, wgt) in Coalesce::coalesce(result.get_collection(&key, &index)
-                                                                   .map(|(v, w)| (v,-w))
-                                                                   .merge_by(buffer.iter().map(|&(ref v, w)| (v, w)), |x,y| {
-                                                                        x.0 <= y.0
-                                                                   }))
-                        {
-                            session.give((reduc(&key, val), wgt));
-                            compact.push(val.clone(), wgt);
-                        }
-                        compact.done(key);
-                        buffer.clear();
-                    }
+            // We only anticipate future times in advance of `upper_limit`.
+            for source_trace in source_traces.iter_mut() {
+                source_trace.advance_by(&upper_limit[..]);
+            }
+            output_reader.advance_by(&upper_limit[..]);
 
-                    if accumulation.vals.len() > 0 {
-                        // println!("group2");
-                        result.set_difference(index.time(), accumulation);
-                    }
-                }
+            // We will only slice the data between future batches.
+            for (source_trace, lower_received) in source_traces.iter_mut().zip(lower_received.iter()) {
+                source_trace.distinguish_since(&lower_received[..]);
             }
-        }))
+            output_reader.distinguish_since(&upper_limit[..]);
+        });
+
+        Arranged { stream: stream, trace: result_trace }
+    }
+}
+
+/// Retains `capability` in `capabilities`, if it is not already covered by some other capability.
+fn insert_capability<T: Timestamp>(capabilities: &mut Vec<Capability<T>>, capability: Capability<T>) {
+    capabilities.retain(|cap| !capability.time().less_than(&cap.time()));
+    if !capabilities.iter().any(|cap| cap.time().less_equal(&capability.time())) {
+        capabilities.push(capability);
+    }
+}
+
+#[inline(never)]
+fn segment<T, F: Fn(&T)->bool>(source: &mut Vec<T>, dest1: &mut Vec<T>, dest2: &mut Vec<T>, pred: F) {
+    for element in source.drain(..) {
+        if pred(&element) {
+            dest1.push(element);
+        }
+        else {
+            dest2.push(element);
+        }
     }
 }
@@ -0,0 +1,306 @@
+//! Multi-way joins that share arrangements, evaluated as delta queries.
+//!
+//! A multi-way join of several inputs can be evaluated as a chain of binary joins, but each binary join
+//! maintains an arrangement of its intermediate results, which can be much larger than the inputs. The
+//! `delta_join` operator instead responds to the changes of each input separately: the changes of an input
+//! form prefixes of output records, which are extended by looking up matching records in the arrangements
+//! of each of the other inputs in turn. No intermediate results are arranged, and only the arrangements of
+//! the inputs themselves (which may be shared with other computations) are maintained.
+//!
+//! To avoid counting simultaneous changes more than once, changes of an input observe the updates of
+//! inputs with smaller indices at times less or equal to their own, and the updates of inputs with larger
+//! indices only at strictly smaller times. This requires the timestamps to be totally ordered.
+//!
+//! #Examples
+//!
+//! This example determines the triangles `(a, b, c)` of a graph, where each of `(a, b)`, `(b, c)`, and
+//! `(a, c)` are edges, using one arrangement of the edges for all three inputs.
+//!
+//! ```ignore
+//! let edges = edges.arrange_by_key_hashed();
+//! let triangles = delta_join(&[&edges, &edges, &edges], vec![
+//!     // changes to `(a, b)` look up `c` from `(b, c)`, and then confirm `(a, c)`.
+//!     DeltaPlan::new(|a: &OrdWrapper<u32>, b: &u32| (a.item, *b, 0))
+//!         .lookup(1, |p| (OrdWrapper { item: p.1 }, None), |p, c| Some((p.0, p.1, *c)))
+//!         .lookup(2, |p| (OrdWrapper { item: p.0 }, Some(p.2)), |p, _| Some(*p)),
+//!     // changes to `(b, c)` look up `a` from `(a, c)` (as arranged by `c`), ...
+//! ]);
+//! ```
+
+use std::rc::Rc;
+use std::ops::Mul;
+
+use timely::order::PartialOrder;
+use timely::dataflow::Scope;
+use timely::dataflow::operators::generic::Binary;
+use timely::dataflow::channels::pact::{Pipeline, Exchange};
+use timely::dataflow::operators::Capability;
+
+use timely_sort::Unsigned;
+
+use hashable::HashOrdered;
+use ::{Data, Diff, Collection, AsCollection};
+use lattice::{Lattice, TotalOrder};
+use operators::arrange::Arranged;
+use trace::{BatchReader, Cursor, TraceReader};
+
+/// The plan for extending the changes of one input of a `delta_join` to output records.
+///
+/// A plan starts from each change `(key, val)` of its input, forming a prefix of an output record, and then
+/// extends the prefix by a sequence of lookups, one for each other input. Each lookup determines a key from
+/// the prefix, and optionally a value, and each matching record of the corresponding input is offered to an
+/// extension function which may produce an extended prefix.
+pub struct DeltaPlan<K, V, D> {
+    start: Box<Fn(&K, &V)->D>,
+    lookups: Vec<(usize, Rc<Fn(&D)->(K, Option<V>)>, Box<Fn(&D, &V)->Option<D>>)>,
+}
+
+impl<K, V, D> DeltaPlan<K, V, D> {
+    /// Creates a new plan whose prefixes are formed from the input's changes by `start`.
+    pub fn new<S: Fn(&K, &V)->D+'static>(start: S) -> Self {
+        DeltaPlan {
+            start: Box::new(start),
+            lookups: Vec::new(),
+        }
+    }
+    /// Extends the plan by a lookup in input `index`.
+    ///
+    /// For each prefix, `key` produces the key to look up and optionally a value; if a value is produced
+    /// only records with that value are matched. Each matching value is presented to `extend` along with
+    /// the prefix, and the prefixes it produces have their multiplicities multiplied by that of the match.
+    pub fn lookup<KF, EF>(mut self, index: usize, key: KF, extend: EF) -> Self
+    where
+        KF: Fn(&D)->(K, Option<V>)+'static,
+        EF: Fn(&D, &V)->Option<D>+'static,
+    {
+        self.lookups.push((index, Rc::new(key), Box::new(extend)));
+        self
+    }
+}
+
+/// Joins several arranged inputs, using `plans[i]` to extend the changes of `inputs[i]`.
+///
+/// Each plan must look up each other input exactly once, and should produce the same output records
+/// from the same combination of input records, regardless of which input's changes it started from.
+/// The result is the collection of output records, with multiplicities the product of those of the
+/// combined input records. The same arrangement may be supplied as several inputs.
+///
+/// # Examples
+///
+/// ```
+/// #
+/// extern crate timely;
+/// extern crate differential_dataflow;
+///
+/// use differential_dataflow::input::Input;
+/// use differential_dataflow::operators::Join;
+/// use differential_dataflow::operators::arrange::ArrangeByKey;
+/// use differential_dataflow::operators::delta_join::{delta_join, DeltaPlan};
+/// use differential_dataflow::hashable::OrdWrapper;
+///
+/// fn main() {
+///     ::timely::example(|scope| {
+///
+///         let x = scope.new_collection_from(vec![(0, 'a'), (1, 'b')]).1;
+///         let y = scope.new_collection_from(vec![(0, 'c'), (1, 'd')]).1;
+///         let z = scope.new_collection_from(vec![(0, 'e'), (2, 'f')]).1;
+///
+///         let expected = x.join(&y).map(|(k,x,y)| (k,(x,y))).join(&z).map(|(k,(x,y),z)| (k,x,y,z));
+///
+///         let x_arr = x.arrange_by_key_hashed();
+///         let y_arr = y.arrange_by_key_hashed();
+///         let z_arr = z.arrange_by_key_hashed();
+///
+///         // prefixes are `(key, x, y, z)`, with placeholders for values not yet determined.
+///         delta_join(&[&x_arr, &y_arr, &z_arr], vec![
+///             DeltaPlan::new(|k: &OrdWrapper<i32>, x: &char| (k.item, *x, ' ', ' '))
+///                 .lookup(1, |p| (OrdWrapper { item: p.0 }, None), |p, y| Some((p.0, p.1, *y, p.3)))
+///                 .lookup(2, |p| (OrdWrapper { item: p.0 }, None), |p, z| Some((p.0, p.1, p.2, *z))),
+///             DeltaPlan::new(|k: &OrdWrapper<i32>, y: &char| (k.item, ' ', *y, ' '))
+///                 .lookup(0, |p| (OrdWrapper { item: p.0 }, None), |p, x| Some((p.0, *x, p.2, p.3)))
+///                 .lookup(2, |p| (OrdWrapper { item: p.0 }, None), |p, z| Some((p.0, p.1, p.2, *z))),
+///             DeltaPlan::new(|k: &OrdWrapper<i32>, z: &char| (k.item, ' ', ' ', *z))
+///                 .lookup(0, |p| (OrdWrapper { item: p.0 }, None), |p, x| Some((p.0, *x, p.2, p.3)))
+///                 .lookup(1, |p| (OrdWrapper { item: p.0 }, None), |p, y| Some((p.0, p.1, *y, p.3))),
+///         ])
+///         .assert_eq(&expected);
+///     });
+/// }
+/// ```
+pub fn delta_join<G, K, V, R, Tr, D>(inputs: &[&Arranged<G, K, V, R, Tr>], plans: Vec<DeltaPlan<K, V, D>>) -> Collection<G, D, R>
+where
+    G: Scope,
+    G::Timestamp: Lattice+TotalOrder+Ord,
+    K: Data+HashOrdered,
+    V: Data,
+    R: Diff+Mul<R, Output=R>,
+    Tr: TraceReader<K, V, G::Timestamp, R>+Clone+'static,
+    Tr::Batch: BatchReader<K, V, G::Timestamp, R>+Clone+'static,
+    D: Data,
+{
+    assert!(inputs.len() > 0, "delta_join requires at least one input");
+    assert!(inputs.len() == plans.len(), "delta_join requires one plan for each input");
+
+    let mut result: Option<Collection<G, D, R>> = None;
+    for (index, plan) in plans.into_iter().enumerate() {
+
+        let mut covered = vec![false; inputs.len()];
+        covered[index] = true;
+        for &(other, _, _) in plan.lookups.iter() {
+            assert!(other < inputs.len() && !covered[other], "plan {} looks up input {} twice or out of range", index, other);
+            covered[other] = true;
+        }
+        assert!(covered.iter().all(|x| *x), "plan {} does not look up all inputs", index);
+
+        let start = plan.start;
+        let mut changes = inputs[index].as_collection(move |k,v| start(k,v));
+        for (other, key, extend) in plan.lookups.into_iter() {
+            changes = half_join(&changes, inputs[other], other > index, key, extend);
+        }
+
+        result = Some(match result {
+            Some(result) => result.concat(&changes),
+            None => changes,
+        });
+    }
+
+    result.unwrap()
+}
+
+/// Extends each change in `changes` by the records of `arrangement` at times not greater than its own.
+///
+/// If `strict` is set, only records at times strictly less than the change are matched. Changes are held
+/// until the arrangement is complete through their times, and outputs are produced at the change times.
+fn half_join<G, K, V, R, Tr, D>(
+    changes: &Collection<G, D, R>,
+    arrangement: &Arranged<G, K, V, R, Tr>,
+    strict: bool,
+    key: Rc<Fn(&D)->(K, Option<V>)>,
+    extend: Box<Fn(&D, &V)->Option<D>>) -> Collection<G, D, R>
+where
+    G: Scope,
+    G::Timestamp: Lattice+TotalOrder+Ord,
+    K: Data+HashOrdered,
+    V: Data,
+    R: Diff+Mul<R, Output=R>,
+    Tr: TraceReader<K, V, G::Timestamp, R>+Clone+'static,
+    Tr::Batch: BatchReader<K, V, G::Timestamp, R>+'static,
+    D: Data,
+{
+    // We only ever read complete contents of the trace, and so do not need to distinguish any times.
+    let mut trace = Some(arrangement.trace.clone());
+    if let Some(ref mut trace) = trace {
+        trace.distinguish_since(&[]);
+    }
+
+    // Changes awaiting the completion of the arrangement, with capabilities for their times.
+    let mut pending = Vec::<(Capability<G::Timestamp>, Vec<(D, G::Timestamp, R)>)>::new();
+
+    // The frontier to which the trace may be advanced when using strict comparisons.
+    let mut lagging = vec![<G::Timestamp as Lattice>::minimum()];
+
+    let exchange_key = key.clone();
+    let exchange = Exchange::new(move |update: &(D, G::Timestamp, R)| exchange_key(&update.0).0.hashed().as_u64());
+
+    changes.inner.binary_notify(&arrangement.stream, exchange, Pipeline, "HalfJoin", Vec::new(), move |input1, input2, output, notificator| {
+
+        input1.for_each(|capability, data| {
+            pending.push((capability, data.drain(..).collect()));
+        });
+
+        // The batches themselves are not needed, as we read the shared trace, but their progress is.
+        input2.for_each(|_, data| { data.drain(..); });
+
+        if let Some(ref mut trace) = trace {
+
+            if pending.len() > 0 {
+
+                let (mut cursor, storage) = trace.cursor();
+                let complete = notificator.frontier(1);
+
+                for &mut (ref mut capability, ref mut updates) in pending.iter_mut() {
+
+                    // Extract the changes at times through which the arrangement is complete.
+                    let mut ready = Vec::new();
+                    let mut index = 0;
+                    while index < updates.len() {
+                        if complete.iter().any(|t| t.less_equal(&updates[index].1)) {
+                            index += 1;
+                        }
+                        else {
+                            let (prefix, time, diff) = updates.swap_remove(index);
+                            let (lookup_key, lookup_val) = key(&prefix);
+                            ready.push((lookup_key, lookup_val, prefix, time, diff));
+                        }
+                    }
+
+                    // Visit the changes in key order, as the cursor only seeks forward.
+                    ready.sort_by(|x,y| x.0.cmp(&y.0));
+                    cursor.rewind_keys(&storage);
+
+                    let mut session = output.session(capability);
+                    for (lookup_key, lookup_val, prefix, time, diff) in ready.drain(..) {
+                        cursor.seek_key(&storage, &lookup_key);
+                        if cursor.key_valid(&storage) && cursor.key(&storage) == &lookup_key {
+                            cursor.rewind_vals(&storage);
+                            if let Some(ref val) = lookup_val {
+                                cursor.seek_val(&storage, val);
+                            }
+                            while cursor.val_valid(&storage) && lookup_val.as_ref().map(|v| cursor.val(&storage) == v).unwrap_or(true) {
+                                let mut count = R::zero();
+                                cursor.map_times(&storage, |t, d| {
+                                    let visible = if strict { t.less_than(&time) } else { t.less_equal(&time) };
+                                    if visible {
+                                        count = count + d;
+                                    }
+                                });
+                                if !count.is_zero() {
+                                    if let Some(result) = extend(&prefix, cursor.val(&storage)) {
+                                        session.give((result, time.clone(), diff * count));
+                                    }
+                                }
+                                cursor.step_val(&storage);
+                            }
+                        }
+                    }
+
+                    // Downgrade the capability to the least remaining time, if any remain.
+                    if let Some(time) = updates.iter().map(|x| &x.1).min() {
+                        *capability = capability.delayed(time);
+                    }
+                }
+
+                pending.retain(|&(_, ref updates)| updates.len() > 0);
+            }
+
+            // Changes at times in advance of `lower` may yet probe the trace.
+            let mut lower = notificator.frontier(0).to_vec();
+            for &(ref capability, _) in pending.iter() {
+                let time = capability.time();
+                if !lower.iter().any(|t| t.less_equal(&time)) {
+                    lower.retain(|t| !time.less_equal(t));
+                    lower.push(time.clone());
+                }
+            }
+
+            // Advancing times to `lower` preserves their comparisons with times in advance of `lower`, except
+            // that strictly smaller times become indistinguishable from `lower` itself. In the strict case we
+            // instead advance to the previous value of `lower`, which is strictly smaller than future times.
+            if strict {
+                if lower != lagging {
+                    trace.advance_by(&lagging[..]);
+                    lagging = lower;
+                }
+            }
+            else {
+                trace.advance_by(&lower[..]);
+            }
+        }
+
+        // Once no changes remain or may yet arrive, we release the trace.
+        if trace.is_some() && pending.len() == 0 && notificator.frontier(0).len() == 0 {
+            trace = None;
+        }
+    })
+    .as_collection()
+}
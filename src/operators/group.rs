@@ -27,6 +27,7 @@ use timely::dataflow::operators::Capability;
 use timely_sort::Unsigned;
 
 use operators::arrange::{Arrange, Arranged, ArrangeByKey, ArrangeBySelf, BatchWrapper, TraceAgent};
+use operators::history_replay::{HistoryReplayer, PerKeyCompute, sort_dedup};
 use lattice::Lattice;
 use trace::{Batch, BatchReader, Cursor, Trace, Builder};
 use trace::cursor::cursor_list::CursorList;
@@ -37,6 +38,9 @@ use trace::implementations::ord::OrdKeySpine as DefaultKeyTrace;
 
 use trace::TraceReader;
 
+#[cfg(feature = "logging")]
+use logging::{DifferentialEvent, GroupEvent};
+
 /// Extension trait for the `group` differential dataflow method.
 pub trait Group<G: Scope, K: Data, V: Data, R: Diff> where G::Timestamp: Lattice+Ord {
     /// Groups records by their first field, and applies reduction logic to the associated values.
@@ -436,14 +440,25 @@ where
                 }
 
                 // cursors for navigating input and output traces.
-                let (mut source_cursor, source_storage): (T1::Cursor, _) = source_trace.cursor_through(&lower_received[..]).unwrap();
+                let (source_cursor, source_storage): (T1::Cursor, _) = source_trace.cursor_through(&lower_received[..]).unwrap();
                 let source_storage = &source_storage;
                 let (mut output_cursor, output_storage): (T2::Cursor, _) = output_reader.cursor(); // TODO: this panicked when as above; WHY???
                 let output_storage = &output_storage;
-                let (mut batch_cursor, batch_storage) = (CursorList::new(batch_cursors, &batch_storage), batch_storage);
+                let (batch_cursor, batch_storage) = (CursorList::new(batch_cursors, &batch_storage), batch_storage);
                 let batch_storage = &batch_storage;
 
-                let mut thinker = history_replay::HistoryReplayer::<V, V2, G::Timestamp, R, R2>::new();
+                // the history replayer works with any number of inputs; we have just the one.
+                let mut source_cursors = [(source_cursor, source_storage)];
+                let mut batch_cursors = [(batch_cursor, batch_storage)];
+                let logic = |key: &K, inputs: &[Vec<(&V, R)>], output: &mut Vec<(V2, R2)>| logic(key, &inputs[0][..], output);
+
+                let mut thinker = HistoryReplayer::<V, V2, G::Timestamp, R, R2>::new();
+
+                // counts of keys considered and records produced, for logging.
+                #[cfg(feature = "logging")]
+                let mut keys_considered = 0;
+                #[cfg(feature = "logging")]
+                let mut records_produced = 0;
 
                 // let timer = ::std::time::Instant::now();
                 // let mut compute_counter = 0;
@@ -456,11 +471,11 @@ where
                 // indicates whether more data remain. We move throuh `exposed` using `exposed_position`.
                 // There could perhaps be a less provocative variable name.
                 let mut exposed_position = 0;
-                while batch_cursor.key_valid(batch_storage) || exposed_position < exposed.len() {
+                while batch_cursors[0].0.key_valid(batch_storage) || exposed_position < exposed.len() {
 
                     // Determine the next key we will work on; could be synthetic, could be from a batch.
                     let key1 = if exposed_position < exposed.len() { Some(exposed[exposed_position].0.clone()) } else { None };
-                    let key2 = if batch_cursor.key_valid(&batch_storage) { Some(batch_cursor.key(batch_storage).clone()) } else { None };
+                    let key2 = if batch_cursors[0].0.key_valid(batch_storage) { Some(batch_cursors[0].0.key(batch_storage).clone()) } else { None };
                     let key = match (key1, key2) {
                         (Some(key1), Some(key2)) => ::std::cmp::min(key1, key2),
                         (Some(key1), None)       => key1,
@@ -490,9 +505,9 @@ where
                     // do the per-key computation.
                     let _counters = thinker.compute(
                         &key, 
-                        (&mut source_cursor, source_storage),
+                        &mut source_cursors[..],
                         (&mut output_cursor, output_storage),
-                        (&mut batch_cursor, batch_storage),
+                        &mut batch_cursors[..],
                         &mut interesting_times, 
                         &logic, 
                         &upper_limit[..], 
@@ -517,8 +532,8 @@ where
                     //     &mut temporary2,
                     // );
 
-                    if batch_cursor.key_valid(batch_storage) && batch_cursor.key(batch_storage) == &key {
-                        batch_cursor.step_key(batch_storage);
+                    if batch_cursors[0].0.key_valid(batch_storage) && batch_cursors[0].0.key(batch_storage) == &key {
+                        batch_cursors[0].0.step_key(batch_storage);
                     }
 
                     // key_count += 1;
@@ -540,6 +555,12 @@ where
                         interesting.push((key.clone(), time)); 
                     }
 
+                    #[cfg(feature = "logging")]
+                    {
+                        keys_considered += 1;
+                        records_produced += buffers.iter().map(|buffer| buffer.1.len()).sum::<usize>();
+                    }
+
                     // Sort each buffer by value and move into the corresponding builder.
                     // TODO: This makes assumptions about at least one of (i) the stability of `sort_by`, 
                     //       (ii) that the buffers are time-ordered, and (iii) that the builders accept 
@@ -552,6 +573,9 @@ where
                     }
                 }
 
+                #[cfg(feature = "logging")]
+                ::logging::log(DifferentialEvent::Group(GroupEvent { keys: keys_considered, produced: records_produced }));
+
                 // build and ship each batch (because only one capability per message).
                 for (index, builder) in builders.drain(..).enumerate() {
                     let mut local_upper = upper_limit.clone();
@@ -622,13 +646,6 @@ where
     }
 }
 
-#[inline(never)]
-fn sort_dedup<T: Ord>(list: &mut Vec<T>) {
-    list.dedup();
-    list.sort();
-    list.dedup();
-}
-
 #[inline(never)]
 fn segment<T, F: Fn(&T)->bool>(source: &mut Vec<T>, dest1: &mut Vec<T>, dest2: &mut Vec<T>, pred: F) {
     for element in source.drain(..) {
@@ -641,18 +658,6 @@ fn segment<T, F: Fn(&T)->bool>(source: &mut Vec<T>, dest1: &mut Vec<T>, dest2: &
     }
 }
 
-#[inline(never)]
-fn consolidate<T: Ord, R: Diff>(list: &mut Vec<(T, R)>) {
-    list.sort_by(|x,y| x.0.cmp(&y.0));
-    for index in 1 .. list.len() {
-        if list[index].0 == list[index-1].0 {
-            list[index].1 = list[index].1 + list[index-1].1;
-            list[index-1].1 = R::zero();
-        }
-    }
-    list.retain(|x| !x.1.is_zero());
-}
-
 /// Scans `vec[off..]` and consolidates differences of adjacent equivalent elements.
 // #[inline(never)]
 pub fn consolidate_from<T: Ord+Clone, R: Diff>(vec: &mut Vec<(T, R)>, off: usize) {
@@ -679,387 +684,6 @@ pub fn consolidate_from<T: Ord+Clone, R: Diff>(vec: &mut Vec<(T, R)>, off: usize
     
 }
 
-trait PerKeyCompute<'a, V1, V2, T, R1, R2> 
-where
-    V1: Ord+Clone+'a,
-    V2: Ord+Clone+'a,
-    T: Lattice+Ord+Clone,
-    R1: Diff,
-    R2: Diff,
-{
-    fn new() -> Self;
-    fn compute<K, C1, C2, C3, L>(
-        &mut self,
-        key: &K, 
-        source_cursor: (&mut C1, &'a C1::Storage), 
-        output_cursor: (&mut C2, &'a C2::Storage),
-        batch_cursor: (&mut C3, &'a C3::Storage),
-        times: &mut Vec<T>, 
-        logic: &L, 
-        upper_limit: &[T],
-        outputs: &mut [(T, Vec<(V2, T, R2)>)],
-        new_interesting: &mut Vec<T>) -> (usize, usize)
-    where 
-        K: Eq+Clone+Debug,
-        C1: Cursor<K, V1, T, R1>, 
-        C2: Cursor<K, V2, T, R2>, 
-        C3: Cursor<K, V1, T, R1>, 
-        L: Fn(&K, &[(&V1, R1)], &mut Vec<(V2, R2)>);
-}
-
-
-/// Implementation based on replaying historical and new updates together.
-mod history_replay {
-
-    use std::fmt::Debug;
-    use std::cmp::Ordering;
-
-    use ::Diff;
-    use lattice::Lattice;
-    use trace::Cursor;
-    use operators::ValueHistory2;
-
-    use super::{PerKeyCompute, consolidate, sort_dedup};
-
-    /// The `HistoryReplayer` is a compute strategy based on moving through existing inputs, interesting times, etc in 
-    /// time order, maintaining consolidated representations of updates with respect to future interesting times.
-    pub struct HistoryReplayer<'a, V1, V2, T, R1, R2> 
-    where
-        V1: Ord+Clone+'a,
-        V2: Ord+Clone+'a,
-        T: Lattice+Ord+Clone,
-        R1: Diff,
-        R2: Diff,
-    {
-        batch_history: ValueHistory2<'a, V1, T, R1>,
-        input_history: ValueHistory2<'a, V1, T, R1>,
-        output_history: ValueHistory2<'a, V2, T, R2>,
-        input_buffer: Vec<(&'a V1, R1)>,
-        output_buffer: Vec<(V2, R2)>,
-        output_produced: Vec<((V2, T), R2)>,
-        synth_times: Vec<T>,
-        meets: Vec<T>,
-        times_current: Vec<T>,
-        temporary: Vec<T>,
-    }
-
-    impl<'a, V1, V2, T, R1, R2> PerKeyCompute<'a, V1, V2, T, R1, R2> for HistoryReplayer<'a, V1, V2, T, R1, R2> 
-    where
-        V1: Ord+Clone+Debug,
-        V2: Ord+Clone+Debug,
-        T: Lattice+Ord+Clone+Debug,
-        R1: Diff+Debug,
-        R2: Diff+Debug,
-    {
-        fn new() -> Self {
-            HistoryReplayer { 
-                batch_history: ValueHistory2::new(),
-                input_history: ValueHistory2::new(),
-                output_history: ValueHistory2::new(),
-                input_buffer: Vec::new(),
-                output_buffer: Vec::new(),
-                output_produced: Vec::new(),
-                synth_times: Vec::new(),
-                meets: Vec::new(),
-                times_current: Vec::new(),
-                temporary: Vec::new(),
-            }
-        }
-        #[inline(never)]
-        fn compute<K, C1, C2, C3, L>(
-            &mut self,
-            key: &K, 
-            (source_cursor, source_storage): (&mut C1, &'a C1::Storage), 
-            (output_cursor, output_storage): (&mut C2, &'a C2::Storage),
-            (batch_cursor, batch_storage): (&mut C3, &'a C3::Storage),
-            times: &mut Vec<T>, 
-            logic: &L, 
-            upper_limit: &[T],
-            outputs: &mut [(T, Vec<(V2, T, R2)>)],
-            new_interesting: &mut Vec<T>) -> (usize, usize)
-        where 
-            K: Eq+Clone+Debug,
-            C1: Cursor<K, V1, T, R1>, 
-            C2: Cursor<K, V2, T, R2>, 
-            C3: Cursor<K, V1, T, R1>, 
-            L: Fn(&K, &[(&V1, R1)], &mut Vec<(V2, R2)>) 
-        {
-
-            // The work to do is defined principally be the contents of `batch_cursor` and `times`, which
-            // together indicate the times at which we should re-evaluate the user logic on the accumulated
-            // inputs. Before anything else, we will want to extract this information, as it will allow us 
-            // to thin out other inputs as we load them.
-
-            // Load the batch contents.
-            self.batch_history.clear(); 
-            if batch_cursor.key_valid(batch_storage) && batch_cursor.key(batch_storage) == key {
-                self.batch_history.load(batch_cursor, batch_storage, |time| time.clone());
-            }
-
-            // We determine the meet of times we must reconsider (those from `batch` and `times`). This meet
-            // can be used to advance other historical times, which may consolidate their representation. As
-            // a first step, we determine the meets of each *suffix* of `times`, which we will use as we play
-            // history forward.
-
-            self.meets.clear();
-            self.meets.extend(times.iter().cloned());
-            for index in (1 .. self.meets.len()).rev() {
-                self.meets[index-1] = self.meets[index-1].meet(&self.meets[index]);
-            }
-
-            // Determine the meet of times in `batch` and `times`.
-            let mut meet = T::maximum();
-            if self.meets.len() > 0 { meet = meet.meet(&self.meets[0]); }
-            if let Some(time) = self.batch_history.meet() { meet = meet.meet(&time); }
-
-            // Having determined the meet, we can load the input and output histories, where we 
-            // advance all times by joining them with `meet`. The resulting times are more compact
-            // and guaranteed to accumulate identically for times greater or equal to `meet`.
-
-            // Load the input history.
-            self.input_history.clear(); 
-            source_cursor.seek_key(source_storage, key);
-            if source_cursor.key_valid(source_storage) && source_cursor.key(source_storage) == key {
-                self.input_history.load(source_cursor, source_storage, |time| time.join(&meet));
-            }
-
-            // Load the output history.
-            self.output_history.clear();
-            output_cursor.seek_key(output_storage, key);
-            if output_cursor.key_valid(output_storage) && output_cursor.key(output_storage) == key {
-               self.output_history.load(output_cursor, output_storage, |time| time.join(&meet));
-            }
-
-            self.synth_times.clear();
-            self.times_current.clear();
-            self.output_produced.clear();
-
-            // The frontier of times we may still consider. 
-            // Derived from frontiers of our update histories, supplied times, and synthetic times.
-
-            let mut times_slice = &times[..];
-            let mut meets_slice = &self.meets[..];
-
-            let mut compute_counter = 0;
-            let mut output_counter = 0;
-
-            // We play history forward, continuing as long as we have any outstanding times.
-
-            while !self.input_history.is_done() || !self.output_history.is_done() || 
-                  !self.batch_history.is_done() || self.synth_times.len() > 0 || times_slice.len() > 0 {
-
-                // Determine the next time we will process from the available source of times.
-                let mut next_time = T::maximum();
-                if let Some(time) = self.input_history.time() { if time.cmp(&next_time) == Ordering::Less { next_time = time.clone(); } }
-                if let Some(time) = self.output_history.time() { if time.cmp(&next_time) == Ordering::Less { next_time = time.clone(); } }
-                if let Some(time) = self.batch_history.time() { if time.cmp(&next_time) == Ordering::Less { next_time = time.clone(); } }
-                if let Some(time) = self.synth_times.first() { if time.cmp(&next_time) == Ordering::Less { next_time = time.clone(); } }
-                if let Some(time) = times_slice.first() { if time.cmp(&next_time) == Ordering::Less { next_time = time.clone(); } }
-                assert!(next_time != T::maximum());
-
-                // advance input and output histories.
-                self.input_history.step_while_time_is(&next_time);
-                self.output_history.step_while_time_is(&next_time);
-
-                // advance batch history, but capture whether an update exists at `next_time`.
-                let mut interesting = self.batch_history.step_while_time_is(&next_time);
-                if interesting { self.batch_history.advance_buffer_by(&meet); }
-
-                // advance both `synth_times` and `times_slice`, marking the time interesting if in either.
-                while self.synth_times.last() == Some(&next_time) {
-                    // We don't know enough about `next_time` to avoid putting it in to `times_current`.
-                    // TODO: If we knew that the time derived from a canceled batch update, we could remove the time. 
-                    self.times_current.push(self.synth_times.pop().unwrap()); // <-- TODO: this could be a min-heap.
-                    interesting = true;
-                }
-                while times_slice.len() > 0 && times_slice[0] == next_time {
-                    // We know nothing about why we were warned about `next_time`, and must include it to scare future times.
-                    self.times_current.push(times_slice[0].clone());
-                    times_slice = &times_slice[1..];
-                    meets_slice = &meets_slice[1..];
-                    interesting = true;
-                }
-
-                // Times could also be interesting if an interesting time is less than them, as they would join
-                // and become the time itself. They may not equal the current time because whatever frontier we
-                // are tracking may not have advanced far enough.
-                // TODO: `batch_history` may or may not be super compact at this point, and so this check might 
-                //       yield false positives if not sufficiently compact. Maybe we should into this and see.
-                interesting = interesting || self.batch_history.buffer.iter().any(|&((_, ref t),_)| t.less_equal(&next_time));
-                interesting = interesting || self.times_current.iter().any(|t| t.less_equal(&next_time));
-
-                // We should only process times that are not in advance of `upper_limit`. 
-                //
-                // We have no particular guarantee that known times will not be in advance of `upper_limit`.
-                // We may have the guarantee that synthetic times will not be, as we test against the limit
-                // before we add the time to `synth_times`.
-                if !upper_limit.iter().any(|t| t.less_equal(&next_time)) {
-
-                    // We should re-evaluate the computation if this is an interesting time.
-                    // If the time is uninteresting (and our logic is sound) it is not possible for there to be 
-                    // output produced. This sounds like a good test to have for debug builds!
-                    if interesting { 
-
-                        compute_counter += 1;
-
-                        // Assemble the input collection at `next_time`. (`self.input_buffer` cleared just after use).
-                        debug_assert!(self.input_buffer.is_empty());
-                        self.input_history.advance_buffer_by(&meet);
-                        for &((value, ref time), diff) in self.input_history.buffer.iter() {
-                            if time.less_equal(&next_time) {
-                                self.input_buffer.push((value, diff));
-                            }
-                            else {
-                                self.temporary.push(next_time.join(time));
-                            }
-                        }
-                        for &((value, ref time), diff) in self.batch_history.buffer.iter() {
-                            if time.less_equal(&next_time) {
-                                self.input_buffer.push((value, diff));
-                            }
-                            else {
-                                self.temporary.push(next_time.join(time));
-                            }
-                        }
-                        consolidate(&mut self.input_buffer);
-
-                        // Apply user logic if non-empty input and see what happens!
-                        if self.input_buffer.len() > 0 {
-                            logic(key, &self.input_buffer[..], &mut self.output_buffer);
-                            self.input_buffer.clear();
-                        }            
-
-                        self.output_history.advance_buffer_by(&meet);
-                        for &((ref value, ref time), diff) in self.output_history.buffer.iter() {
-                            if time.less_equal(&next_time) {
-                                self.output_buffer.push(((*value).clone(), -diff));
-                            }
-                            else {
-                                self.temporary.push(next_time.join(time));
-                            }
-                        }
-                        for &((ref value, ref time), diff) in self.output_produced.iter() {
-                            if time.less_equal(&next_time) {
-                                self.output_buffer.push(((*value).clone(), -diff));
-                            }
-                            else {
-                                self.temporary.push(next_time.join(&time));
-                            }
-                        }
-
-                        // Having subtracted output updates from user output, consolidate the results to determine
-                        // if there is anything worth reporting. Note: this also orders the results by value, so 
-                        // that could make the above merging plan even easier. 
-                        consolidate(&mut self.output_buffer);
-
-                        // Stash produced updates into both capability-indexed buffers and `output_produced`. 
-                        // The two locations are important, in that we will compact `output_produced` as we move 
-                        // through times, but we cannot compact the output buffers because we need their actual
-                        // times.
-                        if self.output_buffer.len() > 0 {
-
-                            output_counter += 1;
-
-                            // We *should* be able to find a capability for `next_time`. Any thing else would 
-                            // indicate a logical error somewhere along the way; either we release a capability 
-                            // we should have kept, or we have computed the output incorrectly (or both!)
-                            let idx = outputs.iter().rev().position(|&(ref time, _)| time.less_equal(&next_time));
-                            let idx = outputs.len() - idx.unwrap() - 1;
-                            for (val, diff) in self.output_buffer.drain(..) {
-                                self.output_produced.push(((val.clone(), next_time.clone()), diff));
-                                outputs[idx].1.push((val, next_time.clone(), diff));
-                            }
-
-                            // Advance times in `self.output_produced` and consolidate the representation.
-                            // NOTE: We only do this when we add records; it could be that there are situations 
-                            //       where we want to consolidate even without changes (because an initially
-                            //       large collection can now be collapsed).
-                            for entry in &mut self.output_produced {
-                                (entry.0).1 = (entry.0).1.join(&meet);
-                            }
-                            consolidate(&mut self.output_produced);
-                        }
-                    }
-
-                    // Determine synthetic interesting times.
-                    //
-                    // Synthetic interesting times are produced differently for interesting and uninteresting
-                    // times. An uninteresting time must join with an interesting time to become interesting,
-                    // which means joins with `self.batch_history` and  `self.times_current`. I think we can
-                    // skip `self.synth_times` as we haven't gotten to them yet, but we will and they will be
-                    // joined against everything.
-
-                    // Any time, even uninteresting times, must be joined with the current accumulation of 
-                    // batch times as well as the current accumulation of `times_current`.
-                    for &((_, ref time), _) in self.batch_history.buffer.iter() {
-                        if !time.less_equal(&next_time) {
-                            self.temporary.push(time.join(&next_time));
-                        }
-                    }
-                    for time in self.times_current.iter() {
-                        if !time.less_equal(&next_time) {
-                            self.temporary.push(time.join(&next_time));
-                        }
-                    }
-
-                    sort_dedup(&mut self.temporary);
-
-                    // Introduce synthetic times, and re-organize if we add any.
-                    let synth_len = self.synth_times.len();
-                    for time in self.temporary.drain(..) {
-                        // We can either service `join` now, or must delay for the future.
-                        if upper_limit.iter().any(|t| t.less_equal(&time)) {
-                            debug_assert!(outputs.iter().any(|&(ref t,_)| t.less_equal(&time)));
-                            new_interesting.push(time);
-                        }
-                        else {
-                            self.synth_times.push(time);
-                        }
-                    }
-                    if self.synth_times.len() > synth_len {
-                        self.synth_times.sort_by(|x,y| y.cmp(x));
-                        self.synth_times.dedup();
-                    }
-                }
-                else {  
-
-                    if interesting {
-                        // We cannot process `next_time` now, and must delay it. 
-                        //
-                        // I think we are probably only here because of an uninteresting time declared interesting,
-                        // as initial interesting times are filtered to be in interval, and synthetic times are also
-                        // filtered before introducing them to `self.synth_times`.
-                        new_interesting.push(next_time.clone());
-                        debug_assert!(outputs.iter().any(|&(ref t,_)| t.less_equal(&next_time)))
-                    }
-                }
-
-                // Update `meet` to track the meet of each source of times.
-                meet = T::maximum();
-                if let Some(time) = self.batch_history.meet() { meet = meet.meet(time); }
-                if let Some(time) = self.input_history.meet() { meet = meet.meet(time); }
-                if let Some(time) = self.output_history.meet() { meet = meet.meet(time); }
-                for time in self.synth_times.iter() { meet = meet.meet(time); }
-                if let Some(time) = meets_slice.first() { meet = meet.meet(time); }
-
-                // Update `times_current` by the frontier.
-                for time in self.times_current.iter_mut() {
-                    *time = time.join(&meet);
-                }
-
-                sort_dedup(&mut self.times_current);
-            }
-
-            // Normalize the representation of `new_interesting`, deduplicating and ordering.
-            sort_dedup(new_interesting);
-
-            (compute_counter, output_counter)
-        }
-    }
-}
-
-
-// /// Implementation based on replaying historical and new updates together.
 // mod history_replay_prior {
 
 //     use std::fmt::Debug;
@@ -0,0 +1,433 @@
+//! Per-key computation based on replaying historical and new updates together.
+//!
+//! This is the strategy used by both `group` and `cogroup`. It presents user logic with the accumulated
+//! values of each of its inputs, so that `group` is the case of a single input.
+
+use std::fmt::Debug;
+use std::cmp::Ordering;
+
+use ::Diff;
+use lattice::Lattice;
+use trace::Cursor;
+use operators::ValueHistory2;
+
+/// A strategy for re-evaluating user logic for a single key.
+pub trait PerKeyCompute<'a, V1, V2, T, R1, R2>
+where
+    V1: Ord+Clone+'a,
+    V2: Ord+Clone+'a,
+    T: Lattice+Ord+Clone,
+    R1: Diff,
+    R2: Diff,
+{
+    fn new() -> Self;
+    fn compute<K, C1, C2, C3, L>(
+        &mut self,
+        key: &K,
+        source_cursors: &mut [(C1, &'a C1::Storage)],
+        output_cursor: (&mut C2, &'a C2::Storage),
+        batch_cursors: &mut [(C3, &'a C3::Storage)],
+        times: &mut Vec<T>,
+        logic: &L,
+        upper_limit: &[T],
+        outputs: &mut [(T, Vec<(V2, T, R2)>)],
+        new_interesting: &mut Vec<T>) -> (usize, usize)
+    where
+        K: Eq+Clone+Debug,
+        C1: Cursor<K, V1, T, R1>,
+        C2: Cursor<K, V2, T, R2>,
+        C3: Cursor<K, V1, T, R1>,
+        L: Fn(&K, &[Vec<(&V1, R1)>], &mut Vec<(V2, R2)>);
+}
+
+/// The `HistoryReplayer` is a compute strategy based on moving through existing inputs, interesting times, etc in
+/// time order, maintaining consolidated representations of updates with respect to future interesting times.
+///
+/// The replayer maintains a batch history and an input history for each input, indexed as the cursors supplied
+/// to `compute`, and presents the user logic with one list of accumulated values for each input.
+pub struct HistoryReplayer<'a, V1, V2, T, R1, R2>
+where
+    V1: Ord+Clone+'a,
+    V2: Ord+Clone+'a,
+    T: Lattice+Ord+Clone,
+    R1: Diff,
+    R2: Diff,
+{
+    batch_history: Vec<ValueHistory2<'a, V1, T, R1>>,
+    input_history: Vec<ValueHistory2<'a, V1, T, R1>>,
+    output_history: ValueHistory2<'a, V2, T, R2>,
+    input_buffer: Vec<Vec<(&'a V1, R1)>>,
+    output_buffer: Vec<(V2, R2)>,
+    output_produced: Vec<((V2, T), R2)>,
+    synth_times: Vec<T>,
+    meets: Vec<T>,
+    times_current: Vec<T>,
+    temporary: Vec<T>,
+}
+
+impl<'a, V1, V2, T, R1, R2> PerKeyCompute<'a, V1, V2, T, R1, R2> for HistoryReplayer<'a, V1, V2, T, R1, R2>
+where
+    V1: Ord+Clone+Debug,
+    V2: Ord+Clone+Debug,
+    T: Lattice+Ord+Clone+Debug,
+    R1: Diff+Debug,
+    R2: Diff+Debug,
+{
+    fn new() -> Self {
+        HistoryReplayer {
+            batch_history: Vec::new(),
+            input_history: Vec::new(),
+            output_history: ValueHistory2::new(),
+            input_buffer: Vec::new(),
+            output_buffer: Vec::new(),
+            output_produced: Vec::new(),
+            synth_times: Vec::new(),
+            meets: Vec::new(),
+            times_current: Vec::new(),
+            temporary: Vec::new(),
+        }
+    }
+    #[inline(never)]
+    fn compute<K, C1, C2, C3, L>(
+        &mut self,
+        key: &K,
+        source_cursors: &mut [(C1, &'a C1::Storage)],
+        (output_cursor, output_storage): (&mut C2, &'a C2::Storage),
+        batch_cursors: &mut [(C3, &'a C3::Storage)],
+        times: &mut Vec<T>,
+        logic: &L,
+        upper_limit: &[T],
+        outputs: &mut [(T, Vec<(V2, T, R2)>)],
+        new_interesting: &mut Vec<T>) -> (usize, usize)
+    where
+        K: Eq+Clone+Debug,
+        C1: Cursor<K, V1, T, R1>,
+        C2: Cursor<K, V2, T, R2>,
+        C3: Cursor<K, V1, T, R1>,
+        L: Fn(&K, &[Vec<(&V1, R1)>], &mut Vec<(V2, R2)>)
+    {
+        // Each input has a source cursor and a batch cursor.
+        assert_eq!(source_cursors.len(), batch_cursors.len());
+        let inputs = source_cursors.len();
+        while self.batch_history.len() < inputs { self.batch_history.push(ValueHistory2::new()); }
+        while self.input_history.len() < inputs { self.input_history.push(ValueHistory2::new()); }
+        while self.input_buffer.len() < inputs { self.input_buffer.push(Vec::new()); }
+
+        // The work to do is defined principally be the contents of `batch_cursors` and `times`, which
+        // together indicate the times at which we should re-evaluate the user logic on the accumulated
+        // inputs. Before anything else, we will want to extract this information, as it will allow us
+        // to thin out other inputs as we load them.
+
+        // Load the batch contents.
+        for (history, &mut (ref mut batch_cursor, batch_storage)) in self.batch_history.iter_mut().zip(batch_cursors.iter_mut()) {
+            history.clear();
+            if batch_cursor.key_valid(batch_storage) && batch_cursor.key(batch_storage) == key {
+                history.load(batch_cursor, batch_storage, |time| time.clone());
+            }
+        }
+
+        // We determine the meet of times we must reconsider (those from `batch` and `times`). This meet
+        // can be used to advance other historical times, which may consolidate their representation. As
+        // a first step, we determine the meets of each *suffix* of `times`, which we will use as we play
+        // history forward.
+
+        self.meets.clear();
+        self.meets.extend(times.iter().cloned());
+        for index in (1 .. self.meets.len()).rev() {
+            self.meets[index-1] = self.meets[index-1].meet(&self.meets[index]);
+        }
+
+        // Determine the meet of times in `batch` and `times`.
+        let mut meet = T::maximum();
+        if self.meets.len() > 0 { meet = meet.meet(&self.meets[0]); }
+        for history in self.batch_history[.. inputs].iter() {
+            if let Some(time) = history.meet() { meet = meet.meet(&time); }
+        }
+
+        // Having determined the meet, we can load the input and output histories, where we
+        // advance all times by joining them with `meet`. The resulting times are more compact
+        // and guaranteed to accumulate identically for times greater or equal to `meet`.
+
+        // Load the input histories.
+        for (history, &mut (ref mut source_cursor, source_storage)) in self.input_history.iter_mut().zip(source_cursors.iter_mut()) {
+            history.clear();
+            source_cursor.seek_key(source_storage, key);
+            if source_cursor.key_valid(source_storage) && source_cursor.key(source_storage) == key {
+                history.load(source_cursor, source_storage, |time| time.join(&meet));
+            }
+        }
+
+        // Load the output history.
+        self.output_history.clear();
+        output_cursor.seek_key(output_storage, key);
+        if output_cursor.key_valid(output_storage) && output_cursor.key(output_storage) == key {
+           self.output_history.load(output_cursor, output_storage, |time| time.join(&meet));
+        }
+
+        self.synth_times.clear();
+        self.times_current.clear();
+        self.output_produced.clear();
+
+        // The frontier of times we may still consider.
+        // Derived from frontiers of our update histories, supplied times, and synthetic times.
+
+        let mut times_slice = &times[..];
+        let mut meets_slice = &self.meets[..];
+
+        let mut compute_counter = 0;
+        let mut output_counter = 0;
+
+        // We play history forward, continuing as long as we have any outstanding times.
+
+        while self.input_history[.. inputs].iter().any(|h| !h.is_done()) || !self.output_history.is_done() ||
+              self.batch_history[.. inputs].iter().any(|h| !h.is_done()) || self.synth_times.len() > 0 || times_slice.len() > 0 {
+
+            // Determine the next time we will process from the available source of times.
+            let mut next_time = T::maximum();
+            for history in self.input_history[.. inputs].iter() {
+                if let Some(time) = history.time() { if time.cmp(&next_time) == Ordering::Less { next_time = time.clone(); } }
+            }
+            if let Some(time) = self.output_history.time() { if time.cmp(&next_time) == Ordering::Less { next_time = time.clone(); } }
+            for history in self.batch_history[.. inputs].iter() {
+                if let Some(time) = history.time() { if time.cmp(&next_time) == Ordering::Less { next_time = time.clone(); } }
+            }
+            if let Some(time) = self.synth_times.first() { if time.cmp(&next_time) == Ordering::Less { next_time = time.clone(); } }
+            if let Some(time) = times_slice.first() { if time.cmp(&next_time) == Ordering::Less { next_time = time.clone(); } }
+            assert!(next_time != T::maximum());
+
+            // advance input and output histories.
+            for history in self.input_history[.. inputs].iter_mut() {
+                history.step_while_time_is(&next_time);
+            }
+            self.output_history.step_while_time_is(&next_time);
+
+            // advance batch histories, but capture whether an update exists at `next_time`.
+            let mut interesting = false;
+            for history in self.batch_history[.. inputs].iter_mut() {
+                if history.step_while_time_is(&next_time) {
+                    history.advance_buffer_by(&meet);
+                    interesting = true;
+                }
+            }
+
+            // advance both `synth_times` and `times_slice`, marking the time interesting if in either.
+            while self.synth_times.last() == Some(&next_time) {
+                // We don't know enough about `next_time` to avoid putting it in to `times_current`.
+                // TODO: If we knew that the time derived from a canceled batch update, we could remove the time.
+                self.times_current.push(self.synth_times.pop().unwrap()); // <-- TODO: this could be a min-heap.
+                interesting = true;
+            }
+            while times_slice.len() > 0 && times_slice[0] == next_time {
+                // We know nothing about why we were warned about `next_time`, and must include it to scare future times.
+                self.times_current.push(times_slice[0].clone());
+                times_slice = &times_slice[1..];
+                meets_slice = &meets_slice[1..];
+                interesting = true;
+            }
+
+            // Times could also be interesting if an interesting time is less than them, as they would join
+            // and become the time itself. They may not equal the current time because whatever frontier we
+            // are tracking may not have advanced far enough.
+            // TODO: `batch_history` may or may not be super compact at this point, and so this check might
+            //       yield false positives if not sufficiently compact. Maybe we should into this and see.
+            interesting = interesting || self.batch_history[.. inputs].iter().any(|h| h.buffer.iter().any(|&((_, ref t),_)| t.less_equal(&next_time)));
+            interesting = interesting || self.times_current.iter().any(|t| t.less_equal(&next_time));
+
+            // We should only process times that are not in advance of `upper_limit`.
+            //
+            // We have no particular guarantee that known times will not be in advance of `upper_limit`.
+            // We may have the guarantee that synthetic times will not be, as we test against the limit
+            // before we add the time to `synth_times`.
+            if !upper_limit.iter().any(|t| t.less_equal(&next_time)) {
+
+                // We should re-evaluate the computation if this is an interesting time.
+                // If the time is uninteresting (and our logic is sound) it is not possible for there to be
+                // output produced. This sounds like a good test to have for debug builds!
+                if interesting {
+
+                    compute_counter += 1;
+
+                    // Assemble the input collections at `next_time`. (`self.input_buffer` cleared just after use).
+                    for index in 0 .. inputs {
+                        debug_assert!(self.input_buffer[index].is_empty());
+                        self.input_history[index].advance_buffer_by(&meet);
+                        for &((value, ref time), diff) in self.input_history[index].buffer.iter() {
+                            if time.less_equal(&next_time) {
+                                self.input_buffer[index].push((value, diff));
+                            }
+                            else {
+                                self.temporary.push(next_time.join(time));
+                            }
+                        }
+                        for &((value, ref time), diff) in self.batch_history[index].buffer.iter() {
+                            if time.less_equal(&next_time) {
+                                self.input_buffer[index].push((value, diff));
+                            }
+                            else {
+                                self.temporary.push(next_time.join(time));
+                            }
+                        }
+                        consolidate(&mut self.input_buffer[index]);
+                    }
+
+                    // Apply user logic if any input is non-empty and see what happens!
+                    if self.input_buffer[.. inputs].iter().any(|buffer| buffer.len() > 0) {
+                        logic(key, &self.input_buffer[.. inputs], &mut self.output_buffer);
+                        for buffer in self.input_buffer[.. inputs].iter_mut() {
+                            buffer.clear();
+                        }
+                    }
+
+                    self.output_history.advance_buffer_by(&meet);
+                    for &((ref value, ref time), diff) in self.output_history.buffer.iter() {
+                        if time.less_equal(&next_time) {
+                            self.output_buffer.push(((*value).clone(), -diff));
+                        }
+                        else {
+                            self.temporary.push(next_time.join(time));
+                        }
+                    }
+                    for &((ref value, ref time), diff) in self.output_produced.iter() {
+                        if time.less_equal(&next_time) {
+                            self.output_buffer.push(((*value).clone(), -diff));
+                        }
+                        else {
+                            self.temporary.push(next_time.join(&time));
+                        }
+                    }
+
+                    // Having subtracted output updates from user output, consolidate the results to determine
+                    // if there is anything worth reporting. Note: this also orders the results by value, so
+                    // that could make the above merging plan even easier.
+                    consolidate(&mut self.output_buffer);
+
+                    // Stash produced updates into both capability-indexed buffers and `output_produced`.
+                    // The two locations are important, in that we will compact `output_produced` as we move
+                    // through times, but we cannot compact the output buffers because we need their actual
+                    // times.
+                    if self.output_buffer.len() > 0 {
+
+                        output_counter += 1;
+
+                        // We *should* be able to find a capability for `next_time`. Any thing else would
+                        // indicate a logical error somewhere along the way; either we release a capability
+                        // we should have kept, or we have computed the output incorrectly (or both!)
+                        let idx = outputs.iter().rev().position(|&(ref time, _)| time.less_equal(&next_time));
+                        let idx = outputs.len() - idx.unwrap() - 1;
+                        for (val, diff) in self.output_buffer.drain(..) {
+                            self.output_produced.push(((val.clone(), next_time.clone()), diff));
+                            outputs[idx].1.push((val, next_time.clone(), diff));
+                        }
+
+                        // Advance times in `self.output_produced` and consolidate the representation.
+                        // NOTE: We only do this when we add records; it could be that there are situations
+                        //       where we want to consolidate even without changes (because an initially
+                        //       large collection can now be collapsed).
+                        for entry in &mut self.output_produced {
+                            (entry.0).1 = (entry.0).1.join(&meet);
+                        }
+                        consolidate(&mut self.output_produced);
+                    }
+                }
+
+                // Determine synthetic interesting times.
+                //
+                // Synthetic interesting times are produced differently for interesting and uninteresting
+                // times. An uninteresting time must join with an interesting time to become interesting,
+                // which means joins with `self.batch_history` and  `self.times_current`. I think we can
+                // skip `self.synth_times` as we haven't gotten to them yet, but we will and they will be
+                // joined against everything.
+
+                // Any time, even uninteresting times, must be joined with the current accumulation of
+                // batch times as well as the current accumulation of `times_current`.
+                for history in self.batch_history[.. inputs].iter() {
+                    for &((_, ref time), _) in history.buffer.iter() {
+                        if !time.less_equal(&next_time) {
+                            self.temporary.push(time.join(&next_time));
+                        }
+                    }
+                }
+                for time in self.times_current.iter() {
+                    if !time.less_equal(&next_time) {
+                        self.temporary.push(time.join(&next_time));
+                    }
+                }
+
+                sort_dedup(&mut self.temporary);
+
+                // Introduce synthetic times, and re-organize if we add any.
+                let synth_len = self.synth_times.len();
+                for time in self.temporary.drain(..) {
+                    // We can either service `join` now, or must delay for the future.
+                    if upper_limit.iter().any(|t| t.less_equal(&time)) {
+                        debug_assert!(outputs.iter().any(|&(ref t,_)| t.less_equal(&time)));
+                        new_interesting.push(time);
+                    }
+                    else {
+                        self.synth_times.push(time);
+                    }
+                }
+                if self.synth_times.len() > synth_len {
+                    self.synth_times.sort_by(|x,y| y.cmp(x));
+                    self.synth_times.dedup();
+                }
+            }
+            else {
+
+                if interesting {
+                    // We cannot process `next_time` now, and must delay it.
+                    //
+                    // I think we are probably only here because of an uninteresting time declared interesting,
+                    // as initial interesting times are filtered to be in interval, and synthetic times are also
+                    // filtered before introducing them to `self.synth_times`.
+                    new_interesting.push(next_time.clone());
+                    debug_assert!(outputs.iter().any(|&(ref t,_)| t.less_equal(&next_time)))
+                }
+            }
+
+            // Update `meet` to track the meet of each source of times.
+            meet = T::maximum();
+            for history in self.batch_history[.. inputs].iter() {
+                if let Some(time) = history.meet() { meet = meet.meet(time); }
+            }
+            for history in self.input_history[.. inputs].iter() {
+                if let Some(time) = history.meet() { meet = meet.meet(time); }
+            }
+            if let Some(time) = self.output_history.meet() { meet = meet.meet(time); }
+            for time in self.synth_times.iter() { meet = meet.meet(time); }
+            if let Some(time) = meets_slice.first() { meet = meet.meet(time); }
+
+            // Update `times_current` by the frontier.
+            for time in self.times_current.iter_mut() {
+                *time = time.join(&meet);
+            }
+
+            sort_dedup(&mut self.times_current);
+        }
+
+        // Normalize the representation of `new_interesting`, deduplicating and ordering.
+        sort_dedup(new_interesting);
+
+        (compute_counter, output_counter)
+    }
+}
+
+/// Sorts and deduplicates `list`.
+#[inline(never)]
+pub fn sort_dedup<T: Ord>(list: &mut Vec<T>) {
+    list.dedup();
+    list.sort();
+    list.dedup();
+}
+
+#[inline(never)]
+fn consolidate<T: Ord, R: Diff>(list: &mut Vec<(T, R)>) {
+    list.sort_by(|x,y| x.0.cmp(&y.0));
+    for index in 1 .. list.len() {
+        if list[index].0 == list[index-1].0 {
+            list[index].1 = list[index].1 + list[index-1].1;
+            list[index-1].1 = R::zero();
+        }
+    }
+    list.retain(|x| !x.1.is_zero());
+}
@@ -2,7 +2,8 @@
 //!
 //! The `iterate` operator takes as an argument a closure from a differential dataflow collection 
 //! to a collection of the same type. The output collection is the result of applying this closure 
-//! an unbounded number of times.
+//! an unbounded number of times. The `iterate_bounded` operator instead applies the closure a fixed
+//! number of times, which is appropriate for computations that do not reach a fixed point exactly.
 //!
 //! The implementation of `iterate` does not directly apply the closure, but rather establishes an
 //! iterative timely dataflow subcomputation, in which differences circulate until they dissipate
@@ -72,6 +73,36 @@ pub trait Iterate<G: Scope, D: Data, R: Diff> {
     fn iterate<F>(&self, logic: F) -> Collection<G, D, R>
         where G::Timestamp: Lattice,
               for<'a> F: FnOnce(&Collection<Child<'a, G, u64>, D, R>)->Collection<Child<'a, G, u64>, D, R>;
+
+    /// Applies `logic` to the source collection exactly `iterations` times.
+    ///
+    /// The result is as if `logic` were applied to its own output `iterations` times in sequence, but it
+    /// is computed within one iterative scope, and so is updated incrementally as the source changes.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// #
+    /// extern crate timely;
+    /// extern crate differential_dataflow;
+    ///
+    /// use differential_dataflow::input::Input;
+    /// use differential_dataflow::operators::Iterate;
+    ///
+    /// fn main() {
+    ///     ::timely::example(|scope| {
+    ///
+    ///         let result = scope.new_collection_from(0 .. 10u32).1
+    ///                           .iterate_bounded(3, |values| values.map(|x| x + 1));
+    ///
+    ///         scope.new_collection_from(3 .. 13u32).1
+    ///              .assert_eq(&result);
+    ///     });
+    /// }
+    /// ```
+    fn iterate_bounded<F>(&self, iterations: u64, logic: F) -> Collection<G, D, R>
+        where G::Timestamp: Lattice,
+              for<'a> F: FnOnce(&Collection<Child<'a, G, u64>, D, R>)->Collection<Child<'a, G, u64>, D, R>;
 }
 
 impl<G: Scope, D: Ord+Data+Debug, R: Diff> Iterate<G, D, R> for Collection<G, D, R> {
@@ -92,6 +123,24 @@ impl<G: Scope, D: Ord+Data+Debug, R: Diff> Iterate<G, D, R> for Collection<G, D,
             result.leave()
         })
     }
+
+    fn iterate_bounded<F>(&self, iterations: u64, logic: F) -> Collection<G, D, R>
+        where G::Timestamp: Lattice,
+              for<'a> F: FnOnce(&Collection<Child<'a, G, u64>, D, R>)->Collection<Child<'a, G, u64>, D, R> {
+
+        if iterations == 0 {
+            return self.clone();
+        }
+
+        self.inner.scope().scoped(|subgraph| {
+            // the variable holds the source in round zero, and the result of round `i` in round `i+1`.
+            // the result in the final round is the `iterations`-th application of `logic`.
+            let variable = Variable::from_bounded(self.enter(subgraph), iterations - 1);
+            let result = logic(&variable);
+            variable.set(&result);
+            result.leave()
+        })
+    }
 }
 
 /// A differential dataflow collection variable
@@ -135,20 +184,31 @@ where G::Timestamp: Lattice {
     collection: Collection<Child<'a, G, u64>, D, R>,
     feedback: Handle<G::Timestamp, u64,(D, Product<G::Timestamp, u64>, R)>,
     source: Collection<Child<'a, G, u64>, D, R>,
+    limit: u64,
 }
 
 impl<'a, G: Scope, D: Data, R: Diff> Variable<'a, G, D, R> where G::Timestamp: Lattice {
     /// Creates a new `Variable` and a `Stream` representing its output, from a supplied `source` stream.
     pub fn from(source: Collection<Child<'a, G, u64>, D, R>) -> Variable<'a, G, D, R> {
+        Variable::from_bounded(source, u64::max_value())
+    }
+    /// Creates a new `Variable` which stops changing after `limit` rounds of iteration.
+    ///
+    /// The variable takes the value of `source` in round zero, and the value of the collection it is set to
+    /// in the previous round for rounds one through `limit`. Results from round `limit` onward are not fed
+    /// back, and the variable keeps its value from round `limit`.
+    pub fn from_bounded(source: Collection<Child<'a, G, u64>, D, R>, limit: u64) -> Variable<'a, G, D, R> {
         let (feedback, updates) = source.inner.scope().loop_variable(u64::max_value(), 1);
         let collection = Collection::new(updates).concat(&source);
-        Variable { collection: collection, feedback: feedback, source: source }
+        Variable { collection: collection, feedback: feedback, source: source, limit: limit }
     }
     /// Adds a new source of data to the `Variable`.
     pub fn set(self, result: &Collection<Child<'a, G, u64>, D, R>) -> Collection<Child<'a, G, u64>, D, R> {
+        let limit = self.limit;
         self.source.negate()
                    .concat(result)
                    .inner
+                   .filter(move |&(_, ref t, _)| t.inner < limit)
                    .map(|(x,t,d)| (x, Product::new(t.outer, t.inner+1), d))
                    .connect_loop(self.feedback);
 
@@ -6,6 +6,7 @@
 use std::fmt::Debug;
 use std::ops::Mul;
 use std::cmp::Ordering;
+use std::rc::Rc;
 
 use timely::progress::Timestamp;
 use timely::dataflow::Scope;
@@ -16,10 +17,11 @@ use timely::dataflow::channels::pushers::tee::Tee;
 
 use timely_sort::Unsigned;
 
-use hashable::{Hashable, UnsignedWrapper, OrdWrapper};
+use hashable::{Hashable, HashOrdered, UnsignedWrapper, OrdWrapper};
 use ::{Data, Diff, Collection, AsCollection};
 use lattice::Lattice;
 use operators::arrange::{Arrange, Arranged, ArrangeByKey, ArrangeBySelf};
+use operators::group::GroupArranged;
 use trace::{BatchReader, Cursor, Trace, consolidate};
 use operators::ValueHistory2;
 
@@ -29,6 +31,8 @@ use trace::implementations::ord::OrdValSpine as DefaultValTrace;
 use trace::implementations::ord::OrdKeySpine as DefaultKeyTrace;
 
 use trace::TraceReader;
+#[cfg(feature = "logging")]
+use logging::{DifferentialEvent, FuelEvent};
 
 /// Join implementations for `(key,val)` data.
 pub trait Join<G: Scope, K: Data, V: Data, R: Diff> {
@@ -147,6 +151,97 @@ pub trait Join<G: Scope, K: Data, V: Data, R: Diff> {
     /// ```
     fn antijoin<R2>(&self, other: &Collection<G, K, R2>) -> Collection<G, (K, V), R>
     where R2: Diff, R: Mul<R2, Output = R>;
+
+    /// Matches pairs `(key,val1)` and `(key,val2)` based on `key`, and retains unmatched pairs `(key,val1)`.
+    ///
+    /// Each matched pair is reported as `(key, val1, Some(val2))`, and each pair `(key,val1)` whose key is absent
+    /// from the second collection is reported as `(key, val1, None)`. A key is present in a collection if it has
+    /// a non-zero accumulated count. The results are maintained incrementally as either input changes.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// #
+    /// extern crate timely;
+    /// extern crate differential_dataflow;
+    ///
+    /// use differential_dataflow::input::Input;
+    /// use differential_dataflow::operators::Join;
+    ///
+    /// fn main() {
+    ///     ::timely::example(|scope| {
+    ///
+    ///         let x = scope.new_collection_from(vec![(0, 1), (1, 3)]).1;
+    ///         let y = scope.new_collection_from(vec![(0, 'a'), (2, 'b')]).1;
+    ///         let z = scope.new_collection_from(vec![(0, 1, Some('a')), (1, 3, None)]).1;
+    ///
+    ///         x.left_join(&y)
+    ///          .assert_eq(&z);
+    ///     });
+    /// }
+    /// ```
+    fn left_join<V2: Data>(&self, other: &Collection<G, (K,V2), R>) -> Collection<G, (K,V,Option<V2>), R>
+    where R: Mul<R, Output=R>+Mul<isize, Output=R>;
+
+    /// Matches pairs `(key,val1)` and `(key,val2)` based on `key`, and retains unmatched pairs `(key,val2)`.
+    ///
+    /// Each matched pair is reported as `(key, Some(val1), val2)`, and each pair `(key,val2)` whose key is absent
+    /// from the first collection is reported as `(key, None, val2)`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// #
+    /// extern crate timely;
+    /// extern crate differential_dataflow;
+    ///
+    /// use differential_dataflow::input::Input;
+    /// use differential_dataflow::operators::Join;
+    ///
+    /// fn main() {
+    ///     ::timely::example(|scope| {
+    ///
+    ///         let x = scope.new_collection_from(vec![(0, 1), (1, 3)]).1;
+    ///         let y = scope.new_collection_from(vec![(0, 'a'), (2, 'b')]).1;
+    ///         let z = scope.new_collection_from(vec![(0, Some(1), 'a'), (2, None, 'b')]).1;
+    ///
+    ///         x.right_join(&y)
+    ///          .assert_eq(&z);
+    ///     });
+    /// }
+    /// ```
+    fn right_join<V2: Data>(&self, other: &Collection<G, (K,V2), R>) -> Collection<G, (K,Option<V>,V2), R>
+    where R: Mul<R, Output=R>+Mul<isize, Output=R>;
+
+    /// Matches pairs `(key,val1)` and `(key,val2)` based on `key`, and retains unmatched pairs from both inputs.
+    ///
+    /// Each matched pair is reported as `(key, Some(val1), Some(val2))`, and unmatched pairs are reported as
+    /// `(key, Some(val1), None)` and `(key, None, Some(val2))` respectively.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// #
+    /// extern crate timely;
+    /// extern crate differential_dataflow;
+    ///
+    /// use differential_dataflow::input::Input;
+    /// use differential_dataflow::operators::Join;
+    ///
+    /// fn main() {
+    ///     ::timely::example(|scope| {
+    ///
+    ///         let x = scope.new_collection_from(vec![(0, 1), (1, 3)]).1;
+    ///         let y = scope.new_collection_from(vec![(0, 'a'), (2, 'b')]).1;
+    ///         let z = scope.new_collection_from(vec![(0, Some(1), Some('a')), (1, Some(3), None), (2, None, Some('b'))]).1;
+    ///
+    ///         x.full_outer_join(&y)
+    ///          .assert_eq(&z);
+    ///     });
+    /// }
+    /// ```
+    fn full_outer_join<V2: Data>(&self, other: &Collection<G, (K,V2), R>) -> Collection<G, (K,Option<V>,Option<V2>), R>
+    where R: Mul<R, Output=R>+Mul<isize, Output=R>;
 } 
 
 /// Join implementations for `(key,val)` data.
@@ -266,6 +361,97 @@ pub trait JoinUnsigned<G: Scope, K: Data, V: Data, R: Diff> {
     /// ```
     fn antijoin_u<R2>(&self, other: &Collection<G, K, R2>) -> Collection<G, (K, V), R>
     where R2: Diff, R: Mul<R2, Output=R>;
+
+    /// Matches pairs `(key,val1)` and `(key,val2)` based on `key`, and retains unmatched pairs `(key,val1)`.
+    ///
+    /// Each matched pair is reported as `(key, val1, Some(val2))`, and each pair `(key,val1)` whose key is absent
+    /// from the second collection is reported as `(key, val1, None)`. A key is present in a collection if it has
+    /// a non-zero accumulated count. The results are maintained incrementally as either input changes.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// #
+    /// extern crate timely;
+    /// extern crate differential_dataflow;
+    ///
+    /// use differential_dataflow::input::Input;
+    /// use differential_dataflow::operators::JoinUnsigned;
+    ///
+    /// fn main() {
+    ///     ::timely::example(|scope| {
+    ///
+    ///         let x = scope.new_collection_from(vec![(0u32, 1), (1, 3)]).1;
+    ///         let y = scope.new_collection_from(vec![(0, 'a'), (2, 'b')]).1;
+    ///         let z = scope.new_collection_from(vec![(0, 1, Some('a')), (1, 3, None)]).1;
+    ///
+    ///         x.left_join_u(&y)
+    ///          .assert_eq(&z);
+    ///     });
+    /// }
+    /// ```
+    fn left_join_u<V2: Data>(&self, other: &Collection<G, (K,V2), R>) -> Collection<G, (K,V,Option<V2>), R>
+    where R: Mul<R, Output=R>+Mul<isize, Output=R>;
+
+    /// Matches pairs `(key,val1)` and `(key,val2)` based on `key`, and retains unmatched pairs `(key,val2)`.
+    ///
+    /// Each matched pair is reported as `(key, Some(val1), val2)`, and each pair `(key,val2)` whose key is absent
+    /// from the first collection is reported as `(key, None, val2)`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// #
+    /// extern crate timely;
+    /// extern crate differential_dataflow;
+    ///
+    /// use differential_dataflow::input::Input;
+    /// use differential_dataflow::operators::JoinUnsigned;
+    ///
+    /// fn main() {
+    ///     ::timely::example(|scope| {
+    ///
+    ///         let x = scope.new_collection_from(vec![(0u32, 1), (1, 3)]).1;
+    ///         let y = scope.new_collection_from(vec![(0, 'a'), (2, 'b')]).1;
+    ///         let z = scope.new_collection_from(vec![(0, Some(1), 'a'), (2, None, 'b')]).1;
+    ///
+    ///         x.right_join_u(&y)
+    ///          .assert_eq(&z);
+    ///     });
+    /// }
+    /// ```
+    fn right_join_u<V2: Data>(&self, other: &Collection<G, (K,V2), R>) -> Collection<G, (K,Option<V>,V2), R>
+    where R: Mul<R, Output=R>+Mul<isize, Output=R>;
+
+    /// Matches pairs `(key,val1)` and `(key,val2)` based on `key`, and retains unmatched pairs from both inputs.
+    ///
+    /// Each matched pair is reported as `(key, Some(val1), Some(val2))`, and unmatched pairs are reported as
+    /// `(key, Some(val1), None)` and `(key, None, Some(val2))` respectively.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// #
+    /// extern crate timely;
+    /// extern crate differential_dataflow;
+    ///
+    /// use differential_dataflow::input::Input;
+    /// use differential_dataflow::operators::JoinUnsigned;
+    ///
+    /// fn main() {
+    ///     ::timely::example(|scope| {
+    ///
+    ///         let x = scope.new_collection_from(vec![(0u32, 1), (1, 3)]).1;
+    ///         let y = scope.new_collection_from(vec![(0, 'a'), (2, 'b')]).1;
+    ///         let z = scope.new_collection_from(vec![(0, Some(1), Some('a')), (1, Some(3), None), (2, None, Some('b'))]).1;
+    ///
+    ///         x.full_outer_join_u(&y)
+    ///          .assert_eq(&z);
+    ///     });
+    /// }
+    /// ```
+    fn full_outer_join_u<V2: Data>(&self, other: &Collection<G, (K,V2), R>) -> Collection<G, (K,Option<V>,Option<V2>), R>
+    where R: Mul<R, Output=R>+Mul<isize, Output=R>;
 } 
 
 impl<G, K, V, R> Join<G, K, V, R> for Collection<G, (K, V), R>
@@ -294,6 +480,27 @@ where
     where R: Mul<R2, Output=R> {
         self.concat(&self.semijoin(other).negate())
     }
+
+    fn left_join<V2: Data>(&self, other: &Collection<G, (K,V2), R>) -> Collection<G, (K,V,Option<V2>), R>
+    where R: Mul<R, Output=R>+Mul<isize, Output=R> {
+        let arranged1 = self.arrange_by_key_hashed();
+        let arranged2 = other.arrange_by_key_hashed();
+        arranged1.left_join_core(&arranged2, |k,v1,v2| (k.item.clone(), v1.clone(), v2.cloned()))
+    }
+
+    fn right_join<V2: Data>(&self, other: &Collection<G, (K,V2), R>) -> Collection<G, (K,Option<V>,V2), R>
+    where R: Mul<R, Output=R>+Mul<isize, Output=R> {
+        let arranged1 = self.arrange_by_key_hashed();
+        let arranged2 = other.arrange_by_key_hashed();
+        arranged1.right_join_core(&arranged2, |k,v1,v2| (k.item.clone(), v1.cloned(), v2.clone()))
+    }
+
+    fn full_outer_join<V2: Data>(&self, other: &Collection<G, (K,V2), R>) -> Collection<G, (K,Option<V>,Option<V2>), R>
+    where R: Mul<R, Output=R>+Mul<isize, Output=R> {
+        let arranged1 = self.arrange_by_key_hashed();
+        let arranged2 = other.arrange_by_key_hashed();
+        arranged1.full_outer_join_core(&arranged2, |k,v1,v2| (k.item.clone(), v1.cloned(), v2.cloned()))
+    }
 }
 
 impl<G, K, V, R> JoinUnsigned<G, K, V, R> for Collection<G, (K, V), R>
@@ -326,6 +533,33 @@ where
     where R2: Diff, R: Mul<R2, Output=R> {
         self.concat(&self.semijoin_u(other).negate())
     }
+
+    fn left_join_u<V2: Data>(&self, other: &Collection<G, (K,V2), R>) -> Collection<G, (K,V,Option<V2>), R>
+    where R: Mul<R, Output=R>+Mul<isize, Output=R> {
+        let arranged1 = self.map(|(k,v)| (UnsignedWrapper::from(k), v))
+                            .arrange(DefaultValTrace::new());
+        let arranged2 = other.map(|(k,v)| (UnsignedWrapper::from(k), v))
+                             .arrange(DefaultValTrace::new());
+        arranged1.left_join_core(&arranged2, |k,v1,v2| (k.item.clone(), v1.clone(), v2.cloned()))
+    }
+
+    fn right_join_u<V2: Data>(&self, other: &Collection<G, (K,V2), R>) -> Collection<G, (K,Option<V>,V2), R>
+    where R: Mul<R, Output=R>+Mul<isize, Output=R> {
+        let arranged1 = self.map(|(k,v)| (UnsignedWrapper::from(k), v))
+                            .arrange(DefaultValTrace::new());
+        let arranged2 = other.map(|(k,v)| (UnsignedWrapper::from(k), v))
+                             .arrange(DefaultValTrace::new());
+        arranged1.right_join_core(&arranged2, |k,v1,v2| (k.item.clone(), v1.cloned(), v2.clone()))
+    }
+
+    fn full_outer_join_u<V2: Data>(&self, other: &Collection<G, (K,V2), R>) -> Collection<G, (K,Option<V>,Option<V2>), R>
+    where R: Mul<R, Output=R>+Mul<isize, Output=R> {
+        let arranged1 = self.map(|(k,v)| (UnsignedWrapper::from(k), v))
+                            .arrange(DefaultValTrace::new());
+        let arranged2 = other.map(|(k,v)| (UnsignedWrapper::from(k), v))
+                             .arrange(DefaultValTrace::new());
+        arranged1.full_outer_join_core(&arranged2, |k,v1,v2| (k.item.clone(), v1.cloned(), v2.cloned()))
+    }
 }
 
 /// Matches the elements of two arranged traces.
@@ -522,6 +756,160 @@ impl<G, K, V, R1, T1> JoinCore<G, K, V, R1> for Arranged<G,K,V,R1,T1>
     }
 }
 
+/// Matches the elements of two arranged traces, retaining unmatched elements of one or both.
+///
+/// These methods are used by the outer `join` implementations, and can be used directly with re-used
+/// arrangements. Each is assembled from `join_core` and from arrangements of the keys present in each
+/// input, and so the results are maintained incrementally as either input changes.
+pub trait OuterJoinCore<G: Scope, K: 'static, V: 'static, R: Diff> where G::Timestamp: Lattice+Ord {
+    /// Joins two arranged collections with the same key type, retaining unmatched records of the first.
+    ///
+    /// Each matching pair of records `(key, val1)` and `(key, val2)` is subjected to `result` as
+    /// `(key, val1, Some(val2))`, and each record `(key, val1)` whose key is absent from the second
+    /// collection is subjected to `result` as `(key, val1, None)`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// #
+    /// extern crate timely;
+    /// extern crate differential_dataflow;
+    ///
+    /// use differential_dataflow::input::Input;
+    /// use differential_dataflow::operators::arrange::ArrangeByKey;
+    /// use differential_dataflow::operators::join::OuterJoinCore;
+    ///
+    /// fn main() {
+    ///     ::timely::example(|scope| {
+    ///
+    ///         let x = scope.new_collection_from(vec![(0, 1), (1, 3)]).1.arrange_by_key_hashed();
+    ///         let y = scope.new_collection_from(vec![(0, 'a'), (2, 'b')]).1.arrange_by_key_hashed();
+    ///         let z = scope.new_collection_from(vec![(1, Some('a')), (3, None)]).1;
+    ///
+    ///         x.left_join_core(&y, |_key, &a, b| (a, b.cloned()))
+    ///          .assert_eq(&z);
+    ///     });
+    /// }
+    /// ```
+    fn left_join_core<V2,T2,D,L>(&self, other: &Arranged<G,K,V2,R,T2>, result: L) -> Collection<G,D,R>
+    where
+        V2: Data,
+        T2: TraceReader<K, V2, G::Timestamp, R>+Clone+'static,
+        T2::Batch: BatchReader<K, V2, G::Timestamp, R>+Clone+Debug+'static,
+        R: Mul<R, Output=R>+Mul<isize, Output=R>,
+        D: Data,
+        L: Fn(&K, &V, Option<&V2>)->D+'static,
+        ;
+    /// Joins two arranged collections with the same key type, retaining unmatched records of the second.
+    ///
+    /// Each matching pair of records `(key, val1)` and `(key, val2)` is subjected to `result` as
+    /// `(key, Some(val1), val2)`, and each record `(key, val2)` whose key is absent from the first
+    /// collection is subjected to `result` as `(key, None, val2)`.
+    fn right_join_core<V2,T2,D,L>(&self, other: &Arranged<G,K,V2,R,T2>, result: L) -> Collection<G,D,R>
+    where
+        V2: Data,
+        T2: TraceReader<K, V2, G::Timestamp, R>+Clone+'static,
+        T2::Batch: BatchReader<K, V2, G::Timestamp, R>+Clone+Debug+'static,
+        R: Mul<R, Output=R>+Mul<isize, Output=R>,
+        D: Data,
+        L: Fn(&K, Option<&V>, &V2)->D+'static,
+        ;
+    /// Joins two arranged collections with the same key type, retaining unmatched records of both.
+    ///
+    /// Each matching pair of records is subjected to `result` as `(key, Some(val1), Some(val2))`, and
+    /// unmatched records as `(key, Some(val1), None)` and `(key, None, Some(val2))` respectively.
+    fn full_outer_join_core<V2,T2,D,L>(&self, other: &Arranged<G,K,V2,R,T2>, result: L) -> Collection<G,D,R>
+    where
+        V2: Data,
+        T2: TraceReader<K, V2, G::Timestamp, R>+Clone+'static,
+        T2::Batch: BatchReader<K, V2, G::Timestamp, R>+Clone+Debug+'static,
+        R: Mul<R, Output=R>+Mul<isize, Output=R>,
+        D: Data,
+        L: Fn(&K, Option<&V>, Option<&V2>)->D+'static,
+        ;
+}
+
+impl<G, K, V, R, T1> OuterJoinCore<G, K, V, R> for Arranged<G,K,V,R,T1>
+    where
+        G: Scope,
+        G::Timestamp: Lattice+Ord+Debug,
+        K: Data+HashOrdered,
+        V: Data,
+        R: Diff,
+        T1: TraceReader<K,V,G::Timestamp,R>+Clone+'static,
+        T1::Batch: BatchReader<K,V,G::Timestamp,R>+Clone+Debug+'static {
+
+    fn left_join_core<V2,T2,D,L>(&self, other: &Arranged<G,K,V2,R,T2>, result: L) -> Collection<G,D,R>
+    where
+        V2: Data,
+        T2: TraceReader<K, V2, G::Timestamp, R>+Clone+'static,
+        T2::Batch: BatchReader<K, V2, G::Timestamp, R>+Clone+Debug+'static,
+        R: Mul<R, Output=R>+Mul<isize, Output=R>,
+        D: Data,
+        L: Fn(&K, &V, Option<&V2>)->D+'static {
+
+        let result = Rc::new(result);
+        let (result1, result2) = (result.clone(), result.clone());
+
+        // records of `self` are reported unmatched, except those whose keys are present in `other`.
+        let present2 = other.group_arranged(|_k,_s,t| t.push(((), 1isize)), DefaultKeyTrace::new());
+        let matched = self.join_core(other, move |k,v1,v2| Some(result1(k, v1, Some(v2))));
+        let retracted = self.join_core(&present2, move |k,v1,_| Some(result2(k, v1, None)));
+
+        self.as_collection(move |k,v1| result(k, v1, None))
+            .concat(&retracted.negate())
+            .concat(&matched)
+    }
+
+    fn right_join_core<V2,T2,D,L>(&self, other: &Arranged<G,K,V2,R,T2>, result: L) -> Collection<G,D,R>
+    where
+        V2: Data,
+        T2: TraceReader<K, V2, G::Timestamp, R>+Clone+'static,
+        T2::Batch: BatchReader<K, V2, G::Timestamp, R>+Clone+Debug+'static,
+        R: Mul<R, Output=R>+Mul<isize, Output=R>,
+        D: Data,
+        L: Fn(&K, Option<&V>, &V2)->D+'static {
+
+        let result = Rc::new(result);
+        let (result1, result2) = (result.clone(), result.clone());
+
+        // records of `other` are reported unmatched, except those whose keys are present in `self`.
+        let present1 = self.group_arranged(|_k,_s,t| t.push(((), 1isize)), DefaultKeyTrace::new());
+        let matched = self.join_core(other, move |k,v1,v2| Some(result1(k, Some(v1), v2)));
+        let retracted = other.join_core(&present1, move |k,v2,_| Some(result2(k, None, v2)));
+
+        other.as_collection(move |k,v2| result(k, None, v2))
+            .concat(&retracted.negate())
+            .concat(&matched)
+    }
+
+    fn full_outer_join_core<V2,T2,D,L>(&self, other: &Arranged<G,K,V2,R,T2>, result: L) -> Collection<G,D,R>
+    where
+        V2: Data,
+        T2: TraceReader<K, V2, G::Timestamp, R>+Clone+'static,
+        T2::Batch: BatchReader<K, V2, G::Timestamp, R>+Clone+Debug+'static,
+        R: Mul<R, Output=R>+Mul<isize, Output=R>,
+        D: Data,
+        L: Fn(&K, Option<&V>, Option<&V2>)->D+'static {
+
+        let result = Rc::new(result);
+        let (result1, result2, result3, result4) = (result.clone(), result.clone(), result.clone(), result.clone());
+
+        // records of either input are reported unmatched, except those whose keys are present in the other.
+        let present1 = self.group_arranged(|_k,_s,t| t.push(((), 1isize)), DefaultKeyTrace::new());
+        let present2 = other.group_arranged(|_k,_s,t| t.push(((), 1isize)), DefaultKeyTrace::new());
+        let matched = self.join_core(other, move |k,v1,v2| Some(result1(k, Some(v1), Some(v2))));
+        let retracted1 = self.join_core(&present2, move |k,v1,_| Some(result2(k, Some(v1), None)));
+        let retracted2 = other.join_core(&present1, move |k,v2,_| Some(result3(k, None, Some(v2))));
+
+        self.as_collection(move |k,v1| result4(k, Some(v1), None))
+            .concat(&other.as_collection(move |k,v2| result(k, None, Some(v2))))
+            .concat(&retracted1.negate())
+            .concat(&retracted2.negate())
+            .concat(&matched)
+    }
+}
+
 /// Deferred join computation.
 ///
 /// The structure wraps cursors which allow us to play out join computation at whatever rate we like.
@@ -592,6 +980,8 @@ where
         let meet = self.capability.time();
 
         let mut effort = 0;
+        #[cfg(feature = "logging")]
+        let mut produced = 0;
         let mut session = output.session(&self.capability);
 
         let trace_storage = &self.trace_storage;
@@ -632,6 +1022,8 @@ where
                     consolidate(temp, 0);
 
                     effort += temp.len();
+                    #[cfg(feature = "logging")]
+                    { produced += temp.len(); }
                     for ((d, t), r) in temp.drain(..) {
                         session.give((d, t, r));
                     }
@@ -646,6 +1038,8 @@ where
         }
 
         self.done = !batch.key_valid(batch_storage) || !trace.key_valid(trace_storage);
+        #[cfg(feature = "logging")]
+        ::logging::log(DifferentialEvent::Fuel(FuelEvent { fuel: effort, produced: produced, complete: self.done }));
 
         if effort > *fuel { *fuel = 0; }
         else              { *fuel -= effort; }
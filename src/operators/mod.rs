@@ -7,8 +7,13 @@
 pub use self::group::{Group, Distinct, Count, consolidate_from};
 pub use self::consolidate::Consolidate;
 pub use self::iterate::Iterate;
-pub use self::join::{Join, JoinUnsigned, JoinCore};
+pub use self::join::{Join, JoinUnsigned, JoinCore, OuterJoinCore};
 pub use self::count::CountTotal;
+pub use self::threshold::Threshold;
+pub use self::cogroup::CoGroup;
+pub use self::window::Window;
+pub use self::topk::TopK;
+pub use self::temporal::TemporalFilter;
 
 pub mod arrange;
 pub mod group;
@@ -16,7 +21,15 @@ pub mod consolidate;
 pub mod iterate;
 pub mod join;
 pub mod count;
-// pub mod min;
+pub mod threshold;
+pub mod cogroup;
+pub mod delta_join;
+pub mod window;
+pub mod topk;
+pub mod temporal;
+pub mod query;
+
+mod history_replay;
 
 use ::Diff;
 use lattice::Lattice;
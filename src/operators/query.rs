@@ -0,0 +1,146 @@
+//! Answer a stream of key lookups against an arrangement.
+//!
+//! The `query` operator receives keys, and for each key reports the values associated with the key in an
+//! arrangement, with their accumulated differences, at the logical time of the query. Each query is held back
+//! until the arrangement is complete through its time, and is answered from the shared trace; queries do not
+//! cause any state to be maintained beyond the arrangement itself.
+//!
+//! Outside of a dataflow, the same information is available from a trace using `TraceReader::lookup`.
+
+use timely::order::PartialOrder;
+use timely::dataflow::*;
+use timely::dataflow::operators::Capability;
+use timely::dataflow::operators::generic::Binary;
+use timely::dataflow::channels::pact::{Pipeline, Exchange};
+
+use timely_sort::Unsigned;
+
+use hashable::HashOrdered;
+use ::{Data, Diff};
+use lattice::Lattice;
+use operators::arrange::Arranged;
+use trace::{BatchReader, Cursor, TraceReader};
+
+/// Extension trait for the `query` differential dataflow method.
+pub trait Query<G: Scope, K: Data, V: Data, R: Diff> where G::Timestamp: Lattice+Ord {
+    /// Reports for each query key its values and their accumulated differences at the query's time.
+    ///
+    /// Keys must be presented in the form used by the arrangement, for example wrapped in `OrdWrapper` for
+    /// arrangements produced by `arrange_by_key_hashed`. Keys without values are reported with an empty list.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// #
+    /// extern crate timely;
+    /// extern crate differential_dataflow;
+    ///
+    /// use timely::dataflow::operators::{ToStream, Inspect};
+    /// use differential_dataflow::input::Input;
+    /// use differential_dataflow::operators::arrange::ArrangeByKey;
+    /// use differential_dataflow::operators::query::Query;
+    /// use differential_dataflow::hashable::OrdWrapper;
+    ///
+    /// fn main() {
+    ///     ::timely::example(|scope| {
+    ///
+    ///         let arranged = scope.new_collection_from(1 .. 10).1
+    ///                             .map(|x| (x / 3, x))
+    ///                             .arrange_by_key_hashed();
+    ///
+    ///         // report the values associated with keys one and five.
+    ///         let queries = vec![1, 5].into_iter()
+    ///                                 .map(|x| OrdWrapper { item: x })
+    ///                                 .to_stream(scope);
+    ///
+    ///         arranged.query(&queries)
+    ///                 .inspect(|x| println!("{:?}", x));
+    ///     });
+    /// }
+    /// ```
+    fn query(&self, queries: &Stream<G, K>) -> Stream<G, (K, Vec<(V, R)>)>;
+}
+
+impl<G: Scope, K: Data+HashOrdered, V: Data, R: Diff, Tr> Query<G, K, V, R> for Arranged<G, K, V, R, Tr>
+where
+    G::Timestamp: Lattice+Ord,
+    Tr: TraceReader<K, V, G::Timestamp, R>+Clone+'static,
+    Tr::Batch: BatchReader<K, V, G::Timestamp, R>+'static {
+
+    fn query(&self, queries: &Stream<G, K>) -> Stream<G, (K, Vec<(V, R)>)> {
+
+        // We only ever read complete contents of the trace, and so do not need to distinguish any times.
+        let mut trace = Some(self.trace.clone());
+        if let Some(ref mut trace) = trace {
+            trace.distinguish_since(&[]);
+        }
+
+        // Queries awaiting the completion of the arrangement, with capabilities for their times.
+        let mut pending = Vec::<(Capability<G::Timestamp>, Vec<K>)>::new();
+
+        let exchange = Exchange::new(|key: &K| key.hashed().as_u64());
+
+        queries.binary_notify(&self.stream, exchange, Pipeline, "Query", Vec::new(), move |input1, input2, output, notificator| {
+
+            input1.for_each(|capability, data| {
+                pending.push((capability, data.drain(..).collect()));
+            });
+
+            // The batches themselves are not needed, as we read the shared trace, but their progress is.
+            input2.for_each(|_, data| { data.drain(..); });
+
+            if let Some(ref mut trace) = trace {
+
+                // Answer those queries whose times the arrangement is complete through.
+                let complete = notificator.frontier(1);
+                let mut index = 0;
+                while index < pending.len() {
+                    if complete.iter().any(|t| t.less_equal(&pending[index].0.time())) {
+                        index += 1;
+                    }
+                    else {
+                        let (capability, mut keys) = pending.swap_remove(index);
+                        let time = capability.time().clone();
+
+                        // Visit the keys in order, as the cursor only seeks forward.
+                        keys.sort();
+                        let (mut cursor, storage) = trace.cursor();
+                        let mut session = output.session(&capability);
+                        for key in keys.drain(..) {
+                            let mut values = Vec::new();
+                            cursor.seek_key(&storage, &key);
+                            if cursor.key_valid(&storage) && cursor.key(&storage) == &key {
+                                cursor.rewind_vals(&storage);
+                                while cursor.val_valid(&storage) {
+                                    let mut sum = R::zero();
+                                    cursor.map_times(&storage, |t, d| if t.less_equal(&time) { sum = sum + d; });
+                                    if !sum.is_zero() {
+                                        values.push((cursor.val(&storage).clone(), sum));
+                                    }
+                                    cursor.step_val(&storage);
+                                }
+                            }
+                            session.give((key, values));
+                        }
+                    }
+                }
+
+                // Queries at times in advance of `lower` may yet read the trace.
+                let mut lower = notificator.frontier(0).to_vec();
+                for &(ref capability, _) in pending.iter() {
+                    let time = capability.time();
+                    if !lower.iter().any(|t| t.less_equal(&time)) {
+                        lower.retain(|t| !time.less_equal(t));
+                        lower.push(time.clone());
+                    }
+                }
+                trace.advance_by(&lower[..]);
+            }
+
+            // Once no queries remain or may yet arrive, we release the trace.
+            if trace.is_some() && pending.len() == 0 && notificator.frontier(0).len() == 0 {
+                trace = None;
+            }
+        })
+    }
+}
@@ -0,0 +1,88 @@
+//! Restrict records to the intervals of time in which they are valid.
+//!
+//! The `temporal_filter` operator acts on records that carry their own validity interval, described by a time
+//! `from` at which the record becomes valid and a time `until` at which it ceases to be valid. Each record is
+//! introduced at `from` and retracted at `until`, which removes the need to schedule the retractions manually.
+//!
+//! Times are combined using `Lattice::join`, rather than replaced, so that the operator may be used in nested
+//! scopes: a record at time `time` is introduced at `time.join(from)` and is retracted at the join of this with
+//! `until`. Records are never introduced before their own times, and are not introduced at all if `until` is
+//! not strictly after `from`.
+
+use timely::order::PartialOrder;
+use timely::dataflow::*;
+use timely::dataflow::operators::Unary;
+use timely::dataflow::channels::pact::Pipeline;
+
+use ::{Data, Collection, Diff};
+use collection::AsCollection;
+use lattice::Lattice;
+
+/// Extension trait for the `temporal_filter` differential dataflow method.
+pub trait TemporalFilter<G: Scope, D: Data, R: Diff> where G::Timestamp: Lattice+Ord {
+    /// Presents each record from the time `from` until the time `until`, as determined by `logic`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// #
+    /// extern crate timely;
+    /// extern crate differential_dataflow;
+    ///
+    /// use differential_dataflow::input::Input;
+    /// use differential_dataflow::operators::TemporalFilter;
+    /// use timely::progress::nested::product::Product;
+    /// use timely::progress::timestamp::RootTimestamp;
+    ///
+    /// fn main() {
+    ///     ::timely::example(|scope| {
+    ///         // each record `x` is valid from epoch `x` until epoch `x + 3`.
+    ///         scope.new_collection_from(1 .. 10usize).1
+    ///              .temporal_filter(|x| (RootTimestamp::new(*x), RootTimestamp::new(*x + 3)))
+    ///              .inspect(|x: &(usize, Product<RootTimestamp, usize>, isize)| println!("{:?}", x));
+    ///     });
+    /// }
+    /// ```
+    fn temporal_filter<L>(&self, logic: L) -> Collection<G, D, R>
+        where L: Fn(&D)->(G::Timestamp, G::Timestamp)+'static;
+}
+
+impl<G: Scope, D: Data, R: Diff> TemporalFilter<G, D, R> for Collection<G, D, R> where G::Timestamp: Lattice+Ord {
+    fn temporal_filter<L>(&self, logic: L) -> Collection<G, D, R>
+        where L: Fn(&D)->(G::Timestamp, G::Timestamp)+'static {
+
+        self.inner.unary_stream(Pipeline, "TemporalFilter", move |input, output| {
+
+            let mut updates = Vec::new();
+
+            input.for_each(|capability, data| {
+
+                for (datum, time, diff) in data.drain(..) {
+                    let (from, until) = logic(&datum);
+                    let from = time.join(&from);
+                    let until = from.join(&until);
+                    if !until.less_equal(&from) {
+                        updates.push((from, datum.clone(), diff));
+                        updates.push((until, datum, -diff));
+                    }
+                }
+
+                // Updates are sent with capabilities for their (possibly future) times.
+                updates.sort_by(|x,y| x.0.cmp(&y.0));
+                let mut index = 0;
+                while index < updates.len() {
+                    let time = updates[index].0.clone();
+                    let delayed = capability.delayed(&time);
+                    let mut session = output.session(&delayed);
+                    while index < updates.len() && updates[index].0 == time {
+                        let (time, datum, diff) = updates[index].clone();
+                        session.give((datum, time, diff));
+                        index += 1;
+                    }
+                }
+                updates.clear();
+            });
+        })
+        .as_collection()
+    }
+}
@@ -0,0 +1,369 @@
+//! Maintain the least or greatest values for each key.
+//!
+//! The `top_k` and `bottom_k` operators act on data that can be viewed as pairs `(key, val)`, and for each key
+//! maintain the `k` greatest or least distinct values present, with `max` and `min` as the special cases where
+//! `k` is one. Although these could be written using `group`, the dedicated operators exploit the sorted order
+//! of values in arranged traces: the search for the least values stops as soon as `k` of them are found, and
+//! output changes are only produced when the set of selected values changes.
+//!
+//! Like `count_total`, these operators require totally ordered timestamps, which allows them to determine the
+//! selected values at each time by accumulating all updates at times less or equal to it.
+
+use std::default::Default;
+use std::cmp::Ordering;
+
+use abomonation::Abomonation;
+
+use timely::order::PartialOrder;
+use timely::dataflow::*;
+use timely::dataflow::operators::Unary;
+use timely::dataflow::channels::pact::Pipeline;
+use timely_sort::Unsigned;
+
+use ::{Data, Collection, Diff};
+use hashable::{Hashable, UnsignedWrapper};
+use collection::AsCollection;
+use operators::arrange::{Arrange, Arranged, ArrangeByKey};
+use lattice::TotalOrder;
+use trace::{BatchReader, Cursor, Trace, TraceReader};
+use trace::cursor::cursor_pair::CursorPair;
+use trace::implementations::ord::OrdValSpine as DefaultValTrace;
+
+/// A wrapper that reverses the order of the wrapped type.
+///
+/// Arranging values in descending order allows the greatest values to be found first.
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+pub struct Descending<T: Ord> {
+    /// The item, so you can grab it.
+    pub item: T
+}
+
+impl<T: Ord> PartialOrd for Descending<T> {
+    #[inline(always)]
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        other.item.partial_cmp(&self.item)
+    }
+}
+impl<T: Ord> Ord for Descending<T> {
+    #[inline(always)]
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.item.cmp(&self.item)
+    }
+}
+
+impl<T: Ord+Abomonation> Abomonation for Descending<T> {
+    #[inline] unsafe fn entomb(&self, _writer: &mut Vec<u8>) {
+        self.item.entomb(_writer);
+    }
+    #[inline] unsafe fn embalm(&mut self) {
+        self.item.embalm();
+    }
+    #[inline] unsafe fn exhume<'a,'b>(&'a mut self, mut bytes: &'b mut [u8]) -> Option<&'b mut [u8]> {
+        let temp = bytes;
+        bytes = if let Some(bytes) = self.item.exhume(temp) { bytes } else { return None };
+        Some(bytes)
+    }
+}
+
+/// Extension trait for the `top_k` and `bottom_k` differential dataflow methods.
+pub trait TopK<G: Scope, K: Data, V: Data, R: Diff> where G::Timestamp: TotalOrder+Ord {
+    /// Reports for each key the `k` least distinct values with non-zero accumulated counts.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// #
+    /// extern crate timely;
+    /// extern crate differential_dataflow;
+    ///
+    /// use differential_dataflow::input::Input;
+    /// use differential_dataflow::operators::TopK;
+    ///
+    /// fn main() {
+    ///     ::timely::example(|scope| {
+    ///         // report the two least values for each key.
+    ///         scope.new_collection_from(1 .. 10).1
+    ///              .map(|x| (x / 3, x))
+    ///              .bottom_k(2);
+    ///     });
+    /// }
+    /// ```
+    fn bottom_k(&self, k: usize) -> Collection<G, (K, V), isize>;
+    /// Reports for each key the `k` greatest distinct values with non-zero accumulated counts.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// #
+    /// extern crate timely;
+    /// extern crate differential_dataflow;
+    ///
+    /// use differential_dataflow::input::Input;
+    /// use differential_dataflow::operators::TopK;
+    ///
+    /// fn main() {
+    ///     ::timely::example(|scope| {
+    ///         // report the two greatest values for each key.
+    ///         scope.new_collection_from(1 .. 10).1
+    ///              .map(|x| (x / 3, x))
+    ///              .top_k(2);
+    ///     });
+    /// }
+    /// ```
+    fn top_k(&self, k: usize) -> Collection<G, (K, V), isize>;
+    /// Reports for each key the least value with a non-zero accumulated count.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// #
+    /// extern crate timely;
+    /// extern crate differential_dataflow;
+    ///
+    /// use differential_dataflow::input::Input;
+    /// use differential_dataflow::operators::TopK;
+    ///
+    /// fn main() {
+    ///     ::timely::example(|scope| {
+    ///         // report the least value for each key.
+    ///         scope.new_collection_from(1 .. 10).1
+    ///              .map(|x| (x / 3, x))
+    ///              .min();
+    ///     });
+    /// }
+    /// ```
+    fn min(&self) -> Collection<G, (K, V), isize> {
+        self.bottom_k(1)
+    }
+    /// Reports for each key the greatest value with a non-zero accumulated count.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// #
+    /// extern crate timely;
+    /// extern crate differential_dataflow;
+    ///
+    /// use differential_dataflow::input::Input;
+    /// use differential_dataflow::operators::TopK;
+    ///
+    /// fn main() {
+    ///     ::timely::example(|scope| {
+    ///         // report the greatest value for each key.
+    ///         scope.new_collection_from(1 .. 10).1
+    ///              .map(|x| (x / 3, x))
+    ///              .max();
+    ///     });
+    /// }
+    /// ```
+    fn max(&self) -> Collection<G, (K, V), isize> {
+        self.top_k(1)
+    }
+    /// Reports for each key the `k` least distinct values with non-zero accumulated counts.
+    ///
+    /// This method is a specialization for when the key is an unsigned integer fit for distributing the data.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// #
+    /// extern crate timely;
+    /// extern crate differential_dataflow;
+    ///
+    /// use differential_dataflow::input::Input;
+    /// use differential_dataflow::operators::TopK;
+    ///
+    /// fn main() {
+    ///     ::timely::example(|scope| {
+    ///         // report the two least values for each key.
+    ///         scope.new_collection_from(1 .. 10u32).1
+    ///              .map(|x| (x / 3, x))
+    ///              .bottom_k_u(2);
+    ///     });
+    /// }
+    /// ```
+    fn bottom_k_u(&self, k: usize) -> Collection<G, (K, V), isize> where K: Unsigned+Copy;
+    /// Reports for each key the `k` greatest distinct values with non-zero accumulated counts.
+    ///
+    /// This method is a specialization for when the key is an unsigned integer fit for distributing the data.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// #
+    /// extern crate timely;
+    /// extern crate differential_dataflow;
+    ///
+    /// use differential_dataflow::input::Input;
+    /// use differential_dataflow::operators::TopK;
+    ///
+    /// fn main() {
+    ///     ::timely::example(|scope| {
+    ///         // report the two greatest values for each key.
+    ///         scope.new_collection_from(1 .. 10u32).1
+    ///              .map(|x| (x / 3, x))
+    ///              .top_k_u(2);
+    ///     });
+    /// }
+    /// ```
+    fn top_k_u(&self, k: usize) -> Collection<G, (K, V), isize> where K: Unsigned+Copy;
+}
+
+impl<G: Scope, K: Data+Default+Hashable, V: Data, R: Diff> TopK<G, K, V, R> for Collection<G, (K, V), R>
+where G::Timestamp: TotalOrder+Ord {
+    fn bottom_k(&self, k: usize) -> Collection<G, (K, V), isize> {
+        self.arrange_by_key_hashed()
+            .bottom_k_core(k)
+            .map(|(k,v)| (k.item, v))
+    }
+    fn top_k(&self, k: usize) -> Collection<G, (K, V), isize> {
+        self.map(|(k,v)| (k, Descending { item: v }))
+            .arrange_by_key_hashed()
+            .bottom_k_core(k)
+            .map(|(k,v)| (k.item, v.item))
+    }
+    fn bottom_k_u(&self, k: usize) -> Collection<G, (K, V), isize> where K: Unsigned+Copy {
+        self.map(|(k,v)| (UnsignedWrapper::from(k), v))
+            .arrange(DefaultValTrace::new())
+            .bottom_k_core(k)
+            .map(|(k,v)| (k.item, v))
+    }
+    fn top_k_u(&self, k: usize) -> Collection<G, (K, V), isize> where K: Unsigned+Copy {
+        self.map(|(k,v)| (UnsignedWrapper::from(k), Descending { item: v }))
+            .arrange(DefaultValTrace::new())
+            .bottom_k_core(k)
+            .map(|(k,v)| (k.item, v.item))
+    }
+}
+
+/// Extension trait for the `bottom_k_core` differential dataflow method.
+pub trait TopKCore<G: Scope, K: Data, V: Data, R: Diff> where G::Timestamp: TotalOrder+Ord {
+    /// Reports for each key the `k` least distinct values of arranged data.
+    ///
+    /// This method is used by the more ergonomic `top_k` and `bottom_k` methods, and can be useful to re-use
+    /// an existing arrangement. The greatest values can be found by arranging values wrapped in `Descending`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// #
+    /// extern crate timely;
+    /// extern crate differential_dataflow;
+    ///
+    /// use differential_dataflow::input::Input;
+    /// use differential_dataflow::operators::arrange::ArrangeByKey;
+    /// use differential_dataflow::operators::topk::TopKCore;
+    ///
+    /// fn main() {
+    ///     ::timely::example(|scope| {
+    ///         // report the two least values for each key.
+    ///         scope.new_collection_from(1 .. 10).1
+    ///              .map(|x| (x / 3, x))
+    ///              .arrange_by_key_hashed()
+    ///              .bottom_k_core(2);
+    ///     });
+    /// }
+    /// ```
+    fn bottom_k_core(&self, k: usize) -> Collection<G, (K, V), isize>;
+}
+
+impl<G: Scope, K: Data, V: Data, R: Diff, T1> TopKCore<G, K, V, R> for Arranged<G, K, V, R, T1>
+where
+    G::Timestamp: TotalOrder+Ord,
+    T1: TraceReader<K, V, G::Timestamp, R>+Clone+'static,
+    T1::Batch: BatchReader<K, V, G::Timestamp, R> {
+
+    fn bottom_k_core(&self, k: usize) -> Collection<G, (K, V), isize> {
+
+        let mut trace = self.trace.clone();
+
+        let mut times = Vec::<G::Timestamp>::new();
+        let mut prev = Vec::<V>::new();
+        let mut next = Vec::<V>::new();
+
+        self.stream.unary_stream(Pipeline, "TopK", move |input, output| {
+
+            input.for_each(|capability, batches| {
+
+                let mut session = output.session(&capability);
+                for batch in batches.drain(..).map(|x| x.item) {
+
+                    // We walk the keys of the batch, and for each key the merged contents of trace and batch.
+                    let (mut batch_cursor, batch_storage) = batch.cursor();
+                    let (mut trace_cursor, trace_storage) = trace.cursor_through(batch.lower()).unwrap();
+                    let (merge_trace, merge_trace_storage) = trace.cursor_through(batch.lower()).unwrap();
+                    let (merge_batch, merge_batch_storage) = batch.cursor();
+                    let merge_storage = (merge_trace_storage, merge_batch_storage);
+                    let mut merge_cursor = CursorPair::new(merge_trace, merge_batch, &merge_storage);
+
+                    while batch_cursor.key_valid(&batch_storage) {
+
+                        let key = batch_cursor.key(&batch_storage);
+
+                        // The times at which the selected values may change.
+                        times.clear();
+                        while batch_cursor.val_valid(&batch_storage) {
+                            batch_cursor.map_times(&batch_storage, |time, _| times.push(time.clone()));
+                            batch_cursor.step_val(&batch_storage);
+                        }
+                        times.sort();
+                        times.dedup();
+
+                        // The trace alone determines the selected values prior to the batch. All of its
+                        // times are not greater than those of the batch, and so need not be filtered.
+                        prev.clear();
+                        select(&mut trace_cursor, &trace_storage, key, None, k, &mut prev);
+
+                        for time in times.iter() {
+                            next.clear();
+                            select(&mut merge_cursor, &merge_storage, key, Some(time), k, &mut next);
+                            for val in prev.iter().filter(|v| !next.contains(v)) {
+                                session.give(((key.clone(), val.clone()), time.clone(), -1));
+                            }
+                            for val in next.iter().filter(|v| !prev.contains(v)) {
+                                session.give(((key.clone(), val.clone()), time.clone(), 1));
+                            }
+                            ::std::mem::swap(&mut prev, &mut next);
+                        }
+
+                        batch_cursor.step_key(&batch_storage);
+                    }
+
+                    // tidy up the shared input trace.
+                    trace.advance_by(batch.upper());
+                    trace.distinguish_since(batch.upper());
+                }
+            });
+        })
+        .as_collection()
+    }
+}
+
+/// Populates `result` with up to `k` least values of `key` with non-zero accumulations through `time`.
+///
+/// The values are visited in order, and the search stops as soon as `k` values are found.
+fn select<K, V, T, R, C>(cursor: &mut C, storage: &C::Storage, key: &K, time: Option<&T>, k: usize, result: &mut Vec<V>)
+where
+    K: Ord,
+    V: Clone,
+    T: PartialOrder,
+    R: Diff,
+    C: Cursor<K, V, T, R> {
+
+    cursor.seek_key(storage, key);
+    if cursor.key_valid(storage) && cursor.key(storage) == key {
+        cursor.rewind_vals(storage);
+        while cursor.val_valid(storage) && result.len() < k {
+            let mut count = R::zero();
+            cursor.map_times(storage, |t, d| {
+                if time.map(|time| t.less_equal(time)).unwrap_or(true) {
+                    count = count + d;
+                }
+            });
+            if !count.is_zero() {
+                result.push(cursor.val(storage).clone());
+            }
+            cursor.step_val(storage);
+        }
+    }
+}
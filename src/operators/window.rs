@@ -0,0 +1,158 @@
+//! Restrict collections to the records of recent epochs.
+//!
+//! The `window` operators present, at each time, only those records whose times lie in a window of recent
+//! epochs. Each record is introduced at its own time and retracted once the window no longer contains it,
+//! without the need to manually construct and negate delayed copies of the input. The result can be fed
+//! into any other operator, for example `count` to produce "counts per key over the last `n` epochs".
+//!
+//! Windows are described by a `size` and a `slide`, both measured in epochs. Windows end at multiples of
+//! `slide` and contain the `size` epochs that precede their end; at each time the output reflects the window
+//! that contains that time. A `slide` of one produces a sliding window over the most recent `size` epochs,
+//! and a `slide` equal to `size` produces tumbling windows, each of which is emptied when it closes.
+
+use timely::dataflow::*;
+use timely::dataflow::operators::Unary;
+use timely::dataflow::channels::pact::Pipeline;
+use timely::progress::nested::product::Product;
+
+use ::{Data, Collection, Diff};
+use collection::AsCollection;
+use lattice::{Empty, TotalOrder};
+
+/// Timestamps whose totally ordered times correspond to numbered epochs.
+pub trait Epoch : TotalOrder {
+    /// The epoch of the time.
+    fn epoch(&self) -> usize;
+    /// A time equal to `self` except with its epoch set to `epoch`.
+    fn with_epoch(&self, epoch: usize) -> Self;
+}
+
+impl Epoch for usize {
+    #[inline(always)]
+    fn epoch(&self) -> usize { *self }
+    #[inline(always)]
+    fn with_epoch(&self, epoch: usize) -> usize { epoch }
+}
+
+impl Epoch for u64 {
+    #[inline(always)]
+    fn epoch(&self) -> usize { *self as usize }
+    #[inline(always)]
+    fn with_epoch(&self, epoch: usize) -> u64 { epoch as u64 }
+}
+
+impl Epoch for u32 {
+    #[inline(always)]
+    fn epoch(&self) -> usize { *self as usize }
+    #[inline(always)]
+    fn with_epoch(&self, epoch: usize) -> u32 { epoch as u32 }
+}
+
+impl<T1: Empty+Clone, T2: Epoch> Epoch for Product<T1, T2> {
+    #[inline(always)]
+    fn epoch(&self) -> usize { self.inner.epoch() }
+    #[inline(always)]
+    fn with_epoch(&self, epoch: usize) -> Product<T1, T2> { Product::new(self.outer.clone(), self.inner.with_epoch(epoch)) }
+}
+
+/// Extension trait for the `window` differential dataflow method.
+pub trait Window<G: Scope, D: Data, R: Diff> where G::Timestamp: Epoch+Ord {
+    /// Restricts the collection at each time to records whose epochs lie in the window containing the time.
+    ///
+    /// Windows end at multiples of `slide` and contain the `size` preceding epochs. If `size` is less than
+    /// `slide` some epochs belong to no window, and their records never appear in the output.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// #
+    /// extern crate timely;
+    /// extern crate differential_dataflow;
+    ///
+    /// use differential_dataflow::input::Input;
+    /// use differential_dataflow::operators::{Count, Window};
+    ///
+    /// fn main() {
+    ///     ::timely::example(|scope| {
+    ///         // count the occurrences of each key over the three most recent epochs.
+    ///         scope.new_collection_from(1 .. 10u32).1
+    ///              .map(|x| x / 3)
+    ///              .window(3, 1)
+    ///              .count();
+    ///     });
+    /// }
+    /// ```
+    fn window(&self, size: usize, slide: usize) -> Collection<G, D, R>;
+    /// Restricts the collection at each time to records whose epochs lie in the same block of `size` epochs.
+    ///
+    /// This is equivalent to `window(size, size)`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// #
+    /// extern crate timely;
+    /// extern crate differential_dataflow;
+    ///
+    /// use differential_dataflow::input::Input;
+    /// use differential_dataflow::operators::{Count, Window};
+    ///
+    /// fn main() {
+    ///     ::timely::example(|scope| {
+    ///         // count the occurrences of each key in blocks of ten epochs.
+    ///         scope.new_collection_from(1 .. 10u32).1
+    ///              .map(|x| x / 3)
+    ///              .tumbling(10)
+    ///              .count();
+    ///     });
+    /// }
+    /// ```
+    fn tumbling(&self, size: usize) -> Collection<G, D, R> {
+        self.window(size, size)
+    }
+}
+
+impl<G: Scope, D: Data, R: Diff> Window<G, D, R> for Collection<G, D, R> where G::Timestamp: Epoch+Ord {
+    fn window(&self, size: usize, slide: usize) -> Collection<G, D, R> {
+
+        assert!(slide > 0, "window slide must be positive");
+
+        self.inner.unary_stream(Pipeline, "Window", move |input, output| {
+
+            let mut retractions = Vec::new();
+
+            input.for_each(|capability, data| {
+
+                // Records are introduced at their own times, and retracted at the end of the last window
+                // containing them, which is the last multiple of `slide` not exceeding `epoch + size`.
+                {
+                    let mut session = output.session(&capability);
+                    for (datum, time, diff) in data.drain(..) {
+                        let epoch = time.epoch();
+                        let expire = ((epoch + size) / slide) * slide;
+                        if expire > epoch {
+                            session.give((datum.clone(), time.clone(), diff));
+                            retractions.push((time.with_epoch(expire), datum, -diff));
+                        }
+                    }
+                }
+
+                // Retractions are sent with capabilities for their (future) times.
+                retractions.sort_by(|x,y| x.0.cmp(&y.0));
+                let mut index = 0;
+                while index < retractions.len() {
+                    let time = retractions[index].0.clone();
+                    let delayed = capability.delayed(&time);
+                    let mut session = output.session(&delayed);
+                    while index < retractions.len() && retractions[index].0 == time {
+                        let (time, datum, diff) = retractions[index].clone();
+                        session.give((datum, time, diff));
+                        index += 1;
+                    }
+                }
+                retractions.clear();
+            });
+        })
+        .as_collection()
+    }
+}
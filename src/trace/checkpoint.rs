@@ -0,0 +1,187 @@
+//! Writing traces to, and reading traces from, a local directory.
+//!
+//! A checkpoint records each batch of a trace, with the `lower`, `upper`, and `since` frontiers of its
+//! `Description`, as well as the advance and distinguish frontiers of the trace itself. Restoring a
+//! checkpoint rebuilds each batch with the trace's own `Builder`, inserts the batches into a new trace,
+//! and then re-applies the recorded frontiers. The restored trace can be handed to `arrange` or wrapped
+//! in a `TraceAgent`, from which `import` presents the restored batches without replaying history.
+//!
+//! A checkpoint directory contains a `manifest` file, which names the files of the checkpoint's batches,
+//! and one `batch-<generation>-<index>` file per batch. Each checkpoint into a directory uses a new
+//! generation, and the manifest is replaced atomically once all of its batch files are written, so that
+//! an interrupted checkpoint leaves the previous one intact. Batch files not named by the new manifest
+//! are then removed.
+//!
+//! Each file is a sequence of segments, each of which is an abomonated value prefixed by its length in
+//! bytes, as a little-endian `u64`.
+
+use std::fs::File;
+use std::io::{Read, Write, BufReader, BufWriter, Result, Error, ErrorKind};
+use std::path::Path;
+
+use abomonation::Abomonation;
+
+use ::Diff;
+use lattice::Lattice;
+use trace::{Trace, TraceReader, Batch, BatchReader, Builder, Cursor};
+
+/// Appends `typed` to `writer` as a length-prefixed segment of abomonated bytes.
+pub fn write_segment<X: Abomonation, W: Write>(writer: &mut W, typed: &X) -> Result<()> {
+	let mut bytes = Vec::new();
+	unsafe { ::abomonation::encode(typed, &mut bytes); }
+	let mut length = [0u8; 8];
+	for (index, byte) in length.iter_mut().enumerate() {
+		*byte = ((bytes.len() as u64) >> (8 * index)) as u8;
+	}
+	try!(writer.write_all(&length[..]));
+	writer.write_all(&bytes[..])
+}
+
+/// Reads the next length-prefixed segment from `reader` into `buffer`, and decodes it in place.
+///
+/// The returned reference borrows `buffer`, which is reused rather than copied. The buffer holds `u64`s
+/// so that the decoded value is aligned as abomonation requires.
+pub fn read_segment_into<'a, X: Abomonation, Rd: Read>(reader: &mut Rd, buffer: &'a mut Vec<u64>) -> Result<&'a X> {
+	let mut length = [0u8; 8];
+	try!(reader.read_exact(&mut length[..]));
+	let length = length.iter().enumerate().fold(0u64, |sum, (index, &byte)| sum | ((byte as u64) << (8 * index))) as usize;
+
+	buffer.clear();
+	buffer.resize((length + 7) / 8, 0);
+	let bytes: &'a mut [u8] = unsafe { ::std::slice::from_raw_parts_mut(buffer.as_mut_ptr() as *mut u8, length) };
+	try!(reader.read_exact(bytes));
+
+	match unsafe { ::abomonation::decode::<X>(bytes) } {
+		Some((typed, remaining)) => {
+			if remaining.len() == 0 { Ok(typed) }
+			else { Err(Error::new(ErrorKind::InvalidData, "segment has trailing bytes")) }
+		},
+		None => Err(Error::new(ErrorKind::InvalidData, "failed to decode segment")),
+	}
+}
+
+/// Reads an owned copy of the next length-prefixed segment from `reader`.
+///
+/// This is meant for small values; large values should be read with `read_segment_into`, which avoids
+/// holding both the encoded and the cloned data.
+pub fn read_segment<X: Abomonation+Clone, Rd: Read>(reader: &mut Rd) -> Result<X> {
+	let mut buffer = Vec::new();
+	let result = read_segment_into::<X, Rd>(reader, &mut buffer).map(|typed| typed.clone());
+	result
+}
+
+/// Writes the batches and frontiers of `trace` to files in `directory`.
+///
+/// The directory is created if it does not exist. Any existing checkpoint in the directory is replaced,
+/// and its batch files are removed once the new manifest is in place.
+pub fn checkpoint<K, V, T, R, Tr>(trace: &mut Tr, directory: &Path) -> Result<()>
+where
+	K: Abomonation+Clone,
+	V: Abomonation+Clone,
+	T: Lattice+Abomonation+Clone,
+	R: Diff,
+	Tr: TraceReader<K, V, T, R>,
+{
+	try!(::std::fs::create_dir_all(directory));
+
+	// Batch files are named by generation, so that writing them cannot clobber the current checkpoint.
+	let generation = File::open(directory.join("manifest")).ok()
+		.and_then(|file| read_segment::<u64, _>(&mut BufReader::new(file)).ok())
+		.map(|generation| generation + 1)
+		.unwrap_or(0);
+
+	let mut batches = Vec::new();
+	trace.map_batches(|batch| batches.push(batch.clone()));
+
+	let mut names = Vec::with_capacity(batches.len());
+	for (index, batch) in batches.iter().enumerate() {
+
+		let mut updates = Vec::with_capacity(batch.len());
+		let (mut cursor, storage) = batch.cursor();
+		while cursor.key_valid(&storage) {
+			while cursor.val_valid(&storage) {
+				let key = cursor.key(&storage).clone();
+				let val = cursor.val(&storage).clone();
+				cursor.map_times(&storage, |time, diff| updates.push((key.clone(), val.clone(), time.clone(), diff)));
+				cursor.step_val(&storage);
+			}
+			cursor.step_key(&storage);
+		}
+
+		let name = format!("batch-{}-{}", generation, index);
+		let description = batch.description();
+		let mut writer = BufWriter::new(try!(File::create(directory.join(&name))));
+		try!(write_segment(&mut writer, &description.lower().to_vec()));
+		try!(write_segment(&mut writer, &description.upper().to_vec()));
+		try!(write_segment(&mut writer, &description.since().to_vec()));
+		try!(write_segment(&mut writer, &updates));
+		try!(writer.flush());
+		names.push(name);
+	}
+
+	// The manifest is written last, and renamed into place, so that an interrupted checkpoint is not
+	// mistaken for a complete one.
+	{
+		let mut writer = BufWriter::new(try!(File::create(directory.join("manifest.tmp"))));
+		try!(write_segment(&mut writer, &generation));
+		try!(write_segment(&mut writer, &names));
+		try!(write_segment(&mut writer, &trace.advance_frontier().to_vec()));
+		try!(write_segment(&mut writer, &trace.distinguish_frontier().to_vec()));
+		try!(writer.flush());
+	}
+	try!(::std::fs::rename(directory.join("manifest.tmp"), directory.join("manifest")));
+
+	// Remove batch files of earlier checkpoints (and of interrupted ones).
+	for entry in try!(::std::fs::read_dir(directory)) {
+		let entry = try!(entry);
+		let stale = entry.file_name().to_str().map(|name| name.starts_with("batch-") && !names.iter().any(|n| n == name));
+		if stale == Some(true) {
+			try!(::std::fs::remove_file(entry.path()));
+		}
+	}
+
+	Ok(())
+}
+
+/// Reconstructs a trace from the checkpoint in `directory`.
+///
+/// The batches are rebuilt using the `Builder` of the trace's batch type, and are inserted into a new trace
+/// in the order they were recorded. The trace is then advanced to the recorded advance and distinguish
+/// frontiers, which allows it to merge batches exactly as the original trace could have.
+pub fn restore<K, V, T, R, Tr>(directory: &Path) -> Result<Tr>
+where
+	K: Abomonation+Clone,
+	V: Abomonation+Clone,
+	T: Lattice+Abomonation+Clone,
+	R: Diff,
+	Tr: Trace<K, V, T, R>,
+	Tr::Batch: Batch<K, V, T, R>,
+{
+	let mut reader = BufReader::new(try!(File::open(directory.join("manifest"))));
+	let _generation: u64 = try!(read_segment(&mut reader));
+	let names: Vec<String> = try!(read_segment(&mut reader));
+	let advance: Vec<T> = try!(read_segment(&mut reader));
+	let through: Vec<T> = try!(read_segment(&mut reader));
+
+	let mut trace = Tr::new();
+
+	// Updates are decoded in place in `buffer`, which is reused across batches.
+	let mut buffer = Vec::new();
+	for name in names.iter() {
+
+		let mut reader = BufReader::new(try!(File::open(directory.join(name))));
+		let lower: Vec<T> = try!(read_segment(&mut reader));
+		let upper: Vec<T> = try!(read_segment(&mut reader));
+		let since: Vec<T> = try!(read_segment(&mut reader));
+		let updates: &Vec<(K, V, T, R)> = try!(read_segment_into(&mut reader, &mut buffer));
+
+		let mut builder = <Tr::Batch as Batch<K, V, T, R>>::Builder::with_capacity(updates.len());
+		builder.extend(updates.iter().cloned());
+		trace.insert(builder.done(&lower[..], &upper[..], &since[..]));
+	}
+
+	trace.advance_by(&advance[..]);
+	trace.distinguish_since(&through[..]);
+
+	Ok(trace)
+}
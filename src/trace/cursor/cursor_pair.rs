@@ -1,70 +1,153 @@
 //! A generic cursor implementation merging pairs of different cursors.
 
+use std::cmp::Ordering;
+
 use super::Cursor;
 
 /// A cursor over the combined updates of two different cursors.
-pub struct CursorPair<C1: Cursor, C2: Cursor<Key=C1::Key, Val=C1::Val, Time=C1::Time>> {
+///
+/// A `CursorPair` presents the union of the updates of its two cursors, which may have different types (and
+/// different storage), for example a trace cursor and the cursor of a batch not yet added to the trace. The
+/// two cursors are navigated in lock step, and the current key (and value) is the least among those of the
+/// two cursors. When both cursors present the same key and value, `map_times` reports the updates of both.
+#[derive(Debug)]
+pub struct CursorPair<K, V, T, R, C1: Cursor<K, V, T, R>, C2: Cursor<K, V, T, R>> {
+	_phantom: ::std::marker::PhantomData<(K, V, T, R)>,
 	cursor1: C1,
 	cursor2: C2,
+	key_order: Ordering,	// Invalid keys are `Greater` than all other keys. `Equal` implies both valid.
+	val_order: Ordering,	// Invalid vals are `Greater` than all other vals. `Equal` implies both valid.
 }
 
-impl<C1: Cursor, C2: Cursor<Key=C1::Key, Val=C1::Val, Time=C1::Time>> CursorPair<C1, C2> {
-	fn active_key1(&self) -> bool {
-		!self.cursor2.key_valid() || (self.cursor1.key_valid() && self.cursor1.key() <= self.cursor2.key())
+impl<K, V, T, R, C1, C2> CursorPair<K, V, T, R, C1, C2>
+where K: Ord, V: Ord, C1: Cursor<K, V, T, R>, C2: Cursor<K, V, T, R> {
+
+	/// Creates a new cursor pair from pre-existing cursors.
+	pub fn new(cursor1: C1, cursor2: C2, storage: &(C1::Storage, C2::Storage)) -> Self {
+		let mut result = CursorPair {
+			_phantom: ::std::marker::PhantomData,
+			cursor1: cursor1,
+			cursor2: cursor2,
+			key_order: Ordering::Equal,
+			val_order: Ordering::Equal,
+		};
+		result.update_key_order(storage);
+		result
 	}
-	fn active_key2(&self) -> bool {
-		!self.cursor1.key_valid() || (self.cursor2.key_valid() && self.cursor2.key() <= self.cursor1.key())
+
+	// Re-determines which cursors present the current key, and then the current value.
+	fn update_key_order(&mut self, storage: &(C1::Storage, C2::Storage)) {
+		self.key_order = match (self.cursor1.key_valid(&storage.0), self.cursor2.key_valid(&storage.1)) {
+			(false, _) => Ordering::Greater,
+			(true, false) => Ordering::Less,
+			(true, true) => self.cursor1.key(&storage.0).cmp(self.cursor2.key(&storage.1)),
+		};
+		self.update_val_order(storage);
 	}
-}
 
+	// Re-determines which cursors present the current value; only meaningful if both present the current key.
+	fn update_val_order(&mut self, storage: &(C1::Storage, C2::Storage)) {
+		self.val_order = if self.key_order == Ordering::Equal {
+			match (self.cursor1.val_valid(&storage.0), self.cursor2.val_valid(&storage.1)) {
+				(false, _) => Ordering::Greater,
+				(true, false) => Ordering::Less,
+				(true, true) => self.cursor1.val(&storage.0).cmp(self.cursor2.val(&storage.1)),
+			}
+		}
+		else {
+			Ordering::Equal
+		};
+	}
+
+	// Indicates that the first cursor presents the current key and value.
+	fn active1(&self) -> bool {
+		self.key_order == Ordering::Less || (self.key_order == Ordering::Equal && self.val_order != Ordering::Greater)
+	}
+
+	// Indicates that the second cursor presents the current key and value.
+	fn active2(&self) -> bool {
+		self.key_order == Ordering::Greater || (self.key_order == Ordering::Equal && self.val_order != Ordering::Less)
+	}
+}
 
-impl<C1: Cursor, C2: Cursor<Key=C1::Key, Val=C1::Val, Time=C1::Time>> Cursor for CursorPair<C1, C2> {
+impl<K, V, T, R, C1, C2> Cursor<K, V, T, R> for CursorPair<K, V, T, R, C1, C2>
+where
+	K: Ord,
+	V: Ord,
+	C1: Cursor<K, V, T, R>,
+	C2: Cursor<K, V, T, R> {
 
-	type Key = C1::Key;
-	type Val = C1::Val;
-	type Time = C1::Time;
+	type Storage = (C1::Storage, C2::Storage);
 
 	// validation methods
-	fn key_valid(&self) -> bool { self.cursor1.key_valid() || self.cursor2.key_valid() }
-	fn val_valid(&self) -> bool { unimplemented!() }
+	fn key_valid(&self, storage: &Self::Storage) -> bool {
+		match self.key_order {
+			Ordering::Less => self.cursor1.key_valid(&storage.0),
+			Ordering::Equal => true,
+			Ordering::Greater => self.cursor2.key_valid(&storage.1),
+		}
+	}
+	fn val_valid(&self, storage: &Self::Storage) -> bool {
+		match (self.key_order, self.val_order) {
+			(Ordering::Less, _) => self.cursor1.val_valid(&storage.0),
+			(Ordering::Greater, _) => self.cursor2.val_valid(&storage.1),
+			(Ordering::Equal, Ordering::Less) => self.cursor1.val_valid(&storage.0),
+			(Ordering::Equal, Ordering::Equal) => true,
+			(Ordering::Equal, Ordering::Greater) => self.cursor2.val_valid(&storage.1),
+		}
+	}
 
-	// accessors 
-	fn key(&self) -> &Self::Key { unimplemented!(); }
-	fn val(&self) -> &Self::Val { unimplemented!(); }
-	fn map_times<L: FnMut(&Self::Time, isize)>(&self, mut logic: L) {
-		unimplemented!();
-		// self.cursor1.map_times(|t,d| logic(t,d));
-		// self.cursor2.map_times(|t,d| logic(t,d));
+	// accessors
+	fn key<'a>(&self, storage: &'a Self::Storage) -> &'a K {
+		debug_assert!(self.key_valid(storage));
+		if self.key_order != Ordering::Greater { self.cursor1.key(&storage.0) }
+		else { self.cursor2.key(&storage.1) }
+	}
+	fn val<'a>(&self, storage: &'a Self::Storage) -> &'a V {
+		debug_assert!(self.key_valid(storage));
+		debug_assert!(self.val_valid(storage));
+		if self.active1() { self.cursor1.val(&storage.0) }
+		else { self.cursor2.val(&storage.1) }
+	}
+	fn map_times<L: FnMut(&T, R)>(&mut self, storage: &Self::Storage, mut logic: L) {
+		if self.active1() { self.cursor1.map_times(&storage.0, |t,d| logic(t,d)); }
+		if self.active2() { self.cursor2.map_times(&storage.1, |t,d| logic(t,d)); }
 	}
 
 	// key methods
-	fn step_key(&mut self) {
-		unimplemented!();
+	fn step_key(&mut self, storage: &Self::Storage) {
+		if self.key_order != Ordering::Greater { self.cursor1.step_key(&storage.0); }
+		if self.key_order != Ordering::Less { self.cursor2.step_key(&storage.1); }
+		self.update_key_order(storage);
 	}
-	fn seek_key(&mut self, key: &Self::Key) {
-		unimplemented!();
+	fn seek_key(&mut self, storage: &Self::Storage, key: &K) {
+		self.cursor1.seek_key(&storage.0, key);
+		self.cursor2.seek_key(&storage.1, key);
+		self.update_key_order(storage);
 	}
-	fn peek_key(&self) -> Option<&Self::Key> {
-		if self.key_valid() { Some(self.key()) } else { None }
-	}
-	
+
 	// value methods
-	fn step_val(&mut self) {
-		unimplemented!();
+	fn step_val(&mut self, storage: &Self::Storage) {
+		let (active1, active2) = (self.active1(), self.active2());
+		if active1 { self.cursor1.step_val(&storage.0); }
+		if active2 { self.cursor2.step_val(&storage.1); }
+		self.update_val_order(storage);
 	}
-	fn seek_val(&mut self, val: &Self::Val) {
-		unimplemented!();
-	}
-	fn peek_val(&self) -> Option<&Self::Val> {
-		if self.val_valid() { Some(self.val()) } else { None }
+	fn seek_val(&mut self, storage: &Self::Storage, val: &V) {
+		if self.key_order != Ordering::Greater { self.cursor1.seek_val(&storage.0, val); }
+		if self.key_order != Ordering::Less { self.cursor2.seek_val(&storage.1, val); }
+		self.update_val_order(storage);
 	}
 
 	// rewinding methods
-	fn rewind_keys(&mut self) { 
-		self.cursor1.rewind_keys();
-		self.cursor2.rewind_keys();
+	fn rewind_keys(&mut self, storage: &Self::Storage) {
+		self.cursor1.rewind_keys(&storage.0);
+		self.cursor2.rewind_keys(&storage.1);
+		self.update_key_order(storage);
 	}
-	fn rewind_vals(&mut self) { 
-		unimplemented!();
+	fn rewind_vals(&mut self, storage: &Self::Storage) {
+		if self.key_order != Ordering::Greater { self.cursor1.rewind_vals(&storage.0); }
+		if self.key_order != Ordering::Less { self.cursor2.rewind_vals(&storage.1); }
+		self.update_val_order(storage);
 	}
-}
\ No newline at end of file
+}
@@ -55,6 +55,8 @@
 //! will often be a logic bug, as `since` does not advance without a corresponding advance in
 //! times at which data may possibly be sent.
 
+use abomonation::Abomonation;
+
 /// Describes an interval of partially ordered times.
 ///
 /// A `Description` indicates a set of partially ordered times, and a moment at which they are
@@ -92,4 +94,23 @@ impl<Time> Description<Time> {
 	pub fn upper(&self) -> &[Time] { &self.upper[..] }
 	/// Times from whose future the interval may be observed.
 	pub fn since(&self) -> &[Time] { &self.since[..] }
-}
\ No newline at end of file
+}
+
+impl<Time: Abomonation> Abomonation for Description<Time> {
+	#[inline] unsafe fn entomb(&self, writer: &mut Vec<u8>) {
+		self.lower.entomb(writer);
+		self.upper.entomb(writer);
+		self.since.entomb(writer);
+	}
+	#[inline] unsafe fn embalm(&mut self) {
+		self.lower.embalm();
+		self.upper.embalm();
+		self.since.embalm();
+	}
+	#[inline] unsafe fn exhume<'a,'b>(&'a mut self, mut bytes: &'b mut [u8]) -> Option<&'b mut [u8]> {
+		let temp = bytes; bytes = if let Some(bytes) = self.lower.exhume(temp) { bytes } else { return None };
+		let temp = bytes; bytes = if let Some(bytes) = self.upper.exhume(temp) { bytes } else { return None };
+		let temp = bytes; bytes = if let Some(bytes) = self.since.exhume(temp) { bytes } else { return None };
+		Some(bytes)
+	}
+}
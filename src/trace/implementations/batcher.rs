@@ -8,6 +8,8 @@ use hashable::HashOrdered;
 
 use lattice::Lattice;
 use trace::{Batch, Batcher, Builder};
+#[cfg(feature = "logging")]
+use logging::{DifferentialEvent, BatchEvent};
 
 /// Creates batches from unordered tuples.
 pub struct RadixBatcher<K: HashOrdered, V, T: PartialOrd, R: Diff, B: Batch<K, V, T, R>> {
@@ -165,6 +167,8 @@ where
         // Return the finished layer with its bounds.
         let result = builder.done(&self.lower[..], upper, &self.lower[..]);
         self.lower = upper.to_vec();
+        #[cfg(feature = "logging")]
+        ::logging::log(DifferentialEvent::Batch(BatchEvent { length: result.len() }));
         result
     }
 
@@ -8,6 +8,8 @@ use hashable::Hashable;
 
 use lattice::Lattice;
 use trace::{Batch, Batcher, Builder, Cursor};
+#[cfg(feature = "logging")]
+use logging::{DifferentialEvent, BatchEvent};
 
 /// Creates batches from unordered tuples.
 pub struct RadixBatcher<K: Hashable, V, T: PartialOrd, R: Diff, B: Batch<K, V, T, R>> {
@@ -154,6 +156,8 @@ where
         let seal = builder_seal.done(&self.lower[..], &upper[..], &self.lower[..]);
         self.lower = upper.to_vec();
         self.sorted = if keep.len() > 0 { Some(keep) } else { None };
+        #[cfg(feature = "logging")]
+        ::logging::log(DifferentialEvent::Batch(BatchEvent { length: seal.len() }));
         seal
     }
 
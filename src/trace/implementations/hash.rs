@@ -10,10 +10,12 @@
 
 use std::rc::Rc;
 
+use abomonation::Abomonation;
+
 use ::Diff;
 use hashable::HashOrdered;
 
-use trace::layers::{Trie, TupleBuilder};
+use trace::layers::{Trie, TupleBuilder, MergeBuilder};
 use trace::layers::Builder as TrieBuilder;
 use trace::layers::Cursor as TrieCursor;
 use trace::layers::hashed::{HashedLayer, HashedBuilder, HashedCursor};
@@ -21,10 +23,11 @@ use trace::layers::ordered::{OrderedLayer, OrderedBuilder};
 use trace::layers::ordered_leaf::{OrderedLeaf, OrderedLeafBuilder};
 
 use lattice::Lattice;
-use trace::{Batch, BatchReader, Builder, Cursor};
+use trace::{Batch, BatchReader, Builder, Merger, Cursor, Size};
 use trace::description::Description;
 
 use super::spine::Spine;
+use super::ord::merged_description;
 use super::batcher::RadixBatcher;
 
 /// A trace implementation using a spine of hash-map batches.
@@ -34,7 +37,7 @@ pub type HashKeySpine<K, T, R> = Spine<K, (), T, R, Rc<HashKeyBatch<K, T, R>>>;
 
 
 /// An immutable collection of update tuples, from a contiguous interval of logical times.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct HashValBatch<K: HashOrdered, V: Ord, T: Lattice, R: Diff> {
 	/// Where all the dataz is.
 	pub layer: HashedLayer<K, OrderedLayer<V, OrderedLeaf<T, R>>>,
@@ -42,6 +45,22 @@ pub struct HashValBatch<K: HashOrdered, V: Ord, T: Lattice, R: Diff> {
 	pub desc: Description<T>,
 }
 
+impl<K: HashOrdered+Abomonation, V: Ord+Abomonation, T: Lattice+Abomonation, R: Diff+Abomonation> Abomonation for HashValBatch<K, V, T, R> {
+	#[inline] unsafe fn entomb(&self, writer: &mut Vec<u8>) {
+		self.layer.entomb(writer);
+		self.desc.entomb(writer);
+	}
+	#[inline] unsafe fn embalm(&mut self) {
+		self.layer.embalm();
+		self.desc.embalm();
+	}
+	#[inline] unsafe fn exhume<'a,'b>(&'a mut self, mut bytes: &'b mut [u8]) -> Option<&'b mut [u8]> {
+		let temp = bytes; bytes = if let Some(bytes) = self.layer.exhume(temp) { bytes } else { return None };
+		let temp = bytes; bytes = if let Some(bytes) = self.desc.exhume(temp) { bytes } else { return None };
+		Some(bytes)
+	}
+}
+
 impl<K, V, T, R> BatchReader<K, V, T, R> for Rc<HashValBatch<K, V, T, R>>
 where K: Clone+Default+HashOrdered+'static, V: Clone+Ord+'static, T: Lattice+Ord+Clone+Default+'static, R: Diff {
 	type Cursor = HashValCursor<V, T, R>;
@@ -57,30 +76,85 @@ where K: Clone+Default+HashOrdered+'static, V: Clone+Ord+'static, T: Lattice+Ord
 	}
 	fn len(&self) -> usize { <HashedLayer<K, OrderedLayer<V, OrderedLeaf<T, R>>> as Trie>::tuples(&self.layer) }
 	fn description(&self) -> &Description<T> { &self.desc }
+	fn size(&self) -> Size {
+		Size {
+			tuples: self.len(),
+			keys: self.layer.occupied(),
+			vals: self.layer.vals.keys.len(),
+			bytes: self.layer.heap_size(),
+		}
+	}
 }
 
 impl<K, V, T, R> Batch<K, V, T, R> for Rc<HashValBatch<K, V, T, R>>
 where K: Clone+Default+HashOrdered+'static, V: Clone+Ord+'static, T: Lattice+Ord+Clone+Default+'static, R: Diff {
 	type Batcher = RadixBatcher<K, V, T, R, Self>;
 	type Builder = HashValBuilder<K, V, T, R>;
-	fn merge(&self, other: &Self) -> Self {
+	type Merger = HashValMerger<K, V, T, R>;
+	fn begin_merge(&self, other: &Self) -> Self::Merger {
+		HashValMerger::new(self, other)
+	}
+}
 
-		// Things are horribly wrong if this is not true.
-		assert!(self.desc.upper() == other.desc.lower());
+/// State for an in-progress merge of two `HashValBatch`es.
+///
+/// The merge proceeds through the layers of the two batches, and spends one unit of fuel for each update it
+/// consumes. Keys with more updates than the fuel available are merged in parts, across several calls.
+pub struct HashValMerger<K: HashOrdered, V: Ord, T, R> {
+	// first batch, and position therein.
+	lower1: usize,
+	upper1: usize,
+	// second batch, and position therein.
+	lower2: usize,
+	upper2: usize,
+	// result that we are currently assembling.
+	result: HashedBuilder<K, OrderedBuilder<V, OrderedLeafBuilder<T, R>>>,
+	description: Description<T>,
+}
 
-		// one of self.desc.since or other.desc.since needs to be not behind the other...
-		let since = if self.desc.since().iter().all(|t1| other.desc.since().iter().any(|t2| t2.less_equal(t1))) {
-			other.desc.since()
+impl<K, V, T, R> HashValMerger<K, V, T, R>
+where K: Clone+Default+HashOrdered, V: Ord+Clone, T: Lattice+Ord+Clone, R: Diff {
+	/// Begins a merge of two consecutive batches.
+	pub fn new(batch1: &HashValBatch<K, V, T, R>, batch2: &HashValBatch<K, V, T, R>) -> Self {
+
+		// Things are horribly wrong if this is not true.
+		assert!(batch1.desc.upper() == batch2.desc.lower());
+
+		HashValMerger {
+			lower1: 0,
+			upper1: batch1.layer.keys(),
+			lower2: 0,
+			upper2: batch2.layer.keys(),
+			result: MergeBuilder::with_capacity(&batch1.layer, &batch2.layer),
+			description: merged_description(&batch1.desc, &batch2.desc),
 		}
-		else {
-			self.desc.since()
-		};
-		
-		Rc::new(HashValBatch {
-			layer: <HashedLayer<K, OrderedLayer<V, OrderedLeaf<T, R>>> as Trie>::merge(&self.layer, &other.layer),
-			desc: Description::new(self.desc.lower(), other.desc.upper(), since),
-		})
 	}
+	/// Merges the two batches until `fuel` is exhausted, spending one unit for each update consumed.
+	pub fn work_on(&mut self, batch1: &HashValBatch<K, V, T, R>, batch2: &HashValBatch<K, V, T, R>, fuel: &mut isize) {
+
+		if *fuel > 0 {
+			let trie1 = &batch1.layer;
+			let trie2 = &batch2.layer;
+			let consumed = self.result.merge_step((trie1, &mut self.lower1, self.upper1), (trie2, &mut self.lower2, self.upper2), *fuel as usize);
+			*fuel -= consumed as isize;
+		}
+	}
+	/// Extracts the merged batch.
+	pub fn finish(self) -> HashValBatch<K, V, T, R> {
+		HashValBatch {
+			layer: self.result.done(),
+			desc: self.description,
+		}
+	}
+}
+
+impl<K, V, T, R> Merger<K, V, T, R, Rc<HashValBatch<K, V, T, R>>> for HashValMerger<K, V, T, R>
+where K: Clone+Default+HashOrdered+'static, V: Clone+Ord+'static, T: Lattice+Ord+Clone+Default+'static, R: Diff {
+	fn work(&mut self, source1: &Rc<HashValBatch<K, V, T, R>>, source2: &Rc<HashValBatch<K, V, T, R>>, fuel: &mut isize) {
+		self.work_on(source1, source2, fuel);
+	}
+	fn is_done(&self) -> bool { self.lower1 == self.upper1 && self.lower2 == self.upper2 }
+	fn done(self) -> Rc<HashValBatch<K, V, T, R>> { Rc::new(self.finish()) }
 }
 
 /// A cursor for navigating a single layer.
@@ -170,7 +244,7 @@ where K: Clone+Default+HashOrdered+'static, V: Ord+Clone+'static, T: Lattice+Ord
 
 
 /// An immutable collection of update tuples, from a contiguous interval of logical times.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct HashKeyBatch<K: HashOrdered, T: Lattice, R> {
 	/// Where all the dataz is.
 	pub layer: HashedLayer<K, OrderedLeaf<T, R>>,
@@ -178,6 +252,22 @@ pub struct HashKeyBatch<K: HashOrdered, T: Lattice, R> {
 	pub desc: Description<T>,
 }
 
+impl<K: HashOrdered+Abomonation, T: Lattice+Abomonation, R: Abomonation> Abomonation for HashKeyBatch<K, T, R> {
+	#[inline] unsafe fn entomb(&self, writer: &mut Vec<u8>) {
+		self.layer.entomb(writer);
+		self.desc.entomb(writer);
+	}
+	#[inline] unsafe fn embalm(&mut self) {
+		self.layer.embalm();
+		self.desc.embalm();
+	}
+	#[inline] unsafe fn exhume<'a,'b>(&'a mut self, mut bytes: &'b mut [u8]) -> Option<&'b mut [u8]> {
+		let temp = bytes; bytes = if let Some(bytes) = self.layer.exhume(temp) { bytes } else { return None };
+		let temp = bytes; bytes = if let Some(bytes) = self.desc.exhume(temp) { bytes } else { return None };
+		Some(bytes)
+	}
+}
+
 impl<K, T, R> BatchReader<K, (), T, R> for Rc<HashKeyBatch<K, T, R>>
 where K: Clone+Default+HashOrdered+'static, T: Lattice+Ord+Clone+Default+'static, R: Diff {
 	type Cursor = HashKeyCursor<T, R>;
@@ -199,30 +289,86 @@ where K: Clone+Default+HashOrdered+'static, T: Lattice+Ord+Clone+Default+'static
 	}
 	fn len(&self) -> usize { <HashedLayer<K, OrderedLeaf<T, R>> as Trie>::tuples(&self.layer) }
 	fn description(&self) -> &Description<T> { &self.desc }
+	fn size(&self) -> Size {
+		let keys = self.layer.occupied();
+		Size {
+			tuples: self.len(),
+			keys: keys,
+			vals: keys,
+			bytes: self.layer.heap_size(),
+		}
+	}
 }
 
 impl<K, T, R> Batch<K, (), T, R> for Rc<HashKeyBatch<K, T, R>>
 where K: Clone+Default+HashOrdered+'static, T: Lattice+Ord+Clone+Default+'static, R: Diff {
 	type Batcher = RadixBatcher<K, (), T, R, Self>;
 	type Builder = HashKeyBuilder<K, T, R>;
-	fn merge(&self, other: &Self) -> Self {
+	type Merger = HashKeyMerger<K, T, R>;
+	fn begin_merge(&self, other: &Self) -> Self::Merger {
+		HashKeyMerger::new(self, other)
+	}
+}
 
-		// Things are horribly wrong if this is not true.
-		assert!(self.desc.upper() == other.desc.lower());
+/// State for an in-progress merge of two `HashKeyBatch`es.
+///
+/// The merge proceeds through the layers of the two batches, and spends one unit of fuel for each update it
+/// consumes. Keys with more updates than the fuel available are merged in parts, across several calls.
+pub struct HashKeyMerger<K: HashOrdered, T, R> {
+	// first batch, and position therein.
+	lower1: usize,
+	upper1: usize,
+	// second batch, and position therein.
+	lower2: usize,
+	upper2: usize,
+	// result that we are currently assembling.
+	result: HashedBuilder<K, OrderedLeafBuilder<T, R>>,
+	description: Description<T>,
+}
+
+impl<K, T, R> HashKeyMerger<K, T, R>
+where K: Clone+Default+HashOrdered, T: Lattice+Ord+Clone, R: Diff {
+	/// Begins a merge of two consecutive batches.
+	pub fn new(batch1: &HashKeyBatch<K, T, R>, batch2: &HashKeyBatch<K, T, R>) -> Self {
 
-		// one of self.desc.since or other.desc.since needs to be not behind the other...
-		let since = if self.desc.since().iter().all(|t1| other.desc.since().iter().any(|t2| t2.less_equal(t1))) {
-			other.desc.since()
+		// Things are horribly wrong if this is not true.
+		assert!(batch1.desc.upper() == batch2.desc.lower());
+
+		HashKeyMerger {
+			lower1: 0,
+			upper1: batch1.layer.keys(),
+			lower2: 0,
+			upper2: batch2.layer.keys(),
+			result: MergeBuilder::with_capacity(&batch1.layer, &batch2.layer),
+			description: merged_description(&batch1.desc, &batch2.desc),
 		}
-		else {
-			self.desc.since()
-		};
-		
-		Rc::new(HashKeyBatch {
-			layer: <HashedLayer<K, OrderedLeaf<T, R>> as Trie>::merge(&self.layer, &other.layer),
-			desc: Description::new(self.desc.lower(), other.desc.upper(), since),
-		})
 	}
+	/// Merges the two batches until `fuel` is exhausted, spending one unit for each update consumed.
+	pub fn work_on(&mut self, batch1: &HashKeyBatch<K, T, R>, batch2: &HashKeyBatch<K, T, R>, fuel: &mut isize) {
+
+		if *fuel > 0 {
+			let trie1 = &batch1.layer;
+			let trie2 = &batch2.layer;
+			let consumed = self.result.merge_step((trie1, &mut self.lower1, self.upper1), (trie2, &mut self.lower2, self.upper2), *fuel as usize);
+			*fuel -= consumed as isize;
+		}
+	}
+	/// Extracts the merged batch.
+	pub fn finish(self) -> HashKeyBatch<K, T, R> {
+		HashKeyBatch {
+			layer: self.result.done(),
+			desc: self.description,
+		}
+	}
+}
+
+impl<K, T, R> Merger<K, (), T, R, Rc<HashKeyBatch<K, T, R>>> for HashKeyMerger<K, T, R>
+where K: Clone+Default+HashOrdered+'static, T: Lattice+Ord+Clone+Default+'static, R: Diff {
+	fn work(&mut self, source1: &Rc<HashKeyBatch<K, T, R>>, source2: &Rc<HashKeyBatch<K, T, R>>, fuel: &mut isize) {
+		self.work_on(source1, source2, fuel);
+	}
+	fn is_done(&self) -> bool { self.lower1 == self.upper1 && self.lower2 == self.upper2 }
+	fn done(self) -> Rc<HashKeyBatch<K, T, R>> { Rc::new(self.finish()) }
 }
 
 /// A cursor for navigating a single layer.
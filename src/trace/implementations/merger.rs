@@ -0,0 +1,134 @@
+//! A merger for batch types without a structural merge.
+//!
+//! The `CursorMerger` walks cursors over both batches in key and value order, and pushes the consolidated
+//! updates for each `(key, val)` pair into the batch type's `Builder`. It is less efficient than merging
+//! the underlying layers, but applies to any batch whose builder accepts updates in cursor order.
+
+use std::cmp::Ordering;
+
+use ::Diff;
+use lattice::Lattice;
+use trace::{Batch, BatchReader, Builder, Merger, Cursor, consolidate};
+
+/// The progress of a merge of two consecutive batches, driven by their cursors.
+pub struct CursorMerger<K, V, T: Lattice+Ord, R: Diff, B: Batch<K, V, T, R>> {
+	phantom: ::std::marker::PhantomData<(K, V)>,
+	lower: Vec<T>,
+	upper: Vec<T>,
+	since: Vec<T>,
+	cursor1: B::Cursor,
+	storage1: <B::Cursor as Cursor<K, V, T, R>>::Storage,
+	cursor2: B::Cursor,
+	storage2: <B::Cursor as Cursor<K, V, T, R>>::Storage,
+	builder: B::Builder,
+	times: Vec<(T, R)>,
+}
+
+impl<K, V, T, R, B> CursorMerger<K, V, T, R, B>
+where K: Ord+Clone, V: Ord+Clone, T: Lattice+Ord+Clone, R: Diff, B: Batch<K, V, T, R> {
+	/// Begins a merge of two consecutive batches.
+	pub fn new(batch1: &B, batch2: &B) -> Self {
+
+		// Things are horribly wrong if this is not true.
+		assert!(batch1.upper() == batch2.lower());
+
+		// one of the two `since` frontiers needs to be not behind the other...
+		let since = if batch1.description().since().iter().all(|t1| batch2.description().since().iter().any(|t2| t2.less_equal(t1))) {
+			batch2.description().since().to_vec()
+		}
+		else {
+			batch1.description().since().to_vec()
+		};
+
+		let (cursor1, storage1) = batch1.cursor();
+		let (cursor2, storage2) = batch2.cursor();
+
+		CursorMerger {
+			phantom: ::std::marker::PhantomData,
+			lower: batch1.lower().to_vec(),
+			upper: batch2.upper().to_vec(),
+			since: since,
+			cursor1: cursor1,
+			storage1: storage1,
+			cursor2: cursor2,
+			storage2: storage2,
+			builder: B::Builder::with_capacity(batch1.len() + batch2.len()),
+			times: Vec::new(),
+		}
+	}
+}
+
+impl<K, V, T, R, B> Merger<K, V, T, R, B> for CursorMerger<K, V, T, R, B>
+where K: Ord+Clone, V: Ord+Clone, T: Lattice+Ord+Clone, R: Diff, B: Batch<K, V, T, R> {
+
+	// The cursors hold their own storage, and so the source batches are not consulted.
+	fn work(&mut self, _source1: &B, _source2: &B, fuel: &mut isize) {
+
+		while *fuel > 0 && !self.is_done() {
+
+			// Determine which cursors present the least `(key, val)` pair, stepping past exhausted keys.
+			// An exhausted cursor orders after any valid cursor.
+			let valid1 = self.cursor1.key_valid(&self.storage1);
+			let valid2 = self.cursor2.key_valid(&self.storage2);
+			let key_order = if valid1 && valid2 { self.cursor1.key(&self.storage1).cmp(self.cursor2.key(&self.storage2)) }
+							else if valid1 { Ordering::Less }
+							else { Ordering::Greater };
+
+			let (use1, use2) = match key_order {
+				Ordering::Less => {
+					if !self.cursor1.val_valid(&self.storage1) { self.cursor1.step_key(&self.storage1); continue; }
+					(true, false)
+				},
+				Ordering::Greater => {
+					if !self.cursor2.val_valid(&self.storage2) { self.cursor2.step_key(&self.storage2); continue; }
+					(false, true)
+				},
+				Ordering::Equal => {
+					let val_valid1 = self.cursor1.val_valid(&self.storage1);
+					let val_valid2 = self.cursor2.val_valid(&self.storage2);
+					if !val_valid1 && !val_valid2 {
+						self.cursor1.step_key(&self.storage1);
+						self.cursor2.step_key(&self.storage2);
+						continue;
+					}
+					let val_order = if val_valid1 && val_valid2 { self.cursor1.val(&self.storage1).cmp(self.cursor2.val(&self.storage2)) }
+									else if val_valid1 { Ordering::Less }
+									else { Ordering::Greater };
+					match val_order {
+						Ordering::Less => (true, false),
+						Ordering::Greater => (false, true),
+						Ordering::Equal => (true, true),
+					}
+				},
+			};
+
+			// Collect and consolidate the updates for the `(key, val)` pair.
+			{
+				let times = &mut self.times;
+				if use1 { self.cursor1.map_times(&self.storage1, |time, diff| times.push((time.clone(), diff))); }
+				if use2 { self.cursor2.map_times(&self.storage2, |time, diff| times.push((time.clone(), diff))); }
+			}
+			*fuel -= self.times.len() as isize + 1;
+			consolidate(&mut self.times, 0);
+
+			if !self.times.is_empty() {
+				let (key, val) = if use1 { (self.cursor1.key(&self.storage1).clone(), self.cursor1.val(&self.storage1).clone()) }
+								 else { (self.cursor2.key(&self.storage2).clone(), self.cursor2.val(&self.storage2).clone()) };
+				for (time, diff) in self.times.drain(..) {
+					self.builder.push((key.clone(), val.clone(), time, diff));
+				}
+			}
+
+			if use1 { self.cursor1.step_val(&self.storage1); }
+			if use2 { self.cursor2.step_val(&self.storage2); }
+		}
+	}
+
+	fn is_done(&self) -> bool {
+		!self.cursor1.key_valid(&self.storage1) && !self.cursor2.key_valid(&self.storage2)
+	}
+
+	fn done(self) -> B {
+		self.builder.done(&self.lower[..], &self.upper[..], &self.since[..])
+	}
+}
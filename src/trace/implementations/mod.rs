@@ -39,6 +39,7 @@
 //! trace, rather than just a batch of the type merged.
 
 pub mod spine;
+pub mod merger;
 
 mod batcher;
 mod batcher_merge;
@@ -47,3 +48,4 @@ pub use self::batcher::RadixBatcher as Batcher;
 
 pub mod ord;
 pub mod hash;
+pub mod persistent;
@@ -9,19 +9,21 @@
 //! and should consume fewer resources (computation and memory) when it applies.
 
 use std::rc::Rc;
+
+use abomonation::Abomonation;
 // use owning_ref::OwningRef;
 
 use ::Diff;
 use hashable::HashOrdered;
 
-use trace::layers::{Trie, TupleBuilder};
+use trace::layers::{Trie, TupleBuilder, MergeBuilder};
 use trace::layers::Builder as TrieBuilder;
 use trace::layers::Cursor as TrieCursor;
 use trace::layers::ordered::{OrderedLayer, OrderedBuilder, OrderedCursor};
 use trace::layers::ordered_leaf::{OrderedLeaf, OrderedLeafBuilder};
 
 use lattice::Lattice;
-use trace::{Batch, BatchReader, Builder, Cursor};
+use trace::{Batch, BatchReader, Builder, Merger, Cursor, Size};
 use trace::description::Description;
 
 use super::spine::Spine;
@@ -34,7 +36,7 @@ pub type OrdKeySpine<K, T, R> = Spine<K, (), T, R, Rc<OrdKeyBatch<K, T, R>>>;
 
 
 /// An immutable collection of update tuples, from a contiguous interval of logical times.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct OrdValBatch<K: Ord+HashOrdered, V: Ord, T: Lattice, R> {
 	/// Where all the dataz is.
 	pub layer: OrderedLayer<K, OrderedLayer<V, OrderedLeaf<T, R>>>,
@@ -42,6 +44,22 @@ pub struct OrdValBatch<K: Ord+HashOrdered, V: Ord, T: Lattice, R> {
 	pub desc: Description<T>,
 }
 
+impl<K: Ord+HashOrdered+Abomonation, V: Ord+Abomonation, T: Lattice+Abomonation, R: Abomonation> Abomonation for OrdValBatch<K, V, T, R> {
+	#[inline] unsafe fn entomb(&self, writer: &mut Vec<u8>) {
+		self.layer.entomb(writer);
+		self.desc.entomb(writer);
+	}
+	#[inline] unsafe fn embalm(&mut self) {
+		self.layer.embalm();
+		self.desc.embalm();
+	}
+	#[inline] unsafe fn exhume<'a,'b>(&'a mut self, mut bytes: &'b mut [u8]) -> Option<&'b mut [u8]> {
+		let temp = bytes; bytes = if let Some(bytes) = self.layer.exhume(temp) { bytes } else { return None };
+		let temp = bytes; bytes = if let Some(bytes) = self.desc.exhume(temp) { bytes } else { return None };
+		Some(bytes)
+	}
+}
+
 impl<K, V, T, R> BatchReader<K, V, T, R> for Rc<OrdValBatch<K, V, T, R>>
 where K: Ord+Clone+HashOrdered+'static, V: Ord+Clone+'static, T: Lattice+Ord+Clone+'static, R: Diff {
 	type Cursor = OrdValCursor<V, T, R>;
@@ -54,29 +72,23 @@ where K: Ord+Clone+HashOrdered+'static, V: Ord+Clone+'static, T: Lattice+Ord+Clo
 	}
 	fn len(&self) -> usize { <OrderedLayer<K, OrderedLayer<V, OrderedLeaf<T, R>>> as Trie>::tuples(&self.layer) }
 	fn description(&self) -> &Description<T> { &self.desc }
+	fn size(&self) -> Size {
+		Size {
+			tuples: self.len(),
+			keys: self.layer.keys.len(),
+			vals: self.layer.vals.keys.len(),
+			bytes: self.layer.heap_size(),
+		}
+	}
 }
 
 impl<K, V, T, R> Batch<K, V, T, R> for Rc<OrdValBatch<K, V, T, R>>
 where K: Ord+Clone+HashOrdered+'static, V: Ord+Clone+'static, T: Lattice+Ord+Clone+::std::fmt::Debug+'static, R: Diff {
 	type Batcher = RadixBatcher<K, V, T, R, Self>;
 	type Builder = OrdValBuilder<K, V, T, R>;
-	fn merge(&self, other: &Self) -> Self {
-
-		// Things are horribly wrong if this is not true.
-		assert!(self.desc.upper() == other.desc.lower());
-
-		// one of self.desc.since or other.desc.since needs to be not behind the other...
-		let since = if self.desc.since().iter().all(|t1| other.desc.since().iter().any(|t2| t2.less_equal(t1))) {
-			other.desc.since()
-		}
-		else {
-			self.desc.since()
-		};
-		
-		Rc::new(OrdValBatch {
-			layer: <OrderedLayer<K, OrderedLayer<V, OrderedLeaf<T, R>>> as Trie>::merge(&self.layer, &other.layer),  //self.layer.merge(&other.layer),
-			desc: Description::new(self.desc.lower(), other.desc.upper(), since),
-		})
+	type Merger = OrdValMerger<K, V, T, R>;
+	fn begin_merge(&self, other: &Self) -> Self::Merger {
+		OrdValMerger::new(self, other)
 	}
 
 	fn advance_mut(&mut self, frontier: &[T]) where K: Ord+Clone, V: Ord+Clone, T: Lattice+Ord+Clone, R: Diff {
@@ -198,6 +210,80 @@ where K: Ord+Clone+HashOrdered+'static, V: Ord+Clone+'static, T: Lattice+Ord+Clo
 
 }
 
+/// State for an in-progress merge of two `OrdValBatch`es.
+///
+/// The merge proceeds through the layers of the two batches, and spends one unit of fuel for each update it
+/// consumes. Keys with more updates than the fuel available are merged in parts, across several calls.
+pub struct OrdValMerger<K: Ord, V: Ord, T, R> {
+	// first batch, and position therein.
+	lower1: usize,
+	upper1: usize,
+	// second batch, and position therein.
+	lower2: usize,
+	upper2: usize,
+	// result that we are currently assembling.
+	result: OrderedBuilder<K, OrderedBuilder<V, OrderedLeafBuilder<T, R>>>,
+	description: Description<T>,
+}
+
+impl<K, V, T, R> OrdValMerger<K, V, T, R>
+where K: Ord+Clone+HashOrdered, V: Ord+Clone, T: Lattice+Ord+Clone, R: Diff {
+	/// Begins a merge of two consecutive batches.
+	pub fn new(batch1: &OrdValBatch<K, V, T, R>, batch2: &OrdValBatch<K, V, T, R>) -> Self {
+
+		// Things are horribly wrong if this is not true.
+		assert!(batch1.desc.upper() == batch2.desc.lower());
+
+		OrdValMerger {
+			lower1: 0,
+			upper1: batch1.layer.keys(),
+			lower2: 0,
+			upper2: batch2.layer.keys(),
+			result: MergeBuilder::with_capacity(&batch1.layer, &batch2.layer),
+			description: merged_description(&batch1.desc, &batch2.desc),
+		}
+	}
+	/// Merges the two batches until `fuel` is exhausted, spending one unit for each update consumed.
+	pub fn work_on(&mut self, batch1: &OrdValBatch<K, V, T, R>, batch2: &OrdValBatch<K, V, T, R>, fuel: &mut isize) {
+
+		if *fuel > 0 {
+			let trie1 = &batch1.layer;
+			let trie2 = &batch2.layer;
+			let consumed = self.result.merge_step((trie1, &mut self.lower1, self.upper1), (trie2, &mut self.lower2, self.upper2), *fuel as usize);
+			*fuel -= consumed as isize;
+		}
+	}
+	/// Extracts the merged batch.
+	pub fn finish(self) -> OrdValBatch<K, V, T, R> {
+		OrdValBatch {
+			layer: self.result.done(),
+			desc: self.description,
+		}
+	}
+}
+
+impl<K, V, T, R> Merger<K, V, T, R, Rc<OrdValBatch<K, V, T, R>>> for OrdValMerger<K, V, T, R>
+where K: Ord+Clone+HashOrdered+'static, V: Ord+Clone+'static, T: Lattice+Ord+Clone+::std::fmt::Debug+'static, R: Diff {
+	fn work(&mut self, source1: &Rc<OrdValBatch<K, V, T, R>>, source2: &Rc<OrdValBatch<K, V, T, R>>, fuel: &mut isize) {
+		self.work_on(source1, source2, fuel);
+	}
+	fn is_done(&self) -> bool { self.lower1 == self.upper1 && self.lower2 == self.upper2 }
+	fn done(self) -> Rc<OrdValBatch<K, V, T, R>> { Rc::new(self.finish()) }
+}
+
+/// Describes the result of merging two consecutive batches.
+///
+/// One of the two `since` frontiers needs to be not behind the other, and the merged batch takes the later one.
+pub fn merged_description<T: Lattice+Clone>(desc1: &Description<T>, desc2: &Description<T>) -> Description<T> {
+	let since = if desc1.since().iter().all(|t1| desc2.since().iter().any(|t2| t2.less_equal(t1))) {
+		desc2.since()
+	}
+	else {
+		desc1.since()
+	};
+	Description::new(desc1.lower(), desc2.upper(), since)
+}
+
 /// A cursor for navigating a single layer.
 #[derive(Debug)]
 pub struct OrdValCursor<V: Ord+Clone, T: Lattice+Ord+Clone, R: Diff> {
@@ -267,7 +353,7 @@ where K: Ord+Clone+HashOrdered+'static, V: Ord+Clone+'static, T: Lattice+Ord+Clo
 
 
 /// An immutable collection of update tuples, from a contiguous interval of logical times.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct OrdKeyBatch<K: Ord+HashOrdered, T: Lattice, R> {
 	/// Where all the dataz is.
 	pub layer: OrderedLayer<K, OrderedLeaf<T, R>>,
@@ -275,6 +361,22 @@ pub struct OrdKeyBatch<K: Ord+HashOrdered, T: Lattice, R> {
 	pub desc: Description<T>,
 }
 
+impl<K: Ord+HashOrdered+Abomonation, T: Lattice+Abomonation, R: Abomonation> Abomonation for OrdKeyBatch<K, T, R> {
+	#[inline] unsafe fn entomb(&self, writer: &mut Vec<u8>) {
+		self.layer.entomb(writer);
+		self.desc.entomb(writer);
+	}
+	#[inline] unsafe fn embalm(&mut self) {
+		self.layer.embalm();
+		self.desc.embalm();
+	}
+	#[inline] unsafe fn exhume<'a,'b>(&'a mut self, mut bytes: &'b mut [u8]) -> Option<&'b mut [u8]> {
+		let temp = bytes; bytes = if let Some(bytes) = self.layer.exhume(temp) { bytes } else { return None };
+		let temp = bytes; bytes = if let Some(bytes) = self.desc.exhume(temp) { bytes } else { return None };
+		Some(bytes)
+	}
+}
+
 impl<K, T, R> BatchReader<K, (), T, R> for Rc<OrdKeyBatch<K, T, R>>
 where K: Ord+Clone+HashOrdered+'static, T: Lattice+Ord+Clone+'static, R: Diff {
 	type Cursor = OrdKeyCursor<T, R>;
@@ -288,29 +390,23 @@ where K: Ord+Clone+HashOrdered+'static, T: Lattice+Ord+Clone+'static, R: Diff {
 	}
 	fn len(&self) -> usize { <OrderedLayer<K, OrderedLeaf<T, R>> as Trie>::tuples(&self.layer) }
 	fn description(&self) -> &Description<T> { &self.desc }
+	fn size(&self) -> Size {
+		Size {
+			tuples: self.len(),
+			keys: self.layer.keys.len(),
+			vals: self.layer.keys.len(),
+			bytes: self.layer.heap_size(),
+		}
+	}
 }
 
 impl<K, T, R> Batch<K, (), T, R> for Rc<OrdKeyBatch<K, T, R>>
 where K: Ord+Clone+HashOrdered+'static, T: Lattice+Ord+Clone+'static, R: Diff {
 	type Batcher = RadixBatcher<K, (), T, R, Self>;
 	type Builder = OrdKeyBuilder<K, T, R>;
-	fn merge(&self, other: &Self) -> Self {
-
-		// Things are horribly wrong if this is not true.
-		assert!(self.desc.upper() == other.desc.lower());
-
-		// one of self.desc.since or other.desc.since needs to be not behind the other...
-		let since = if self.desc.since().iter().all(|t1| other.desc.since().iter().any(|t2| t2.less_equal(t1))) {
-			other.desc.since()
-		}
-		else {
-			self.desc.since()
-		};
-		
-		Rc::new(OrdKeyBatch {
-			layer: <OrderedLayer<K, OrderedLeaf<T, R>> as Trie>::merge(&self.layer, &other.layer),
-			desc: Description::new(self.desc.lower(), other.desc.upper(), since),
-		})
+	type Merger = OrdKeyMerger<K, T, R>;
+	fn begin_merge(&self, other: &Self) -> Self::Merger {
+		OrdKeyMerger::new(self, other)
 	}
 
 	// TODO: The following looks good to me, but causes a perf reduction in Eintopf when I uncomment it.
@@ -406,6 +502,67 @@ where K: Ord+Clone+HashOrdered+'static, T: Lattice+Ord+Clone+'static, R: Diff {
 	}
 }
 
+/// State for an in-progress merge of two `OrdKeyBatch`es.
+///
+/// The merge proceeds through the layers of the two batches, and spends one unit of fuel for each update it
+/// consumes. Keys with more updates than the fuel available are merged in parts, across several calls.
+pub struct OrdKeyMerger<K: Ord, T, R> {
+	// first batch, and position therein.
+	lower1: usize,
+	upper1: usize,
+	// second batch, and position therein.
+	lower2: usize,
+	upper2: usize,
+	// result that we are currently assembling.
+	result: OrderedBuilder<K, OrderedLeafBuilder<T, R>>,
+	description: Description<T>,
+}
+
+impl<K, T, R> OrdKeyMerger<K, T, R>
+where K: Ord+Clone+HashOrdered, T: Lattice+Ord+Clone, R: Diff {
+	/// Begins a merge of two consecutive batches.
+	pub fn new(batch1: &OrdKeyBatch<K, T, R>, batch2: &OrdKeyBatch<K, T, R>) -> Self {
+
+		// Things are horribly wrong if this is not true.
+		assert!(batch1.desc.upper() == batch2.desc.lower());
+
+		OrdKeyMerger {
+			lower1: 0,
+			upper1: batch1.layer.keys(),
+			lower2: 0,
+			upper2: batch2.layer.keys(),
+			result: MergeBuilder::with_capacity(&batch1.layer, &batch2.layer),
+			description: merged_description(&batch1.desc, &batch2.desc),
+		}
+	}
+	/// Merges the two batches until `fuel` is exhausted, spending one unit for each update consumed.
+	pub fn work_on(&mut self, batch1: &OrdKeyBatch<K, T, R>, batch2: &OrdKeyBatch<K, T, R>, fuel: &mut isize) {
+
+		if *fuel > 0 {
+			let trie1 = &batch1.layer;
+			let trie2 = &batch2.layer;
+			let consumed = self.result.merge_step((trie1, &mut self.lower1, self.upper1), (trie2, &mut self.lower2, self.upper2), *fuel as usize);
+			*fuel -= consumed as isize;
+		}
+	}
+	/// Extracts the merged batch.
+	pub fn finish(self) -> OrdKeyBatch<K, T, R> {
+		OrdKeyBatch {
+			layer: self.result.done(),
+			desc: self.description,
+		}
+	}
+}
+
+impl<K, T, R> Merger<K, (), T, R, Rc<OrdKeyBatch<K, T, R>>> for OrdKeyMerger<K, T, R>
+where K: Ord+Clone+HashOrdered+'static, T: Lattice+Ord+Clone+'static, R: Diff {
+	fn work(&mut self, source1: &Rc<OrdKeyBatch<K, T, R>>, source2: &Rc<OrdKeyBatch<K, T, R>>, fuel: &mut isize) {
+		self.work_on(source1, source2, fuel);
+	}
+	fn is_done(&self) -> bool { self.lower1 == self.upper1 && self.lower2 == self.upper2 }
+	fn done(self) -> Rc<OrdKeyBatch<K, T, R>> { Rc::new(self.finish()) }
+}
+
 /// A cursor for navigating a single layer.
 #[derive(Debug)]
 pub struct OrdKeyCursor<T: Lattice+Ord+Clone, R: Diff> {
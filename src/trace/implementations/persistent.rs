@@ -0,0 +1,852 @@
+//! Trace and batch implementations whose contents are stored in local files.
+//!
+//! The types and type aliases in this module mirror those of the `ord` and `hash` modules:
+//!
+//! * `PersistentVal`: Collections whose data have the form `(key, val)` where `key` is ordered.
+//! * `PersistentKey`: Collections whose data have the form `key` where `key` is ordered.
+//! * `PersistentHashVal`: Collections whose data have the form `(key, val)` where `key` is hash-ordered.
+//! * `PersistentHashKey`: Collections whose data have the form `key` where `key` is hash-ordered.
+//!
+//! Each batch is a directory holding one file for each column of its layers (keys, offsets, values, and
+//! updates), in the layout that abomonation uses for the contents of a `Vec`. Builders write each tuple to
+//! these files as it is pushed, and merges push their output through a builder, so a batch is never held
+//! in memory in its entirety; the exception is the keys of hash-map batches, whose layout is only known
+//! once all keys are, and which are held in memory until the batch is complete.
+//!
+//! Once written, the files are mapped back into memory with private (copy-on-write) mappings, and their
+//! contents decoded in place. Cursors read the layers directly from the mappings, and so the operating
+//! system pages the batch in as it is navigated and may evict its pages under memory pressure; only the
+//! pages holding pointers rewritten by decoding become private to the process. The directory is removed
+//! once the last reference to the batch is dropped.
+//!
+//! Directories are created in the directory named by the `DIFFERENTIAL_SPILL_DIR` environment variable,
+//! or in the system temporary directory if the variable is not set. The `Builder` and `Merger` traits have
+//! no way to report errors, and so failing to write or map a batch's files panics, naming the file and the
+//! cause.
+
+use std::fmt;
+use std::mem;
+use std::ptr;
+use std::rc::Rc;
+use std::fs::File;
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, ATOMIC_USIZE_INIT, Ordering};
+
+use abomonation::Abomonation;
+use memmap::{Mmap, Protection};
+
+use ::Diff;
+use hashable::HashOrdered;
+
+use trace::layers::Trie;
+use trace::layers::Cursor as TrieCursor;
+use trace::layers::ordered::{OrderedLayer, OrderedCursor};
+use trace::layers::ordered_leaf::OrderedLeaf;
+use trace::layers::hashed::{HashedLayer, HashedCursor, Entry, lay_out};
+
+use lattice::Lattice;
+use trace::{Batch, BatchReader, Builder, Cursor, Size};
+use trace::description::Description;
+
+use super::spine::Spine;
+use super::batcher::RadixBatcher;
+use super::merger::CursorMerger;
+use super::ord::{OrdValBatch, OrdKeyBatch};
+use super::hash::{HashValBatch, HashKeyBatch};
+
+/// A trace implementation using a spine of file-backed ordered batches.
+pub type PersistentValSpine<K, V, T, R> = Spine<K, V, T, R, Rc<PersistentValBatch<K, V, T, R>>>;
+/// A trace implementation for empty values using a spine of file-backed ordered batches.
+pub type PersistentKeySpine<K, T, R> = Spine<K, (), T, R, Rc<PersistentKeyBatch<K, T, R>>>;
+/// A trace implementation using a spine of file-backed hash-map batches.
+pub type PersistentHashValSpine<K, V, T, R> = Spine<K, V, T, R, Rc<PersistentHashValBatch<K, V, T, R>>>;
+/// A trace implementation for empty values using a spine of file-backed hash-map batches.
+pub type PersistentHashKeySpine<K, T, R> = Spine<K, (), T, R, Rc<PersistentHashKeyBatch<K, T, R>>>;
+
+/// A file-backed batch of `(key, val)` updates with ordered keys.
+pub type PersistentValBatch<K, V, T, R> = Persisted<OrdValBatch<K, V, T, R>>;
+/// A file-backed batch of `key` updates with ordered keys.
+pub type PersistentKeyBatch<K, T, R> = Persisted<OrdKeyBatch<K, T, R>>;
+/// A file-backed batch of `(key, val)` updates with hash-ordered keys.
+pub type PersistentHashValBatch<K, V, T, R> = Persisted<HashValBatch<K, V, T, R>>;
+/// A file-backed batch of `key` updates with hash-ordered keys.
+pub type PersistentHashKeyBatch<K, T, R> = Persisted<HashKeyBatch<K, T, R>>;
+
+/// Environment variable naming the directory in which batch files are written.
+pub const SPILL_DIR_VARIABLE: &'static str = "DIFFERENTIAL_SPILL_DIR";
+
+// Distinguishes the directories of batches created by the same process.
+static BATCH_COUNTER: AtomicUsize = ATOMIC_USIZE_INIT;
+
+/// Unwraps the result of an I/O operation on `path`, panicking with the path and the cause on failure.
+fn checked<X>(result: io::Result<X>, action: &str, path: &Path) -> X {
+	match result {
+		Ok(x) => x,
+		Err(error) => panic!("failed to {} {:?}: {}", action, path, error),
+	}
+}
+
+/// A directory holding the files of one batch, removed when dropped.
+struct SpillDirectory {
+	path: PathBuf,
+}
+
+impl SpillDirectory {
+	/// Creates a fresh directory in the spill directory.
+	fn create() -> Self {
+		let directory = ::std::env::var_os(SPILL_DIR_VARIABLE)
+			.map(PathBuf::from)
+			.unwrap_or_else(::std::env::temp_dir);
+		let index = BATCH_COUNTER.fetch_add(1, Ordering::SeqCst);
+		let path = directory.join(format!("differential-{}-{}", ::std::process::id(), index));
+		checked(::std::fs::create_dir(&path), "create batch directory", &path);
+		SpillDirectory { path: path }
+	}
+}
+
+impl Drop for SpillDirectory {
+	fn drop(&mut self) {
+		// The directory may already be gone (e.g. a cleaned temporary directory); nothing to do then.
+		// On unix systems, mappings of its files remain valid until they are dropped.
+		let _ = ::std::fs::remove_dir_all(&self.path);
+	}
+}
+
+// The number of bytes preceding the elements of a column: their number, padded to their alignment.
+fn header_len<T>() -> usize { ::std::cmp::max(mem::size_of::<u64>(), mem::align_of::<T>()) }
+
+// The file holding the data owned by the elements of the column at `path`.
+fn heap_path(path: &Path) -> PathBuf { path.with_extension("heap") }
+
+/// Writes elements to a file as they are pushed, as the contents of a `Vec<T>` to be read by `read_column`.
+///
+/// The elements themselves follow a header recording their number, and the data they own, if any, is
+/// written to a second file in the order that `exhume` reads it back.
+struct ColumnWriter<T> {
+	path: PathBuf,
+	file: BufWriter<File>,
+	heap: Option<BufWriter<File>>,
+	// the data owned by one element, as `entomb` only writes to a `Vec<u8>`.
+	scratch: Vec<u8>,
+	len: usize,
+	phantom: PhantomData<T>,
+}
+
+impl<T: Abomonation> ColumnWriter<T> {
+	/// Creates the file `name` in `directory`.
+	fn create(directory: &Path, name: &str) -> Self {
+		let path = directory.join(name);
+		let mut file = BufWriter::new(checked(File::create(&path), "create batch file", &path));
+		checked(file.write_all(&vec![0u8; header_len::<T>()]), "write batch file", &path);
+		ColumnWriter {
+			path: path,
+			file: file,
+			heap: None,
+			scratch: Vec::new(),
+			len: 0,
+			phantom: PhantomData,
+		}
+	}
+	/// The number of elements pushed so far.
+	fn len(&self) -> usize { self.len }
+	/// Appends `element` to the column.
+	fn push(&mut self, element: &T) {
+		let bytes = unsafe { ::std::slice::from_raw_parts(element as *const T as *const u8, mem::size_of::<T>()) };
+		checked(self.file.write_all(bytes), "write batch file", &self.path);
+
+		self.scratch.clear();
+		unsafe { element.entomb(&mut self.scratch); }
+		if self.scratch.len() > 0 {
+			if self.heap.is_none() {
+				let path = heap_path(&self.path);
+				self.heap = Some(BufWriter::new(checked(File::create(&path), "create batch file", &path)));
+			}
+			if let Some(ref mut heap) = self.heap {
+				checked(heap.write_all(&self.scratch[..]), "write batch file", &heap_path(&self.path));
+			}
+		}
+		self.len += 1;
+	}
+	/// Records the number of elements in the header, and flushes the files.
+	fn finish(mut self) {
+		let len: [u8; 8] = unsafe { mem::transmute(self.len as u64) };
+		checked(self.file.seek(SeekFrom::Start(0)), "write batch file", &self.path);
+		checked(self.file.write_all(&len), "write batch file", &self.path);
+		checked(self.file.flush(), "write batch file", &self.path);
+		if let Some(ref mut heap) = self.heap {
+			checked(heap.flush(), "write batch file", &heap_path(&self.path));
+		}
+	}
+}
+
+/// Maps the column `name` written by a `ColumnWriter`, and decodes its elements in place.
+///
+/// The mappings are added to `maps`, and the result borrows its allocation from them. It must not be dropped,
+/// and `maps` must outlive it.
+unsafe fn read_column<T: Abomonation>(directory: &Path, name: &str, maps: &mut Vec<Mmap>) -> Vec<T> {
+
+	// The mappings are page aligned, and copy-on-write so that decoding may rewrite pointers in place.
+	let path = directory.join(name);
+	let mut map = checked(Mmap::open_path(&path, Protection::ReadCopy), "map batch file", &path);
+	let mut column = {
+		let bytes = map.as_mut_slice();
+		let len = *(bytes.as_ptr() as *const u64) as usize;
+		assert!(bytes.len() == header_len::<T>() + len * mem::size_of::<T>(), "batch file {:?} has the wrong length", path);
+		Vec::from_raw_parts(bytes[header_len::<T>() ..].as_mut_ptr() as *mut T, len, len)
+	};
+	maps.push(map);
+
+	// A column whose elements own no data has no heap file, and decoding its elements reads no bytes.
+	let heap = heap_path(&path);
+	let mut heap_map = if heap.exists() { Some(checked(Mmap::open_path(&heap, Protection::ReadCopy), "map batch file", &heap)) } else { None };
+	let mut empty: [u8; 0] = [];
+	{
+		let mut bytes: &mut [u8] = match heap_map { Some(ref mut map) => map.as_mut_slice(), None => &mut empty[..] };
+		for element in column.iter_mut() {
+			let temp = bytes;
+			bytes = match element.exhume(temp) { Some(bytes) => bytes, None => panic!("failed to decode batch file {:?}", heap) };
+		}
+		assert!(bytes.len() == 0, "batch file {:?} has the wrong length", heap);
+	}
+	maps.extend(heap_map);
+
+	column
+}
+
+/// Writes the description of a batch to the column `desc`.
+fn write_description<T: Clone+Abomonation>(directory: &Path, lower: &[T], upper: &[T], since: &[T]) {
+	let mut column = ColumnWriter::create(directory, "desc");
+	column.push(&Description::new(lower, upper, since));
+	column.finish();
+}
+
+/// Reads the description of a batch from the column `desc`.
+unsafe fn read_description<T: Abomonation>(directory: &Path, maps: &mut Vec<Mmap>) -> Description<T> {
+	let column = read_column::<Description<T>>(directory, "desc", maps);
+	let description = ptr::read(&column[0]);
+	mem::forget(column);
+	description
+}
+
+/// An in-memory batch, whose layers are stored in local files and read in place through memory mappings.
+pub struct Persisted<B> {
+	/// The directory holding the batch's files.
+	directory: SpillDirectory,
+	/// Private mappings of the files, in which the batch was decoded.
+	_maps: Vec<Mmap>,
+	/// The decoded batch, whose allocations all point into `_maps`.
+	///
+	/// The batch is never dropped, as it owns none of its allocations.
+	batch: Option<B>,
+}
+
+impl<B> Persisted<B> {
+
+	/// The batch, as read from the mapped files.
+	pub fn batch(&self) -> &B { self.batch.as_ref().expect("batch already dropped") }
+
+	/// The location of the directory holding the batch's files.
+	pub fn path(&self) -> &Path { &self.directory.path }
+}
+
+impl<B> Drop for Persisted<B> {
+	fn drop(&mut self) {
+		// The directory and the mappings are released once this returns.
+		if let Some(batch) = self.batch.take() {
+			mem::forget(batch);
+		}
+	}
+}
+
+impl<B> fmt::Debug for Persisted<B> {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "Persisted({:?})", self.directory.path)
+	}
+}
+
+impl<K, V, T, R> BatchReader<K, V, T, R> for Rc<PersistentValBatch<K, V, T, R>>
+where K: Ord+Clone+HashOrdered+Abomonation+'static, V: Ord+Clone+Abomonation+'static, T: Lattice+Ord+Clone+Abomonation+'static, R: Diff {
+	type Cursor = PersistentValCursor<V, T, R>;
+	fn cursor(&self) -> (Self::Cursor, <Self::Cursor as Cursor<K, V, T, R>>::Storage) {
+		(PersistentValCursor { cursor: self.batch().layer.cursor() }, self.clone())
+	}
+	fn len(&self) -> usize { <OrderedLayer<K, OrderedLayer<V, OrderedLeaf<T, R>>> as Trie>::tuples(&self.batch().layer) }
+	fn description(&self) -> &Description<T> { &self.batch().desc }
+	fn size(&self) -> Size {
+		// The layers are mapped from files, rather than allocated on the heap.
+		let layer = &self.batch().layer;
+		Size { tuples: self.len(), keys: layer.keys.len(), vals: layer.vals.keys.len(), bytes: 0 }
+	}
+}
+
+
+impl<K, V, T, R> Batch<K, V, T, R> for Rc<PersistentValBatch<K, V, T, R>>
+where K: Ord+Clone+HashOrdered+Abomonation+'static, V: Ord+Clone+Abomonation+'static, T: Lattice+Ord+Clone+Abomonation+'static, R: Diff {
+	type Batcher = RadixBatcher<K, V, T, R, Self>;
+	type Builder = PersistentValBuilder<K, V, T, R>;
+	type Merger = CursorMerger<K, V, T, R, Self>;
+	fn begin_merge(&self, other: &Self) -> Self::Merger {
+		CursorMerger::new(self, other)
+	}
+}
+
+/// A cursor for navigating a file-backed batch of `(key, val)` updates.
+#[derive(Debug)]
+pub struct PersistentValCursor<V: Ord+Clone, T: Lattice+Ord+Clone, R: Diff> {
+	cursor: OrderedCursor<OrderedLayer<V, OrderedLeaf<T, R>>>,
+}
+
+impl<K, V, T, R> Cursor<K, V, T, R> for PersistentValCursor<V, T, R>
+where K: Ord+Clone+HashOrdered, V: Ord+Clone, T: Lattice+Ord+Clone, R: Diff {
+
+	type Storage = Rc<PersistentValBatch<K, V, T, R>>;
+
+	fn key<'a>(&self, storage: &'a Self::Storage) -> &'a K { &self.cursor.key(&storage.batch().layer) }
+	fn val<'a>(&self, storage: &'a Self::Storage) -> &'a V { &self.cursor.child.key(&storage.batch().layer.vals) }
+	fn map_times<L: FnMut(&T, R)>(&mut self, storage: &Self::Storage, mut logic: L) {
+		let times = &storage.batch().layer.vals.vals;
+		self.cursor.child.child.rewind(times);
+		while self.cursor.child.child.valid(times) {
+			logic(&self.cursor.child.child.key(times).0, self.cursor.child.child.key(times).1);
+			self.cursor.child.child.step(times);
+		}
+	}
+	fn key_valid(&self, storage: &Self::Storage) -> bool { self.cursor.valid(&storage.batch().layer) }
+	fn val_valid(&self, storage: &Self::Storage) -> bool { self.cursor.child.valid(&storage.batch().layer.vals) }
+	fn step_key(&mut self, storage: &Self::Storage){ self.cursor.step(&storage.batch().layer); }
+	fn seek_key(&mut self, storage: &Self::Storage, key: &K) { self.cursor.seek(&storage.batch().layer, key); }
+	fn step_val(&mut self, storage: &Self::Storage) { self.cursor.child.step(&storage.batch().layer.vals); }
+	fn seek_val(&mut self, storage: &Self::Storage, val: &V) { self.cursor.child.seek(&storage.batch().layer.vals, val); }
+	fn rewind_keys(&mut self, storage: &Self::Storage) { self.cursor.rewind(&storage.batch().layer); }
+	fn rewind_vals(&mut self, storage: &Self::Storage) { self.cursor.child.rewind(&storage.batch().layer.vals); }
+}
+
+/// A builder for file-backed layers, which writes update tuples to files as they are pushed.
+///
+/// Tuples must be pushed in order of key and then value, as batchers and mergers do.
+pub struct PersistentValBuilder<K, V, T, R> {
+	directory: SpillDirectory,
+	keys: ColumnWriter<K>,
+	offs: ColumnWriter<usize>,
+	vals: ColumnWriter<V>,
+	val_offs: ColumnWriter<usize>,
+	updates: ColumnWriter<(T, R)>,
+	// the most recent key and value, whose offsets are not yet written.
+	last: Option<(K, V)>,
+}
+
+impl<K, V, T, R> Builder<K, V, T, R, Rc<PersistentValBatch<K, V, T, R>>> for PersistentValBuilder<K, V, T, R>
+where K: Ord+Clone+HashOrdered+Abomonation+'static, V: Ord+Clone+Abomonation+'static, T: Lattice+Ord+Clone+Abomonation+'static, R: Diff {
+
+	fn new() -> Self {
+		let directory = SpillDirectory::create();
+		let mut offs = ColumnWriter::create(&directory.path, "offs");
+		let mut val_offs = ColumnWriter::create(&directory.path, "val_offs");
+		offs.push(&0);
+		val_offs.push(&0);
+		PersistentValBuilder {
+			keys: ColumnWriter::create(&directory.path, "keys"),
+			offs: offs,
+			vals: ColumnWriter::create(&directory.path, "vals"),
+			val_offs: val_offs,
+			updates: ColumnWriter::create(&directory.path, "updates"),
+			last: None,
+			directory: directory,
+		}
+	}
+	// Tuples are written out as they arrive, and so there is nothing to reserve.
+	fn with_capacity(_cap: usize) -> Self { Self::new() }
+
+	#[inline(always)]
+	fn push(&mut self, (key, val, time, diff): (K, V, T, R)) {
+		let (new_key, new_val) = match self.last {
+			Some((ref last_key, ref last_val)) => (last_key != &key, last_key != &key || last_val != &val),
+			None => (true, true),
+		};
+		if self.last.is_some() {
+			if new_val { self.val_offs.push(&self.updates.len()); }
+			if new_key { self.offs.push(&self.vals.len()); }
+		}
+		if new_key { self.keys.push(&key); }
+		if new_val {
+			self.vals.push(&val);
+			self.last = Some((key, val));
+		}
+		self.updates.push(&(time, diff));
+	}
+
+	#[inline(never)]
+	fn done(mut self, lower: &[T], upper: &[T], since: &[T]) -> Rc<PersistentValBatch<K, V, T, R>> {
+		if self.last.is_some() {
+			self.val_offs.push(&self.updates.len());
+			self.offs.push(&self.vals.len());
+		}
+		self.keys.finish();
+		self.offs.finish();
+		self.vals.finish();
+		self.val_offs.finish();
+		self.updates.finish();
+		write_description(&self.directory.path, lower, upper, since);
+
+		let mut maps = Vec::new();
+		let batch = unsafe {
+			let path = &self.directory.path;
+			OrdValBatch {
+				layer: OrderedLayer {
+					keys: read_column(path, "keys", &mut maps),
+					offs: read_column(path, "offs", &mut maps),
+					vals: OrderedLayer {
+						keys: read_column(path, "vals", &mut maps),
+						offs: read_column(path, "val_offs", &mut maps),
+						vals: OrderedLeaf { vals: read_column(path, "updates", &mut maps) },
+					},
+				},
+				desc: read_description(path, &mut maps),
+			}
+		};
+
+		Rc::new(Persisted {
+			directory: self.directory,
+			_maps: maps,
+			batch: Some(batch),
+		})
+	}
+}
+
+
+
+
+impl<K, T, R> BatchReader<K, (), T, R> for Rc<PersistentKeyBatch<K, T, R>>
+where K: Ord+Clone+HashOrdered+Abomonation+'static, T: Lattice+Ord+Clone+Abomonation+'static, R: Diff {
+	type Cursor = PersistentKeyCursor<T, R>;
+	fn cursor(&self) -> (Self::Cursor, <Self::Cursor as Cursor<K, (), T, R>>::Storage) {
+		(PersistentKeyCursor { empty: (), valid: true, cursor: self.batch().layer.cursor() }, self.clone())
+	}
+	fn len(&self) -> usize { <OrderedLayer<K, OrderedLeaf<T, R>> as Trie>::tuples(&self.batch().layer) }
+	fn description(&self) -> &Description<T> { &self.batch().desc }
+	fn size(&self) -> Size {
+		// The layers are mapped from files, rather than allocated on the heap.
+		let keys = self.batch().layer.keys.len();
+		Size { tuples: self.len(), keys: keys, vals: keys, bytes: 0 }
+	}
+}
+
+
+impl<K, T, R> Batch<K, (), T, R> for Rc<PersistentKeyBatch<K, T, R>>
+where K: Ord+Clone+HashOrdered+Abomonation+'static, T: Lattice+Ord+Clone+Abomonation+'static, R: Diff {
+	type Batcher = RadixBatcher<K, (), T, R, Self>;
+	type Builder = PersistentKeyBuilder<K, T, R>;
+	type Merger = CursorMerger<K, (), T, R, Self>;
+	fn begin_merge(&self, other: &Self) -> Self::Merger {
+		CursorMerger::new(self, other)
+	}
+}
+
+/// A cursor for navigating a file-backed batch of `key` updates.
+#[derive(Debug)]
+pub struct PersistentKeyCursor<T: Lattice+Ord+Clone, R: Diff> {
+	valid: bool,
+	empty: (),
+	cursor: OrderedCursor<OrderedLeaf<T, R>>,
+}
+
+impl<K: Ord+Clone+HashOrdered, T: Lattice+Ord+Clone, R: Diff> Cursor<K, (), T, R> for PersistentKeyCursor<T, R> {
+
+	type Storage = Rc<PersistentKeyBatch<K, T, R>>;
+
+	fn key<'a>(&self, storage: &'a Self::Storage) -> &'a K { &self.cursor.key(&storage.batch().layer) }
+	fn val<'a>(&self, _storage: &'a Self::Storage) -> &'a () { unsafe { ::std::mem::transmute(&self.empty) } }
+	fn map_times<L: FnMut(&T, R)>(&mut self, storage: &Self::Storage, mut logic: L) {
+		let times = &storage.batch().layer.vals;
+		self.cursor.child.rewind(times);
+		while self.cursor.child.valid(times) {
+			logic(&self.cursor.child.key(times).0, self.cursor.child.key(times).1);
+			self.cursor.child.step(times);
+		}
+	}
+	fn key_valid(&self, storage: &Self::Storage) -> bool { self.cursor.valid(&storage.batch().layer) }
+	fn val_valid(&self, _storage: &Self::Storage) -> bool { self.valid }
+	fn step_key(&mut self, storage: &Self::Storage){ self.cursor.step(&storage.batch().layer); self.valid = true; }
+	fn seek_key(&mut self, storage: &Self::Storage, key: &K) { self.cursor.seek(&storage.batch().layer, key); self.valid = true; }
+	fn step_val(&mut self, _storage: &Self::Storage) { self.valid = false; }
+	fn seek_val(&mut self, _storage: &Self::Storage, _val: &()) { }
+	fn rewind_keys(&mut self, storage: &Self::Storage) { self.cursor.rewind(&storage.batch().layer); self.valid = true; }
+	fn rewind_vals(&mut self, _storage: &Self::Storage) { self.valid = true; }
+}
+
+/// A builder for file-backed layers, which writes update tuples to files as they are pushed.
+///
+/// Tuples must be pushed in order of key, as batchers and mergers do.
+pub struct PersistentKeyBuilder<K, T, R> {
+	directory: SpillDirectory,
+	keys: ColumnWriter<K>,
+	offs: ColumnWriter<usize>,
+	updates: ColumnWriter<(T, R)>,
+	// the most recent key, whose offset is not yet written.
+	last: Option<K>,
+}
+
+impl<K, T, R> Builder<K, (), T, R, Rc<PersistentKeyBatch<K, T, R>>> for PersistentKeyBuilder<K, T, R>
+where K: Ord+Clone+HashOrdered+Abomonation+'static, T: Lattice+Ord+Clone+Abomonation+'static, R: Diff {
+
+	fn new() -> Self {
+		let directory = SpillDirectory::create();
+		let mut offs = ColumnWriter::create(&directory.path, "offs");
+		offs.push(&0);
+		PersistentKeyBuilder {
+			keys: ColumnWriter::create(&directory.path, "keys"),
+			offs: offs,
+			updates: ColumnWriter::create(&directory.path, "updates"),
+			last: None,
+			directory: directory,
+		}
+	}
+	// Tuples are written out as they arrive, and so there is nothing to reserve.
+	fn with_capacity(_cap: usize) -> Self { Self::new() }
+
+	#[inline(always)]
+	fn push(&mut self, (key, _, time, diff): (K, (), T, R)) {
+		let new_key = match self.last {
+			Some(ref last_key) => last_key != &key,
+			None => true,
+		};
+		if new_key {
+			if self.last.is_some() { self.offs.push(&self.updates.len()); }
+			self.keys.push(&key);
+			self.last = Some(key);
+		}
+		self.updates.push(&(time, diff));
+	}
+
+	#[inline(never)]
+	fn done(mut self, lower: &[T], upper: &[T], since: &[T]) -> Rc<PersistentKeyBatch<K, T, R>> {
+		if self.last.is_some() {
+			self.offs.push(&self.updates.len());
+		}
+		self.keys.finish();
+		self.offs.finish();
+		self.updates.finish();
+		write_description(&self.directory.path, lower, upper, since);
+
+		let mut maps = Vec::new();
+		let batch = unsafe {
+			let path = &self.directory.path;
+			OrdKeyBatch {
+				layer: OrderedLayer {
+					keys: read_column(path, "keys", &mut maps),
+					offs: read_column(path, "offs", &mut maps),
+					vals: OrderedLeaf { vals: read_column(path, "updates", &mut maps) },
+				},
+				desc: read_description(path, &mut maps),
+			}
+		};
+
+		Rc::new(Persisted {
+			directory: self.directory,
+			_maps: maps,
+			batch: Some(batch),
+		})
+	}
+}
+
+
+
+
+impl<K, V, T, R> BatchReader<K, V, T, R> for Rc<PersistentHashValBatch<K, V, T, R>>
+where K: Clone+Default+HashOrdered+Abomonation+'static, V: Clone+Ord+Abomonation+'static, T: Lattice+Ord+Clone+Default+Abomonation+'static, R: Diff {
+	type Cursor = PersistentHashValCursor<V, T, R>;
+	fn cursor(&self) -> (Self::Cursor, <Self::Cursor as Cursor<K, V, T, R>>::Storage) {
+		(PersistentHashValCursor { cursor: self.batch().layer.cursor() }, self.clone())
+	}
+	fn len(&self) -> usize { <HashedLayer<K, OrderedLayer<V, OrderedLeaf<T, R>>> as Trie>::tuples(&self.batch().layer) }
+	fn description(&self) -> &Description<T> { &self.batch().desc }
+	fn size(&self) -> Size {
+		// The layers are mapped from files, rather than allocated on the heap.
+		let layer = &self.batch().layer;
+		Size { tuples: self.len(), keys: layer.occupied(), vals: layer.vals.keys.len(), bytes: 0 }
+	}
+}
+
+
+impl<K, V, T, R> Batch<K, V, T, R> for Rc<PersistentHashValBatch<K, V, T, R>>
+where K: Clone+Default+HashOrdered+Abomonation+'static, V: Clone+Ord+Abomonation+'static, T: Lattice+Ord+Clone+Default+Abomonation+'static, R: Diff {
+	type Batcher = RadixBatcher<K, V, T, R, Self>;
+	type Builder = PersistentHashValBuilder<K, V, T, R>;
+	type Merger = CursorMerger<K, V, T, R, Self>;
+	fn begin_merge(&self, other: &Self) -> Self::Merger {
+		CursorMerger::new(self, other)
+	}
+}
+
+/// A cursor for navigating a file-backed hash-map batch of `(key, val)` updates.
+#[derive(Debug)]
+pub struct PersistentHashValCursor<V: Ord+Clone, T: Lattice+Ord+Clone, R: Diff> {
+	cursor: HashedCursor<OrderedLayer<V, OrderedLeaf<T, R>>>,
+}
+
+impl<K, V, T, R> Cursor<K, V, T, R> for PersistentHashValCursor<V, T, R>
+where K: Clone+HashOrdered, V: Ord+Clone, T: Lattice+Ord+Clone, R: Diff {
+
+	type Storage = Rc<PersistentHashValBatch<K, V, T, R>>;
+
+	fn key<'a>(&self, storage: &'a Self::Storage) -> &'a K { &self.cursor.key(&storage.batch().layer) }
+	fn val<'a>(&self, storage: &'a Self::Storage) -> &'a V { &self.cursor.child.key(&storage.batch().layer.vals) }
+	fn map_times<L: FnMut(&T, R)>(&mut self, storage: &Self::Storage, mut logic: L) {
+		let times = &storage.batch().layer.vals.vals;
+		self.cursor.child.child.rewind(times);
+		while self.cursor.child.child.valid(times) {
+			logic(&self.cursor.child.child.key(times).0, self.cursor.child.child.key(times).1);
+			self.cursor.child.child.step(times);
+		}
+	}
+	fn key_valid(&self, storage: &Self::Storage) -> bool { self.cursor.valid(&storage.batch().layer) }
+	fn val_valid(&self, storage: &Self::Storage) -> bool { self.cursor.child.valid(&storage.batch().layer.vals) }
+	fn step_key(&mut self, storage: &Self::Storage){ self.cursor.step(&storage.batch().layer); }
+	fn seek_key(&mut self, storage: &Self::Storage, key: &K) { self.cursor.seek(&storage.batch().layer, key); }
+	fn step_val(&mut self, storage: &Self::Storage) { self.cursor.child.step(&storage.batch().layer.vals); }
+	fn seek_val(&mut self, storage: &Self::Storage, val: &V) { self.cursor.child.seek(&storage.batch().layer.vals, val); }
+	fn rewind_keys(&mut self, storage: &Self::Storage) { self.cursor.rewind(&storage.batch().layer); }
+	fn rewind_vals(&mut self, storage: &Self::Storage) { self.cursor.child.rewind(&storage.batch().layer.vals); }
+}
+
+/// A builder for file-backed hash-map layers, which writes update tuples to files as they are pushed.
+///
+/// Tuples must be pushed in order of key and then value, as batchers and mergers do. The keys are laid
+/// out as a hash map once all of them are known, and are held in memory until then.
+pub struct PersistentHashValBuilder<K: HashOrdered, V, T, R> {
+	directory: SpillDirectory,
+	keys: Vec<Entry<K>>,
+	vals: ColumnWriter<V>,
+	val_offs: ColumnWriter<usize>,
+	updates: ColumnWriter<(T, R)>,
+	// the most recent key and value, whose offsets are not yet written, and the offset of the key's values.
+	last: Option<(K, V)>,
+	lower: usize,
+}
+
+impl<K, V, T, R> Builder<K, V, T, R, Rc<PersistentHashValBatch<K, V, T, R>>> for PersistentHashValBuilder<K, V, T, R>
+where K: Clone+Default+HashOrdered+Abomonation+'static, V: Clone+Ord+Abomonation+'static, T: Lattice+Ord+Clone+Default+Abomonation+'static, R: Diff {
+
+	fn new() -> Self {
+		let directory = SpillDirectory::create();
+		let mut val_offs = ColumnWriter::create(&directory.path, "val_offs");
+		val_offs.push(&0);
+		PersistentHashValBuilder {
+			keys: Vec::new(),
+			vals: ColumnWriter::create(&directory.path, "vals"),
+			val_offs: val_offs,
+			updates: ColumnWriter::create(&directory.path, "updates"),
+			last: None,
+			lower: 0,
+			directory: directory,
+		}
+	}
+	// Tuples are written out as they arrive, and so there is nothing to reserve.
+	fn with_capacity(_cap: usize) -> Self { Self::new() }
+
+	#[inline(always)]
+	fn push(&mut self, (key, val, time, diff): (K, V, T, R)) {
+		let (new_key, new_val) = match self.last {
+			Some((ref last_key, ref last_val)) => (last_key != &key, last_key != &key || last_val != &val),
+			None => (true, true),
+		};
+		if new_val && self.last.is_some() {
+			self.val_offs.push(&self.updates.len());
+		}
+		if new_key {
+			if let Some((last_key, _)) = self.last.take() {
+				self.keys.push(Entry::new(last_key, self.lower, self.vals.len()));
+			}
+			self.lower = self.vals.len();
+		}
+		if new_val {
+			self.vals.push(&val);
+			self.last = Some((key, val));
+		}
+		self.updates.push(&(time, diff));
+	}
+
+	#[inline(never)]
+	fn done(mut self, lower: &[T], upper: &[T], since: &[T]) -> Rc<PersistentHashValBatch<K, V, T, R>> {
+		if let Some((last_key, _)) = self.last.take() {
+			self.val_offs.push(&self.updates.len());
+			self.keys.push(Entry::new(last_key, self.lower, self.vals.len()));
+		}
+		let mut entries = Vec::new();
+		lay_out(&mut self.keys, &mut entries);
+		let mut keys = ColumnWriter::create(&self.directory.path, "keys");
+		for entry in entries.iter() {
+			keys.push(entry);
+		}
+		keys.finish();
+		self.vals.finish();
+		self.val_offs.finish();
+		self.updates.finish();
+		write_description(&self.directory.path, lower, upper, since);
+
+		let mut maps = Vec::new();
+		let batch = unsafe {
+			let path = &self.directory.path;
+			HashValBatch {
+				layer: HashedLayer {
+					keys: read_column(path, "keys", &mut maps),
+					vals: OrderedLayer {
+						keys: read_column(path, "vals", &mut maps),
+						offs: read_column(path, "val_offs", &mut maps),
+						vals: OrderedLeaf { vals: read_column(path, "updates", &mut maps) },
+					},
+				},
+				desc: read_description(path, &mut maps),
+			}
+		};
+
+		Rc::new(Persisted {
+			directory: self.directory,
+			_maps: maps,
+			batch: Some(batch),
+		})
+	}
+}
+
+
+
+
+impl<K, T, R> BatchReader<K, (), T, R> for Rc<PersistentHashKeyBatch<K, T, R>>
+where K: Clone+Default+HashOrdered+Abomonation+'static, T: Lattice+Ord+Clone+Default+Abomonation+'static, R: Diff {
+	type Cursor = PersistentHashKeyCursor<T, R>;
+	fn cursor(&self) -> (Self::Cursor, <Self::Cursor as Cursor<K, (), T, R>>::Storage) {
+		(PersistentHashKeyCursor { empty: (), valid: true, cursor: self.batch().layer.cursor() }, self.clone())
+	}
+	fn len(&self) -> usize { <HashedLayer<K, OrderedLeaf<T, R>> as Trie>::tuples(&self.batch().layer) }
+	fn description(&self) -> &Description<T> { &self.batch().desc }
+	fn size(&self) -> Size {
+		// The layers are mapped from files, rather than allocated on the heap.
+		let keys = self.batch().layer.occupied();
+		Size { tuples: self.len(), keys: keys, vals: keys, bytes: 0 }
+	}
+}
+
+
+impl<K, T, R> Batch<K, (), T, R> for Rc<PersistentHashKeyBatch<K, T, R>>
+where K: Clone+Default+HashOrdered+Abomonation+'static, T: Lattice+Ord+Clone+Default+Abomonation+'static, R: Diff {
+	type Batcher = RadixBatcher<K, (), T, R, Self>;
+	type Builder = PersistentHashKeyBuilder<K, T, R>;
+	type Merger = CursorMerger<K, (), T, R, Self>;
+	fn begin_merge(&self, other: &Self) -> Self::Merger {
+		CursorMerger::new(self, other)
+	}
+}
+
+/// A cursor for navigating a file-backed hash-map batch of `key` updates.
+#[derive(Debug)]
+pub struct PersistentHashKeyCursor<T: Lattice+Ord+Clone, R: Diff> {
+	valid: bool,
+	empty: (),
+	cursor: HashedCursor<OrderedLeaf<T, R>>,
+}
+
+impl<K: Clone+HashOrdered, T: Lattice+Ord+Clone, R: Diff> Cursor<K, (), T, R> for PersistentHashKeyCursor<T, R> {
+
+	type Storage = Rc<PersistentHashKeyBatch<K, T, R>>;
+
+	fn key<'a>(&self, storage: &'a Self::Storage) -> &'a K { &self.cursor.key(&storage.batch().layer) }
+	fn val<'a>(&self, _storage: &'a Self::Storage) -> &'a () { unsafe { ::std::mem::transmute(&self.empty) } }
+	fn map_times<L: FnMut(&T, R)>(&mut self, storage: &Self::Storage, mut logic: L) {
+		let times = &storage.batch().layer.vals;
+		self.cursor.child.rewind(times);
+		while self.cursor.child.valid(times) {
+			logic(&self.cursor.child.key(times).0, self.cursor.child.key(times).1);
+			self.cursor.child.step(times);
+		}
+	}
+	fn key_valid(&self, storage: &Self::Storage) -> bool { self.cursor.valid(&storage.batch().layer) }
+	fn val_valid(&self, _storage: &Self::Storage) -> bool { self.valid }
+	fn step_key(&mut self, storage: &Self::Storage){ self.cursor.step(&storage.batch().layer); self.valid = true; }
+	fn seek_key(&mut self, storage: &Self::Storage, key: &K) { self.cursor.seek(&storage.batch().layer, key); self.valid = true; }
+	fn step_val(&mut self, _storage: &Self::Storage) { self.valid = false; }
+	fn seek_val(&mut self, _storage: &Self::Storage, _val: &()) { }
+	fn rewind_keys(&mut self, storage: &Self::Storage) { self.cursor.rewind(&storage.batch().layer); self.valid = true; }
+	fn rewind_vals(&mut self, _storage: &Self::Storage) { self.valid = true; }
+}
+
+/// A builder for file-backed hash-map layers, which writes update tuples to files as they are pushed.
+///
+/// Tuples must be pushed in order of key, as batchers and mergers do. The keys are laid out as a hash map
+/// once all of them are known, and are held in memory until then.
+pub struct PersistentHashKeyBuilder<K: HashOrdered, T, R> {
+	directory: SpillDirectory,
+	keys: Vec<Entry<K>>,
+	updates: ColumnWriter<(T, R)>,
+	// the most recent key, whose upper offset is not yet known, and the offset of its updates.
+	last: Option<K>,
+	lower: usize,
+}
+
+impl<K, T, R> Builder<K, (), T, R, Rc<PersistentHashKeyBatch<K, T, R>>> for PersistentHashKeyBuilder<K, T, R>
+where K: Clone+Default+HashOrdered+Abomonation+'static, T: Lattice+Ord+Clone+Default+Abomonation+'static, R: Diff {
+
+	fn new() -> Self {
+		let directory = SpillDirectory::create();
+		PersistentHashKeyBuilder {
+			keys: Vec::new(),
+			updates: ColumnWriter::create(&directory.path, "updates"),
+			last: None,
+			lower: 0,
+			directory: directory,
+		}
+	}
+	// Tuples are written out as they arrive, and so there is nothing to reserve.
+	fn with_capacity(_cap: usize) -> Self { Self::new() }
+
+	#[inline(always)]
+	fn push(&mut self, (key, _, time, diff): (K, (), T, R)) {
+		let new_key = match self.last {
+			Some(ref last_key) => last_key != &key,
+			None => true,
+		};
+		if new_key {
+			if let Some(last_key) = self.last.take() {
+				self.keys.push(Entry::new(last_key, self.lower, self.updates.len()));
+			}
+			self.lower = self.updates.len();
+			self.last = Some(key);
+		}
+		self.updates.push(&(time, diff));
+	}
+
+	#[inline(never)]
+	fn done(mut self, lower: &[T], upper: &[T], since: &[T]) -> Rc<PersistentHashKeyBatch<K, T, R>> {
+		if let Some(last_key) = self.last.take() {
+			self.keys.push(Entry::new(last_key, self.lower, self.updates.len()));
+		}
+		let mut entries = Vec::new();
+		lay_out(&mut self.keys, &mut entries);
+		let mut keys = ColumnWriter::create(&self.directory.path, "keys");
+		for entry in entries.iter() {
+			keys.push(entry);
+		}
+		keys.finish();
+		self.updates.finish();
+		write_description(&self.directory.path, lower, upper, since);
+
+		let mut maps = Vec::new();
+		let batch = unsafe {
+			let path = &self.directory.path;
+			HashKeyBatch {
+				layer: HashedLayer {
+					keys: read_column(path, "keys", &mut maps),
+					vals: OrderedLeaf { vals: read_column(path, "updates", &mut maps) },
+				},
+				desc: read_description(path, &mut maps),
+			}
+		};
+
+		Rc::new(Persisted {
+			directory: self.directory,
+			_maps: maps,
+			batch: Some(batch),
+		})
+	}
+}
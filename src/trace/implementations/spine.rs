@@ -1,31 +1,47 @@
 //! An append-only collection of update batches.
 //!
-//! The `Spine` is a general-purpose trace implementation based on collection and merging 
-//! immutable batches of updates. It is generic with respect to the batch type, and can be 
+//! The `Spine` is a general-purpose trace implementation based on collection and merging
+//! immutable batches of updates. It is generic with respect to the batch type, and can be
 //! instantiated for any implementor of `trace::Batch`.
+//!
+//! Merges are performed progressively, using the batch type's own `Merger`. Each batch provides an amount
+//! of "fuel", some multiple of its length, which is spent advancing each merge in progress as the batch joins
+//! the merging batches.
+//! A merge in progress retains its two input batches, which are presented to readers until the
+//! merge completes. Additional fuel can be supplied through `Trace::exert`, for example when a
+//! worker would otherwise be idle.
+
+use std::fmt;
+use std::time::{Duration, Instant};
 
 use ::Diff;
 use lattice::Lattice;
-use trace::{Batch, BatchReader, Trace, TraceReader};
+use trace::{Batch, BatchReader, Merger, Trace, TraceReader};
 use trace::cursor::cursor_list::CursorList;
 use trace::cursor::Cursor;
+#[cfg(feature = "logging")]
+use logging::{DifferentialEvent, MergeEvent, DropEvent};
+
+/// The default multiple of an inserted batch's length spent as fuel on each merge in progress.
+pub const DEFAULT_EFFORT: usize = 4;
 
 /// An append-only collection of update tuples.
 ///
 /// A spine maintains a small number of immutable collections of update tuples, merging the collections when
 /// two have similar sizes. In this way, it allows the addition of more tuples, which may then be merged with
-/// other immutable collections. 
+/// other immutable collections.
 #[derive(Debug)]
 pub struct Spine<K, V, T: Lattice+Ord, R: Diff, B: Batch<K, V, T, R>> {
 	phantom: ::std::marker::PhantomData<(K, V, R)>,
 	advance_frontier: Vec<T>,	// Times after which the trace must accumulate correctly.
 	through_frontier: Vec<T>,	// Times after which the trace must be able to subset its inputs.
-	merging: Vec<B>,			// Several possibly shared collections of updates.
+	merging: Vec<MergeState<K, V, T, R, B>>,	// Several possibly shared collections of updates, some merging.
 	pending: Vec<B>,			// Batches at times in advance of `frontier`.
+	effort: usize,				// Multiple of inserted batch lengths spent as fuel on each merge.
 }
 
-impl<K, V, T, R, B> TraceReader<K, V, T, R> for Spine<K, V, T, R, B> 
-where 
+impl<K, V, T, R, B> TraceReader<K, V, T, R> for Spine<K, V, T, R, B>
+where
 	K: Ord+Clone,			// Clone is required by `batch::advance_*` (in-place could remove).
 	V: Ord+Clone,			// Clone is required by `batch::advance_*` (in-place could remove).
 	T: Lattice+Ord+Clone,	// Clone is required by `advance_by` and `batch::advance_*`.
@@ -47,9 +63,15 @@ where
 			let mut cursors = Vec::new();
 			let mut storage = Vec::new();
 
-			for (cursor, store) in self.merging.iter().filter(|b| b.len() > 0).map(|b| b.cursor()) {
-				cursors.push(cursor);
-				storage.push(store);
+			// merges in progress present both of their inputs.
+			for state in &self.merging {
+				state.map_batches(|batch| {
+					if batch.len() > 0 {
+						let (cursor, store) = batch.cursor();
+						cursors.push(cursor);
+						storage.push(store);
+					}
+				});
 			}
 
 			for batch in &self.pending {
@@ -61,7 +83,7 @@ where
 					// return None;
 				}
 
-				// include pending batches 
+				// include pending batches
 				if include_upper {
 					let (cursor, store) = batch.cursor();
 					cursors.push(cursor);
@@ -89,8 +111,8 @@ where
 	fn distinguish_frontier(&mut self) -> &[T] { &self.through_frontier[..] }
 
 	fn map_batches<F: FnMut(&Self::Batch)>(&mut self, mut f: F) {
-		for batch in self.merging.iter() {
-			f(batch);
+		for state in self.merging.iter() {
+			state.map_batches(&mut f);
 		}
 		for batch in self.pending.iter() {
 			f(batch);
@@ -100,8 +122,8 @@ where
 
 // A trace implementation for any key type that can be borrowed from or converted into `Key`.
 // TODO: Almost all this implementation seems to be generic with respect to the trace and batch types.
-impl<K, V, T, R, B> Trace<K, V, T, R> for Spine<K, V, T, R, B> 
-where 
+impl<K, V, T, R, B> Trace<K, V, T, R> for Spine<K, V, T, R, B>
+where
 	K: Ord+Clone,			// Clone is required by `batch::advance_*` (in-place could remove).
 	V: Ord+Clone,			// Clone is required by `batch::advance_*` (in-place could remove).
 	T: Lattice+Ord+Clone,	// Clone is required by `advance_by` and `batch::advance_*`.
@@ -110,15 +132,16 @@ where
 {
 
 	fn new() -> Self {
-		Spine { 
+		Spine {
 			phantom: ::std::marker::PhantomData,
 			advance_frontier: vec![<T as Lattice>::minimum()],
 			through_frontier: vec![<T as Lattice>::minimum()],
 			merging: Vec::new(),
 			pending: Vec::new(),
+			effort: DEFAULT_EFFORT,
 		}
 	}
-	// Note: this does not perform progressive merging; that code is around somewhere though.
+
 	fn insert(&mut self, batch: Self::Batch) {
 
 		// we can ignore degenerate batches (TODO: learn where they come from; suppress them?)
@@ -131,56 +154,212 @@ where
 			assert!(batch.len() == 0);
 		}
 	}
+
+	fn exert(&mut self, fuel: &mut isize) {
+		self.work(fuel);
+	}
 }
 
-impl<K, V, T, R, B> Spine<K, V, T, R, B> 
-where 
+impl<K, V, T, R, B> Spine<K, V, T, R, B>
+where
 	K: Ord+Clone,			// Clone is required by `advance_mut`.
 	V: Ord+Clone,			// Clone is required by `advance_mut`.
 	T: Lattice+Ord+Clone,	// Clone is required by `advance_mut`.
 	R: Diff,
 	B: Batch<K, V, T, R>,
 {
+	/// Sets the multiple of each inserted batch's length spent as fuel on each merge in progress.
+	///
+	/// Larger values complete merges sooner, at the expense of more work per insertion. Merges that have
+	/// not completed continue to present their inputs to readers, and can be advanced with `exert`.
+	pub fn set_effort(&mut self, effort: usize) {
+		self.effort = effort;
+	}
+
 	// Migrate data from `self.pending` into `self.merging`.
 	#[inline(never)]
 	fn consider_merges(&mut self) {
 
-		// TODO: We could consider merging in batches here, rather than in sequence. 
+		// TODO: We could consider merging in batches here, rather than in sequence.
 		//       Little is currently known about whether this is important ...
-		while self.pending.len() > 0 && 
-		      self.through_frontier.iter().all(|t1| self.pending[0].upper().iter().any(|t2| t2.less_equal(t1))) 
+		while self.pending.len() > 0 &&
+		      self.through_frontier.iter().all(|t1| self.pending[0].upper().iter().any(|t2| t2.less_equal(t1)))
 		{
 			// this could be a VecDeque, if we ever notice this.
 			let batch = self.pending.remove(0);
 
-			// while last two elements exist, both less than batch.len()
-			while self.merging.len() >= 2 && self.merging[self.merging.len() - 2].len() < batch.len() {
-				let batch1 = self.merging.pop().unwrap();
-				let batch2 = self.merging.pop().unwrap();
-				let result = batch2.merge(&batch1);
-				self.merging.push(result);
+			// each batch supplies fuel in proportion to its size; even empty batches supply some,
+			// so that merges complete once data stop arriving.
+			let fuel = (self.effort * (batch.len() + 1)) as isize;
+
+			self.merging.push(MergeState::Complete(batch));
+			self.start_merges();
+			self.work_each(fuel);
+		}
+	}
+
+	// Spends `fuel` on each merge in progress, retiring those that complete.
+	//
+	// A merge whose inputs hold `n` updates must complete before about `n` further updates arrive, or batches
+	// accumulate behind it. Each merge receives its own fuel, rather than a share of it, so that merges at all
+	// levels keep pace with insertions; the work per inserted update is then logarithmic in the trace's size.
+	fn work_each(&mut self, fuel: isize) {
+
+		for state in self.merging.iter_mut() {
+			let mut fuel = fuel;
+			state.work(&mut fuel);
+		}
+
+		// retire completed merges, which may allow new merges to start.
+		let states = ::std::mem::replace(&mut self.merging, Vec::new());
+		self.merging = states.into_iter().map(|state| state.settle()).collect();
+		self.start_merges();
+	}
+
+	// Spends fuel on merges in progress, retiring those that complete.
+	fn work(&mut self, fuel: &mut isize) {
+
+		loop {
+
+			// apply fuel to the most recent (and smallest) merges first.
+			for state in self.merging.iter_mut().rev() {
+				if *fuel <= 0 { break; }
+				state.work(fuel);
 			}
 
-			self.merging.push(batch);
+			// retire completed merges, which may allow new merges to start.
+			let states = ::std::mem::replace(&mut self.merging, Vec::new());
+			self.merging = states.into_iter().map(|state| state.settle()).collect();
+			self.start_merges();
+
+			if *fuel <= 0 || self.merging.iter().all(|state| state.is_complete()) {
+				break;
+			}
+		}
+	}
 
-			// `len` exists only to narrow while condition.
-			let mut len = self.merging.len();
-			while len >= 2 && self.merging[len - 2].len() < 2 * self.merging[len - 1].len() {
+	// Starts merges between adjacent complete batches of similar sizes.
+	//
+	// Merges in progress are not merged further until they complete, at which point retiring them calls this
+	// method again. This keeps each merge's work bounded by its own inputs.
+	fn start_merges(&mut self) {
+		let mut index = self.merging.len();
+		while index >= 2 {
+			let mergeable = {
+				let older = &self.merging[index - 2];
+				let newer = &self.merging[index - 1];
+				older.is_complete() && newer.is_complete() && older.len() < 2 * newer.len()
+			};
 
-				let mut batch1 = self.merging.pop().unwrap();
-				let mut batch2 = self.merging.pop().unwrap();
+			if mergeable {
+				let mut batch1 = self.merging.remove(index - 1).unwrap_complete();
+				let mut batch2 = self.merging.remove(index - 2).unwrap_complete();
 
 				// advance inputs, rather than outputs.
-				if self.merging.len() == 0 {
+				if index == 2 {
 					batch1.advance_mut(&self.advance_frontier[..]);
 					batch2.advance_mut(&self.advance_frontier[..]);
 				}
 
-				let result = batch2.merge(&batch1);
+				let merger = batch2.begin_merge(&batch1);
+				#[cfg(feature = "logging")]
+				::logging::log(DifferentialEvent::Merge(MergeEvent { length1: batch2.len(), length2: batch1.len(), complete: None, elapsed: None }));
+				self.merging.insert(index - 2, MergeState::Merging(batch2, batch1, merger, Duration::new(0, 0)));
+			}
+
+			index -= 1;
+		}
+	}
+}
 
-				self.merging.push(result);
-				len = self.merging.len();
+impl<K, V, T: Lattice+Ord, R: Diff, B: Batch<K, V, T, R>> Drop for Spine<K, V, T, R, B> {
+	fn drop(&mut self) {
+		#[cfg(feature = "logging")]
+		{
+			let mut length = self.pending.iter().map(|batch| batch.len()).sum::<usize>();
+			for state in self.merging.iter() {
+				length += match *state {
+					MergeState::Complete(ref batch) => batch.len(),
+					MergeState::Merging(ref batch1, ref batch2, _, _) => batch1.len() + batch2.len(),
+				};
 			}
+			::logging::log(DifferentialEvent::Drop(DropEvent { length: length }));
+		}
+	}
+}
+
+/// A batch, or a pair of adjacent batches being merged.
+enum MergeState<K, V, T: Lattice+Ord, R: Diff, B: Batch<K, V, T, R>> {
+	/// A batch that is not being merged.
+	Complete(B),
+	/// An older and a newer batch, the progress of their merge, and the time spent on it so far.
+	Merging(B, B, B::Merger, Duration),
+}
+
+impl<K, V, T, R, B> MergeState<K, V, T, R, B>
+where K: Ord+Clone, V: Ord+Clone, T: Lattice+Ord+Clone, R: Diff, B: Batch<K, V, T, R> {
+
+	fn len(&self) -> usize {
+		match *self {
+			MergeState::Complete(ref batch) => batch.len(),
+			MergeState::Merging(ref batch1, ref batch2, _, _) => batch1.len() + batch2.len(),
+		}
+	}
+
+	fn is_complete(&self) -> bool {
+		match *self {
+			MergeState::Complete(_) => true,
+			MergeState::Merging(_, _, _, _) => false,
+		}
+	}
+
+	// Applies `logic` to each batch, in order of time.
+	fn map_batches<F: FnMut(&B)>(&self, mut logic: F) {
+		match *self {
+			MergeState::Complete(ref batch) => logic(batch),
+			MergeState::Merging(ref batch1, ref batch2, _, _) => { logic(batch1); logic(batch2); },
+		}
+	}
+
+	// Spends fuel on the merge, if one is in progress.
+	fn work(&mut self, fuel: &mut isize) {
+		if let MergeState::Merging(ref batch1, ref batch2, ref mut merger, ref mut elapsed) = *self {
+			let start = Instant::now();
+			merger.work(batch1, batch2, fuel);
+			*elapsed += start.elapsed();
+		}
+	}
+
+	// Replaces a finished merge with its result.
+	fn settle(self) -> Self {
+		match self {
+			MergeState::Merging(batch1, batch2, merger, elapsed) => {
+				if merger.is_done() {
+					let result = merger.done();
+					#[cfg(feature = "logging")]
+					::logging::log(DifferentialEvent::Merge(MergeEvent { length1: batch1.len(), length2: batch2.len(), complete: Some(result.len()), elapsed: Some(elapsed) }));
+					MergeState::Complete(result)
+				}
+				else { MergeState::Merging(batch1, batch2, merger, elapsed) }
+			},
+			complete => complete,
+		}
+	}
+
+	// Extracts the batch of a state that is not merging.
+	fn unwrap_complete(self) -> B {
+		match self {
+			MergeState::Complete(batch) => batch,
+			MergeState::Merging(_, _, _, _) => panic!("unwrap_complete called on a merge in progress"),
+		}
+	}
+}
+
+impl<K, V, T: Lattice+Ord, R: Diff, B: Batch<K, V, T, R>+fmt::Debug> fmt::Debug for MergeState<K, V, T, R, B> {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			MergeState::Complete(ref batch) => write!(f, "Complete({:?})", batch),
+			MergeState::Merging(ref batch1, ref batch2, _, _) => write!(f, "Merging({:?}, {:?})", batch1, batch2),
 		}
 	}
 }
@@ -2,10 +2,12 @@
 
 use std::default::Default;
 
+use abomonation::Abomonation;
+
 use timely_sort::Unsigned;
 
 use ::hashable::{Hashable, HashOrdered};
-use super::{Trie, Cursor, Builder, MergeBuilder, TupleBuilder};
+use super::{Trie, Cursor, Builder, MergeBuilder, TupleBuilder, OpenKey};
 
 const MINIMUM_SHIFT : usize = 4;
 const BLOAT_FACTOR : f64 = 1.1;
@@ -32,7 +34,7 @@ const BLOAT_FACTOR : f64 = 1.1;
 ///
 /// We might do something like "if X or fewer elements, just use an ordered list".
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct HashedLayer<K: HashOrdered, L> {
 	/// Keys and offsets for the keys.
 	pub keys: Vec<Entry<K>>,	// track upper and lower bounds, because trickery is hard.
@@ -41,6 +43,8 @@ pub struct HashedLayer<K: HashOrdered, L> {
 }
 
 impl<K: HashOrdered, L> HashedLayer<K, L> {
+	/// The number of occupied entries, as distinct from the number of slots reported by `keys()`.
+	pub fn occupied(&self) -> usize { self.keys.iter().filter(|entry| entry.is_some()).count() }
 	fn _entry_valid(&self, index: usize) -> bool { self.keys[index].is_some() }
 	fn lower(&self, index: usize) -> usize { self.keys[index].get_lower() }
 	fn upper(&self, index: usize) -> usize { self.keys[index].get_upper() }
@@ -54,6 +58,17 @@ impl<K: Clone+HashOrdered+Default, L: Trie> Trie for HashedLayer<K, L> {
 
 	fn keys(&self) -> usize { self.keys.len() }
 	fn tuples(&self) -> usize { self.vals.tuples() }
+	fn tuples_in(&self, lower: usize, upper: usize) -> usize {
+		let first = (lower .. upper).find(|&index| self.keys[index].is_some());
+		let last = (lower .. upper).rev().find(|&index| self.keys[index].is_some());
+		match (first, last) {
+			(Some(first), Some(last)) => self.vals.tuples_in(self.lower(first), self.upper(last)),
+			_ => 0,
+		}
+	}
+	fn heap_size(&self) -> usize {
+		self.keys.capacity() * ::std::mem::size_of::<Entry<K>>() + self.vals.heap_size()
+	}
 	fn cursor_from(&self, lower: usize, upper: usize) -> Self::Cursor {
 
 		if lower < upper {
@@ -89,6 +104,22 @@ impl<K: Clone+HashOrdered+Default, L: Trie> Trie for HashedLayer<K, L> {
 	}
 }
 
+impl<K: HashOrdered+Abomonation, L: Abomonation> Abomonation for HashedLayer<K, L> {
+	#[inline] unsafe fn entomb(&self, writer: &mut Vec<u8>) {
+		self.keys.entomb(writer);
+		self.vals.entomb(writer);
+	}
+	#[inline] unsafe fn embalm(&mut self) {
+		self.keys.embalm();
+		self.vals.embalm();
+	}
+	#[inline] unsafe fn exhume<'a,'b>(&'a mut self, mut bytes: &'b mut [u8]) -> Option<&'b mut [u8]> {
+		let temp = bytes; bytes = if let Some(bytes) = self.keys.exhume(temp) { bytes } else { return None };
+		let temp = bytes; bytes = if let Some(bytes) = self.vals.exhume(temp) { bytes } else { return None };
+		Some(bytes)
+	}
+}
+
 /// An entry in hash tables.
 #[derive(Debug, Clone)]
 pub struct Entry<K: HashOrdered> {
@@ -99,7 +130,8 @@ pub struct Entry<K: HashOrdered> {
 }
 
 impl<K: HashOrdered> Entry<K> {
-	fn new(key: K, lower: usize, upper: usize) -> Self {
+	/// An entry for `key`, whose values are found at `lower .. upper` in the layer below.
+	pub fn new(key: K, lower: usize, upper: usize) -> Self {
 		Entry {
 			key: key, 
 			lower1: lower as u32,
@@ -115,6 +147,66 @@ impl<K: HashOrdered> Entry<K> {
 	fn set_upper(&mut self, x: usize) { self.upper1 = x as u32; }
 }
 
+impl<K: HashOrdered+Abomonation> Abomonation for Entry<K> {
+	#[inline] unsafe fn entomb(&self, writer: &mut Vec<u8>) {
+		self.key.entomb(writer);
+	}
+	#[inline] unsafe fn embalm(&mut self) {
+		self.key.embalm();
+	}
+	#[inline] unsafe fn exhume<'a,'b>(&'a mut self, mut bytes: &'b mut [u8]) -> Option<&'b mut [u8]> {
+		let temp = bytes; bytes = if let Some(bytes) = self.key.exhume(temp) { bytes } else { return None };
+		Some(bytes)
+	}
+}
+
+/// Moves the entries of `temp` to the end of `keys`, laid out as a robin hood hash map.
+///
+/// The entries of `temp` must be valid, and sorted by key.
+pub fn lay_out<K: HashOrdered+Default>(temp: &mut Vec<Entry<K>>, keys: &mut Vec<Entry<K>>) {
+
+	// having densely packed everything, we now want to extend the allocation and rewrite the contents 
+	// so that their spacing is in line with how robin hood hashing works.
+	let lower = keys.len();
+	if temp.len() < (1 << MINIMUM_SHIFT) {
+		keys.extend(temp.drain(..));
+	}
+	else {
+		let target = (BLOAT_FACTOR * (temp.len() as f64)) as u64;
+		let mut shift = MINIMUM_SHIFT;
+		while  (1 << shift) < target {
+			shift += 1;
+		}
+
+		keys.reserve(1 << shift);
+
+		// now going to start pushing things in to keys
+		let mut cursor: usize = 0;	// <-- current write pos in keys.
+		for entry in temp.drain(..) {
+			// acquire top `shift` bits from `key.hashed()`
+			let target = (entry.key.hashed().as_u64() >> ((<K as Hashable>::Output::bytes() * 8) - shift)) as usize;
+			debug_assert!(target < (1 << shift));
+
+			while cursor < target {
+				// filling with bogus stuff
+				keys.push(Entry::empty());
+				cursor += 1;
+			}
+			keys.push(entry);
+			cursor += 1;
+		}
+
+		// fill out the space, if not full.
+		while cursor < (1 << shift) {
+			keys.push(Entry::empty());
+			cursor += 1;
+		}
+
+		// assert that we haven't doubled the allocation (would confuse the "what is shift?" logic)
+		assert!((keys.len() - lower) < (2 << shift));
+	}
+}
+
 /// Assembles a layer of this 
 pub struct HashedBuilder<K: HashOrdered, L> {
 	temp: Vec<Entry<K>>,		// staging for building; densely packed here and then re-laid out in self.keys.
@@ -122,6 +214,8 @@ pub struct HashedBuilder<K: HashOrdered, L> {
 	pub keys: Vec<Entry<K>>,	// keys and offs co-located because we expect to find the right answers fast.
 	/// A builder for the layer below.
 	pub vals: L,
+	// A key whose values are partly merged by `merge_step`.
+	open: Option<OpenKey>,
 }
 
 impl<K: HashOrdered+Clone+Default, L> HashedBuilder<K, L> {
@@ -159,46 +253,7 @@ impl<K: HashOrdered+Clone+Default, L: Builder> Builder for HashedBuilder<K, L> {
 				self.temp[pos].set_upper(boundary);
 			}
 
-			// having densely packed everything, we now want to extend the allocation and rewrite the contents 
-			// so that their spacing is in line with how robin hood hashing works.
-			let lower = self.keys.len();
-			if self.temp.len() < (1 << MINIMUM_SHIFT) {
-				self.keys.extend(self.temp.drain(..));
-			}
-			else {
-				let target = (BLOAT_FACTOR * (self.temp.len() as f64)) as u64;
-				let mut shift = MINIMUM_SHIFT;
-				while  (1 << shift) < target {
-					shift += 1;
-				}
-
-				self.keys.reserve(1 << shift);
-
-				// now going to start pushing things in to self.keys
-				let mut cursor: usize = 0;	// <-- current write pos in self.keys.
-				for entry in self.temp.drain(..) {
-					// acquire top `shift` bits from `key.hashed()`
-					let target = (entry.key.hashed().as_u64() >> ((<K as Hashable>::Output::bytes() * 8) - shift)) as usize;
-					debug_assert!(target < (1 << shift));
-
-					while cursor < target {
-						// filling with bogus stuff
-						self.keys.push(Entry::empty());
-						cursor += 1;
-					}
-					self.keys.push(entry);
-					cursor += 1;
-				}
-
-				// fill out the space, if not full.
-				while cursor < (1 << shift) {
-					self.keys.push(Entry::empty());
-					cursor += 1;
-				}
-
-				// assert that we haven't doubled the allocation (would confuse the "what is shift?" logic)
-				assert!((self.keys.len() - lower) < (2 << shift));
-			}
+			lay_out(&mut self.temp, &mut self.keys);
 		}
 
 		self.keys.len()
@@ -228,6 +283,7 @@ impl<K: HashOrdered+Clone+Default, L: MergeBuilder> MergeBuilder for HashedBuild
 			temp: Vec::new(),
 			keys: Vec::with_capacity(other1.keys() + other2.keys()),
 			vals: L::with_capacity(&other1.vals, &other2.vals),
+			open: None,
 		}
 	}
 	/// Copies fully formed ranges (note plural) of keys from another trie.
@@ -305,18 +361,117 @@ impl<K: HashOrdered+Clone+Default, L: MergeBuilder> MergeBuilder for HashedBuild
 
 		self.boundary()
 	}
+	/// Merges part of two ranges of entries, returning the number of tuples consumed.
+	///
+	/// Merged entries are staged in `self.temp`, and laid out as a hash map when the builder is done.
+	fn merge_step(&mut self, other1: (&Self::Trie, &mut usize, usize), other2: (&Self::Trie, &mut usize, usize), fuel: usize) -> usize {
+
+		// just rebinding names to clarify code.
+		let (trie1, lower1, upper1) = other1;
+		let (trie2, lower2, upper2) = other2;
+
+		let mut consumed = 0;
+		loop {
+
+			// continue merging the values of a key too large for earlier fuel.
+			if let Some(mut open) = self.open.take() {
+				consumed += open.resume(&mut self.vals, &trie1.vals, &trie2.vals, fuel.saturating_sub(consumed));
+				if !open.is_done() {
+					self.open = Some(open);
+					return consumed;
+				}
+				let upper = self.vals.boundary();
+				if upper > open.lower {
+					let key = if open.range1.is_some() { &trie1.keys[*lower1].key } else { &trie2.keys[*lower2].key };
+					self.temp.push(Entry::new(key.clone(), open.lower, upper));
+				}
+				if open.range1.is_some() { *lower1 += 1; }
+				if open.range2.is_some() { *lower2 += 1; }
+			}
+
+			while *lower1 < upper1 && !trie1.keys[*lower1].is_some() { *lower1 += 1; }
+			while *lower2 < upper2 && !trie2.keys[*lower2].is_some() { *lower2 += 1; }
+
+			let valid1 = *lower1 < upper1;
+			let valid2 = *lower2 < upper2;
+			if consumed >= fuel || (!valid1 && !valid2) {
+				return consumed;
+			}
+
+			let order = if valid1 && valid2 { trie1.keys[*lower1].key.cmp(&trie2.keys[*lower2].key) }
+						else if valid1 { ::std::cmp::Ordering::Less }
+						else { ::std::cmp::Ordering::Greater };
+
+			let remaining = fuel - consumed;
+			match order {
+				::std::cmp::Ordering::Less => {
+					let bound = if valid2 { Some(&trie2.keys[*lower2].key) } else { None };
+					let tuples = self.push_while_fits(trie1, lower1, upper1, bound, remaining);
+					if tuples > 0 {
+						consumed += tuples;
+					}
+					else {
+						self.open = Some(OpenKey {
+							range1: Some((trie1.lower(*lower1), trie1.upper(*lower1))),
+							range2: None,
+							lower: self.vals.boundary(),
+						});
+					}
+				}
+				::std::cmp::Ordering::Equal => {
+					let tuples = trie1.tuples_in(*lower1, *lower1 + 1) + trie2.tuples_in(*lower2, *lower2 + 1);
+					if tuples <= remaining {
+						let lower = self.vals.boundary();
+						let upper = self.vals.push_merge(
+							(&trie1.vals, trie1.lower(*lower1), trie1.upper(*lower1)), 
+							(&trie2.vals, trie2.lower(*lower2), trie2.upper(*lower2))
+						);
+						if upper > lower {
+							self.temp.push(Entry::new(trie1.keys[*lower1].key.clone(), lower, upper));
+						}
+
+						*lower1 += 1;
+						*lower2 += 1;
+						consumed += tuples;
+					}
+					else {
+						self.open = Some(OpenKey {
+							range1: Some((trie1.lower(*lower1), trie1.upper(*lower1))),
+							range2: Some((trie2.lower(*lower2), trie2.upper(*lower2))),
+							lower: self.vals.boundary(),
+						});
+					}
+				}
+				::std::cmp::Ordering::Greater => {
+					let bound = if valid1 { Some(&trie1.keys[*lower1].key) } else { None };
+					let tuples = self.push_while_fits(trie2, lower2, upper2, bound, remaining);
+					if tuples > 0 {
+						consumed += tuples;
+					}
+					else {
+						self.open = Some(OpenKey {
+							range1: None,
+							range2: Some((trie2.lower(*lower2), trie2.upper(*lower2))),
+							lower: self.vals.boundary(),
+						});
+					}
+				}
+			}
+		}
+	}
 }
 
 
 impl<K: HashOrdered+Clone+Default, L: TupleBuilder> TupleBuilder for HashedBuilder<K, L> {
 
 	type Item = (K, L::Item);
-	fn new() -> Self { HashedBuilder { temp: Vec::new(), keys: Vec::new(), vals: L::new() } }
+	fn new() -> Self { HashedBuilder { temp: Vec::new(), keys: Vec::new(), vals: L::new(), open: None } }
 	fn with_capacity(cap: usize) -> Self { 
 		HashedBuilder { 
 			temp: Vec::with_capacity(cap), 
 			keys: Vec::with_capacity(cap), 
 			vals: L::with_capacity(cap),
+			open: None,
 		} 
 	}
 	#[inline(always)]
@@ -365,6 +520,33 @@ impl<K: HashOrdered+Clone+Default, L: MergeBuilder> HashedBuilder<K, L> {
 		index - lower
 	}
 
+	/// Moves entries less than `bound` into `self.temp`, while their values hold at most `fuel` tuples in total.
+	///
+	/// The entry at `*lower` must be valid, and `*lower` advances past the entries moved. Returns the number of
+	/// tuples moved, which is zero if the first entry's values alone exceed `fuel`.
+	fn push_while_fits(&mut self, other: &HashedLayer<K, L::Trie>, lower: &mut usize, upper: usize, bound: Option<&K>, fuel: usize) -> usize {
+
+		let other_basis = other.lower(*lower);	// from where in `other` the offsets do start.
+		let self_basis = self.vals.boundary();	// from where in `self` the offsets must start.
+
+		let mut limit = other_basis;	// tracks the end of the values moved.
+		while *lower < upper && !(other.keys[*lower].is_some() && bound.map(|vs| &other.keys[*lower].key >= vs).unwrap_or(false)) {
+			if other.keys[*lower].is_some() {
+				if other.vals.tuples_in(other_basis, other.upper(*lower)) > fuel { break; }
+				let entry_lower = (other.lower(*lower) + self_basis) - other_basis;
+				let entry_upper = (other.upper(*lower) + self_basis) - other_basis;
+				self.temp.push(Entry::new(other.keys[*lower].key.clone(), entry_lower, entry_upper));
+				limit = other.upper(*lower);
+			}
+			*lower += 1;
+		}
+
+		if limit > other_basis {
+			self.vals.copy_range(&other.vals, other_basis, limit);
+		}
+		other.vals.tuples_in(other_basis, limit)
+	}
+
 	fn push_all(&mut self, other: &HashedLayer<K, L::Trie>, lower: usize, upper: usize) {
 
 		debug_assert!(lower < upper);
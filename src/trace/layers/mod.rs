@@ -29,6 +29,12 @@ pub trait Trie  : ::std::marker::Sized {
 	fn keys(&self) -> usize;
 	/// The total number of tuples in the collection.
 	fn tuples(&self) -> usize;
+	/// The number of tuples in the sub-collections `lower .. upper`.
+	fn tuples_in(&self, lower: usize, upper: usize) -> usize;
+	/// The approximate number of bytes allocated on the heap by the collection's layers.
+	///
+	/// This counts the capacity of the layers' own allocations, and not any allocations owned by the items.
+	fn heap_size(&self) -> usize;
 	/// Returns a cursor capable of navigating the collection.
 	fn cursor(&self) -> Self::Cursor { self.cursor_from(0, self.keys()) }
 	/// Returns a cursor over a range of data, commonly used by others to restrict navigation to 
@@ -70,6 +76,45 @@ pub trait MergeBuilder : Builder {
 	fn copy_range(&mut self, other: &Self::Trie, lower: usize, upper: usize);
 	/// Merges two sub-collections into one sub-collection.
 	fn push_merge(&mut self, other1: (&Self::Trie, usize, usize), other2: (&Self::Trie, usize, usize)) -> usize;
+	/// Merges part of two sequences of sub-collections, returning the number of tuples consumed.
+	///
+	/// The sequences are `lower1 .. upper1` of `other1` and `lower2 .. upper2` of `other2`, and their lower bounds
+	/// advance past the sub-collections merged. Merging stops once `fuel` tuples have been consumed, perhaps a
+	/// few more, or the sequences are exhausted. A sub-collection too large for the remaining fuel is merged in
+	/// parts across several calls, which must present the same sequences, and the lower bounds do not advance past
+	/// it until it is complete.
+	fn merge_step(&mut self, other1: (&Self::Trie, &mut usize, usize), other2: (&Self::Trie, &mut usize, usize), fuel: usize) -> usize;
+}
+
+/// A sub-collection partly merged by `MergeBuilder::merge_step`.
+///
+/// The ranges locate the parts of the sub-collection's children not yet merged, in each input that contains the
+/// sub-collection.
+#[derive(Debug, Clone, Copy)]
+pub struct OpenKey {
+	/// The remaining range of children in the first input, if it contains the sub-collection.
+	pub range1: Option<(usize, usize)>,
+	/// The remaining range of children in the second input, if it contains the sub-collection.
+	pub range2: Option<(usize, usize)>,
+	/// The boundary of the merged children when the sub-collection was opened.
+	pub lower: usize,
+}
+
+impl OpenKey {
+	/// Continues merging the children of the sub-collection into `builder`, returning the number of tuples consumed.
+	pub fn resume<B: MergeBuilder>(&mut self, builder: &mut B, other1: &B::Trie, other2: &B::Trie, fuel: usize) -> usize {
+		let (mut lower1, upper1) = self.range1.unwrap_or((0, 0));
+		let (mut lower2, upper2) = self.range2.unwrap_or((0, 0));
+		let consumed = builder.merge_step((other1, &mut lower1, upper1), (other2, &mut lower2, upper2), fuel);
+		if self.range1.is_some() { self.range1 = Some((lower1, upper1)); }
+		if self.range2.is_some() { self.range2 = Some((lower2, upper2)); }
+		consumed
+	}
+	/// Indicates that the children of the sub-collection are completely merged.
+	pub fn is_done(&self) -> bool {
+		self.range1.map(|(lower, upper)| lower == upper).unwrap_or(true) &&
+		self.range2.map(|(lower, upper)| lower == upper).unwrap_or(true)
+	}
 }
 
 /// A type used to assemble collections from ordered sequences of tuples.
@@ -1,11 +1,13 @@
 //! Implementation using ordered keys and exponential search.
 
-use super::{Trie, Cursor, Builder, MergeBuilder, TupleBuilder};
+use abomonation::Abomonation;
+
+use super::{Trie, Cursor, Builder, MergeBuilder, TupleBuilder, OpenKey};
 
 /// A level of the trie, with keys and offsets into a lower layer.
 ///
 /// In this representation, the values for `keys[i]` are found at `vals[offs[i] .. offs[i+1]]`.
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Debug, Clone, Eq, PartialEq)]
 pub struct OrderedLayer<K: Ord, L> {
 	/// The keys of the layer.
 	pub keys: Vec<K>,
@@ -26,6 +28,12 @@ impl<K: Ord+Clone, L: Trie> Trie for OrderedLayer<K, L> {
 
 	fn keys(&self) -> usize { self.keys.len() }
 	fn tuples(&self) -> usize { self.vals.tuples() }
+	fn tuples_in(&self, lower: usize, upper: usize) -> usize { self.vals.tuples_in(self.offs[lower], self.offs[upper]) }
+	fn heap_size(&self) -> usize {
+		self.keys.capacity() * ::std::mem::size_of::<K>() +
+		self.offs.capacity() * ::std::mem::size_of::<usize>() +
+		self.vals.heap_size()
+	}
 	fn cursor_from(&self, lower: usize, upper: usize) -> Self::Cursor {
 		// let child_lower = if lower == 0 { 0 } else { self.offs[lower-1] };
 		// let child_upper = self.offs[lower];
@@ -54,6 +62,25 @@ impl<K: Ord+Clone, L: Trie> Trie for OrderedLayer<K, L> {
 	}
 }
 
+impl<K: Ord+Abomonation, L: Abomonation> Abomonation for OrderedLayer<K, L> {
+	#[inline] unsafe fn entomb(&self, writer: &mut Vec<u8>) {
+		self.keys.entomb(writer);
+		self.offs.entomb(writer);
+		self.vals.entomb(writer);
+	}
+	#[inline] unsafe fn embalm(&mut self) {
+		self.keys.embalm();
+		self.offs.embalm();
+		self.vals.embalm();
+	}
+	#[inline] unsafe fn exhume<'a,'b>(&'a mut self, mut bytes: &'b mut [u8]) -> Option<&'b mut [u8]> {
+		let temp = bytes; bytes = if let Some(bytes) = self.keys.exhume(temp) { bytes } else { return None };
+		let temp = bytes; bytes = if let Some(bytes) = self.offs.exhume(temp) { bytes } else { return None };
+		let temp = bytes; bytes = if let Some(bytes) = self.vals.exhume(temp) { bytes } else { return None };
+		Some(bytes)
+	}
+}
+
 /// Assembles a layer of this 
 pub struct OrderedBuilder<K: Ord, L> {
 	/// Keys
@@ -62,6 +89,8 @@ pub struct OrderedBuilder<K: Ord, L> {
 	pub offs: Vec<usize>,
 	/// The next layer down
 	pub vals: L,
+	// A key whose values are partly merged by `merge_step`.
+	open: Option<OpenKey>,
 }
 
 impl<K: Ord+Clone, L: Builder> Builder for OrderedBuilder<K, L> {
@@ -90,6 +119,7 @@ impl<K: Ord+Clone, L: MergeBuilder> MergeBuilder for OrderedBuilder<K, L> {
 			keys: Vec::with_capacity(other1.keys() + other2.keys()),
 			offs: offs,
 			vals: L::with_capacity(&other1.vals, &other2.vals),
+			open: None,
 		}
 	}
 	fn copy_range(&mut self, other: &Self::Trie, lower: usize, upper: usize) {
@@ -113,52 +143,123 @@ impl<K: Ord+Clone, L: MergeBuilder> MergeBuilder for OrderedBuilder<K, L> {
 		let (trie2, mut lower2, upper2) = other2;
 
 		self.keys.reserve((upper1 - lower1) + (upper2 - lower2));
+		self.merge_step((trie1, &mut lower1, upper1), (trie2, &mut lower2, upper2), usize::max_value());
+
+		self.keys.len()
+	}
+	fn merge_step(&mut self, other1: (&Self::Trie, &mut usize, usize), other2: (&Self::Trie, &mut usize, usize), fuel: usize) -> usize {
+		let (trie1, lower1, upper1) = other1;
+		let (trie2, lower2, upper2) = other2;
+
+		let mut consumed = 0;
+		loop {
+
+			// continue merging the values of a key too large for earlier fuel.
+			if let Some(mut open) = self.open.take() {
+				consumed += open.resume(&mut self.vals, &trie1.vals, &trie2.vals, fuel.saturating_sub(consumed));
+				if !open.is_done() {
+					self.open = Some(open);
+					return consumed;
+				}
+				let upper = self.vals.boundary();
+				if upper > open.lower {
+					let key = if open.range1.is_some() { &trie1.keys[*lower1] } else { &trie2.keys[*lower2] };
+					self.keys.push(key.clone());
+					self.offs.push(upper);
+				}
+				if open.range1.is_some() { *lower1 += 1; }
+				if open.range2.is_some() { *lower2 += 1; }
+			}
 
-		// while both mergees are still active
-		while lower1 < upper1 && lower2 < upper2 {
+			let valid1 = *lower1 < upper1;
+			let valid2 = *lower2 < upper2;
+			if consumed >= fuel || (!valid1 && !valid2) {
+				return consumed;
+			}
+
+			let order = if valid1 && valid2 { trie1.keys[*lower1].cmp(&trie2.keys[*lower2]) }
+						else if valid1 { ::std::cmp::Ordering::Less }
+						else { ::std::cmp::Ordering::Greater };
 
-			match trie1.keys[lower1].cmp(&trie2.keys[lower2]) {
+			let remaining = fuel - consumed;
+			match order {
 				::std::cmp::Ordering::Less => {
-					// determine how far we can advance lower1 until we reach/pass lower2
-					let step = 1 + advance(&trie1.keys[(1+lower1)..upper1], |x| x < &trie2.keys[lower2]);
-					self.copy_range(trie1, lower1, lower1 + step);
-					lower1 += step;
+					// determine how far we can advance lower1 until we reach/pass lower2, or exhaust the fuel.
+					let step = if valid2 { 1 + advance(&trie1.keys[(1 + *lower1)..upper1], |x| x < &trie2.keys[*lower2]) }
+							   else { upper1 - *lower1 };
+					let step = fits(trie1, *lower1, step, remaining);
+					if step > 0 {
+						consumed += trie1.tuples_in(*lower1, *lower1 + step);
+						self.copy_range(trie1, *lower1, *lower1 + step);
+						*lower1 += step;
+					}
+					else {
+						self.open = Some(OpenKey {
+							range1: Some((trie1.offs[*lower1], trie1.offs[*lower1 + 1])),
+							range2: None,
+							lower: self.vals.boundary(),
+						});
+					}
 				}
 				::std::cmp::Ordering::Equal => {
-					let lower = self.vals.boundary();
-					// record vals_length so we can tell if anything was pushed.
-					let upper = self.vals.push_merge(
-						(&trie1.vals, trie1.offs[lower1], trie1.offs[lower1+1]), 
-						(&trie2.vals, trie2.offs[lower2], trie2.offs[lower2+1])
-					);
-					if upper > lower {
-						self.keys.push(trie1.keys[lower1].clone());
-						self.offs.push(upper);
-					}
+					let tuples = trie1.tuples_in(*lower1, *lower1 + 1) + trie2.tuples_in(*lower2, *lower2 + 1);
+					if tuples <= remaining {
+						let lower = self.vals.boundary();
+						// record vals_length so we can tell if anything was pushed.
+						let upper = self.vals.push_merge(
+							(&trie1.vals, trie1.offs[*lower1], trie1.offs[*lower1+1]), 
+							(&trie2.vals, trie2.offs[*lower2], trie2.offs[*lower2+1])
+						);
+						if upper > lower {
+							self.keys.push(trie1.keys[*lower1].clone());
+							self.offs.push(upper);
+						}
 
-					lower1 += 1;
-					lower2 += 1;
-				} 
+						*lower1 += 1;
+						*lower2 += 1;
+						consumed += tuples;
+					}
+					else {
+						self.open = Some(OpenKey {
+							range1: Some((trie1.offs[*lower1], trie1.offs[*lower1 + 1])),
+							range2: Some((trie2.offs[*lower2], trie2.offs[*lower2 + 1])),
+							lower: self.vals.boundary(),
+						});
+					}
+				}
 				::std::cmp::Ordering::Greater => {
-					// determine how far we can advance lower2 until we reach/pass lower1
-					let step = 1 + advance(&trie2.keys[(1+lower2)..upper2], |x| x < &trie1.keys[lower1]);
-					self.copy_range(trie2, lower2, lower2 + step);
-					lower2 += step;
+					// determine how far we can advance lower2 until we reach/pass lower1, or exhaust the fuel.
+					let step = if valid1 { 1 + advance(&trie2.keys[(1 + *lower2)..upper2], |x| x < &trie1.keys[*lower1]) }
+							   else { upper2 - *lower2 };
+					let step = fits(trie2, *lower2, step, remaining);
+					if step > 0 {
+						consumed += trie2.tuples_in(*lower2, *lower2 + step);
+						self.copy_range(trie2, *lower2, *lower2 + step);
+						*lower2 += step;
+					}
+					else {
+						self.open = Some(OpenKey {
+							range1: None,
+							range2: Some((trie2.offs[*lower2], trie2.offs[*lower2 + 1])),
+							lower: self.vals.boundary(),
+						});
+					}
 				}
 			}
 		}
-
-		if lower1 < upper1 { self.copy_range(trie1, lower1, upper1); }
-		if lower2 < upper2 { self.copy_range(trie2, lower2, upper2); }
-
-		self.keys.len()
 	}
 }
 
+/// The number of keys from `lower`, at most `step`, whose values hold at most `fuel` tuples in total.
+fn fits<K: Ord, L: Trie>(trie: &OrderedLayer<K, L>, lower: usize, step: usize, fuel: usize) -> usize {
+	let basis = trie.offs[lower];
+	advance(&trie.offs[(lower + 1)..(lower + step + 1)], |&off| trie.vals.tuples_in(basis, off) <= fuel)
+}
+
 impl<K: Ord+Clone, L: TupleBuilder> TupleBuilder for OrderedBuilder<K, L> {
 
 	type Item = (K, L::Item);
-	fn new() -> Self { OrderedBuilder { keys: Vec::new(), offs: vec![0], vals: L::new() } }
+	fn new() -> Self { OrderedBuilder { keys: Vec::new(), offs: vec![0], vals: L::new(), open: None } }
 	fn with_capacity(cap: usize) -> Self { 
 		let mut offs = Vec::with_capacity(cap + 1);
 		offs.push(0);
@@ -166,6 +267,7 @@ impl<K: Ord+Clone, L: TupleBuilder> TupleBuilder for OrderedBuilder<K, L> {
 			keys: Vec::with_capacity(cap), 
 			offs: offs, 
 			vals: L::with_capacity(cap),
+			open: None,
 		}
 	}
 	#[inline(always)]
@@ -1,11 +1,13 @@
 //! Implementation using ordered keys and exponential search.
 
+use abomonation::Abomonation;
+
 use difference::Diff;
 
 use super::{Trie, Cursor, Builder, MergeBuilder, TupleBuilder};
 
 /// A layer of unordered values. 
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Debug, Clone, Eq, PartialEq)]
 pub struct OrderedLeaf<K, R> {
     /// Unordered values.
     pub vals: Vec<(K, R)>,
@@ -18,6 +20,8 @@ impl<K: Ord+Clone, R: Diff+Clone> Trie for OrderedLeaf<K, R> {
     type TupleBuilder = OrderedLeafBuilder<K, R>;
     fn keys(&self) -> usize { self.vals.len() }
     fn tuples(&self) -> usize { <OrderedLeaf<K, R> as Trie>::keys(&self) }
+    fn tuples_in(&self, lower: usize, upper: usize) -> usize { upper - lower }
+    fn heap_size(&self) -> usize { self.vals.capacity() * ::std::mem::size_of::<(K, R)>() }
     fn cursor_from(&self, lower: usize, upper: usize) -> Self::Cursor { 
         // println!("unordered: {} .. {}", lower, upper);
         OrderedLeafCursor {
@@ -28,6 +32,19 @@ impl<K: Ord+Clone, R: Diff+Clone> Trie for OrderedLeaf<K, R> {
     }
 }
 
+impl<K: Abomonation, R: Abomonation> Abomonation for OrderedLeaf<K, R> {
+    #[inline] unsafe fn entomb(&self, writer: &mut Vec<u8>) {
+        self.vals.entomb(writer);
+    }
+    #[inline] unsafe fn embalm(&mut self) {
+        self.vals.embalm();
+    }
+    #[inline] unsafe fn exhume<'a,'b>(&'a mut self, mut bytes: &'b mut [u8]) -> Option<&'b mut [u8]> {
+        let temp = bytes; bytes = if let Some(bytes) = self.vals.exhume(temp) { bytes } else { return None };
+        Some(bytes)
+    }
+}
+
 /// A builder for unordered values.
 pub struct OrderedLeafBuilder<K, R> {
     /// Unordered values.
@@ -50,48 +67,61 @@ impl<K: Ord+Clone, R: Diff+Clone> MergeBuilder for OrderedLeafBuilder<K, R> {
         self.vals.extend_from_slice(&other.vals[lower .. upper]);
     }
     fn push_merge(&mut self, other1: (&Self::Trie, usize, usize), other2: (&Self::Trie, usize, usize)) -> usize {
-        // self.copy_range(&other1.0, other1.1, other1.2);
-        // self.copy_range(&other2.0, other2.1, other2.2);
-        // self.vals.len()
 
         let (trie1, mut lower1, upper1) = other1;
         let (trie2, mut lower2, upper2) = other2;
 
         self.vals.reserve((upper1 - lower1) + (upper2 - lower2));
+        self.merge_step((trie1, &mut lower1, upper1), (trie2, &mut lower2, upper2), usize::max_value());
+
+        self.vals.len()
+    }
+    fn merge_step(&mut self, other1: (&Self::Trie, &mut usize, usize), other2: (&Self::Trie, &mut usize, usize), fuel: usize) -> usize {
+
+        let (trie1, lower1, upper1) = other1;
+        let (trie2, lower2, upper2) = other2;
+
+        let mut consumed = 0;
+        while consumed < fuel && (*lower1 < upper1 || *lower2 < upper2) {
 
-        // while both mergees are still active
-        while lower1 < upper1 && lower2 < upper2 {
+            let order = if *lower1 < upper1 && *lower2 < upper2 { trie1.vals[*lower1].0.cmp(&trie2.vals[*lower2].0) }
+                        else if *lower1 < upper1 { ::std::cmp::Ordering::Less }
+                        else { ::std::cmp::Ordering::Greater };
 
-            match trie1.vals[lower1].0.cmp(&trie2.vals[lower2].0) {
+            match order {
                 ::std::cmp::Ordering::Less => {
-                    // determine how far we can advance lower1 until we reach/pass lower2
-                    let step = 1 + advance(&trie1.vals[(1+lower1)..upper1], |x| x < &trie2.vals[lower2]);
-                    <OrderedLeafBuilder<K, R> as MergeBuilder>::copy_range(self, trie1, lower1, lower1 + step);
-                    lower1 += step;
+                    // determine how far we can advance lower1 until we reach/pass lower2, or exhaust the fuel.
+                    let step = if *lower2 < upper2 { 1 + advance(&trie1.vals[(1 + *lower1)..upper1], |x| x.0 < trie2.vals[*lower2].0) }
+                               else { upper1 - *lower1 };
+                    let step = ::std::cmp::min(step, fuel - consumed);
+                    <OrderedLeafBuilder<K, R> as MergeBuilder>::copy_range(self, trie1, *lower1, *lower1 + step);
+                    *lower1 += step;
+                    consumed += step;
                 }
                 ::std::cmp::Ordering::Equal => {
 
-                    let sum = trie1.vals[lower1].1 + trie2.vals[lower2].1;
+                    let sum = trie1.vals[*lower1].1 + trie2.vals[*lower2].1;
                     if !sum.is_zero() {
-                        self.vals.push((trie1.vals[lower1].0.clone(), sum));
+                        self.vals.push((trie1.vals[*lower1].0.clone(), sum));
                     }
 
-                    lower1 += 1;
-                    lower2 += 1;
-                } 
+                    *lower1 += 1;
+                    *lower2 += 1;
+                    consumed += 2;
+                }
                 ::std::cmp::Ordering::Greater => {
-                    // determine how far we can advance lower2 until we reach/pass lower1
-                    let step = 1 + advance(&trie2.vals[(1+lower2)..upper2], |x| x < &trie1.vals[lower1]);
-                    <OrderedLeafBuilder<K, R> as MergeBuilder>::copy_range(self, trie2, lower2, lower2 + step);
-                    lower2 += step;
+                    // determine how far we can advance lower2 until we reach/pass lower1, or exhaust the fuel.
+                    let step = if *lower1 < upper1 { 1 + advance(&trie2.vals[(1 + *lower2)..upper2], |x| x.0 < trie1.vals[*lower1].0) }
+                               else { upper2 - *lower2 };
+                    let step = ::std::cmp::min(step, fuel - consumed);
+                    <OrderedLeafBuilder<K, R> as MergeBuilder>::copy_range(self, trie2, *lower2, *lower2 + step);
+                    *lower2 += step;
+                    consumed += step;
                 }
             }
         }
 
-        if lower1 < upper1 { <OrderedLeafBuilder<K, R> as MergeBuilder>::copy_range(self, trie1, lower1, upper1); }
-        if lower2 < upper2 { <OrderedLeafBuilder<K, R> as MergeBuilder>::copy_range(self, trie2, lower2, upper2); }
-
-        self.vals.len()
+        consumed
     }
 }
 
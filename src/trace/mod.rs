@@ -7,6 +7,7 @@
 //! collection trace. This trait allows operator implementations to be generic with respect to the type of trace,
 //! and allows various data structures to be interpretable as multiple different types of trace.
 
+pub mod checkpoint;
 pub mod cursor;
 pub mod description;
 pub mod implementations;
@@ -105,6 +106,99 @@ pub trait TraceReader<Key, Val, Time, R> {
 	/// cursor methods, as they (by default) just move through batches accumulating cursors into a cursor list.
 	fn map_batches<F: FnMut(&Self::Batch)>(&mut self, f: F);
 
+	/// Reports the total size of the batches the trace manages.
+	///
+	/// Merges in progress present both of their input batches, and the partially built merge result is not
+	/// included in the total.
+	fn size(&mut self) -> Size {
+		let mut size = Size::default();
+		self.map_batches(|batch| size = size + batch.size());
+		size
+	}
+
+	/// Reports the values associated with `key` at `time`, with their non-zero accumulated differences.
+	///
+	/// Returns `None` if `time` is not in advance of the advance frontier, as the accumulations may then be
+	/// inaccurate. The result reflects only the batches already introduced to the trace, and so is only correct
+	/// for the collection at `time` once the trace is complete through `time`.
+	fn lookup(&mut self, key: &Key, time: &Time) -> Option<Vec<(Val, R)>>
+	where Key: Ord, Val: Ord+Clone, Time: Lattice, R: Diff {
+
+		if !self.advance_frontier().iter().any(|t| t.less_equal(time)) {
+			return None;
+		}
+
+		let mut result = Vec::new();
+		let (mut cursor, storage) = self.cursor();
+		cursor.seek_key(&storage, key);
+		if cursor.key_valid(&storage) && cursor.key(&storage) == key {
+			while cursor.val_valid(&storage) {
+				let mut sum = R::zero();
+				cursor.map_times(&storage, |t, d| if t.less_equal(time) { sum = sum + d; });
+				if !sum.is_zero() {
+					result.push((cursor.val(&storage).clone(), sum));
+				}
+				cursor.step_val(&storage);
+			}
+		}
+		Some(result)
+	}
+
+	/// Reports the keys from `lower` up to but not including `upper` at `time`, with their values and their
+	/// non-zero accumulated differences.
+	///
+	/// As with `lookup`, returns `None` if `time` is not in advance of the advance frontier. Keys are visited in
+	/// the order of the trace, which for hash-ordered keys is not the natural order of the wrapped type.
+	fn lookup_range(&mut self, lower: &Key, upper: &Key, time: &Time) -> Option<Vec<(Key, Val, R)>>
+	where Key: Ord+Clone, Val: Ord+Clone, Time: Lattice, R: Diff {
+
+		if !self.advance_frontier().iter().any(|t| t.less_equal(time)) {
+			return None;
+		}
+
+		let mut result = Vec::new();
+		let (mut cursor, storage) = self.cursor();
+		cursor.seek_key(&storage, lower);
+		while cursor.key_valid(&storage) && cursor.key(&storage) < upper {
+			while cursor.val_valid(&storage) {
+				let mut sum = R::zero();
+				cursor.map_times(&storage, |t, d| if t.less_equal(time) { sum = sum + d; });
+				if !sum.is_zero() {
+					result.push((cursor.key(&storage).clone(), cursor.val(&storage).clone(), sum));
+				}
+				cursor.step_val(&storage);
+			}
+			cursor.step_key(&storage);
+		}
+		Some(result)
+	}
+
+	/// Reports the contents of the collection at `time`, as `(key, val, diff)` triples with non-zero differences.
+	///
+	/// As with `lookup`, returns `None` if `time` is not in advance of the advance frontier, and the result is only
+	/// correct once the trace is complete through `time`. The triples are in the order of the trace.
+	fn as_of(&mut self, time: &Time) -> Option<Vec<(Key, Val, R)>>
+	where Key: Ord+Clone, Val: Ord+Clone, Time: Lattice, R: Diff {
+
+		if !self.advance_frontier().iter().any(|t| t.less_equal(time)) {
+			return None;
+		}
+
+		let mut result = Vec::new();
+		let (mut cursor, storage) = self.cursor();
+		while cursor.key_valid(&storage) {
+			while cursor.val_valid(&storage) {
+				let mut sum = R::zero();
+				cursor.map_times(&storage, |t, d| if t.less_equal(time) { sum = sum + d; });
+				if !sum.is_zero() {
+					result.push((cursor.key(&storage).clone(), cursor.val(&storage).clone(), sum));
+				}
+				cursor.step_val(&storage);
+			}
+			cursor.step_key(&storage);
+		}
+		Some(result)
+	}
 }
 
 /// An append-only collection of `(key, val, time, diff)` tuples.
@@ -128,6 +222,12 @@ pub trait Trace<Key, Val, Time, R> : TraceReader<Key, Val, Time, R> where <Self
 	/// This restriction could be relaxed, especially if we discover ways in which batch interval order could 
 	/// commute. For now, the trace should complain, to the extent that it cares about contiguous intervals.
 	fn insert(&mut self, batch: Self::Batch);
+
+	/// Performs deferred work, such as merging batches, decrementing `fuel` by the work done.
+	///
+	/// Implementations may stop once `fuel` is no longer positive, and need not do any work at all;
+	/// the default implementation does nothing.
+	fn exert(&mut self, _fuel: &mut isize) { }
 }
 
 /// A batch of updates whose contents may be read.
@@ -152,6 +252,52 @@ pub trait BatchReader<K, V, T, R> {
 	/// All times in the batch are not greater or equal to any element of `upper`.
 	fn upper(&self) -> &[T] { self.description().upper() }
 
+	/// Reports the size of the batch.
+	///
+	/// The default implementation counts keys and values using a cursor, and reports no heap bytes, as it
+	/// does not know the batch's representation. Implementations should override this where they can.
+	fn size(&self) -> Size {
+		let mut size = Size { tuples: self.len(), keys: 0, vals: 0, bytes: 0 };
+		let (mut cursor, storage) = self.cursor();
+		while cursor.key_valid(&storage) {
+			size.keys += 1;
+			while cursor.val_valid(&storage) {
+				size.vals += 1;
+				cursor.step_val(&storage);
+			}
+			cursor.step_key(&storage);
+		}
+		size
+	}
+}
+
+/// The size of a batch or trace.
+///
+/// Counts of keys and values are per batch, and so a key or value present in several batches of a trace is
+/// counted once for each batch. The number of bytes is an approximation of the heap allocations of the batch
+/// layers, and does not include allocations owned by keys, values, times, or differences themselves.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Size {
+	/// The number of update tuples.
+	pub tuples: usize,
+	/// The number of distinct keys.
+	pub keys: usize,
+	/// The number of distinct `(key, val)` pairs.
+	pub vals: usize,
+	/// The approximate number of heap bytes allocated.
+	pub bytes: usize,
+}
+
+impl ::std::ops::Add for Size {
+	type Output = Size;
+	fn add(self, other: Size) -> Size {
+		Size {
+			tuples: self.tuples + other.tuples,
+			keys: self.keys + other.keys,
+			vals: self.vals + other.vals,
+			bytes: self.bytes + other.bytes,
+		}
+	}
 }
 
 /// An immutable collection of updates.
@@ -160,13 +306,26 @@ pub trait Batch<K, V, T, R> : BatchReader<K, V, T, R> where Self: ::std::marker:
 	type Batcher: Batcher<K, V, T, R, Self>;
 	/// A type used to assemble batches from ordered update sequences.
 	type Builder: Builder<K, V, T, R, Self>;
+	/// A type used to progressively merge batches.
+	type Merger: Merger<K, V, T, R, Self>;
 
 	/// Merges two consecutive batches.
 	///
 	/// Panics if `self.upper()` does not equal `other.lower()`. This is almost certainly a logic bug,
 	/// as the resulting batch does not have a contiguous description. If you would like to put an empty
 	/// interval between the two, you can create an empty interval and do two merges.
-	fn merge(&self, other: &Self) -> Self;
+	fn merge(&self, other: &Self) -> Self {
+		let mut merger = self.begin_merge(other);
+		let mut fuel = isize::max_value();
+		merger.work(self, other, &mut fuel);
+		merger.done()
+	}
+	/// Initiates the merging of consecutive batches.
+	///
+	/// The result can be exercised with bounded amounts of fuel, and eventually produces the same batch
+	/// that `self.merge(other)` would produce. This allows a trace to spread the work of a large merge
+	/// across many insertions, rather than stalling on it.
+	fn begin_merge(&self, other: &Self) -> Self::Merger;
 	/// Advance times to `frontier` creating a new batch.
 	fn advance_ref(&self, frontier: &[T]) -> Self where K: Ord+Clone, V: Ord+Clone, T: Lattice+Ord+Clone, R: Diff {
 
@@ -204,6 +363,19 @@ pub trait Batch<K, V, T, R> : BatchReader<K, V, T, R> where Self: ::std::marker:
 	}
 }
 
+/// Represents a merge in progress.
+pub trait Merger<K, V, T, R, Output: Batch<K, V, T, R>> {
+	/// Performs some amount of work, decrementing `fuel`.
+	///
+	/// The `source1` and `source2` arguments must be the batches the merge was begun with, in the same order.
+	/// Work stops once `fuel` is exhausted or the merge is complete, and may overshoot `fuel` by a small amount.
+	fn work(&mut self, source1: &Output, source2: &Output, fuel: &mut isize);
+	/// Indicates that all of the inputs have been merged.
+	fn is_done(&self) -> bool;
+	/// Extracts the merged batch; should only be called once `is_done()` is true.
+	fn done(self) -> Output;
+}
+
 /// Functionality for collecting and batching updates.
 pub trait Batcher<K, V, T, R, Output: Batch<K, V, T, R>> {
 	/// Allocates a new empty batcher.
@@ -3,7 +3,7 @@
 use timely::progress::nested::product::Product;
 
 use lattice::Lattice;
-use trace::{TraceReader, BatchReader, Description};
+use trace::{TraceReader, BatchReader, Description, Size};
 use trace::cursor::Cursor;
 
 /// Wrapper to provide trace to nested scope.
@@ -125,6 +125,7 @@ where B: BatchReader<K, V, T, R>, T: Clone+Default, TInner: Default {
     }
     fn len(&self) -> usize { self.batch.len() }
     fn description(&self) -> &Description<Product<T, TInner>> { &self.description }
+    fn size(&self) -> Size { self.batch.size() }
 }
 
 impl<K, V, T, R, B, TInner> BatchEnter<K, V, T, R, B, TInner> 
@@ -0,0 +1,164 @@
+extern crate timely;
+extern crate differential_dataflow;
+
+use std::sync::{Arc, Mutex};
+
+use timely::dataflow::operators::Capture;
+use timely::dataflow::operators::capture::Extract;
+use timely::progress::nested::product::Product;
+use timely::progress::timestamp::RootTimestamp;
+
+use differential_dataflow::input::Input;
+use differential_dataflow::operators::arrange::Arrange;
+use differential_dataflow::trace::implementations::ord::OrdValSpine;
+use differential_dataflow::trace::{Trace, TraceReader, Batch, Batcher};
+use differential_dataflow::trace::cursor::CursorDebug;
+use differential_dataflow::trace::checkpoint::{checkpoint, restore};
+use differential_dataflow::hashable::UnsignedWrapper;
+
+type IntegerTrace = OrdValSpine<UnsignedWrapper<u64>, u64, usize, i64>;
+type ProductTrace = OrdValSpine<UnsignedWrapper<u64>, u64, Product<RootTimestamp, u64>, isize>;
+
+fn get_trace() -> IntegerTrace {
+    let mut trace = IntegerTrace::new();
+    {
+        let mut batcher = <<
+            IntegerTrace as TraceReader<UnsignedWrapper<u64>, u64, usize, i64>>::Batch as Batch<
+            UnsignedWrapper<u64>, u64, usize, i64>>::Batcher::new();
+
+        batcher.push_batch(&mut vec![
+            ((1.into(), 2), 0, 1),
+            ((2.into(), 3), 1, 1),
+            ((2.into(), 3), 2, -1),
+        ]);
+
+        let batch_ts = &[1, 2, 3];
+        let batches = batch_ts.iter().map(move |i| batcher.seal(&[*i]));
+        for b in batches {
+            trace.insert(b);
+        }
+    }
+    trace
+}
+
+#[test]
+fn test_checkpoint_restore() {
+
+    let directory = ::std::env::temp_dir().join(format!("differential-checkpoint-{}", ::std::process::id()));
+
+    let mut trace = get_trace();
+    trace.advance_by(&[1]);
+    trace.distinguish_since(&[2]);
+    checkpoint(&mut trace, &directory).unwrap();
+
+    let mut restored: IntegerTrace = restore(&directory).unwrap();
+    assert_eq!(restored.advance_frontier(), &[1]);
+    assert_eq!(restored.distinguish_frontier(), &[2]);
+
+    let mut lowers = Vec::new();
+    let mut restored_lowers = Vec::new();
+    trace.map_batches(|batch| lowers.push(batch.description().lower().to_vec()));
+    restored.map_batches(|batch| restored_lowers.push(batch.description().lower().to_vec()));
+    assert_eq!(lowers, restored_lowers);
+
+    let (mut cursor1, storage1) = trace.cursor_through(&[3]).unwrap();
+    let (mut cursor2, storage2) = restored.cursor_through(&[3]).unwrap();
+    assert_eq!(cursor1.to_vec(&storage1), cursor2.to_vec(&storage2));
+
+    ::std::fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn test_checkpoint_removes_stale_batches() {
+
+    let directory = ::std::env::temp_dir().join(format!("differential-checkpoint-stale-{}", ::std::process::id()));
+
+    let mut trace = get_trace();
+    checkpoint(&mut trace, &directory).unwrap();
+
+    // a second checkpoint, of a trace with no batches, should leave no batch files behind.
+    let mut empty = IntegerTrace::new();
+    checkpoint(&mut empty, &directory).unwrap();
+
+    let mut files = ::std::fs::read_dir(&directory).unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .collect::<Vec<_>>();
+    files.sort();
+    assert_eq!(files, vec!["manifest".to_string()]);
+
+    let mut restored: IntegerTrace = restore(&directory).unwrap();
+    let mut count = 0;
+    restored.map_batches(|_| count += 1);
+    assert_eq!(count, 0);
+
+    ::std::fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn test_checkpoint_arrange_restored() {
+
+    let directory = ::std::env::temp_dir().join(format!("differential-checkpoint-arrange-{}", ::std::process::id()));
+
+    let mut trace = ProductTrace::new();
+    {
+        let mut batcher = <<
+            ProductTrace as TraceReader<UnsignedWrapper<u64>, u64, Product<RootTimestamp, u64>, isize>>::Batch as Batch<
+            UnsignedWrapper<u64>, u64, Product<RootTimestamp, u64>, isize>>::Batcher::new();
+
+        batcher.push_batch(&mut vec![
+            ((1.into(), 2), RootTimestamp::new(0), 1),
+            ((2.into(), 3), RootTimestamp::new(1), 1),
+        ]);
+        trace.insert(batcher.seal(&[RootTimestamp::new(2)]));
+    }
+    checkpoint(&mut trace, &directory).unwrap();
+
+    let (send, recv) = ::std::sync::mpsc::channel();
+    let send = Arc::new(Mutex::new(send));
+    let restore_from = directory.clone();
+
+    timely::execute(timely::Configuration::Thread, move |worker| {
+
+        let restored: ProductTrace = restore(&restore_from).unwrap();
+
+        // the arrangement's own output should carry only updates received after the restore.
+        let (mut input, mut trace) = worker.dataflow(|scope| {
+            let send = send.lock().unwrap().clone();
+            let (input, collection) = scope.new_collection();
+            let arranged = collection.arrange(restored);
+            arranged.as_collection(|k: &UnsignedWrapper<u64>, v: &u64| (false, k.item, *v))
+                    .inner
+                    .capture_into(send);
+            (input, arranged.trace)
+        });
+
+        // an import of the trace should present the restored updates as well.
+        worker.dataflow(|scope| {
+            let send = send.lock().unwrap().clone();
+            trace.import(scope)
+                 .as_collection(|k: &UnsignedWrapper<u64>, v: &u64| (true, k.item, *v))
+                 .inner
+                 .capture_into(send);
+        });
+
+        input.advance_to(2);
+        input.insert((UnsignedWrapper::from(3), 4));
+        input.advance_to(3);
+
+    }).unwrap();
+
+    let mut results = recv.extract()
+        .into_iter()
+        .flat_map(|(_, list)| list.into_iter().map(|(data, time, diff)| (data, time.inner, diff)))
+        .collect::<Vec<_>>();
+    results.sort();
+
+    assert_eq!(results, vec![
+        ((false, 3, 4), 2, 1),
+        ((true, 1, 2), 0, 1),
+        ((true, 2, 3), 1, 1),
+        ((true, 3, 4), 2, 1),
+    ]);
+
+    ::std::fs::remove_dir_all(&directory).unwrap();
+}
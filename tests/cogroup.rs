@@ -0,0 +1,135 @@
+extern crate timely;
+extern crate differential_dataflow;
+
+use std::rc::Rc;
+use std::cell::RefCell;
+
+use timely::progress::nested::product::Product;
+use timely::progress::timestamp::RootTimestamp;
+use timely::dataflow::operators::{ToStream, Capture};
+use timely::dataflow::operators::capture::Extract;
+use differential_dataflow::AsCollection;
+use differential_dataflow::operators::CoGroup;
+use differential_dataflow::input::Input;
+
+#[test]
+fn cogroup() {
+
+    let data = timely::example(|scope| {
+
+        let col1 = vec![((0,0), RootTimestamp::new(0), 1),((1,2), RootTimestamp::new(0), 1)]
+                        .into_iter()
+                        .to_stream(scope)
+                        .as_collection();
+
+        let col2 = vec![((0,1), RootTimestamp::new(1), 1),((2,3), RootTimestamp::new(0), 1)]
+                        .into_iter()
+                        .to_stream(scope)
+                        .as_collection();
+
+        // report the number of values from each input, for each key.
+        col1.cogroup(&[col2], |_,s,t| t.push(((s[0].len(), s[1].len()), 1))).inner.capture()
+    });
+
+    let mut results = data.extract().into_iter().flat_map(|(_, list)| list.into_iter()).collect::<Vec<_>>();
+    results.sort();
+
+    assert_eq!(results, vec![
+        ((0,(1,0)), RootTimestamp::new(0), 1),
+        ((0,(1,0)), RootTimestamp::new(1), -1),
+        ((0,(1,1)), RootTimestamp::new(1), 1),
+        ((1,(1,0)), RootTimestamp::new(0), 1),
+        ((2,(0,1)), RootTimestamp::new(0), 1),
+    ]);
+}
+
+#[test]
+fn cogroup_three() {
+
+    let data = timely::example(|scope| {
+
+        let col1 = vec![((0,0), RootTimestamp::new(0), 1),((1,2), RootTimestamp::new(0), 1)]
+                        .into_iter()
+                        .to_stream(scope)
+                        .as_collection();
+
+        let col2 = vec![((0,1), RootTimestamp::new(1), 1),((2,3), RootTimestamp::new(0), 1)]
+                        .into_iter()
+                        .to_stream(scope)
+                        .as_collection();
+
+        let col3 = vec![((1,5), RootTimestamp::new(1), 1)]
+                        .into_iter()
+                        .to_stream(scope)
+                        .as_collection();
+
+        // report the number of values from each input, for each key.
+        col1.cogroup(&[col2, col3], |_,s,t| t.push(((s[0].len(), s[1].len(), s[2].len()), 1))).inner.capture()
+    });
+
+    let mut results = data.extract().into_iter().flat_map(|(_, list)| list.into_iter()).collect::<Vec<_>>();
+    results.sort();
+
+    assert_eq!(results, vec![
+        ((0,(1,0,0)), RootTimestamp::new(0), 1),
+        ((0,(1,0,0)), RootTimestamp::new(1), -1),
+        ((0,(1,1,0)), RootTimestamp::new(1), 1),
+        ((1,(1,0,0)), RootTimestamp::new(0), 1),
+        ((1,(1,0,0)), RootTimestamp::new(1), -1),
+        ((1,(1,0,1)), RootTimestamp::new(1), 1),
+        ((2,(0,1,0)), RootTimestamp::new(0), 1),
+    ]);
+}
+
+#[test]
+fn cogroup_lagging_input() {
+
+    timely::execute(timely::Configuration::Thread, |worker| {
+
+        let seen = Rc::new(RefCell::new(Vec::new()));
+        let seen2 = seen.clone();
+
+        let (mut input1, mut input2, probe) = worker.dataflow::<u64,_,_>(|scope| {
+            let (input1, col1) = scope.new_collection::<(u64, u64), isize>();
+            let (input2, col2) = scope.new_collection::<(u64, u64), isize>();
+            let probe = col1.cogroup(&[col2], |_,s,t| t.push(((s[0].len(), s[1].len()), 1)))
+                            .inspect(move |x: &((u64, (usize, usize)), Product<RootTimestamp, u64>, isize)| seen2.borrow_mut().push((x.0, x.1.inner, x.2)))
+                            .probe();
+            (input1, input2, probe)
+        });
+
+        input1.insert((0, 0));
+        input2.insert((0, 5));
+        input1.advance_to(2);
+        input1.insert((0, 1));
+        input1.advance_to(3);
+        input1.remove((0, 0));
+
+        // the first input runs ahead, while the second has only completed time `0`.
+        input1.advance_to(5);
+        input2.advance_to(1);
+        input1.flush();
+        input2.flush();
+        while probe.less_than(&RootTimestamp::new(1)) { worker.step(); }
+        for _ in 0 .. 10 { worker.step(); }
+
+        // updates from the first input at later times must wait for the second input.
+        assert_eq!(*seen.borrow(), vec![((0, (1, 1)), 0, 1)]);
+
+        input2.advance_to(2);
+        input2.insert((0, 6));
+        input2.advance_to(5);
+        input2.flush();
+        while probe.less_than(&RootTimestamp::new(5)) { worker.step(); }
+
+        seen.borrow_mut().sort();
+        assert_eq!(*seen.borrow(), vec![
+            ((0, (1, 1)), 0, 1),
+            ((0, (1, 1)), 2, -1),
+            ((0, (1, 2)), 3, 1),
+            ((0, (2, 2)), 2, 1),
+            ((0, (2, 2)), 3, -1),
+        ]);
+
+    }).unwrap();
+}
@@ -0,0 +1,69 @@
+extern crate timely;
+extern crate differential_dataflow;
+
+use timely::progress::timestamp::RootTimestamp;
+use timely::dataflow::operators::{ToStream, Capture};
+use timely::dataflow::operators::capture::Extract;
+use differential_dataflow::AsCollection;
+use differential_dataflow::hashable::OrdWrapper;
+use differential_dataflow::operators::{Join, Consolidate};
+use differential_dataflow::operators::arrange::ArrangeByKey;
+use differential_dataflow::operators::delta_join::{delta_join, DeltaPlan};
+
+#[test]
+fn delta_join_three_way() {
+
+    let (delta, joined) = timely::example(|scope| {
+
+        // simultaneous changes to several inputs, and retractions, exercise the handling of equal times.
+        let x = vec![((0,'a'), RootTimestamp::new(0), 1), ((1,'b'), RootTimestamp::new(1), 1), ((0,'a'), RootTimestamp::new(2), -1)]
+                    .into_iter()
+                    .to_stream(scope)
+                    .as_collection();
+
+        let y = vec![((0,'c'), RootTimestamp::new(0), 1), ((1,'d'), RootTimestamp::new(1), 1), ((0,'e'), RootTimestamp::new(1), 2)]
+                    .into_iter()
+                    .to_stream(scope)
+                    .as_collection();
+
+        let z = vec![((0,'f'), RootTimestamp::new(0), 1), ((1,'g'), RootTimestamp::new(1), 1), ((1,'g'), RootTimestamp::new(3), -1)]
+                    .into_iter()
+                    .to_stream(scope)
+                    .as_collection();
+
+        let joined = x.join(&y).map(|(k,x,y)| (k,(x,y))).join(&z).map(|(k,(x,y),z)| (k,x,y,z));
+
+        let x_arr = x.arrange_by_key_hashed();
+        let y_arr = y.arrange_by_key_hashed();
+        let z_arr = z.arrange_by_key_hashed();
+
+        let delta = delta_join(&[&x_arr, &y_arr, &z_arr], vec![
+            DeltaPlan::new(|k: &OrdWrapper<i32>, x: &char| (k.item, *x, ' ', ' '))
+                .lookup(1, |p| (OrdWrapper { item: p.0 }, None), |p, y| Some((p.0, p.1, *y, p.3)))
+                .lookup(2, |p| (OrdWrapper { item: p.0 }, None), |p, z| Some((p.0, p.1, p.2, *z))),
+            DeltaPlan::new(|k: &OrdWrapper<i32>, y: &char| (k.item, ' ', *y, ' '))
+                .lookup(2, |p| (OrdWrapper { item: p.0 }, None), |p, z| Some((p.0, p.1, p.2, *z)))
+                .lookup(0, |p| (OrdWrapper { item: p.0 }, None), |p, x| Some((p.0, *x, p.2, p.3))),
+            DeltaPlan::new(|k: &OrdWrapper<i32>, z: &char| (k.item, ' ', ' ', *z))
+                .lookup(0, |p| (OrdWrapper { item: p.0 }, None), |p, x| Some((p.0, *x, p.2, p.3)))
+                .lookup(1, |p| (OrdWrapper { item: p.0 }, None), |p, y| Some((p.0, p.1, *y, p.3))),
+        ]);
+
+        (delta.consolidate().inner.capture(), joined.consolidate().inner.capture())
+    });
+
+    let mut delta = delta.extract().into_iter().flat_map(|(_, list)| list.into_iter()).collect::<Vec<_>>();
+    let mut joined = joined.extract().into_iter().flat_map(|(_, list)| list.into_iter()).collect::<Vec<_>>();
+    delta.sort();
+    joined.sort();
+
+    assert_eq!(delta, vec![
+        ((0,'a','c','f'), RootTimestamp::new(0), 1),
+        ((0,'a','c','f'), RootTimestamp::new(2), -1),
+        ((0,'a','e','f'), RootTimestamp::new(1), 2),
+        ((0,'a','e','f'), RootTimestamp::new(2), -2),
+        ((1,'b','d','g'), RootTimestamp::new(1), 1),
+        ((1,'b','d','g'), RootTimestamp::new(3), -1),
+    ]);
+    assert_eq!(delta, joined);
+}
@@ -0,0 +1,276 @@
+extern crate rand;
+extern crate timely;
+extern crate differential_dataflow;
+
+use rand::{Rng, SeedableRng, StdRng};
+
+use std::sync::{Arc, Mutex};
+use std::collections::{HashMap, HashSet};
+
+use timely::dataflow::operators::Capture;
+use timely::dataflow::operators::capture::Extract;
+
+use differential_dataflow::input::Input;
+use differential_dataflow::operators::{Consolidate, Count};
+use differential_dataflow::algorithms::graphs::{connected_components, strongly_connected, k_core, pagerank};
+use differential_dataflow::algorithms::graphs::{bfs, reach, bidijkstra, count_triangles};
+
+type Node = usize;
+type Edge = (Node, Node);
+
+#[test] fn cc_10_15_10() { test_sizes("cc", 10, 15, 10); }
+#[test] fn cc_30_20_20() { test_sizes("cc", 30, 20, 20); }
+#[test] fn scc_10_15_10() { test_sizes("scc", 10, 15, 10); }
+#[test] fn scc_10_30_10() { test_sizes("scc", 10, 30, 10); }
+#[test] fn kcore_10_25_10() { test_sizes("kcore", 10, 25, 10); }
+#[test] fn pagerank_10_20_10() { test_sizes("pagerank", 10, 20, 10); }
+#[test] fn pagerank_20_60_10() { test_sizes("pagerank", 20, 60, 10); }
+#[test] fn bfs_10_20_10() { test_sizes("bfs", 10, 20, 10); }
+#[test] fn reach_10_15_10() { test_sizes("reach", 10, 15, 10); }
+#[test] fn bidijkstra_10_20_10() { test_sizes("bidijkstra", 10, 20, 10); }
+#[test] fn triangles_10_30_10() { test_sizes("triangles", 10, 30, 10); }
+
+fn test_sizes(algorithm: &'static str, nodes: usize, edges: usize, rounds: usize) {
+
+    let mut edge_list = Vec::new();
+
+    let seed: &[_] = &[1, 2, 3, 4];
+    let mut rng1: StdRng = SeedableRng::from_seed(seed);    // rng for edge additions
+    let mut rng2: StdRng = SeedableRng::from_seed(seed);    // rng for edge deletions
+
+    for _ in 0 .. edges {
+        edge_list.push(((rng1.gen_range(0, nodes), rng1.gen_range(0, nodes)), 0, 1));
+    }
+
+    for round in 1 .. rounds {
+        edge_list.push(((rng1.gen_range(0, nodes), rng1.gen_range(0, nodes)), round, 1));
+        edge_list.push(((rng2.gen_range(0, nodes), rng2.gen_range(0, nodes)), round,-1));
+    }
+
+    let results = differential(algorithm, nodes, edge_list.clone());
+
+    for round in 0 .. rounds {
+        let edges = present(&edge_list, round);
+        let mut expected = match algorithm {
+            "cc" => cc_sequential(&edges),
+            "scc" => scc_sequential(&edges),
+            "kcore" => kcore_sequential(&edges, 2),
+            "pagerank" => pagerank_sequential(&counts(&edge_list, round), 5),
+            "bfs" => bfs_sequential(&edges, 0).into_iter().collect(),
+            "reach" => bfs_sequential(&edges, 0).into_iter().map(|(node, _)| (node, 0)).collect(),
+            "bidijkstra" => bidijkstra_sequential(&edges, nodes),
+            "triangles" => triangles_sequential(&edges),
+            _ => panic!("unknown algorithm: {}", algorithm),
+        };
+        expected.sort();
+        assert_eq!(present(&results, round), expected, "{} differs in round {}", algorithm, round);
+    }
+}
+
+// the accumulated counts of records through `round`.
+fn counts(updates: &[(Edge, usize, isize)], round: usize) -> HashMap<Edge, isize> {
+    let mut counts = HashMap::new();
+    for &(edge, time, diff) in updates.iter() {
+        if time <= round { *counts.entry(edge).or_insert(0) += diff; }
+    }
+    counts
+}
+
+// the distinct records with positive accumulated counts through `round`.
+fn present(updates: &[(Edge, usize, isize)], round: usize) -> Vec<Edge> {
+    let mut result = counts(updates, round).into_iter().filter(|&(_, count)| count > 0).map(|(edge, _)| edge).collect::<Vec<_>>();
+    result.sort();
+    result
+}
+
+fn cc_sequential(edges: &[Edge]) -> Vec<Edge> {
+    let mut labels = HashMap::new();
+    for &(src, dst) in edges.iter() {
+        labels.insert(src, src);
+        labels.insert(dst, dst);
+    }
+    let mut changes = true;
+    while changes {
+        changes = false;
+        for &(src, dst) in edges.iter() {
+            let min = ::std::cmp::min(labels[&src], labels[&dst]);
+            if labels[&src] > min { labels.insert(src, min); changes = true; }
+            if labels[&dst] > min { labels.insert(dst, min); changes = true; }
+        }
+    }
+    labels.into_iter().collect()
+}
+
+fn scc_sequential(edges: &[Edge]) -> Vec<Edge> {
+    // an edge is in a strongly connected component if its destination reaches its source.
+    edges.iter().cloned().filter(|&(src, dst)| reaches(edges, dst, src)).collect()
+}
+
+fn reaches(edges: &[Edge], from: Node, to: Node) -> bool {
+    let mut reached = HashSet::new();
+    let mut todo = vec![from];
+    reached.insert(from);
+    while let Some(node) = todo.pop() {
+        for &(src, dst) in edges.iter() {
+            if src == node && reached.insert(dst) {
+                todo.push(dst);
+            }
+        }
+    }
+    reached.contains(&to)
+}
+
+fn kcore_sequential(edges: &[Edge], k: usize) -> Vec<Edge> {
+    let mut edges = edges.iter().cloned().filter(|&(src, dst)| src != dst).flat_map(|(src, dst)| vec![(src, dst), (dst, src)]).collect::<HashSet<_>>();
+    let mut changes = true;
+    while changes {
+        let mut degrees = HashMap::new();
+        for &(src, _) in edges.iter() { *degrees.entry(src).or_insert(0) += 1; }
+        let before = edges.len();
+        edges.retain(|&(src, dst)| degrees[&src] >= k && degrees[&dst] >= k);
+        changes = edges.len() < before;
+    }
+    edges.into_iter().collect()
+}
+
+fn pagerank_sequential(edges: &HashMap<Edge, isize>, iterations: usize) -> Vec<Edge> {
+    let mut degrs = HashMap::new();
+    let mut ranks = HashMap::new();
+    for (&(src, dst), &count) in edges.iter() {
+        if count > 0 {
+            *degrs.entry(src).or_insert(0) += count;
+            ranks.insert(src, 6_000_000);
+            ranks.insert(dst, 6_000_000);
+        }
+    }
+    for _ in 0 .. iterations {
+        let mut next = ranks.keys().map(|&node| (node, 1_000_000)).collect::<HashMap<_,_>>();
+        for (&(src, dst), &count) in edges.iter() {
+            if count > 0 {
+                *next.get_mut(&dst).unwrap() += count * ((5 * ranks[&src]) / (6 * degrs[&src]));
+            }
+        }
+        ranks = next;
+    }
+    ranks.into_iter().map(|(node, rank)| (node, rank as usize)).collect()
+}
+
+// breadth-first distances of nodes reachable from `root`.
+fn bfs_sequential(edges: &[Edge], root: Node) -> HashMap<Node, usize> {
+    let mut distances = HashMap::new();
+    let mut frontier = vec![root];
+    distances.insert(root, 0);
+    let mut distance = 0;
+    while frontier.len() > 0 {
+        distance += 1;
+        let mut next = Vec::new();
+        for &(src, dst) in edges.iter() {
+            if frontier.contains(&src) && !distances.contains_key(&dst) {
+                distances.insert(dst, distance);
+                next.push(dst);
+            }
+        }
+        frontier = next;
+    }
+    distances
+}
+
+// the goals used for `bidijkstra`, from the first three nodes to every node.
+fn goal_list(nodes: usize) -> Vec<Edge> {
+    (0 .. 3).flat_map(|src| (0 .. nodes).map(move |dst| (src, dst))).collect()
+}
+
+// path lengths for each reachable goal `(src, dst)`, reported as `(src * nodes + dst, length)`.
+fn bidijkstra_sequential(edges: &[Edge], nodes: usize) -> Vec<Edge> {
+    let mut result = Vec::new();
+    for (src, dst) in goal_list(nodes) {
+        if let Some(&length) = bfs_sequential(edges, src).get(&dst) {
+            result.push((src * nodes + dst, length));
+        }
+    }
+    result
+}
+
+fn triangles_sequential(edges: &[Edge]) -> Vec<Edge> {
+    let edges = edges.iter().cloned().filter(|&(src, dst)| src != dst).flat_map(|(src, dst)| vec![(src, dst), (dst, src)]).collect::<HashSet<_>>();
+    let mut nodes = edges.iter().map(|&(src, _)| src).collect::<Vec<_>>();
+    nodes.sort();
+    nodes.dedup();
+    let mut counts = HashMap::new();
+    for &a in nodes.iter() {
+        for &b in nodes.iter().filter(|&&b| a < b) {
+            for &c in nodes.iter().filter(|&&c| b < c) {
+                if edges.contains(&(a, b)) && edges.contains(&(b, c)) && edges.contains(&(a, c)) {
+                    for &node in [a, b, c].iter() {
+                        *counts.entry(node).or_insert(0) += 1;
+                    }
+                }
+            }
+        }
+    }
+    counts.into_iter().collect()
+}
+
+fn differential(algorithm: &'static str, nodes: usize, edges_list: Vec<(Edge, usize, isize)>) -> Vec<(Edge, usize, isize)> {
+
+    let (send, recv) = ::std::sync::mpsc::channel();
+    let send = Arc::new(Mutex::new(send));
+
+    timely::execute(timely::Configuration::Thread, move |worker| {
+
+        let mut edges_list = edges_list.clone();
+
+        let mut edges = worker.dataflow(|scope| {
+
+            let send = send.lock().unwrap().clone();
+
+            let (edge_input, edges) = scope.new_collection();
+
+            let results = match algorithm {
+                "cc" => connected_components(&edges),
+                "scc" => strongly_connected(&edges),
+                "kcore" => k_core(&edges, 2),
+                "pagerank" => pagerank(5, &edges).count().map(|(node, rank)| (node, rank as usize)),
+                "bfs" => {
+                    let roots = scope.new_collection_from(vec![0]).1;
+                    bfs(&edges, &roots).map(|(node, dist)| (node, dist as usize))
+                },
+                "reach" => {
+                    let roots = scope.new_collection_from(vec![0]).1;
+                    reach(&edges, &roots).map(|node| (node, 0))
+                },
+                "bidijkstra" => {
+                    let goals = scope.new_collection_from(goal_list(nodes)).1;
+                    bidijkstra(&edges, &goals).map(move |((src, dst), length)| (src * nodes + dst, length as usize))
+                },
+                "triangles" => count_triangles(&edges).map(|(node, count)| (node, count as usize)),
+                _ => panic!("unknown algorithm: {}", algorithm),
+            };
+
+            results.consolidate()
+                   .inner
+                   .capture_into(send);
+
+            edge_input
+        });
+
+        // sort by decreasing insertion time.
+        edges_list.sort_by(|x,y| y.1.cmp(&x.1));
+
+        let mut round = 0;
+        while edges_list.len() > 0 {
+            while edges_list.last().map(|x| x.1) == Some(round) {
+                let (edge, _time, diff) = edges_list.pop().unwrap();
+                edges.update(edge, diff);
+            }
+            round += 1;
+            edges.advance_to(round);
+        }
+
+    }).unwrap();
+
+    recv.extract()
+        .into_iter()
+        .flat_map(|(_, list)| list.into_iter().map(|(edge, time, diff)| (edge, time.inner, diff)))
+        .collect()
+}
@@ -5,7 +5,7 @@ use timely::progress::timestamp::RootTimestamp;
 use timely::dataflow::operators::{ToStream, Capture, Map};
 use timely::dataflow::operators::capture::Extract;
 use differential_dataflow::AsCollection;
-use differential_dataflow::operators::{Group, Count};
+use differential_dataflow::operators::{Group, Count, Threshold, TopK};
 
 #[test]
 fn group() {
@@ -43,4 +43,57 @@ fn group_scaling() {
 
     let extracted = data.extract();
     assert_eq!(extracted.len(), 1);
-}
\ No newline at end of file
+}
+
+#[test]
+fn threshold() {
+
+    let data = timely::example(|scope| {
+
+        let col1 = vec![(0, Default::default(), 1),(1, Default::default(), 2),(2, Default::default(), 3),(2, Default::default(), 1)]
+                        .into_iter()
+                        .to_stream(scope)
+                        .as_collection();
+
+        col1.threshold(|_,c| if c >= 2 { c - 1 } else { 0 }).inner.capture()
+    });
+
+    let mut extracted = data.extract();
+    assert_eq!(extracted.len(), 1);
+    extracted[0].1.sort();
+    assert_eq!(extracted[0].1, vec![(1,Default::default(), 1), (2,Default::default(), 3)]);
+}
+
+#[test]
+fn top_k() {
+
+    let (bottom, max) = timely::example(|scope| {
+
+        let col1 = vec![((0,1), RootTimestamp::new(0), 1),((0,2), RootTimestamp::new(0), 1),((0,3), RootTimestamp::new(0), 1),
+                        ((0,1), RootTimestamp::new(1), -1),((0,5), RootTimestamp::new(1), 1),((1,4), RootTimestamp::new(1), 1)]
+                        .into_iter()
+                        .to_stream(scope)
+                        .as_collection();
+
+        (col1.bottom_k(2).inner.capture(), col1.max().inner.capture())
+    });
+
+    let mut bottom = bottom.extract().into_iter().flat_map(|(_, list)| list.into_iter()).collect::<Vec<_>>();
+    let mut max = max.extract().into_iter().flat_map(|(_, list)| list.into_iter()).collect::<Vec<_>>();
+    bottom.sort();
+    max.sort();
+
+    assert_eq!(bottom, vec![
+        ((0,1), RootTimestamp::new(0), 1),
+        ((0,1), RootTimestamp::new(1), -1),
+        ((0,2), RootTimestamp::new(0), 1),
+        ((0,3), RootTimestamp::new(1), 1),
+        ((1,4), RootTimestamp::new(1), 1),
+    ]);
+    assert_eq!(max, vec![
+        ((0,3), RootTimestamp::new(0), 1),
+        ((0,3), RootTimestamp::new(1), -1),
+        ((0,5), RootTimestamp::new(1), 1),
+        ((1,4), RootTimestamp::new(1), 1),
+    ]);
+}
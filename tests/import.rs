@@ -195,3 +195,111 @@ fn import_skewed() {
         (RootTimestamp::new(4), vec![((0, 1), 1)]),
     ]);
 }
+
+#[test]
+fn test_rekey() {
+    let captured = timely::execute(timely::Configuration::Process(2), |worker| {
+        let index = worker.index();
+
+        let (mut input, captured) = worker.dataflow(|scope| {
+            let (input, edges) = scope.new_input();
+            let captured = edges.as_collection()
+                                .arrange_by_key_hashed()
+                                .rekey(OrdValSpine::new(), |src: &OrdWrapper<u64>, dst: &u64| (OrdWrapper { item: *dst }, src.item))
+                                .as_collection(|dst: &OrdWrapper<u64>, src: &u64| (dst.item, *src))
+                                .inner.exchange(|_| 0)
+                                .capture();
+            (input, captured)
+        });
+
+        if index == 0 {
+            input.send(((1u64, 2u64), RootTimestamp::new(0), 1i64));
+            input.send(((1, 3), RootTimestamp::new(0), 1));
+            input.send(((2, 3), RootTimestamp::new(0), 1));
+        }
+        input.advance_to(1);
+        if index == 0 {
+            input.send(((1, 3), RootTimestamp::new(1), -1));
+        }
+        input.close();
+
+        captured
+    }).unwrap().join().into_iter().map(|x| x.unwrap()).next().unwrap();
+
+    let mut results = captured.extract().into_iter().flat_map(|(_, data)| data).collect::<Vec<_>>();
+    results.sort();
+    assert_eq!(results, vec![
+        ((2, 1), RootTimestamp::new(0), 1),
+        ((3, 1), RootTimestamp::new(0), 1),
+        ((3, 1), RootTimestamp::new(1), -1),
+        ((3, 2), RootTimestamp::new(0), 1),
+    ]);
+}
+
+#[test]
+fn test_import_compacted() {
+    let captured = timely::execute(timely::Configuration::Thread, |worker| {
+
+        let (mut input, mut trace, probe) = worker.dataflow(|scope| {
+            let (input, edges) = scope.new_input();
+            let arranged = edges.as_collection()
+                                .arrange_by_key_hashed();
+            let probe = arranged.stream.probe();
+            (input, arranged.trace.clone(), probe)
+        });
+
+        input.send(((1u64, 1u64), RootTimestamp::new(0), 1i64));
+        input.send(((2, 2), RootTimestamp::new(0), 1));
+        input.advance_to(1);
+        input.send(((1, 1), RootTimestamp::new(1), -1));
+        input.send(((3, 3), RootTimestamp::new(1), 1));
+        input.advance_to(2);
+        while probe.less_than(input.time()) { worker.step(); }
+
+        // the snapshot should present the contents at time two, followed by subsequent changes.
+        trace.advance_by(&[RootTimestamp::new(2)]);
+        let captured = worker.dataflow(move |scope| {
+            trace.import_compacted(scope, RootTimestamp::new(2))
+                 .map(|(k, v): (OrdWrapper<u64>, u64)| (k.item, v))
+                 .inner
+                 .capture()
+        });
+
+        input.send(((4, 4), RootTimestamp::new(2), 1));
+        input.advance_to(3);
+        input.send(((2, 2), RootTimestamp::new(3), -1));
+        input.close();
+
+        captured
+    }).unwrap().join().into_iter().map(|x| x.unwrap()).next().unwrap();
+
+    let mut results = captured.extract().into_iter().flat_map(|(_, data)| data).collect::<Vec<_>>();
+    results.sort();
+    assert_eq!(results, vec![
+        ((2, 2), RootTimestamp::new(2), 1),
+        ((2, 2), RootTimestamp::new(3), -1),
+        ((3, 3), RootTimestamp::new(2), 1),
+        ((4, 4), RootTimestamp::new(2), 1),
+    ]);
+}
+
+#[test]
+#[should_panic]
+fn test_import_compacted_before_frontier() {
+    timely::execute(timely::Configuration::Thread, |worker| {
+
+        let mut trace = worker.dataflow::<usize,_,_>(|scope| {
+            scope.new_input::<((u64, u64), Product<RootTimestamp, usize>, i64)>().1
+                 .as_collection()
+                 .arrange_by_key_hashed()
+                 .trace
+        });
+
+        // times before the trace's advance frontier cannot be distinguished, and should be refused.
+        trace.advance_by(&[RootTimestamp::new(2)]);
+        worker.dataflow(move |scope| {
+            trace.import_compacted(scope, RootTimestamp::new(1));
+        });
+
+    }).unwrap().join().into_iter().map(|x| x.unwrap()).count();
+}
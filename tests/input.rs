@@ -0,0 +1,52 @@
+extern crate timely;
+extern crate differential_dataflow;
+
+use std::rc::Rc;
+use std::cell::RefCell;
+
+use timely::progress::nested::product::Product;
+use timely::progress::timestamp::RootTimestamp;
+
+use differential_dataflow::input::Input;
+
+#[test]
+fn update_at() {
+
+    timely::execute(timely::Configuration::Thread, |worker| {
+
+        let seen = Rc::new(RefCell::new(Vec::new()));
+        let seen2 = seen.clone();
+
+        let (mut input, probe) = worker.dataflow::<u64,_,_>(|scope| {
+            let (input, data) = scope.new_collection();
+            let probe = data.inspect(move |x: &(u64, Product<RootTimestamp, u64>, isize)| seen2.borrow_mut().push((x.0, x.1.inner, x.2)))
+                            .probe();
+            (input, probe)
+        });
+
+        // insert an element now, schedule its removal, and schedule some later insertions.
+        input.insert(1);
+        input.update_at(1, 3, -1);
+        input.update_at(2, 2, 1);
+        input.update_at(3, 10, 1);
+
+        // held back updates should not be visible until the session reaches their times.
+        input.advance_to(1);
+        input.flush();
+        while probe.less_than(input.time()) { worker.step(); }
+        assert_eq!(*seen.borrow(), vec![(1, 0, 1)]);
+
+        input.advance_to(4);
+        input.flush();
+        while probe.less_than(input.time()) { worker.step(); }
+        seen.borrow_mut().sort();
+        assert_eq!(*seen.borrow(), vec![(1, 0, 1), (1, 3, -1), (2, 2, 1)]);
+
+        // updates still held back are sent when the session is dropped.
+        drop(input);
+        while probe.less_than(&RootTimestamp::new(11)) { worker.step(); }
+        seen.borrow_mut().sort();
+        assert_eq!(*seen.borrow(), vec![(1, 0, 1), (1, 3, -1), (2, 2, 1), (3, 10, 1)]);
+
+    }).unwrap();
+}
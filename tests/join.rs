@@ -77,6 +77,40 @@ fn antijoin() {
     assert_eq!(extracted[0].1, vec![((1,2), Default::default(),1)]);
 }
 
+#[test]
+fn left_join() {
+    let data = timely::example(|scope| {
+        let col1 = vec![((0,0), Default::default(),1isize),((1,2), Default::default(),1)].into_iter().to_stream(scope).as_collection();
+        let col2 = vec![((0,'a'), Default::default(),1isize),((2,'b'), Default::default(),1)].into_iter().to_stream(scope).as_collection();
+
+        // should match `(0,0)` with `(0,'a')` and report `(1,2)` unmatched.
+        col1.left_join(&col2).consolidate().inner.capture()
+    });
+    let extracted = data.extract();
+    assert_eq!(extracted.len(), 1);
+    assert_eq!(extracted[0].1, vec![((0,0,Some('a')), Default::default(),1), ((1,2,None), Default::default(),1)]);
+}
+
+#[test]
+fn full_outer_join() {
+    let data = timely::example(|scope| {
+        let col1 = vec![((0,0), RootTimestamp::new(0),1isize),((1,2), RootTimestamp::new(0),1)].into_iter().to_stream(scope).as_collection();
+        let col2 = vec![((0,'a'), RootTimestamp::new(0),1isize),((2,'b'), RootTimestamp::new(0),1),((1,'c'), RootTimestamp::new(1),1)].into_iter().to_stream(scope).as_collection();
+
+        // `(1,2)` is unmatched until `(1,'c')` arrives at the second time.
+        col1.full_outer_join(&col2).consolidate().inner.capture()
+    });
+    let mut results = data.extract().into_iter().flat_map(|(_, list)| list.into_iter()).collect::<Vec<_>>();
+    results.sort();
+    assert_eq!(results, vec![
+        ((0,Some(0),Some('a')), RootTimestamp::new(0),1),
+        ((1,Some(2),None), RootTimestamp::new(0),1),
+        ((1,Some(2),None), RootTimestamp::new(1),-1),
+        ((1,Some(2),Some('c')), RootTimestamp::new(1),1),
+        ((2,None,Some('b')), RootTimestamp::new(0),1),
+    ]);
+}
+
 #[test] fn join_scale_1() { join_scaling(1); }
 #[test] fn join_scale_10() { join_scaling(10); }
 #[test] fn join_scale_100() { join_scaling(100); }
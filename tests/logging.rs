@@ -0,0 +1,75 @@
+// Events are only reported when built with the `logging` feature.
+#![cfg(feature = "logging")]
+
+extern crate timely;
+extern crate differential_dataflow;
+
+use differential_dataflow::trace::implementations::ord::OrdValSpine;
+use differential_dataflow::trace::{Trace, TraceReader, Batch, Batcher};
+use differential_dataflow::hashable::UnsignedWrapper;
+use differential_dataflow::input::Input;
+use differential_dataflow::operators::Count;
+use differential_dataflow::logging::{self, EventBuffer, DifferentialEvent, BatchEvent, MergeEvent, DropEvent, GroupEvent};
+
+type IntegerTrace = OrdValSpine<UnsignedWrapper<u64>, u64, usize, i64>;
+
+#[test]
+fn trace_events() {
+
+    let buffer = EventBuffer::register();
+
+    {
+        let mut trace = IntegerTrace::new();
+        trace.distinguish_since(&[]);
+
+        let mut batcher = <<
+            IntegerTrace as TraceReader<UnsignedWrapper<u64>, u64, usize, i64>>::Batch as Batch<
+            UnsignedWrapper<u64>, u64, usize, i64>>::Batcher::new();
+
+        batcher.push_batch(&mut vec![((1.into(), 2), 0, 1), ((2.into(), 3), 0, 1)]);
+        trace.insert(batcher.seal(&[1]));
+        batcher.push_batch(&mut vec![((3.into(), 4), 1, 1), ((4.into(), 5), 1, 1)]);
+        trace.insert(batcher.seal(&[2]));
+        trace.exert(&mut 1_000_000);
+    }
+
+    logging::unregister();
+
+    let events = buffer.drain();
+    assert!(events.contains(&DifferentialEvent::Batch(BatchEvent { length: 2 })));
+    assert!(events.contains(&DifferentialEvent::Merge(MergeEvent { length1: 2, length2: 2, complete: None, elapsed: None })));
+    assert!(events.iter().any(|event| match *event {
+        DifferentialEvent::Merge(MergeEvent { length1: 2, length2: 2, complete: Some(4), elapsed: Some(_) }) => true,
+        _ => false,
+    }));
+    assert!(events.contains(&DifferentialEvent::Drop(DropEvent { length: 4 })));
+}
+
+#[test]
+fn group_events() {
+
+    timely::execute(timely::Configuration::Thread, |worker| {
+
+        let buffer = EventBuffer::register();
+
+        let (mut input, probe) = worker.dataflow::<usize,_,_>(|scope| {
+            let (input, data) = scope.new_collection();
+            let probe = data.map(|x: u64| x % 3)
+                            .count()
+                            .probe();
+            (input, probe)
+        });
+
+        for x in 0 .. 10 { input.insert(x); }
+        input.advance_to(1);
+        input.flush();
+        while probe.less_than(input.time()) { worker.step(); }
+
+        logging::unregister();
+
+        // the three keys should each have produced a count.
+        let events = buffer.drain();
+        assert!(events.contains(&DifferentialEvent::Group(GroupEvent { keys: 3, produced: 3 })));
+
+    }).unwrap();
+}
@@ -0,0 +1,154 @@
+extern crate timely;
+extern crate differential_dataflow;
+
+use differential_dataflow::trace::implementations::persistent::{PersistentValSpine, PersistentHashValSpine};
+use differential_dataflow::trace::{Trace, TraceReader, Batch, BatchReader, Batcher};
+use differential_dataflow::trace::cursor::CursorDebug;
+use differential_dataflow::hashable::UnsignedWrapper;
+
+type IntegerTrace = PersistentValSpine<UnsignedWrapper<u64>, u64, usize, i64>;
+
+fn get_trace() -> IntegerTrace {
+    let mut trace = IntegerTrace::new();
+    {
+        let mut batcher = <<
+            IntegerTrace as TraceReader<UnsignedWrapper<u64>, u64, usize, i64>>::Batch as Batch<
+            UnsignedWrapper<u64>, u64, usize, i64>>::Batcher::new();
+
+        batcher.push_batch(&mut vec![
+            ((1.into(), 2), 0, 1),
+            ((2.into(), 3), 1, 1),
+            ((2.into(), 3), 2, -1),
+        ]);
+
+        let batch_ts = &[1, 2, 3];
+        let batches = batch_ts.iter().map(move |i| batcher.seal(&[*i]));
+        for b in batches {
+            trace.insert(b);
+        }
+    }
+    trace
+}
+
+#[test]
+fn test_persistent_trace() {
+    let mut trace = get_trace();
+
+    let (mut cursor1, storage1) = trace.cursor_through(&[1]).unwrap();
+    assert_eq!(cursor1.to_vec(&storage1), vec![((1.into(), 2), vec![(0, 1)])]);
+
+    let (mut cursor2, storage2) = trace.cursor();
+    assert_eq!(cursor2.to_vec(&storage2), vec![
+               ((1.into(), 2), vec![(0, 1)]),
+               ((2.into(), 3), vec![(1, 1), (2, -1)]),
+    ]);
+}
+
+#[test]
+fn test_persistent_merge() {
+    let mut trace = get_trace();
+
+    // permit all batches to merge, and compact times to `3`.
+    trace.advance_by(&[3]);
+    trace.distinguish_since(&[3]);
+
+    let mut paths = Vec::new();
+    trace.map_batches(|batch| paths.push(batch.path().to_owned()));
+    assert!(paths.iter().all(|path| path.exists()));
+
+    // merged batches need not consolidate across inputs, so compare accumulated counts.
+    let (mut cursor, storage) = trace.cursor();
+    let counts = cursor.to_vec(&storage)
+                       .into_iter()
+                       .map(|(kv, times)| (kv, times.iter().map(|x| x.1).sum::<i64>()))
+                       .filter(|x| x.1 != 0)
+                       .collect::<Vec<_>>();
+    assert_eq!(counts, vec![((1.into(), 2), 1)]);
+
+    // dropping the trace should remove its files.
+    drop(storage);
+    drop(trace);
+    assert!(paths.iter().all(|path| !path.exists()));
+}
+
+#[test]
+fn test_persistent_mapped() {
+    let mut trace = get_trace();
+
+    // batches are read in place from their mapped files, rather than copied onto the heap.
+    let mut sizes = Vec::new();
+    trace.map_batches(|batch| sizes.push(batch.size()));
+    assert_eq!(sizes.iter().map(|size| size.tuples).sum::<usize>(), 3);
+    assert!(sizes.iter().all(|size| size.bytes == 0));
+
+    // cursors over the same batches share the mapping.
+    let (mut cursor1, storage1) = trace.cursor();
+    let (mut cursor2, storage2) = trace.cursor();
+    assert_eq!(cursor1.to_vec(&storage1), cursor2.to_vec(&storage2));
+}
+
+type HashTrace = PersistentHashValSpine<UnsignedWrapper<u64>, u64, usize, i64>;
+
+#[test]
+fn test_persistent_hash_trace() {
+    let mut trace = HashTrace::new();
+    {
+        let mut batcher = <<
+            HashTrace as TraceReader<UnsignedWrapper<u64>, u64, usize, i64>>::Batch as Batch<
+            UnsignedWrapper<u64>, u64, usize, i64>>::Batcher::new();
+
+        batcher.push_batch(&mut vec![
+            ((1.into(), 2), 0, 1),
+            ((2.into(), 3), 1, 1),
+            ((2.into(), 3), 2, -1),
+        ]);
+
+        for i in 1 .. 4 {
+            trace.insert(batcher.seal(&[i]));
+        }
+    }
+
+    trace.advance_by(&[3]);
+    trace.distinguish_since(&[3]);
+
+    let (mut cursor, storage) = trace.cursor();
+    let counts = cursor.to_vec(&storage)
+                       .into_iter()
+                       .map(|(kv, times)| (kv, times.iter().map(|x| x.1).sum::<i64>()))
+                       .filter(|x| x.1 != 0)
+                       .collect::<Vec<_>>();
+    assert_eq!(counts, vec![((1.into(), 2), 1)]);
+}
+
+#[test]
+fn test_persistent_owned_data() {
+
+    type StringTrace = PersistentHashValSpine<UnsignedWrapper<u64>, String, usize, i64>;
+
+    // enough keys to be laid out as a hash map, with values that own data on the heap.
+    let mut trace = StringTrace::new();
+    {
+        let mut batcher = <<
+            StringTrace as TraceReader<UnsignedWrapper<u64>, String, usize, i64>>::Batch as Batch<
+            UnsignedWrapper<u64>, String, usize, i64>>::Batcher::new();
+
+        for round in 0 .. 4 {
+            batcher.push_batch(&mut (0 .. 100u64).map(|i| ((i.into(), format!("{}-{}", i, round)), round, 1)).collect());
+            trace.insert(batcher.seal(&[round + 1]));
+        }
+    }
+
+    trace.advance_by(&[4]);
+    trace.distinguish_since(&[4]);
+
+    // merged batches may advance times, so compare accumulated counts.
+    let (mut cursor, storage) = trace.cursor();
+    let mut counts = cursor.to_vec(&storage)
+                           .into_iter()
+                           .map(|(kv, times)| (kv, times.iter().map(|x| x.1).sum::<i64>()))
+                           .collect::<Vec<_>>();
+    counts.sort();
+    let mut expected = (0 .. 100u64).flat_map(|i| (0 .. 4).map(move |round| ((i.into(), format!("{}-{}", i, round)), 1))).collect::<Vec<_>>();
+    expected.sort();
+    assert_eq!(counts, expected);
+}
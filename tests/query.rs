@@ -0,0 +1,75 @@
+extern crate timely;
+extern crate differential_dataflow;
+
+use std::rc::Rc;
+use std::cell::RefCell;
+
+use timely::dataflow::operators::{Inspect, Probe};
+use timely::dataflow::operators::Input as TimelyInput;
+
+use differential_dataflow::input::Input;
+use differential_dataflow::operators::arrange::ArrangeByKey;
+use differential_dataflow::operators::query::Query;
+use differential_dataflow::hashable::OrdWrapper;
+
+#[test]
+fn query() {
+
+    timely::execute(timely::Configuration::Thread, |worker| {
+
+        let results = Rc::new(RefCell::new(Vec::new()));
+        let results2 = results.clone();
+
+        let (mut data, mut queries, probe) = worker.dataflow::<usize,_,_>(|scope| {
+            let (data, collection) = scope.new_collection();
+            let (queries, stream) = scope.new_input();
+            let probe = collection.arrange_by_key_hashed()
+                                  .query(&stream)
+                                  .inspect_batch(move |time, answers| {
+                                      for &(ref key, ref values) in answers.iter() {
+                                          results2.borrow_mut().push((time.inner, key.item, values.clone()));
+                                      }
+                                  })
+                                  .probe();
+            (data, queries, probe)
+        });
+
+        data.insert((1u64, 10u64));
+        data.insert((1, 11));
+        data.insert((2, 20));
+        queries.send(OrdWrapper { item: 1u64 });
+        queries.send(OrdWrapper { item: 3 });
+        data.advance_to(1);
+        data.flush();
+        queries.advance_to(1);
+        while probe.less_than(queries.time()) { worker.step(); }
+
+        results.borrow_mut().sort();
+        assert_eq!(*results.borrow(), vec![
+            (0, 1, vec![(10, 1), (11, 1)]),
+            (0, 3, vec![]),
+        ]);
+
+        // queries at time one must wait until the data are complete through time one.
+        queries.send(OrdWrapper { item: 1 });
+        queries.send(OrdWrapper { item: 3 });
+        queries.advance_to(2);
+        for _ in 0 .. 10 { worker.step(); }
+        assert_eq!(results.borrow().len(), 2);
+
+        data.remove((1, 10));
+        data.insert((3, 30));
+        data.advance_to(2);
+        data.flush();
+        while probe.less_than(queries.time()) { worker.step(); }
+
+        results.borrow_mut().sort();
+        assert_eq!(*results.borrow(), vec![
+            (0, 1, vec![(10, 1), (11, 1)]),
+            (0, 3, vec![]),
+            (1, 1, vec![(11, 1)]),
+            (1, 3, vec![(30, 1)]),
+        ]);
+
+    }).unwrap();
+}
@@ -0,0 +1,38 @@
+extern crate timely;
+extern crate differential_dataflow;
+
+use timely::progress::timestamp::RootTimestamp;
+use timely::dataflow::operators::{ToStream, Capture};
+use timely::dataflow::operators::capture::Extract;
+use differential_dataflow::AsCollection;
+use differential_dataflow::operators::{Consolidate, TemporalFilter};
+
+#[test]
+fn temporal_filter() {
+
+    let data = timely::example(|scope| {
+
+        // records `(from, until)` valid in the interval they describe.
+        let col = vec![((0,2), RootTimestamp::new(0), 1),((3,5), RootTimestamp::new(1), 1),((1,4), RootTimestamp::new(2), 1),((4,4), RootTimestamp::new(0), 1)]
+                        .into_iter()
+                        .to_stream(scope)
+                        .as_collection();
+
+        col.temporal_filter(|&(from, until)| (RootTimestamp::new(from), RootTimestamp::new(until)))
+           .consolidate()
+           .inner
+           .capture()
+    });
+
+    let mut results = data.extract().into_iter().flat_map(|(_, list)| list.into_iter()).collect::<Vec<_>>();
+    results.sort();
+
+    assert_eq!(results, vec![
+        ((0,2), RootTimestamp::new(0), 1),
+        ((0,2), RootTimestamp::new(2), -1),
+        ((1,4), RootTimestamp::new(2), 1),
+        ((1,4), RootTimestamp::new(4), -1),
+        ((3,5), RootTimestamp::new(3), 1),
+        ((3,5), RootTimestamp::new(5), -1),
+    ]);
+}
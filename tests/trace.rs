@@ -1,14 +1,18 @@
 extern crate timely;
+extern crate abomonation;
 extern crate differential_dataflow;
 
 use std::rc::Rc;
 
 use differential_dataflow::trace::implementations::ord::OrdValSpine;
 use differential_dataflow::trace::{Trace, TraceReader, Batch, Batcher};
+use differential_dataflow::trace::{BatchReader, Cursor, Merger};
 use differential_dataflow::trace::cursor::CursorDebug;
+use differential_dataflow::trace::cursor::cursor_pair::CursorPair;
 use differential_dataflow::hashable::UnsignedWrapper;
 
 use differential_dataflow::trace::implementations::ord::OrdValBatch;
+use differential_dataflow::trace::implementations::hash::HashValSpine;
 use differential_dataflow::trace::implementations::spine::Spine;
 
 type IntegerTrace = OrdValSpine<UnsignedWrapper<u64>, u64, usize, i64>;
@@ -84,3 +88,300 @@ fn test_advance() {
         cursor2.to_vec(&storage2),
         vec![((1.into(), 2), vec![(2, 1)]), ((2.into(), 3), vec![(2, 1), (2, -1)])]);
 }
+
+#[test]
+fn test_progressive_merge() {
+
+    let mut trace = IntegerTrace::new();
+    trace.set_effort(0);
+    trace.distinguish_since(&[]);
+
+    let mut batcher = <<
+        IntegerTrace as TraceReader<UnsignedWrapper<u64>, u64, usize, i64>>::Batch as Batch<
+        UnsignedWrapper<u64>, u64, usize, i64>>::Batcher::new();
+
+    for i in 0 .. 10 {
+        batcher.push_batch(&mut vec![((i.into(), i), i as usize, 1)]);
+        trace.insert(batcher.seal(&[i as usize + 1]));
+    }
+
+    // without fuel, merges start but do not complete, and their inputs remain visible.
+    let mut batches = 0;
+    trace.map_batches(|_| batches += 1);
+    assert_eq!(batches, 10);
+
+    let expected = (0 .. 10).map(|i| ((i.into(), i), vec![(i as usize, 1)])).collect::<Vec<_>>();
+    let (mut cursor1, storage1) = trace.cursor();
+    assert_eq!(cursor1.to_vec(&storage1), expected);
+
+    // with ample fuel, merges complete.
+    trace.exert(&mut 1_000_000);
+
+    let mut batches = 0;
+    trace.map_batches(|_| batches += 1);
+    assert!(batches < 10);
+
+    let (mut cursor2, storage2) = trace.cursor();
+    assert_eq!(cursor2.to_vec(&storage2), expected);
+}
+
+#[test]
+fn test_merges_keep_pace() {
+
+    let mut trace = IntegerTrace::new();
+    trace.distinguish_since(&[]);
+
+    let mut batcher = <<
+        IntegerTrace as TraceReader<UnsignedWrapper<u64>, u64, usize, i64>>::Batch as Batch<
+        UnsignedWrapper<u64>, u64, usize, i64>>::Batcher::new();
+
+    // at the default effort, merges should complete quickly enough that the number of batches,
+    // including the inputs of merges in progress, stays logarithmic in the number of insertions.
+    let inserts = 1000;
+    let bound = 2 * (64 - (inserts as u64).leading_zeros() as usize);
+    for i in 0 .. inserts {
+        batcher.push_batch(&mut (0 .. 100).map(|j| ((((100 * i + j) as u64).into(), 0), i, 1)).collect());
+        trace.insert(batcher.seal(&[i + 1]));
+
+        let mut batches = 0;
+        trace.map_batches(|_| batches += 1);
+        assert!(batches <= bound, "{} batches after {} insertions", batches, i + 1);
+    }
+
+    let (mut cursor, storage) = trace.cursor();
+    assert_eq!(cursor.to_vec(&storage).len(), 100 * inserts);
+}
+
+#[test]
+fn test_fueled_merge() {
+
+    let mut batcher = <<
+        IntegerTrace as TraceReader<UnsignedWrapper<u64>, u64, usize, i64>>::Batch as Batch<
+        UnsignedWrapper<u64>, u64, usize, i64>>::Batcher::new();
+
+    batcher.push_batch(&mut (0 .. 10).map(|i| ((i.into(), i), 0, 1)).collect());
+    let batch1 = batcher.seal(&[1]);
+    batcher.push_batch(&mut (5 .. 15).map(|i| ((i.into(), i), 1, 1)).collect());
+    let batch2 = batcher.seal(&[2]);
+
+    // a little fuel at a time should make progress, but not complete the merge at once.
+    let mut merger = batch1.begin_merge(&batch2);
+    let mut rounds = 0;
+    while !merger.is_done() {
+        let mut fuel = 3;
+        merger.work(&batch1, &batch2, &mut fuel);
+        assert!(fuel <= 0 || merger.is_done());
+        rounds += 1;
+    }
+    assert!(rounds > 1);
+
+    let merged = merger.done();
+    let expected = batch1.merge(&batch2);
+    assert_eq!(merged.len(), 20);
+    assert_eq!(merged.description().lower(), &[0]);
+    assert_eq!(merged.description().upper(), &[2]);
+
+    let (mut cursor1, storage1) = merged.cursor();
+    let (mut cursor2, storage2) = expected.cursor();
+    assert_eq!(cursor1.to_vec(&storage1), cursor2.to_vec(&storage2));
+}
+
+#[test]
+fn test_fueled_merge_large_key() {
+
+    let mut batcher = <<
+        IntegerTrace as TraceReader<UnsignedWrapper<u64>, u64, usize, i64>>::Batch as Batch<
+        UnsignedWrapper<u64>, u64, usize, i64>>::Batcher::new();
+
+    batcher.push_batch(&mut (0 .. 100).map(|i| ((1.into(), i), 0, 1)).collect());
+    let batch1 = batcher.seal(&[1]);
+    batcher.push_batch(&mut (0 .. 100).map(|i| ((1.into(), 2 * i), 1, 1)).collect());
+    let batch2 = batcher.seal(&[2]);
+
+    // a single key with many updates should be merged in parts, each bounded by the fuel.
+    let mut merger = batch1.begin_merge(&batch2);
+    let mut rounds = 0;
+    while !merger.is_done() {
+        let mut fuel = 10;
+        merger.work(&batch1, &batch2, &mut fuel);
+        assert!(fuel >= -1);
+        assert!(fuel <= 0 || merger.is_done());
+        rounds += 1;
+    }
+    assert!(rounds >= 200 / 11);
+
+    let merged = merger.done();
+    let expected = batch1.merge(&batch2);
+    assert_eq!(merged.len(), 200);
+
+    let (mut cursor1, storage1) = merged.cursor();
+    let (mut cursor2, storage2) = expected.cursor();
+    assert_eq!(cursor1.to_vec(&storage1), cursor2.to_vec(&storage2));
+}
+
+#[test]
+fn test_fueled_merge_hashed() {
+
+    type HashTrace = HashValSpine<UnsignedWrapper<u64>, u64, usize, i64>;
+
+    let mut hash_batcher = <<
+        HashTrace as TraceReader<UnsignedWrapper<u64>, u64, usize, i64>>::Batch as Batch<
+        UnsignedWrapper<u64>, u64, usize, i64>>::Batcher::new();
+    let mut ord_batcher = <<
+        IntegerTrace as TraceReader<UnsignedWrapper<u64>, u64, usize, i64>>::Batch as Batch<
+        UnsignedWrapper<u64>, u64, usize, i64>>::Batcher::new();
+
+    // many small keys, interleaved between the batches, and one key with many updates in each.
+    let updates1 = (0 .. 100).map(|i| ((((i % 50) * 2).into(), i), 0, 1)).chain((0 .. 100).map(|i| ((7.into(), i), 0, 1))).collect::<Vec<_>>();
+    let updates2 = (0 .. 100).map(|i| ((((i % 50) * 3).into(), i), 1, 1)).chain((0 .. 100).map(|i| ((7.into(), 2 * i), 1, 1))).collect::<Vec<_>>();
+
+    hash_batcher.push_batch(&mut updates1.clone());
+    let batch1 = hash_batcher.seal(&[1]);
+    hash_batcher.push_batch(&mut updates2.clone());
+    let batch2 = hash_batcher.seal(&[2]);
+
+    ord_batcher.push_batch(&mut updates1.clone());
+    let ord1 = ord_batcher.seal(&[1]);
+    ord_batcher.push_batch(&mut updates2.clone());
+    let ord2 = ord_batcher.seal(&[2]);
+
+    let mut merger = batch1.begin_merge(&batch2);
+    while !merger.is_done() {
+        let mut fuel = 10;
+        merger.work(&batch1, &batch2, &mut fuel);
+        assert!(fuel >= -1);
+        assert!(fuel <= 0 || merger.is_done());
+    }
+
+    let merged = merger.done();
+    let expected = ord1.merge(&ord2);
+    assert_eq!(merged.len(), 400);
+
+    // `UnsignedWrapper` hashes to its own value, so both traces present keys in the same order.
+    let (mut cursor1, storage1) = merged.cursor();
+    let (mut cursor2, storage2) = expected.cursor();
+    assert_eq!(cursor1.to_vec(&storage1), cursor2.to_vec(&storage2));
+}
+
+#[test]
+fn test_cursor_pair() {
+
+    let mut trace = IntegerTrace::new();
+
+    let mut batcher = <<
+        IntegerTrace as TraceReader<UnsignedWrapper<u64>, u64, usize, i64>>::Batch as Batch<
+        UnsignedWrapper<u64>, u64, usize, i64>>::Batcher::new();
+
+    batcher.push_batch(&mut vec![
+        ((1.into(), 2), 0, 1),
+        ((2.into(), 3), 0, 1),
+        ((2.into(), 3), 1, -1),
+        ((2.into(), 4), 1, 1),
+        ((3.into(), 5), 1, 1),
+    ]);
+
+    // insert the first batch, and hold the second back as "pending".
+    trace.insert(batcher.seal(&[1]));
+    let pending = batcher.seal(&[2]);
+
+    let (cursor1, storage1) = trace.cursor();
+    let (cursor2, storage2) = pending.cursor();
+    let storage = (storage1, storage2);
+    let mut pair = CursorPair::new(cursor1, cursor2, &storage);
+
+    assert_eq!(pair.to_vec(&storage), vec![
+               ((1.into(), 2), vec![(0, 1)]),
+               ((2.into(), 3), vec![(0, 1), (1, -1)]),
+               ((2.into(), 4), vec![(1, 1)]),
+               ((3.into(), 5), vec![(1, 1)]),
+    ]);
+
+    // seeking should land on keys present in either cursor.
+    pair.rewind_keys(&storage);
+    pair.seek_key(&storage, &3.into());
+    assert!(pair.key_valid(&storage));
+    assert_eq!(pair.key(&storage), &3.into());
+    assert_eq!(pair.val(&storage), &5);
+}
+
+#[test]
+fn test_lookup() {
+    let mut trace = get_trace();
+
+    assert_eq!(trace.lookup(&2.into(), &1), Some(vec![(3, 1)]));
+    assert_eq!(trace.lookup(&2.into(), &2), Some(vec![]));
+    assert_eq!(trace.lookup(&4.into(), &2), Some(vec![]));
+    assert_eq!(trace.lookup_range(&1.into(), &3.into(), &1), Some(vec![(1.into(), 2, 1), (2.into(), 3, 1)]));
+    assert_eq!(trace.lookup_range(&2.into(), &3.into(), &2), Some(vec![]));
+
+    // times not in advance of the advance frontier may not accumulate correctly.
+    trace.advance_by(&[2]);
+    assert_eq!(trace.lookup(&1.into(), &1), None);
+    assert_eq!(trace.lookup(&1.into(), &2), Some(vec![(2, 1)]));
+}
+
+#[test]
+fn test_as_of() {
+    let mut trace = get_trace();
+
+    assert_eq!(trace.as_of(&0), Some(vec![(1.into(), 2, 1)]));
+    assert_eq!(trace.as_of(&1), Some(vec![(1.into(), 2, 1), (2.into(), 3, 1)]));
+    assert_eq!(trace.as_of(&2), Some(vec![(1.into(), 2, 1)]));
+
+    trace.advance_by(&[2]);
+    assert_eq!(trace.as_of(&1), None);
+}
+
+#[test]
+fn test_size() {
+    let mut batcher = <<
+        IntegerTrace as TraceReader<UnsignedWrapper<u64>, u64, usize, i64>>::Batch as Batch<
+        UnsignedWrapper<u64>, u64, usize, i64>>::Batcher::new();
+
+    batcher.push_batch(&mut vec![
+        ((1.into(), 2), 0, 1),
+        ((1.into(), 3), 0, 1),
+        ((2.into(), 3), 0, 1),
+        ((2.into(), 3), 0, 1),
+    ]);
+
+    let size = batcher.seal(&[1]).size();
+    assert_eq!(size.tuples, 3);
+    assert_eq!(size.keys, 2);
+    assert_eq!(size.vals, 3);
+    assert!(size.bytes > 0);
+
+    // batches may or may not have merged, but each update is present exactly once.
+    let size = get_trace().size();
+    assert_eq!(size.tuples, 3);
+    assert!(size.keys >= 2 && size.keys <= 3);
+    assert!(size.bytes > 0);
+}
+
+#[test]
+fn test_encode() {
+    let mut batcher = <<
+        IntegerTrace as TraceReader<UnsignedWrapper<u64>, u64, usize, i64>>::Batch as Batch<
+        UnsignedWrapper<u64>, u64, usize, i64>>::Batcher::new();
+
+    batcher.push_batch(&mut vec![
+        ((1.into(), 2), 0, 1),
+        ((1.into(), 3), 1, 1),
+        ((2.into(), 3), 2, -1),
+    ]);
+
+    let batch = batcher.seal(&[3]);
+
+    let mut bytes = Vec::new();
+    unsafe { abomonation::encode(&*batch, &mut bytes); }
+    let decoded = unsafe { abomonation::decode::<OrdValBatch<UnsignedWrapper<u64>, u64, usize, i64>>(&mut bytes[..]) };
+    let decoded = Rc::new(decoded.expect("failed to decode batch").0.clone());
+
+    assert_eq!(decoded.description().lower(), batch.description().lower());
+    assert_eq!(decoded.description().upper(), batch.description().upper());
+    assert_eq!(decoded.description().since(), batch.description().since());
+
+    let (mut cursor1, storage1) = batch.cursor();
+    let (mut cursor2, storage2) = decoded.cursor();
+    assert_eq!(cursor1.to_vec(&storage1), cursor2.to_vec(&storage2));
+}
@@ -0,0 +1,44 @@
+extern crate timely;
+extern crate differential_dataflow;
+
+use timely::progress::timestamp::RootTimestamp;
+use timely::dataflow::operators::{ToStream, Capture};
+use timely::dataflow::operators::capture::Extract;
+use differential_dataflow::AsCollection;
+use differential_dataflow::operators::{Consolidate, Window};
+
+#[test]
+fn window() {
+
+    let (sliding, tumbling) = timely::example(|scope| {
+
+        let col = vec![('a', RootTimestamp::new(0), 1),('b', RootTimestamp::new(1), 1),('c', RootTimestamp::new(4), 1)]
+                        .into_iter()
+                        .to_stream(scope)
+                        .as_collection();
+
+        (col.window(2, 1).consolidate().inner.capture(), col.tumbling(3).consolidate().inner.capture())
+    });
+
+    let mut sliding = sliding.extract().into_iter().flat_map(|(_, list)| list.into_iter()).collect::<Vec<_>>();
+    let mut tumbling = tumbling.extract().into_iter().flat_map(|(_, list)| list.into_iter()).collect::<Vec<_>>();
+    sliding.sort();
+    tumbling.sort();
+
+    assert_eq!(sliding, vec![
+        ('a', RootTimestamp::new(0), 1),
+        ('a', RootTimestamp::new(2), -1),
+        ('b', RootTimestamp::new(1), 1),
+        ('b', RootTimestamp::new(3), -1),
+        ('c', RootTimestamp::new(4), 1),
+        ('c', RootTimestamp::new(6), -1),
+    ]);
+    assert_eq!(tumbling, vec![
+        ('a', RootTimestamp::new(0), 1),
+        ('a', RootTimestamp::new(3), -1),
+        ('b', RootTimestamp::new(1), 1),
+        ('b', RootTimestamp::new(3), -1),
+        ('c', RootTimestamp::new(4), 1),
+        ('c', RootTimestamp::new(6), -1),
+    ]);
+}